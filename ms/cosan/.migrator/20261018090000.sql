@@ -0,0 +1,5 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS fuzzystrmatch;
+
+CREATE INDEX IF NOT EXISTS words_word_trgm_idx ON words USING GIN (lower(word) gin_trgm_ops);
+COMMENT ON INDEX words_word_trgm_idx IS 'case-insensitive substring and trigram similarity search on words';
@@ -0,0 +1,45 @@
+ALTER TABLE words ADD COLUMN IF NOT EXISTS normalized_word VARCHAR(255);
+COMMENT ON COLUMN words.normalized_word IS 'canonical form of word (NFKC, case folded, whitespace collapsed) used for uniqueness and lookup';
+
+UPDATE words
+    SET normalized_word = regexp_replace(btrim(lower(normalize(word, NFKC))), '\s+', ' ', 'g');
+
+-- fold rows that only differed in case, width or spacing onto the oldest one
+CREATE TEMPORARY TABLE duplicate_words ON COMMIT DROP AS
+    SELECT
+        word_id,
+        MIN(word_id) OVER (PARTITION BY normalized_word) AS canonical_word_id
+    FROM
+        words;
+
+DELETE FROM user_words AS uw
+    USING duplicate_words AS d
+WHERE
+    uw.word_id = d.word_id
+    AND d.word_id <> d.canonical_word_id
+    AND EXISTS (
+        SELECT 1 FROM user_words AS c
+        WHERE c.user_id = uw.user_id AND c.word_id = d.canonical_word_id
+    );
+
+UPDATE user_words AS uw
+    SET word_id = d.canonical_word_id
+FROM
+    duplicate_words AS d
+WHERE
+    uw.word_id = d.word_id
+    AND d.word_id <> d.canonical_word_id;
+
+DELETE FROM words AS w
+    USING duplicate_words AS d
+WHERE
+    w.word_id = d.word_id
+    AND d.word_id <> d.canonical_word_id;
+
+ALTER TABLE words ALTER COLUMN normalized_word SET NOT NULL;
+ALTER TABLE words DROP CONSTRAINT IF EXISTS words_word_key;
+ALTER TABLE words ADD CONSTRAINT words_normalized_word_key UNIQUE (normalized_word);
+
+DROP INDEX IF EXISTS words_word_trgm_idx;
+CREATE INDEX IF NOT EXISTS words_normalized_word_trgm_idx ON words USING GIN (normalized_word gin_trgm_ops);
+COMMENT ON INDEX words_normalized_word_trgm_idx IS 'substring and trigram similarity search on normalized words';
@@ -0,0 +1,13 @@
+ALTER TABLE user_words ADD COLUMN IF NOT EXISTS ease_factor FLOAT8 NOT NULL DEFAULT 2.5;
+ALTER TABLE user_words ADD COLUMN IF NOT EXISTS interval_days INT NOT NULL DEFAULT 0;
+ALTER TABLE user_words ADD COLUMN IF NOT EXISTS repetitions INT NOT NULL DEFAULT 0;
+ALTER TABLE user_words ADD COLUMN IF NOT EXISTS due_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE user_words ADD COLUMN IF NOT EXISTS last_reviewed_at TIMESTAMP;
+COMMENT ON COLUMN user_words.ease_factor IS 'SM-2 ease factor, never below 1.3';
+COMMENT ON COLUMN user_words.interval_days IS 'days between the last review and the next one';
+COMMENT ON COLUMN user_words.repetitions IS 'consecutive successful reviews';
+COMMENT ON COLUMN user_words.due_at IS 'next scheduled review';
+COMMENT ON COLUMN user_words.last_reviewed_at IS 'last submitted review, null until the first one';
+
+CREATE INDEX IF NOT EXISTS user_words_user_id_due_at_idx ON user_words (user_id, due_at);
+COMMENT ON INDEX user_words_user_id_due_at_idx IS 'due review listing per user';
@@ -0,0 +1,42 @@
+CREATE TABLE IF NOT EXISTS quizzes (
+    quiz_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    mode VARCHAR(32) NOT NULL,
+    seed BIGINT NOT NULL,
+    score INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (quiz_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+COMMENT ON TABLE quizzes IS 'quizzes generated from user words';
+COMMENT ON COLUMN quizzes.quiz_id IS 'quiz id';
+COMMENT ON COLUMN quizzes.user_id IS 'user id';
+COMMENT ON COLUMN quizzes.mode IS 'multiple_choice or typing';
+COMMENT ON COLUMN quizzes.seed IS 'generator seed, replays the same quiz over the same words';
+COMMENT ON COLUMN quizzes.score IS 'number of correct answers';
+
+CREATE TABLE IF NOT EXISTS quiz_questions (
+    quiz_question_id BIGSERIAL,
+    quiz_id BIGINT NOT NULL,
+    position INT NOT NULL,
+    word_id BIGINT NOT NULL,
+    prompt VARCHAR(255) NOT NULL,
+    choices TEXT[] NOT NULL DEFAULT '{}',
+    answer VARCHAR(255),
+    correct BOOLEAN,
+    answered_at TIMESTAMP,
+    PRIMARY KEY (quiz_question_id),
+    UNIQUE (quiz_id, position),
+    FOREIGN KEY (quiz_id) REFERENCES quizzes(quiz_id) ON DELETE CASCADE,
+    FOREIGN KEY (word_id) REFERENCES words(word_id)
+);
+COMMENT ON TABLE quiz_questions IS 'quiz questions and their answers';
+COMMENT ON COLUMN quiz_questions.quiz_question_id IS 'quiz question id';
+COMMENT ON COLUMN quiz_questions.quiz_id IS 'quiz id';
+COMMENT ON COLUMN quiz_questions.position IS 'zero based position in the quiz';
+COMMENT ON COLUMN quiz_questions.word_id IS 'expected word';
+COMMENT ON COLUMN quiz_questions.prompt IS 'anagram or first letter hint shown to the user';
+COMMENT ON COLUMN quiz_questions.choices IS 'multiple choice options, empty for typing quizzes';
+COMMENT ON COLUMN quiz_questions.answer IS 'submitted answer, null until answered';
+COMMENT ON COLUMN quiz_questions.correct IS 'whether the submitted answer matched';
+COMMENT ON COLUMN quiz_questions.answered_at IS 'answer time, null until answered';
@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS decks (
+    deck_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    share_token VARCHAR(64),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (deck_id),
+    UNIQUE (user_id, name),
+    UNIQUE (share_token),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+COMMENT ON TABLE decks IS 'user owned collections of user words';
+COMMENT ON COLUMN decks.deck_id IS 'deck id';
+COMMENT ON COLUMN decks.user_id IS 'owner user id';
+COMMENT ON COLUMN decks.name IS 'deck name, unique per user';
+COMMENT ON COLUMN decks.share_token IS 'read-only share token, null when the deck is not shared';
+
+CREATE TABLE IF NOT EXISTS deck_words (
+    deck_id BIGINT NOT NULL,
+    user_word_id BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (deck_id, user_word_id),
+    FOREIGN KEY (deck_id) REFERENCES decks(deck_id) ON DELETE CASCADE,
+    FOREIGN KEY (user_word_id) REFERENCES user_words(user_word_id) ON DELETE CASCADE
+);
+COMMENT ON TABLE deck_words IS 'deck membership of user words';
+COMMENT ON COLUMN deck_words.deck_id IS 'deck id';
+COMMENT ON COLUMN deck_words.user_word_id IS 'user word id';
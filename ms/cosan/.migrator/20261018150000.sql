@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    refresh_token_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    family_id VARCHAR(64) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (refresh_token_id),
+    UNIQUE (token_hash),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+COMMENT ON TABLE refresh_tokens IS 'refresh tokens issued at login, rotated on every use';
+COMMENT ON COLUMN refresh_tokens.refresh_token_id IS 'refresh token id';
+COMMENT ON COLUMN refresh_tokens.user_id IS 'user id the token was issued to';
+COMMENT ON COLUMN refresh_tokens.family_id IS 'shared by every token rotated from the same login';
+COMMENT ON COLUMN refresh_tokens.token_hash IS 'sha-256 hex digest of the token, the token itself is never stored';
+COMMENT ON COLUMN refresh_tokens.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN refresh_tokens.used_at IS 'set when the token was exchanged, a second use is treated as theft';
+COMMENT ON COLUMN refresh_tokens.revoked_at IS 'set on logout or reuse detection';
+
+CREATE TABLE IF NOT EXISTS revoked_access_tokens (
+    jti VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (jti)
+);
+COMMENT ON TABLE revoked_access_tokens IS 'access tokens revoked before their expiration';
+COMMENT ON COLUMN revoked_access_tokens.jti IS 'jti claim of the revoked token';
+COMMENT ON COLUMN revoked_access_tokens.expires_at IS 'exp claim of the revoked token, rows past it can be purged';
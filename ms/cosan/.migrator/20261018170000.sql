@@ -0,0 +1,24 @@
+-- accounts created before verification existed are trusted, new ones start unverified
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ALTER COLUMN email_verified SET DEFAULT FALSE;
+COMMENT ON COLUMN users.email_verified IS 'set once the user confirmed the current email address';
+
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+    email_verification_token_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (email_verification_token_id),
+    UNIQUE (token_hash),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+COMMENT ON TABLE email_verification_tokens IS 'single-use tokens mailed to confirm an email address';
+COMMENT ON COLUMN email_verification_tokens.email_verification_token_id IS 'email verification token id';
+COMMENT ON COLUMN email_verification_tokens.user_id IS 'user id the token was mailed to';
+COMMENT ON COLUMN email_verification_tokens.email IS 'address the token was mailed to, the token is void once the user changes it';
+COMMENT ON COLUMN email_verification_tokens.token_hash IS 'sha-256 hex digest of the token, the token itself is never stored';
+COMMENT ON COLUMN email_verification_tokens.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN email_verification_tokens.used_at IS 'set when the token confirmed the address';
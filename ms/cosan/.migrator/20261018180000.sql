@@ -0,0 +1,22 @@
+-- access tokens issued before this instant are rejected, refresh tokens are revoked row by row
+ALTER TABLE users ADD COLUMN IF NOT EXISTS sessions_revoked_at TIMESTAMP;
+COMMENT ON COLUMN users.sessions_revoked_at IS 'set when every session of the user was ended, e.g. by a password reset';
+
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    password_reset_token_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (password_reset_token_id),
+    UNIQUE (token_hash),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+COMMENT ON TABLE password_reset_tokens IS 'single-use tokens mailed to reset a forgotten password';
+COMMENT ON COLUMN password_reset_tokens.password_reset_token_id IS 'password reset token id';
+COMMENT ON COLUMN password_reset_tokens.user_id IS 'user id the token was mailed to';
+COMMENT ON COLUMN password_reset_tokens.token_hash IS 'sha-256 hex digest of the token, the token itself is never stored';
+COMMENT ON COLUMN password_reset_tokens.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN password_reset_tokens.used_at IS 'set when the token reset the password, or voided by a later reset';
+COMMENT ON COLUMN password_reset_tokens.created_at IS 'created datetime';
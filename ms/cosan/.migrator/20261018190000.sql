@@ -0,0 +1,45 @@
+-- optional second factor, the secret stays readable because every code is derived from it
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret VARCHAR(64);
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
+COMMENT ON COLUMN users.totp_secret IS 'base32 TOTP secret, set on enrollment and pending until confirmed';
+COMMENT ON COLUMN users.totp_enabled IS 'set once the user confirmed the secret with a code, login then asks for one';
+COMMENT ON COLUMN users.totp_last_step IS 'time step of the last accepted code, no code of it or earlier is accepted again';
+
+CREATE TABLE IF NOT EXISTS totp_recovery_codes (
+    totp_recovery_code_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (totp_recovery_code_id),
+    UNIQUE (user_id, code_hash),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+COMMENT ON TABLE totp_recovery_codes IS 'single-use codes that stand in for a TOTP code when the authenticator is lost';
+COMMENT ON COLUMN totp_recovery_codes.totp_recovery_code_id IS 'totp recovery code id';
+COMMENT ON COLUMN totp_recovery_codes.user_id IS 'user id the code belongs to';
+COMMENT ON COLUMN totp_recovery_codes.code_hash IS 'sha-256 hex digest of the code, the code itself is never stored';
+COMMENT ON COLUMN totp_recovery_codes.used_at IS 'set when the code was used';
+COMMENT ON COLUMN totp_recovery_codes.created_at IS 'created datetime';
+
+CREATE TABLE IF NOT EXISTS login_challenges (
+    login_challenge_id BIGSERIAL,
+    user_id BIGINT NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (login_challenge_id),
+    UNIQUE (token_hash),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+COMMENT ON TABLE login_challenges IS 'logins that passed the password check and wait for a second factor';
+COMMENT ON COLUMN login_challenges.login_challenge_id IS 'login challenge id';
+COMMENT ON COLUMN login_challenges.user_id IS 'user id that passed the password check';
+COMMENT ON COLUMN login_challenges.token_hash IS 'sha-256 hex digest of the challenge token, the token itself is never stored';
+COMMENT ON COLUMN login_challenges.attempts IS 'rejected codes so far, the challenge is void after a few';
+COMMENT ON COLUMN login_challenges.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN login_challenges.used_at IS 'set when a code completed the login, or when too many were rejected';
+COMMENT ON COLUMN login_challenges.created_at IS 'created datetime';
@@ -0,0 +1,6 @@
+-- bumped by every write that changes what GET returns, exposed as the ETag of the resource
+ALTER TABLE users ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+COMMENT ON COLUMN users.version IS 'row version for optimistic concurrency control, sent as ETag';
+
+ALTER TABLE words ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+COMMENT ON COLUMN words.version IS 'row version for optimistic concurrency control, sent as ETag';
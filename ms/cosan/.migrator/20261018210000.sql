@@ -0,0 +1,25 @@
+-- responses of create requests kept for a day, so that a retried request replays instead of creating twice
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    idempotency_key_id BIGSERIAL,
+    principal VARCHAR(64) NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    request_hash VARCHAR(64) NOT NULL,
+    status_code INTEGER,
+    content_type VARCHAR(255),
+    response_body BYTEA,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (idempotency_key_id),
+    UNIQUE (principal, idempotency_key)
+);
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires_at ON idempotency_keys (expires_at);
+COMMENT ON TABLE idempotency_keys IS 'Idempotency-Key headers of create requests and the response they produced';
+COMMENT ON COLUMN idempotency_keys.idempotency_key_id IS 'idempotency key id';
+COMMENT ON COLUMN idempotency_keys.principal IS 'user the key belongs to, or anonymous for requests without a token';
+COMMENT ON COLUMN idempotency_keys.idempotency_key IS 'key chosen by the client';
+COMMENT ON COLUMN idempotency_keys.request_hash IS 'sha-256 hex digest of method, path and body, a reuse with another request is rejected';
+COMMENT ON COLUMN idempotency_keys.status_code IS 'status of the stored response, null while the first request is still running';
+COMMENT ON COLUMN idempotency_keys.content_type IS 'content type of the stored response';
+COMMENT ON COLUMN idempotency_keys.response_body IS 'body of the stored response';
+COMMENT ON COLUMN idempotency_keys.expires_at IS 'expiration datetime, the key may be used for a new request afterwards';
+COMMENT ON COLUMN idempotency_keys.created_at IS 'created datetime';
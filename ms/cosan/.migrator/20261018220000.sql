@@ -0,0 +1,13 @@
+-- the SQL backfill of normalized_word only lowercased ASCII and never folded ß or final sigma,
+-- so every existing key is recomputed once by the API with WordString::normalized at startup
+CREATE TABLE IF NOT EXISTS word_rekey_queue (
+    word_id BIGINT NOT NULL,
+    PRIMARY KEY (word_id),
+    FOREIGN KEY (word_id) REFERENCES words(word_id) ON DELETE CASCADE
+);
+COMMENT ON TABLE word_rekey_queue IS 'words whose normalized_word still has to be recomputed by the API';
+COMMENT ON COLUMN word_rekey_queue.word_id IS 'word id';
+
+INSERT INTO word_rekey_queue (word_id)
+    SELECT word_id FROM words
+ON CONFLICT (word_id) DO NOTHING;
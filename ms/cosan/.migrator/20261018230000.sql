@@ -0,0 +1,3 @@
+-- multiple choice quizzes show the definition, so the prompt no longer gives the answer away
+ALTER TABLE words ADD COLUMN IF NOT EXISTS definition VARCHAR(1000);
+COMMENT ON COLUMN words.definition IS 'meaning of the word, the prompt of multiple choice quiz questions';
@@ -0,0 +1,12 @@
+-- replays carry every header of the first response, not only its content type
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS response_headers JSONB;
+UPDATE idempotency_keys
+    SET response_headers = jsonb_build_array(jsonb_build_array('content-type', content_type))
+WHERE
+    content_type IS NOT NULL;
+ALTER TABLE idempotency_keys DROP COLUMN IF EXISTS content_type;
+-- anonymous keys are scoped by client address or request digest, which outgrow the user prefix
+ALTER TABLE idempotency_keys ALTER COLUMN principal TYPE VARCHAR(128);
+COMMENT ON COLUMN idempotency_keys.principal IS 'user the key belongs to, else the client address, else the request digest for requests without a token';
+COMMENT ON COLUMN idempotency_keys.status_code IS 'status of the stored response whatever it is, null while the first request is still running';
+COMMENT ON COLUMN idempotency_keys.response_headers IS 'headers of the stored response as [name, value] pairs in the order sent';
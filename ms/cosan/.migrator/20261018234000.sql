@@ -0,0 +1,6 @@
+-- anonymous keys are scoped by the request digest alone, the client address no longer plays a part
+DELETE FROM idempotency_keys WHERE principal LIKE 'ip:%';
+-- server errors are released for the retry to run again rather than replayed
+DELETE FROM idempotency_keys WHERE status_code >= 500;
+COMMENT ON COLUMN idempotency_keys.principal IS 'user the key belongs to, else the request digest for requests without a token';
+COMMENT ON COLUMN idempotency_keys.status_code IS 'status of the stored success or client error, null while the first request is still running';
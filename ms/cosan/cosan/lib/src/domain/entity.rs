@@ -1,5 +1,9 @@
 use crate::util;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Debug, Clone)]
 pub struct UserId(i64);
@@ -60,9 +64,14 @@ impl PasswordHash {
         self.0.as_str()
     }
 
-    pub async fn verify(&self, password: &str) -> Result<bool, bcrypt::BcryptError> {
+    pub async fn verify(&self, password: &str) -> Result<bool, util::crypt::CryptError> {
         util::crypt::verify_password(password, &self.0).await
     }
+
+    /// True for legacy bcrypt hashes and Argon2id hashes with weaker parameters.
+    pub fn needs_rehash(&self) -> bool {
+        util::crypt::needs_rehash(&self.0)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -72,12 +81,12 @@ impl Password {
         Self(password.to_string())
     }
 
-    pub async fn hash(&self) -> Result<PasswordHash, bcrypt::BcryptError> {
+    pub async fn hash(&self) -> Result<PasswordHash, util::crypt::CryptError> {
         let hashed_password = util::crypt::hash_password(&self.0).await?;
         Ok(PasswordHash::new(hashed_password.as_str()))
     }
 
-    pub async fn verify(&self, password: &str) -> Result<bool, bcrypt::BcryptError> {
+    pub async fn verify(&self, password: &str) -> Result<bool, util::crypt::CryptError> {
         util::crypt::verify_password(password, &self.0).await
     }
 
@@ -98,6 +107,32 @@ impl Email {
     }
 }
 
+/// Whether the user confirmed the current email address, required to register words.
+#[derive(Debug, Clone)]
+pub struct EmailVerified(bool);
+impl EmailVerified {
+    pub fn new(email_verified: bool) -> Self {
+        Self(email_verified)
+    }
+
+    pub fn value(&self) -> bool {
+        self.0
+    }
+}
+
+/// Row version, bumped by every write that changes the representation and sent as its ETag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version(i64);
+impl Version {
+    pub fn new(version: i64) -> Self {
+        Self(version)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Country(String);
 impl Country {
@@ -125,13 +160,108 @@ impl WordId {
 #[derive(Debug, Clone)]
 pub struct WordString(String);
 impl WordString {
+    /// Keeps the display form: NFC, trimmed, inner whitespace collapsed.
     pub fn new(word: &str) -> Self {
-        Self(word.to_string())
+        Self(Self::collapse_whitespace(&word.nfc().collect::<String>()))
     }
 
     pub fn value(&self) -> &str {
         self.0.as_str()
     }
+
+    /// Canonical form used for uniqueness and lookup: NFKC, case folded,
+    /// whitespace collapsed. "Apple", "apple " and "ａｐｐｌｅ" all map to "apple".
+    pub fn normalized(&self) -> String {
+        let folded = caseless::default_case_fold_str(&self.0.nfkc().collect::<String>());
+        Self::collapse_whitespace(&folded.nfkc().collect::<String>())
+    }
+
+    fn collapse_whitespace(word: &str) -> String {
+        word.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+}
+
+/// Words recomputed per query when the startup rekey drains word_rekey_queue.
+pub const WORD_REKEY_BATCH_SIZE: i64 = 500;
+
+/// Meaning of a word, asked for in multiple choice quizzes.
+#[derive(Debug, Clone)]
+pub struct WordDefinition(String);
+impl WordDefinition {
+    pub const MAX_LENGTH: usize = 1000;
+
+    pub fn new(definition: &str) -> Self {
+        Self(definition.trim().to_string())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimilarityScore(f64);
+impl SimilarityScore {
+    pub fn new(score: f64) -> Self {
+        Self(score)
+    }
+
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rank(i64);
+impl Rank {
+    pub fn new(rank: i64) -> Self {
+        Self(rank)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistrationCount(i64);
+impl RegistrationCount {
+    pub fn new(registration_count: i64) -> Self {
+        Self(registration_count)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankingPeriod {
+    Day,
+    Week,
+    Month,
+    All,
+}
+impl RankingPeriod {
+    pub fn new(period: &str) -> Option<Self> {
+        match period {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+
+    /// Length of the window in days, `None` means no lower bound.
+    pub fn days(&self) -> Option<i32> {
+        match self {
+            Self::Day => Some(1),
+            Self::Week => Some(7),
+            Self::Month => Some(30),
+            Self::All => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -146,6 +276,388 @@ impl UserWordId {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserWordSort {
+    CreatedAtAsc,
+    CreatedAtDesc,
+    Word,
+}
+impl UserWordSort {
+    pub fn new(sort: &str) -> Option<Self> {
+        match sort {
+            "created_at_asc" => Some(Self::CreatedAtAsc),
+            "created_at_desc" => Some(Self::CreatedAtDesc),
+            "word" => Some(Self::Word),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> &'static str {
+        match self {
+            Self::CreatedAtAsc => "created_at_asc",
+            Self::CreatedAtDesc => "created_at_desc",
+            Self::Word => "word",
+        }
+    }
+}
+
+/// Sort key of the last row of a page, typed by the sort order it was taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageKey {
+    CreatedAt(NaiveDateTime),
+    Word(String),
+}
+impl PageKey {
+    const CREATED_AT_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+
+    pub fn new(sort: UserWordSort, key: &str) -> Option<Self> {
+        match sort {
+            UserWordSort::CreatedAtAsc | UserWordSort::CreatedAtDesc => {
+                NaiveDateTime::parse_from_str(key, Self::CREATED_AT_FORMAT)
+                    .ok()
+                    .map(Self::CreatedAt)
+            }
+            UserWordSort::Word => Some(Self::Word(key.to_string())),
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match self {
+            Self::CreatedAt(created_at) => created_at.format(Self::CREATED_AT_FORMAT).to_string(),
+            Self::Word(word) => word.clone(),
+        }
+    }
+}
+
+/// Keyset position of the last row of a page: the sort key of that row and
+/// its user_word_id as a tie breaker. Encoded as an opaque hex string so
+/// clients do not depend on its layout.
+#[derive(Debug, Clone)]
+pub struct PageCursor {
+    sort: UserWordSort,
+    key: PageKey,
+    id: i64,
+}
+impl PageCursor {
+    const SEPARATOR: char = '\n';
+
+    pub fn new(sort: UserWordSort, key: PageKey, id: i64) -> Self {
+        Self { sort, key, id }
+    }
+
+    pub fn encode(&self) -> String {
+        format!(
+            "{}{}{}{}{}",
+            self.sort.value(),
+            Self::SEPARATOR,
+            self.id,
+            Self::SEPARATOR,
+            self.key.value()
+        )
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+    }
+
+    /// Returns `None` if the cursor is malformed, its key does not fit the sort
+    /// order or it was issued for another sort order.
+    pub fn decode(cursor: &str, sort: UserWordSort) -> Option<Self> {
+        if !cursor.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let decoded = String::from_utf8(bytes).ok()?;
+
+        let mut parts = decoded.splitn(3, Self::SEPARATOR);
+        let cursor_sort = UserWordSort::new(parts.next()?)?;
+        let id = parts.next()?.parse::<i64>().ok()?;
+        if cursor_sort != sort {
+            return None;
+        }
+        let key = PageKey::new(sort, parts.next()?)?;
+
+        Some(Self::new(sort, key, id))
+    }
+
+    pub fn key(&self) -> &PageKey {
+        &self.key
+    }
+
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+impl ExportFormat {
+    pub fn new(format: &str) -> Option<Self> {
+        match format {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "ndjson" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EaseFactor(f64);
+impl EaseFactor {
+    pub const INITIAL: f64 = 2.5;
+    pub const MIN: f64 = 1.3;
+
+    pub fn new(ease_factor: f64) -> Self {
+        Self(ease_factor.max(Self::MIN))
+    }
+
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntervalDays(i32);
+impl IntervalDays {
+    pub fn new(interval_days: i32) -> Self {
+        Self(interval_days)
+    }
+
+    pub fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Repetitions(i32);
+impl Repetitions {
+    pub fn new(repetitions: i32) -> Self {
+        Self(repetitions)
+    }
+
+    pub fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DueAt(DateTime<Utc>);
+impl DueAt {
+    pub fn new(due_at: DateTime<Utc>) -> Self {
+        Self(due_at)
+    }
+
+    pub fn value(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// SM-2 recall grade: 0 (blackout) to 5 (perfect), 3 and above is a pass.
+#[derive(Debug, Clone, Copy)]
+pub struct ReviewGrade(u8);
+impl ReviewGrade {
+    pub const MAX: u8 = 5;
+    pub const PASS: u8 = 3;
+
+    pub fn new(grade: u8) -> Option<Self> {
+        (grade <= Self::MAX).then_some(Self(grade))
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReviewState {
+    pub ease_factor: EaseFactor,
+    pub interval_days: IntervalDays,
+    pub repetitions: Repetitions,
+    pub due_at: DueAt,
+}
+impl ReviewState {
+    pub fn new(
+        ease_factor: EaseFactor,
+        interval_days: IntervalDays,
+        repetitions: Repetitions,
+        due_at: DueAt,
+    ) -> Self {
+        Self {
+            ease_factor,
+            interval_days,
+            repetitions,
+            due_at,
+        }
+    }
+
+    /// Next state after a review at `now`, following SM-2: a failed recall
+    /// restarts the repetition sequence and keeps the ease factor, a pass grows
+    /// the interval by the ease factor and moves the ease factor with the grade.
+    pub fn schedule(&self, grade: ReviewGrade, now: DateTime<Utc>) -> Self {
+        if grade.value() < ReviewGrade::PASS {
+            return Self::new(
+                self.ease_factor,
+                IntervalDays::new(1),
+                Repetitions::new(0),
+                DueAt::new(now + chrono::Duration::days(1)),
+            );
+        }
+
+        let interval_days = match self.repetitions.value() {
+            0 => 1,
+            1 => 6,
+            _ => (self.interval_days.value() as f64 * self.ease_factor.value()).round() as i32,
+        };
+        let repetitions = self.repetitions.value() + 1;
+
+        let miss = (ReviewGrade::MAX - grade.value()) as f64;
+        let ease_factor = self.ease_factor.value() + (0.1 - miss * (0.08 + miss * 0.02));
+
+        Self::new(
+            EaseFactor::new(ease_factor),
+            IntervalDays::new(interval_days),
+            Repetitions::new(repetitions),
+            DueAt::new(now + chrono::Duration::days(interval_days as i64)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuizMode {
+    MultipleChoice,
+    Typing,
+}
+impl QuizMode {
+    pub fn new(mode: &str) -> Option<Self> {
+        match mode {
+            "multiple_choice" => Some(Self::MultipleChoice),
+            "typing" => Some(Self::Typing),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> &'static str {
+        match self {
+            Self::MultipleChoice => "multiple_choice",
+            Self::Typing => "typing",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuizId(i64);
+impl QuizId {
+    pub fn new(quiz_id: i64) -> Self {
+        Self(quiz_id)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Seed of the quiz generator, the same seed over the same words yields the same quiz.
+#[derive(Debug, Clone, Copy)]
+pub struct QuizSeed(u64);
+impl QuizSeed {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuizPosition(i32);
+impl QuizPosition {
+    pub fn new(position: i32) -> Self {
+        Self(position)
+    }
+
+    pub fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuizScore(i32);
+impl QuizScore {
+    pub fn new(score: i32) -> Self {
+        Self(score)
+    }
+
+    pub fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeckId(i64);
+impl DeckId {
+    pub fn new(deck_id: i64) -> Self {
+        Self(deck_id)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeckName(String);
+impl DeckName {
+    pub fn new(name: &str) -> Self {
+        Self(name.trim().to_string())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Read-only capability for a deck, anyone holding it can view and clone the deck.
+#[derive(Debug, Clone)]
+pub struct ShareToken(String);
+impl ShareToken {
+    pub fn new(share_token: &str) -> Self {
+        Self(share_token.to_string())
+    }
+
+    pub fn generate() -> Self {
+        let bytes: [u8; 24] = rand::thread_rng().gen();
+        Self(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CreatedAt(DateTime<Utc>);
 impl CreatedAt {
@@ -158,6 +670,48 @@ impl CreatedAt {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    /// Every scope a cosan route may require, granted to all roles alike.
+    pub const SCOPES: &'static [&'static str] = &[
+        "users:read",
+        "users:write",
+        "words:read",
+        "words:write",
+        "user_words:read",
+        "user_words:write",
+        "reviews:read",
+        "reviews:write",
+        "quizzes:write",
+        "decks:read",
+        "decks:write",
+    ];
+
+    pub fn new(role: &str) -> Option<Self> {
+        match role {
+            "user" => Some(Self::User),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn scopes(&self) -> Vec<String> {
+        Self::SCOPES.iter().map(|scope| scope.to_string()).collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct User {
     pub user_id: UserId,
@@ -167,11 +721,50 @@ pub struct User {
     pub password: PasswordHash,
     pub email: Email,
     pub country: Country,
+    pub email_verified: EmailVerified,
+    pub version: Version,
 }
 
 impl User {
     pub fn new(
         user_id: UserId,
+        profile: UserProfile,
+        email_verified: EmailVerified,
+        version: Version,
+    ) -> Self {
+        Self {
+            user_id,
+            last_name: profile.last_name,
+            first_name: profile.first_name,
+            login_id: profile.login_id,
+            password: profile.password,
+            email: profile.email,
+            country: profile.country,
+            email_verified,
+            version,
+        }
+    }
+
+    /// Words are registered, whether one by one, by import or by cloning a deck, only from a
+    /// verified address.
+    pub fn may_register_words(&self) -> bool {
+        self.email_verified.value()
+    }
+}
+
+/// The fields a user registers with, the password already hashed.
+#[derive(Debug, Clone)]
+pub struct UserProfile {
+    pub last_name: LastName,
+    pub first_name: FirstName,
+    pub login_id: LoginId,
+    pub password: PasswordHash,
+    pub email: Email,
+    pub country: Country,
+}
+
+impl UserProfile {
+    pub fn new(
         last_name: LastName,
         first_name: FirstName,
         login_id: LoginId,
@@ -180,7 +773,6 @@ impl User {
         country: Country,
     ) -> Self {
         Self {
-            user_id,
             last_name,
             first_name,
             login_id,
@@ -191,15 +783,452 @@ impl User {
     }
 }
 
+/// The fields a full update replaces, everything a user registers with but the password.
 #[derive(Debug, Clone)]
-pub struct Word {
-    pub word_id: WordId,
-    pub word: WordString,
+pub struct UserDetails {
+    pub last_name: LastName,
+    pub first_name: FirstName,
+    pub login_id: LoginId,
+    pub email: Email,
+    pub country: Country,
 }
-impl Word {
-    pub fn new(word_id: WordId, word: WordString) -> Self {
-        Self { word_id, word }
-    }
+
+impl UserDetails {
+    pub fn new(
+        last_name: LastName,
+        first_name: FirstName,
+        login_id: LoginId,
+        email: Email,
+        country: Country,
+    ) -> Self {
+        Self {
+            last_name,
+            first_name,
+            login_id,
+            email,
+            country,
+        }
+    }
+}
+
+/// What the login endpoint needs to authenticate a user and mint a token.
+#[derive(Debug, Clone)]
+pub struct UserCredential {
+    pub user_id: UserId,
+    pub password: PasswordHash,
+    pub role: Role,
+    /// The password alone does not finish the login.
+    pub totp_enabled: bool,
+}
+
+impl UserCredential {
+    pub fn new(user_id: UserId, password: PasswordHash, role: Role, totp_enabled: bool) -> Self {
+        Self {
+            user_id,
+            password,
+            role,
+            totp_enabled,
+        }
+    }
+}
+
+/// Opaque token exchanged for a new access token, only its digest is persisted.
+#[derive(Debug, Clone)]
+pub struct RefreshToken(String);
+impl RefreshToken {
+    pub fn new(refresh_token: &str) -> Self {
+        Self(refresh_token.trim().to_string())
+    }
+
+    pub fn generate() -> Self {
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        Self(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn digest(&self) -> String {
+        util::crypt::digest_token(&self.0)
+    }
+}
+
+/// Single-use secret mailed to the user, only its digest is persisted.
+#[derive(Debug, Clone)]
+pub struct VerificationToken(String);
+impl VerificationToken {
+    pub fn new(token: &str) -> Self {
+        Self(token.trim().to_string())
+    }
+
+    pub fn generate() -> Self {
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        Self(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn digest(&self) -> String {
+        util::crypt::digest_token(&self.0)
+    }
+}
+
+pub const EMAIL_VERIFICATION_TTL_SECONDS: i64 = 24 * 60 * 60;
+
+/// Reset tokens grant a password change, so they live far shorter than verification ones.
+pub const PASSWORD_RESET_TTL_SECONDS: i64 = 60 * 60;
+
+/// Second factor state of a user, the secret is pending until a code confirmed it.
+#[derive(Debug, Clone)]
+pub struct UserTotp {
+    pub user_id: UserId,
+    pub login_id: LoginId,
+    pub secret: Option<String>,
+    pub enabled: bool,
+}
+
+impl UserTotp {
+    pub fn new(user_id: UserId, login_id: LoginId, secret: Option<String>, enabled: bool) -> Self {
+        Self {
+            user_id,
+            login_id,
+            secret,
+            enabled,
+        }
+    }
+}
+
+/// Code entered for the second factor, either from the authenticator or a recovery code.
+#[derive(Debug, Clone)]
+pub struct TotpCode(String);
+impl TotpCode {
+    pub fn new(code: &str) -> Self {
+        Self(code.chars().filter(|c| !c.is_whitespace()).collect())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Authenticator codes are six digits, anything else can only be a recovery code.
+    pub fn is_authenticator_code(&self) -> bool {
+        self.0.len() == 6 && self.0.chars().all(|c| c.is_ascii_digit())
+    }
+}
+
+/// Single-use stand-in for an authenticator code, shown once at enrollment, only its digest is persisted.
+#[derive(Debug, Clone)]
+pub struct RecoveryCode(String);
+impl RecoveryCode {
+    /// Letters and digits that cannot be mistaken for one another when typed from paper.
+    const ALPHABET: &'static [u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+    pub fn new(code: &str) -> Self {
+        Self(code.trim().to_string())
+    }
+
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut chars = (0..10).map(|_| *Self::ALPHABET.choose(&mut rng).unwrap() as char);
+        let head: String = chars.by_ref().take(5).collect();
+        let tail: String = chars.collect();
+        Self(format!("{}-{}", head, tail))
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Case and separators are ignored, so `ABCDE-FGHJK` and `abcdefghjk` are the same code.
+    pub fn digest(&self) -> String {
+        let normalized: String = self
+            .0
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        util::crypt::digest_token(&normalized)
+    }
+}
+
+pub const TOTP_ISSUER: &str = "Cosan";
+pub const TOTP_RECOVERY_CODE_COUNT: usize = 10;
+
+/// Login that passed the password check and waits for a second factor.
+#[derive(Debug, Clone)]
+pub struct LoginChallenge {
+    pub login_challenge_id: i64,
+    pub user_id: UserId,
+    /// Rejected codes count as failed logins of this id.
+    pub login_id: LoginId,
+    pub role: Role,
+    pub attempts: i32,
+}
+
+impl LoginChallenge {
+    pub fn new(
+        login_challenge_id: i64,
+        user_id: UserId,
+        login_id: LoginId,
+        role: Role,
+        attempts: i32,
+    ) -> Self {
+        Self {
+            login_challenge_id,
+            user_id,
+            login_id,
+            role,
+            attempts,
+        }
+    }
+}
+
+pub const LOGIN_CHALLENGE_TTL_SECONDS: i64 = 5 * 60;
+/// Rejected codes after which the challenge is void and the login starts over with the password.
+pub const LOGIN_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+
+/// How long the response to a create request answers retries with the same `Idempotency-Key`.
+pub const IDEMPOTENCY_KEY_TTL_SECONDS: i64 = 24 * 60 * 60;
+/// A key still running after this long is taken over, its request most likely died on the way.
+pub const IDEMPOTENCY_KEY_LOCK_SECONDS: i64 = 60;
+
+/// An `Idempotency-Key` already in use and the response of its first request, if that has finished.
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    pub request_hash: String,
+    pub response: Option<StoredResponse>,
+}
+
+impl IdempotencyKey {
+    pub fn new(request_hash: String, response: Option<StoredResponse>) -> Self {
+        Self {
+            request_hash,
+            response,
+        }
+    }
+}
+
+/// The final response to a request, kept to be replayed byte for byte.
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status_code: u16,
+    /// Name and value pairs in the order sent, a repeated header appears once per value.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    pub fn new(status_code: u16, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        Self {
+            status_code,
+            headers,
+            body,
+        }
+    }
+
+    /// Successes and client errors answer the request for good, a server error leaves it to be retried.
+    pub fn is_replayable(&self) -> bool {
+        matches!(self.status_code, 200..=299 | 400..=499)
+    }
+}
+
+/// Groups every refresh token rotated from the same login.
+#[derive(Debug, Clone)]
+pub struct TokenFamilyId(String);
+impl TokenFamilyId {
+    pub fn new(family_id: &str) -> Self {
+        Self(family_id.to_string())
+    }
+
+    pub fn generate() -> Self {
+        let bytes: [u8; 16] = rand::thread_rng().gen();
+        Self(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn value(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiresAt(DateTime<Utc>);
+impl ExpiresAt {
+    pub fn new(expires_at: DateTime<Utc>) -> Self {
+        Self(expires_at)
+    }
+
+    pub fn value(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// Stored state of one refresh token, together with the current role of its user.
+#[derive(Debug, Clone)]
+pub struct RefreshSession {
+    pub refresh_token_id: i64,
+    pub user_id: UserId,
+    pub family_id: TokenFamilyId,
+    pub role: Role,
+    pub expires_at: ExpiresAt,
+    pub used: bool,
+    pub revoked: bool,
+}
+
+impl RefreshSession {
+    pub fn new(
+        refresh_token_id: i64,
+        user_id: UserId,
+        family_id: TokenFamilyId,
+        role: Role,
+        expires_at: ExpiresAt,
+        used: bool,
+        revoked: bool,
+    ) -> Self {
+        Self {
+            refresh_token_id,
+            user_id,
+            family_id,
+            role,
+            expires_at,
+            used,
+            revoked,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.value() <= now
+    }
+}
+
+/// How many failed logins are free before each further attempt waits twice as long.
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    pub threshold: i64,
+    pub base_delay_seconds: i64,
+    pub max_delay_seconds: i64,
+}
+
+/// Per login id, reset by a successful login.
+pub const LOGIN_ID_LOCKOUT: LockoutPolicy = LockoutPolicy {
+    threshold: 5,
+    base_delay_seconds: 30,
+    max_delay_seconds: 15 * 60,
+};
+
+/// Per client address, looser because one address may front many users.
+pub const IP_ADDRESS_LOCKOUT: LockoutPolicy = LockoutPolicy {
+    threshold: 20,
+    base_delay_seconds: 30,
+    max_delay_seconds: 15 * 60,
+};
+
+/// Failures older than this no longer count towards a lockout.
+pub const LOGIN_ATTEMPT_WINDOW_SECONDS: i64 = 60 * 60;
+
+/// Recent failed logins for one login id or client address.
+#[derive(Debug, Clone)]
+pub struct LoginFailures {
+    pub count: i64,
+    pub last_failed_at: Option<DateTime<Utc>>,
+}
+
+impl LoginFailures {
+    pub fn new(count: i64, last_failed_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            count,
+            last_failed_at,
+        }
+    }
+
+    /// Seconds until the next attempt is allowed, `None` when it is allowed now.
+    pub fn retry_after(&self, policy: &LockoutPolicy, now: DateTime<Utc>) -> Option<u64> {
+        let last_failed_at = self.last_failed_at?;
+        if self.count < policy.threshold {
+            return None;
+        }
+
+        // 30s, 60s, 120s, ... capped, the exponent is clamped before it can overflow
+        let doublings = u32::try_from(self.count - policy.threshold)
+            .unwrap_or(u32::MAX)
+            .min(32);
+        let delay = policy
+            .base_delay_seconds
+            .saturating_mul(1_i64 << doublings)
+            .min(policy.max_delay_seconds);
+        let locked_until = last_failed_at + chrono::Duration::seconds(delay);
+
+        let remaining = (locked_until - now).num_milliseconds();
+        if remaining <= 0 {
+            return None;
+        }
+        // round up so clients never retry a moment too early
+        Some(u64::try_from((remaining + 999) / 1000).unwrap_or(1))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub word_id: WordId,
+    pub word: WordString,
+    pub definition: Option<WordDefinition>,
+    pub version: Version,
+}
+impl Word {
+    pub fn new(
+        word_id: WordId,
+        word: WordString,
+        definition: Option<WordDefinition>,
+        version: Version,
+    ) -> Self {
+        Self {
+            word_id,
+            word,
+            definition,
+            version,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchedWord {
+    pub word_id: WordId,
+    pub word: WordString,
+    pub score: SimilarityScore,
+}
+impl SearchedWord {
+    pub fn new(word_id: WordId, word: WordString, score: SimilarityScore) -> Self {
+        Self {
+            word_id,
+            word,
+            score,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WordRanking {
+    pub rank: Rank,
+    pub word_id: WordId,
+    pub word: WordString,
+    pub registration_count: RegistrationCount,
+}
+impl WordRanking {
+    pub fn new(
+        rank: Rank,
+        word_id: WordId,
+        word: WordString,
+        registration_count: RegistrationCount,
+    ) -> Self {
+        Self {
+            rank,
+            word_id,
+            word,
+            registration_count,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -217,29 +1246,79 @@ pub struct UserWord {
 impl UserWord {
     pub fn new(
         user_word_id: UserWordId,
+        owner: UserWordOwner,
+        word_id: WordId,
+        word: WordString,
+        created_at: CreatedAt,
+    ) -> Self {
+        Self {
+            user_word_id,
+            user_id: owner.user_id,
+            last_name: owner.last_name,
+            first_name: owner.first_name,
+            email: owner.email,
+            country: owner.country,
+            word_id,
+            word,
+            created_at,
+        }
+    }
+}
+
+/// The user a registered word belongs to, as listed with it.
+#[derive(Debug, Clone)]
+pub struct UserWordOwner {
+    pub user_id: UserId,
+    pub last_name: LastName,
+    pub first_name: FirstName,
+    pub email: Email,
+    pub country: Country,
+}
+impl UserWordOwner {
+    pub fn new(
         user_id: UserId,
         last_name: LastName,
         first_name: FirstName,
         email: Email,
         country: Country,
-        word_id: WordId,
-        word: WordString,
-        created_at: CreatedAt,
     ) -> Self {
         Self {
-            user_word_id,
             user_id,
             last_name,
             first_name,
             email,
             country,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportedWord {
+    pub word_id: WordId,
+    pub word: WordString,
+    pub created: bool,
+}
+impl ImportedWord {
+    pub fn new(word_id: WordId, word: WordString, created: bool) -> Self {
+        Self {
             word_id,
             word,
-            created_at,
+            created,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct ExportedWord {
+    pub word: WordString,
+    pub created_at: CreatedAt,
+}
+impl ExportedWord {
+    pub fn new(word: WordString, created_at: CreatedAt) -> Self {
+        Self { word, created_at }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UserWordRelation {
     pub user_id: UserId,
@@ -255,3 +1334,564 @@ impl UserWordRelation {
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub user_word_id: UserWordId,
+    pub word_id: WordId,
+    pub word: WordString,
+    pub state: ReviewState,
+}
+impl Review {
+    pub fn new(
+        user_word_id: UserWordId,
+        word_id: WordId,
+        word: WordString,
+        state: ReviewState,
+    ) -> Self {
+        Self {
+            user_word_id,
+            word_id,
+            word,
+            state,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuizQuestion {
+    pub position: QuizPosition,
+    pub word_id: WordId,
+    pub word: WordString,
+    pub prompt: String,
+    pub choices: Vec<WordString>,
+}
+impl QuizQuestion {
+    pub const CHOICES: usize = 4;
+
+    pub fn new(
+        position: QuizPosition,
+        word_id: WordId,
+        word: WordString,
+        prompt: &str,
+        choices: Vec<WordString>,
+    ) -> Self {
+        Self {
+            position,
+            word_id,
+            word,
+            prompt: prompt.to_string(),
+            choices,
+        }
+    }
+
+    /// Builds `count` questions from `words` with a ChaCha8 stream seeded by `seed`,
+    /// so inputs in the same order always produce the same quiz.
+    /// Multiple choice shows the definition and asks for the word among `distractors`,
+    /// so it only uses words that have one; typing shows the first letter and asks for the rest.
+    pub fn generate(
+        mode: QuizMode,
+        seed: QuizSeed,
+        count: usize,
+        words: &[Word],
+        distractors: &[Word],
+    ) -> Vec<Self> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.value());
+
+        let mut words = words
+            .iter()
+            .filter(|word| mode == QuizMode::Typing || word.definition.is_some())
+            .collect::<Vec<&Word>>();
+        words.shuffle(&mut rng);
+        words.truncate(count);
+
+        words
+            .into_iter()
+            .enumerate()
+            .map(|(position, word)| match mode {
+                QuizMode::MultipleChoice => {
+                    let normalized = word.word.normalized();
+                    let candidates = distractors
+                        .iter()
+                        .filter(|distractor| distractor.word.normalized() != normalized)
+                        .collect::<Vec<&Word>>();
+                    let mut choices = candidates
+                        .choose_multiple(&mut rng, Self::CHOICES - 1)
+                        .map(|distractor| distractor.word.clone())
+                        .collect::<Vec<WordString>>();
+                    choices.push(word.word.clone());
+                    choices.shuffle(&mut rng);
+
+                    Self::new(
+                        QuizPosition::new(position as i32),
+                        word.word_id.clone(),
+                        word.word.clone(),
+                        word.definition
+                            .as_ref()
+                            .map(|definition| definition.value())
+                            .unwrap_or_default(),
+                        choices,
+                    )
+                }
+                QuizMode::Typing => {
+                    let prompt = word
+                        .word
+                        .value()
+                        .chars()
+                        .enumerate()
+                        .map(|(i, c)| {
+                            if i == 0 || !c.is_alphanumeric() {
+                                c
+                            } else {
+                                '_'
+                            }
+                        })
+                        .collect::<String>();
+
+                    Self::new(
+                        QuizPosition::new(position as i32),
+                        word.word_id.clone(),
+                        word.word.clone(),
+                        &prompt,
+                        Vec::new(),
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// Answers are compared on the canonical form, so case and width do not matter.
+    pub fn is_correct(&self, answer: &WordString) -> bool {
+        self.word.normalized() == answer.normalized()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Quiz {
+    pub quiz_id: QuizId,
+    pub mode: QuizMode,
+    pub seed: QuizSeed,
+    pub questions: Vec<QuizQuestion>,
+}
+impl Quiz {
+    pub fn new(
+        quiz_id: QuizId,
+        mode: QuizMode,
+        seed: QuizSeed,
+        questions: Vec<QuizQuestion>,
+    ) -> Self {
+        Self {
+            quiz_id,
+            mode,
+            seed,
+            questions,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuizAnswer {
+    pub quiz_id: QuizId,
+    pub position: QuizPosition,
+    pub correct: bool,
+    pub score: QuizScore,
+    pub answered: i32,
+    pub total: i32,
+}
+impl QuizAnswer {
+    pub fn new(
+        quiz_id: QuizId,
+        position: QuizPosition,
+        correct: bool,
+        score: QuizScore,
+        answered: i32,
+        total: i32,
+    ) -> Self {
+        Self {
+            quiz_id,
+            position,
+            correct,
+            score,
+            answered,
+            total,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Deck {
+    pub deck_id: DeckId,
+    pub user_id: UserId,
+    pub name: DeckName,
+    pub share_token: Option<ShareToken>,
+    pub word_count: i64,
+    pub created_at: CreatedAt,
+}
+impl Deck {
+    pub fn new(
+        deck_id: DeckId,
+        user_id: UserId,
+        name: DeckName,
+        share_token: Option<ShareToken>,
+        word_count: i64,
+        created_at: CreatedAt,
+    ) -> Self {
+        Self {
+            deck_id,
+            user_id,
+            name,
+            share_token,
+            word_count,
+            created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeckWord {
+    pub user_word_id: UserWordId,
+    pub word_id: WordId,
+    pub word: WordString,
+}
+impl DeckWord {
+    pub fn new(user_word_id: UserWordId, word_id: WordId, word: WordString) -> Self {
+        Self {
+            user_word_id,
+            word_id,
+            word,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClonedDeck {
+    pub created: i64,
+    pub already_present: i64,
+}
+impl ClonedDeck {
+    pub fn new(created: i64, already_present: i64) -> Self {
+        Self {
+            created,
+            already_present,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_string_normalized_folds_case_width_and_spacing() {
+        assert_eq!(WordString::new(" Apple  Pie ").normalized(), "apple pie");
+        assert_eq!(WordString::new("ＡＰＰＬＥ").normalized(), "apple");
+    }
+
+    #[test]
+    fn word_string_normalized_gives_folded_characters_one_key() {
+        // what the startup rekey stores for existing words and what a new registration looks up
+        assert_eq!(WordString::new("Straße").normalized(), "strasse");
+        assert_eq!(
+            WordString::new("Straße").normalized(),
+            WordString::new("STRASSE").normalized()
+        );
+        assert_eq!(
+            WordString::new("ΟΔΟΣ").normalized(),
+            WordString::new("οδος").normalized()
+        );
+        assert_eq!(WordString::new("ÉCOLE").normalized(), "école");
+    }
+
+    fn review_state(ease_factor: f64, interval_days: i32, repetitions: i32) -> ReviewState {
+        ReviewState::new(
+            EaseFactor::new(ease_factor),
+            IntervalDays::new(interval_days),
+            Repetitions::new(repetitions),
+            DueAt::new(Utc::now()),
+        )
+    }
+
+    #[test]
+    fn review_state_schedule_follows_sm2_intervals_on_pass() {
+        let now = Utc::now();
+        let first =
+            review_state(EaseFactor::INITIAL, 0, 0).schedule(ReviewGrade::new(5).unwrap(), now);
+        assert_eq!(first.interval_days.value(), 1);
+        assert_eq!(first.repetitions.value(), 1);
+        assert!((first.ease_factor.value() - 2.6).abs() < 1e-9);
+        assert_eq!(first.due_at.value(), now + chrono::Duration::days(1));
+
+        let second = first.schedule(ReviewGrade::new(4).unwrap(), now);
+        assert_eq!(second.interval_days.value(), 6);
+        assert!((second.ease_factor.value() - 2.6).abs() < 1e-9);
+
+        let third = second.schedule(ReviewGrade::new(3).unwrap(), now);
+        assert_eq!(third.interval_days.value(), 16);
+        assert_eq!(third.repetitions.value(), 3);
+        assert!((third.ease_factor.value() - 2.46).abs() < 1e-9);
+    }
+
+    #[test]
+    fn review_state_schedule_keeps_ease_factor_on_failure() {
+        let now = Utc::now();
+        let failed = review_state(2.2, 15, 4).schedule(ReviewGrade::new(1).unwrap(), now);
+
+        assert!((failed.ease_factor.value() - 2.2).abs() < 1e-9);
+        assert_eq!(failed.interval_days.value(), 1);
+        assert_eq!(failed.repetitions.value(), 0);
+        assert_eq!(failed.due_at.value(), now + chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn review_state_schedule_never_drops_ease_factor_below_minimum() {
+        let state =
+            review_state(EaseFactor::MIN, 6, 2).schedule(ReviewGrade::new(3).unwrap(), Utc::now());
+
+        assert_eq!(state.ease_factor.value(), EaseFactor::MIN);
+    }
+
+    fn quiz_word(word_id: i64, word: &str, definition: Option<&str>) -> Word {
+        Word::new(
+            WordId::new(word_id),
+            WordString::new(word),
+            definition.map(WordDefinition::new),
+            Version::new(1),
+        )
+    }
+
+    fn quiz_words() -> Vec<Word> {
+        vec![
+            quiz_word(1, "apple", Some("a round fruit")),
+            quiz_word(2, "river", Some("a large natural stream of water")),
+            quiz_word(3, "window", None),
+            quiz_word(4, "candle", Some("a stick of wax with a wick")),
+        ]
+    }
+
+    fn quiz_distractors() -> Vec<Word> {
+        ["banana", "mountain", "door", "lamp", "Apple"]
+            .iter()
+            .enumerate()
+            .map(|(i, word)| quiz_word(10 + i as i64, word, None))
+            .collect()
+    }
+
+    #[test]
+    fn quiz_question_generate_is_fixed_by_seed() {
+        let questions = QuizQuestion::generate(
+            QuizMode::MultipleChoice,
+            QuizSeed::new(42),
+            3,
+            &quiz_words(),
+            &quiz_distractors(),
+        );
+
+        let generated = questions
+            .iter()
+            .map(|question| {
+                (
+                    question.position.value(),
+                    question.word.value().to_string(),
+                    question.prompt.clone(),
+                    question
+                        .choices
+                        .iter()
+                        .map(|choice| choice.value().to_string())
+                        .collect::<Vec<String>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            generated,
+            vec![
+                (
+                    0,
+                    "candle".to_string(),
+                    "a stick of wax with a wick".to_string(),
+                    vec!["banana", "candle", "door", "lamp"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                ),
+                (
+                    1,
+                    "river".to_string(),
+                    "a large natural stream of water".to_string(),
+                    vec!["Apple", "banana", "mountain", "river"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                ),
+                (
+                    2,
+                    "apple".to_string(),
+                    "a round fruit".to_string(),
+                    vec!["lamp", "door", "mountain", "apple"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn quiz_question_typing_masks_all_but_the_first_letter() {
+        let questions =
+            QuizQuestion::generate(QuizMode::Typing, QuizSeed::new(42), 4, &quiz_words(), &[]);
+
+        assert_eq!(questions.len(), 4);
+        let window = questions
+            .iter()
+            .find(|question| question.word.value() == "window")
+            .unwrap();
+        assert_eq!(window.prompt, "w_____");
+        assert!(window.choices.is_empty());
+    }
+
+    #[test]
+    fn page_cursor_round_trips_created_at_key() {
+        let created_at =
+            NaiveDateTime::parse_from_str("2026-10-18T09:30:15.123456Z", "%Y-%m-%dT%H:%M:%S%.fZ")
+                .unwrap();
+        let cursor = PageCursor::new(
+            UserWordSort::CreatedAtDesc,
+            PageKey::CreatedAt(created_at),
+            42,
+        );
+
+        let decoded = PageCursor::decode(&cursor.encode(), UserWordSort::CreatedAtDesc).unwrap();
+        assert_eq!(decoded.key(), &PageKey::CreatedAt(created_at));
+        assert_eq!(decoded.id(), 42);
+    }
+
+    #[test]
+    fn page_cursor_round_trips_word_key_with_separator() {
+        let cursor = PageCursor::new(UserWordSort::Word, PageKey::Word("a\nb".to_string()), 7);
+
+        let decoded = PageCursor::decode(&cursor.encode(), UserWordSort::Word).unwrap();
+        assert_eq!(decoded.key(), &PageKey::Word("a\nb".to_string()));
+        assert_eq!(decoded.id(), 7);
+    }
+
+    #[test]
+    fn page_cursor_rejects_other_sort_order() {
+        let cursor = PageCursor::new(UserWordSort::Word, PageKey::Word("apple".to_string()), 1);
+
+        assert!(PageCursor::decode(&cursor.encode(), UserWordSort::CreatedAtAsc).is_none());
+    }
+
+    #[test]
+    fn page_cursor_rejects_malformed_input() {
+        assert!(PageCursor::decode("abc", UserWordSort::Word).is_none());
+        assert!(PageCursor::decode("zz", UserWordSort::Word).is_none());
+        assert!(PageCursor::decode("", UserWordSort::Word).is_none());
+    }
+
+    #[test]
+    fn page_cursor_rejects_created_at_key_that_is_not_a_timestamp() {
+        let forged: String = "created_at_asc\n1\nyesterday"
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        assert!(PageCursor::decode(&forged, UserWordSort::CreatedAtAsc).is_none());
+    }
+
+    fn login_failures(count: i64, seconds_ago: i64, now: DateTime<Utc>) -> LoginFailures {
+        LoginFailures::new(count, Some(now - chrono::Duration::seconds(seconds_ago)))
+    }
+
+    #[test]
+    fn login_failures_retry_after_allows_attempts_below_threshold() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(4, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            None
+        );
+        assert_eq!(
+            LoginFailures::new(9, None).retry_after(&LOGIN_ID_LOCKOUT, now),
+            None
+        );
+    }
+
+    #[test]
+    fn login_failures_retry_after_doubles_delay_per_failure_over_threshold() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(5, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(30)
+        );
+        assert_eq!(
+            login_failures(6, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(60)
+        );
+        assert_eq!(
+            login_failures(7, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(120)
+        );
+    }
+
+    #[test]
+    fn login_failures_retry_after_caps_delay_and_survives_huge_counts() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(10, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(900)
+        );
+        assert_eq!(
+            login_failures(i64::MAX, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(900)
+        );
+    }
+
+    #[test]
+    fn login_failures_retry_after_counts_down_and_rounds_up() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(5, 10, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(20)
+        );
+        let failed_at = now - chrono::Duration::milliseconds(29_500);
+        assert_eq!(
+            LoginFailures::new(5, Some(failed_at)).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(1)
+        );
+        assert_eq!(
+            login_failures(5, 30, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            None
+        );
+    }
+
+    fn user(email_verified: bool) -> User {
+        User::new(
+            UserId::new(1),
+            UserProfile::new(
+                LastName::new("Doe"),
+                FirstName::new("Jane"),
+                LoginId::new("jane"),
+                PasswordHash::new("$argon2id$"),
+                Email::new("jane@example.com"),
+                Country::new("JP"),
+            ),
+            EmailVerified::new(email_verified),
+            Version::new(1),
+        )
+    }
+
+    #[test]
+    fn user_may_register_words_only_once_email_is_verified() {
+        assert!(!user(false).may_register_words());
+        assert!(user(true).may_register_words());
+    }
+
+    #[test]
+    fn stored_response_replays_successes_and_client_errors_only() {
+        let stored = |status_code| StoredResponse::new(status_code, vec![], vec![]);
+        assert!(stored(201).is_replayable());
+        assert!(stored(422).is_replayable());
+        assert!(!stored(500).is_replayable());
+        assert!(!stored(503).is_replayable());
+        assert!(!stored(304).is_replayable());
+    }
+}
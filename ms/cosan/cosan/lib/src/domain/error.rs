@@ -0,0 +1,132 @@
+use crate::util::crypt::CryptError;
+use crate::util::mailer::MailError;
+use crate::util::totp::TotpError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+// Postgres SQLSTATE codes surfaced as client errors
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+const NOT_NULL_VIOLATION: &str = "23502";
+const CHECK_VIOLATION: &str = "23514";
+
+#[derive(Debug, Error)]
+pub enum CosanError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("One or more fields are invalid")]
+    InvalidFields(FieldErrors),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    /// The `If-Match` version is no longer current.
+    #[error("{0}")]
+    PreconditionFailed(String),
+    /// A write came without `If-Match`.
+    #[error("{0}")]
+    PreconditionRequired(String),
+    /// Rejected until the given number of seconds has passed.
+    #[error("{0}")]
+    TooManyRequests(String, u64),
+    #[error("{0}")]
+    Internal(String),
+}
+
+/// Validation messages keyed by the request field they belong to.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(transparent)]
+pub struct FieldErrors(BTreeMap<String, Vec<String>>);
+
+impl FieldErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0
+            .entry(field.to_string())
+            .or_default()
+            .push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn value(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.0
+    }
+
+    /// Ok when nothing was collected, otherwise every message at once.
+    pub fn into_result(self) -> Result<(), CosanError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(CosanError::InvalidFields(self))
+        }
+    }
+}
+
+impl From<sqlx::Error> for CosanError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::RowNotFound => Self::NotFound("Resource not found".to_string()),
+            sqlx::Error::Database(db_err) => {
+                let detail = db_err
+                    .constraint()
+                    .map(|constraint| format!(" ({})", constraint))
+                    .unwrap_or_default();
+                match db_err.code().as_deref() {
+                    Some(UNIQUE_VIOLATION) => {
+                        Self::Conflict(format!("Resource already exists{}", detail))
+                    }
+                    Some(FOREIGN_KEY_VIOLATION) => {
+                        Self::Validation(format!("Referenced resource does not exist{}", detail))
+                    }
+                    Some(NOT_NULL_VIOLATION) | Some(CHECK_VIOLATION) => {
+                        Self::Validation(format!("Constraint violated{}", detail))
+                    }
+                    _ => Self::Internal(err.to_string()),
+                }
+            }
+            _ => Self::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<CryptError> for CosanError {
+    fn from(err: CryptError) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+impl From<MailError> for CosanError {
+    fn from(err: MailError) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+impl From<TotpError> for CosanError {
+    fn from(err: TotpError) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+impl From<anyhow::Error> for CosanError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<sqlx::Error>() {
+            Ok(err) => err.into(),
+            Err(err) => Self::Internal(err.to_string()),
+        }
+    }
+}
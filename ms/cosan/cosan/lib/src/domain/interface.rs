@@ -1,5 +1,6 @@
 use crate::domain::entity;
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use sqlx;
 use sqlx::Pool;
 
@@ -19,24 +20,115 @@ pub trait UserRepositoryTrait: Clone + Send + Sync + 'static {
         country: &str,
     ) -> Result<Option<entity::User>, sqlx::Error>;
 
+    /// `versions` is the If-Match precondition, None accepts any, a stale one finds no row.
     async fn update_user(
         &self,
         user_id: i64,
-        last_name: &str,
-        first_name: &str,
+        details: &entity::UserDetails,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<entity::User>, sqlx::Error>;
+
+    /// Updates the supplied profile fields and leaves the others as they are, `versions` as for `update_user`.
+    async fn patch_user(
+        &self,
+        user_id: i64,
+        last_name: Option<&str>,
+        first_name: Option<&str>,
+        email: Option<&str>,
+        country: Option<&str>,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<entity::User>, sqlx::Error>;
+
+    /// None when no user with one of `versions` exists.
+    async fn delete_user(
+        &self,
+        id: i64,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn get_user_credential(
+        &self,
         login_id: &str,
+    ) -> Result<Option<entity::UserCredential>, sqlx::Error>;
+
+    /// Stores an already hashed password.
+    async fn update_user_password(
+        &self,
+        user_id: i64,
+        password: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// Replaces the password hash while it still is `current_password` and ends every session.
+    async fn change_password(
+        &self,
+        user_id: i64,
+        current_password: &str,
         password: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn create_email_verification_token(
+        &self,
+        user_id: i64,
         email: &str,
-        country: &str,
-    ) -> Result<Option<entity::User>, sqlx::Error>;
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// Consumes an unused, unexpired token and marks the address it was mailed to as verified.
+    async fn verify_email(&self, token_hash: &str) -> Result<Option<entity::UserId>, sqlx::Error>;
 
-    async fn delete_user(&self, id: i64) -> Result<Option<()>, sqlx::Error>;
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<entity::User>, sqlx::Error>;
 
-    async fn get_user_by_login_id_and_password(
+    async fn create_password_reset_token(
         &self,
-        login_id: &str,
-        hashed_password: &str,
+        user_id: i64,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// The user an unused and unexpired reset token was mailed to.
+    async fn get_password_reset_user(
+        &self,
+        token_hash: &str,
     ) -> Result<Option<entity::User>, sqlx::Error>;
+
+    /// Consumes the token, stores the new hash and ends every session of the user in one transaction.
+    async fn reset_password(
+        &self,
+        token_hash: &str,
+        password: &str,
+    ) -> Result<Option<entity::UserId>, sqlx::Error>;
+
+    async fn get_user_totp(&self, user_id: i64) -> Result<Option<entity::UserTotp>, sqlx::Error>;
+
+    /// Stores a secret awaiting confirmation, None when TOTP is already enabled.
+    async fn set_pending_totp_secret(
+        &self,
+        user_id: i64,
+        secret: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// Enables the pending `secret` and replaces the recovery codes, None when it is no longer pending.
+    async fn enable_totp(
+        &self,
+        user_id: i64,
+        secret: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// Forgets the secret and the recovery codes.
+    async fn disable_totp(&self, user_id: i64) -> Result<Option<()>, sqlx::Error>;
+
+    /// Records the step of an accepted code, None when it or a later one was accepted before.
+    async fn use_totp_step(&self, user_id: i64, step: i64) -> Result<Option<()>, sqlx::Error>;
+
+    /// Marks an unused recovery code used, None when the user has no such code.
+    async fn use_recovery_code(
+        &self,
+        user_id: i64,
+        code_hash: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -45,15 +137,49 @@ pub trait WordRepositoryTrait: Clone + Send + Sync + 'static {
 
     async fn get_word(&self, word_id: i64) -> Result<Option<entity::Word>, sqlx::Error>;
 
-    async fn create_word(&self, word: &str) -> Result<Option<entity::Word>, sqlx::Error>;
+    async fn create_word(
+        &self,
+        word: &str,
+        normalized_word: &str,
+        definition: Option<&str>,
+    ) -> Result<Option<entity::Word>, sqlx::Error>;
 
+    /// `versions` is the If-Match precondition, None accepts any, a stale one finds no row.
     async fn update_word(
         &self,
         word_id: i64,
         word: &str,
+        normalized_word: &str,
+        definition: Option<&str>,
+        versions: Option<&[i64]>,
     ) -> Result<Option<entity::Word>, sqlx::Error>;
 
-    async fn delete_word(&self, id: i64) -> Result<Option<()>, sqlx::Error>;
+    /// None when no word with one of `versions` exists.
+    async fn delete_word(
+        &self,
+        id: i64,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn search_words(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::SearchedWord>>, sqlx::Error>;
+
+    /// Words still queued for a normalized_word recomputation, lowest id first.
+    async fn get_words_to_rekey(
+        &self,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::Word>>, sqlx::Error>;
+
+    /// Stores `normalized_word` for a queued word and dequeues it. When another word already
+    /// has that key the queued word is merged into it, like the migration that added the column.
+    async fn rekey_word(
+        &self,
+        word_id: i64,
+        normalized_word: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -69,11 +195,17 @@ pub trait UserWordRepositoryTrait: Clone + Send + Sync + 'static {
     async fn get_user_word_by_user_id(
         &self,
         user_id: i64,
+        sort: entity::UserWordSort,
+        cursor: Option<entity::PageCursor>,
+        limit: i64,
     ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error>;
 
     async fn get_user_word_by_word_id(
         &self,
         word_id: i64,
+        sort: entity::UserWordSort,
+        cursor: Option<entity::PageCursor>,
+        limit: i64,
     ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error>;
 
     async fn create_user_word(
@@ -83,4 +215,272 @@ pub trait UserWordRepositoryTrait: Clone + Send + Sync + 'static {
     ) -> Result<Option<entity::UserWordRelation>, sqlx::Error>;
 
     async fn delete_user_word(&self, id: i64) -> Result<Option<()>, sqlx::Error>;
+
+    async fn get_user_word_owner(
+        &self,
+        user_word_id: i64,
+    ) -> Result<Option<entity::UserId>, sqlx::Error>;
+
+    async fn import_user_words(
+        &self,
+        user_id: i64,
+        words: &[entity::WordString],
+    ) -> Result<Option<Vec<entity::ImportedWord>>, sqlx::Error>;
+
+    async fn get_due_reviews(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::Review>>, sqlx::Error>;
+
+    async fn get_review(
+        &self,
+        user_id: i64,
+        user_word_id: i64,
+    ) -> Result<Option<entity::Review>, sqlx::Error>;
+
+    /// Replaces `previous` with `next`, None when the stored state is no longer `previous`
+    /// because another review of the word was recorded in between.
+    async fn update_review(
+        &self,
+        user_id: i64,
+        user_word_id: i64,
+        previous: &entity::ReviewState,
+        next: &entity::ReviewState,
+    ) -> Result<Option<entity::Review>, sqlx::Error>;
+
+    fn export_user_words(
+        &self,
+        user_id: i64,
+    ) -> BoxStream<'static, Result<entity::ExportedWord, sqlx::Error>>;
+
+    async fn get_word_ranking(
+        &self,
+        days: Option<i32>,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::WordRanking>>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait QuizRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self;
+
+    async fn get_quiz_words(&self, user_id: i64) -> Result<Option<Vec<entity::Word>>, sqlx::Error>;
+
+    /// Words the user has not registered, a sample that is stable for one `seed`.
+    async fn get_distractor_words(
+        &self,
+        user_id: i64,
+        seed: i64,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::Word>>, sqlx::Error>;
+
+    async fn create_quiz(
+        &self,
+        user_id: i64,
+        mode: &str,
+        seed: i64,
+        questions: &[entity::QuizQuestion],
+    ) -> Result<Option<entity::QuizId>, sqlx::Error>;
+
+    async fn get_quiz_question(
+        &self,
+        user_id: i64,
+        quiz_id: i64,
+        position: i32,
+    ) -> Result<Option<entity::QuizQuestion>, sqlx::Error>;
+
+    async fn answer_quiz_question(
+        &self,
+        user_id: i64,
+        quiz_id: i64,
+        position: i32,
+        answer: &str,
+        correct: bool,
+    ) -> Result<Option<entity::QuizAnswer>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait DeckRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self;
+
+    async fn create_deck(
+        &self,
+        user_id: i64,
+        name: &str,
+    ) -> Result<Option<entity::Deck>, sqlx::Error>;
+
+    async fn get_decks(&self, user_id: i64) -> Result<Option<Vec<entity::Deck>>, sqlx::Error>;
+
+    async fn get_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<Option<entity::Deck>, sqlx::Error>;
+
+    async fn update_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        name: &str,
+    ) -> Result<Option<entity::Deck>, sqlx::Error>;
+
+    async fn delete_deck(&self, user_id: i64, deck_id: i64) -> Result<Option<()>, sqlx::Error>;
+
+    async fn get_deck_words(
+        &self,
+        deck_id: i64,
+    ) -> Result<Option<Vec<entity::DeckWord>>, sqlx::Error>;
+
+    async fn add_deck_words(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        user_word_ids: &[i64],
+    ) -> Result<Option<u64>, sqlx::Error>;
+
+    async fn remove_deck_word(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        user_word_id: i64,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn update_share_token(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        share_token: Option<&str>,
+    ) -> Result<Option<entity::Deck>, sqlx::Error>;
+
+    async fn get_shared_deck(&self, share_token: &str)
+        -> Result<Option<entity::Deck>, sqlx::Error>;
+
+    async fn clone_shared_deck(
+        &self,
+        user_id: i64,
+        share_token: &str,
+    ) -> Result<Option<entity::ClonedDeck>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SessionRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self;
+
+    async fn create_refresh_token(
+        &self,
+        user_id: i64,
+        family_id: &str,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn get_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<entity::RefreshSession>, sqlx::Error>;
+
+    /// Marks the token used and stores its successor, None when it was used concurrently.
+    async fn rotate_refresh_token(
+        &self,
+        refresh_token_id: i64,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<u64, sqlx::Error>;
+
+    async fn revoke_access_token(&self, jti: &str, expires_at: &str) -> Result<(), sqlx::Error>;
+
+    async fn create_login_challenge(
+        &self,
+        user_id: i64,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// The unused and unexpired challenge the token belongs to.
+    async fn get_login_challenge(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<entity::LoginChallenge>, sqlx::Error>;
+
+    /// Counts a rejected code and voids the challenge once `max_attempts` are reached.
+    async fn record_login_challenge_failure(
+        &self,
+        login_challenge_id: i64,
+        max_attempts: i32,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Marks the challenge used, None when it was completed or voided concurrently.
+    async fn complete_login_challenge(
+        &self,
+        login_challenge_id: i64,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// True when the `jti` was revoked or the user ended all sessions after `issued_at`.
+    async fn is_access_token_revoked(
+        &self,
+        jti: Option<&str>,
+        user_id: Option<i64>,
+        issued_at: Option<&str>,
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn record_login_attempt(
+        &self,
+        login_id: &str,
+        ip_address: Option<&str>,
+        succeeded: bool,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Failures within the window since the last successful login of `login_id`.
+    async fn get_login_failures_by_login_id(
+        &self,
+        login_id: &str,
+        window_seconds: i64,
+    ) -> Result<entity::LoginFailures, sqlx::Error>;
+
+    /// Failures within the window from `ip_address`, whichever login ids they targeted.
+    async fn get_login_failures_by_ip_address(
+        &self,
+        ip_address: &str,
+        window_seconds: i64,
+    ) -> Result<entity::LoginFailures, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait IdempotencyRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self;
+
+    /// Reserves the key for a request, None when it is in use and neither expired nor abandoned
+    /// for `lock_seconds`.
+    async fn claim_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+        expires_at: &str,
+        lock_seconds: i64,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    async fn get_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<entity::IdempotencyKey>, sqlx::Error>;
+
+    /// Stores the response of the request holding the key.
+    async fn complete_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+        response: &entity::StoredResponse,
+    ) -> Result<Option<()>, sqlx::Error>;
+
+    /// Frees a key whose response could not be stored, so that the request can be retried as is.
+    async fn release_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+    ) -> Result<(), sqlx::Error>;
 }
@@ -1,55 +1,111 @@
+use crate::domain::error::{CosanError, FieldErrors};
 use crate::domain::interface;
 use crate::router::request;
 use crate::router::response;
+use crate::util;
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use std::sync::Arc;
+use tracing::warn;
 
 use super::entity;
 
 #[derive(Clone)]
-pub struct CosanService<U, W, UW>
+pub struct CosanService<U, W, UW, Q, D, S, I>
 where
     U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
     W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
     UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+    Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+    D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+    S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+    I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
 {
     user_repository: U,
     word_repository: W,
     user_word_repository: UW,
+    quiz_repository: Q,
+    deck_repository: D,
+    session_repository: S,
+    idempotency_repository: I,
+    mailer: Arc<dyn util::mailer::Mailer>,
 }
 
 impl<
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
-    > CosanService<U, W, UW>
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    > CosanService<U, W, UW, Q, D, S, I>
 {
-    pub fn new(user_repository: U, word_repository: W, user_word_repository: UW) -> Self {
+    pub fn new(
+        user_repository: U,
+        word_repository: W,
+        user_word_repository: UW,
+        quiz_repository: Q,
+        deck_repository: D,
+        session_repository: S,
+        idempotency_repository: I,
+    ) -> Self {
         Self {
             user_repository,
             word_repository,
             user_word_repository,
+            quiz_repository,
+            deck_repository,
+            session_repository,
+            idempotency_repository,
+            mailer: Arc::new(util::mailer::LogMailer::new(None)),
         }
     }
 
-    pub async fn get_user(&self, id: i64) -> Result<response::GetUserResponse, anyhow::Error> {
+    /// Replaces the default mailer, which only writes to the log.
+    pub fn with_mailer(self, mailer: Arc<dyn util::mailer::Mailer>) -> Self {
+        Self { mailer, ..self }
+    }
+
+    pub async fn get_user(
+        &self,
+        id: i64,
+    ) -> Result<response::Versioned<response::GetUserResponse>, CosanError> {
         let user_id = entity::UserId::new(id);
 
         let user = self.user_repository.get_user(user_id.value()).await?;
         match user {
-            Some(user) => Ok(response::GetUserResponse {
-                user_id: u64::try_from(user.user_id.value()).unwrap(),
-                user_last_name: user.last_name.value().to_string(),
-                user_first_name: user.first_name.value().to_string(),
-                user_email: user.email.value().to_string(),
-                user_country: user.country.value().to_string(),
+            Some(user) => Ok(response::Versioned {
+                version: user.version.value(),
+                body: response::GetUserResponse {
+                    user_id: u64::try_from(user.user_id.value()).unwrap(),
+                    user_last_name: user.last_name.value().to_string(),
+                    user_first_name: user.first_name.value().to_string(),
+                    user_email: user.email.value().to_string(),
+                    user_country: user.country.value().to_string(),
+                    user_email_verified: user.email_verified.value(),
+                },
             }),
-            None => Err(anyhow::anyhow!("User not found")),
+            None => Err(CosanError::NotFound("User not found".to_string())),
+        }
+    }
+
+    /// Tells a stale If-Match from a missing user once a conditional write matched no row.
+    async fn user_precondition_error(&self, user_id: i64) -> CosanError {
+        match self.user_repository.get_user(user_id).await {
+            Ok(Some(_)) => CosanError::PreconditionFailed("User has been modified".to_string()),
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                CosanError::NotFound("User not found".to_string())
+            }
+            Err(err) => err.into(),
         }
     }
 
     pub async fn create_user(
         &self,
         request: request::CreateUserRequest,
-    ) -> Result<response::CreateUserResponse, anyhow::Error> {
+    ) -> Result<response::CreateUserResponse, CosanError> {
         let last_name = entity::LastName::new(request.last_name.as_str());
         let first_name = entity::FirstName::new(request.first_name.as_str());
         let login_id = entity::LoginId::new(request.login_id.as_str());
@@ -71,288 +127,2382 @@ impl<
             )
             .await?;
 
-        match result {
-            Some(user) => Ok(response::CreateUserResponse {
-                user_id: user.user_id.value() as u64,
-                user_last_name: user.last_name.value().to_string(),
-                user_first_name: user.first_name.value().to_string(),
-                user_email: user.email.value().to_string(),
-                user_country: user.country.value().to_string(),
-            }),
-            None => Err(anyhow::anyhow!("User not created")),
+        let user = result.ok_or_else(|| CosanError::Internal("User not created".to_string()))?;
+
+        // the account exists either way, a lost mail can be sent again
+        if let Err(err) = self
+            .send_verification_email(&user.user_id, &user.email)
+            .await
+        {
+            warn!(
+                "create_user: verification mail to user {} failed: {}",
+                user.user_id.value(),
+                err
+            );
         }
+
+        Ok(response::CreateUserResponse {
+            user_id: user.user_id.value() as u64,
+            user_last_name: user.last_name.value().to_string(),
+            user_first_name: user.first_name.value().to_string(),
+            user_email: user.email.value().to_string(),
+            user_country: user.country.value().to_string(),
+            user_email_verified: user.email_verified.value(),
+        })
     }
 
-    pub async fn update_user(
+    /// Confirms the address a verification token was mailed to.
+    pub async fn verify_email(
         &self,
-        request: request::UpdateUserRequest,
-    ) -> Result<response::UpdateUserResponse, anyhow::Error> {
-        let user_id = entity::UserId::new(request.user_id);
-        let last_name = entity::LastName::new(request.last_name.as_str());
-        let first_name = entity::FirstName::new(request.first_name.as_str());
-        let login_id = entity::LoginId::new(request.login_id.as_str());
-        let hashed_password = entity::Password::new(request.password.as_str())
-            .hash()
-            .await?;
-        let email = entity::Email::new(request.email.as_str());
-        let country = entity::Country::new(request.country.as_str());
+        request: request::VerifyEmailRequest,
+    ) -> Result<response::VerifyEmailResponse, CosanError> {
+        let token = entity::VerificationToken::new(request.token.as_str());
+
+        let user_id = match self
+            .user_repository
+            .verify_email(token.digest().as_str())
+            .await
+        {
+            Ok(Some(user_id)) => user_id,
+            // unknown, used, expired and superseded tokens all look the same to the client
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(CosanError::BadRequest(
+                    "Invalid or expired verification token".to_string(),
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(response::VerifyEmailResponse {
+            user_id: user_id.value() as u64,
+            user_email_verified: true,
+        })
+    }
 
+    /// Mails a fresh verification token, earlier ones stay valid until they expire.
+    pub async fn resend_verification_email(
+        &self,
+        user_id: i64,
+    ) -> Result<response::ResendVerificationEmailResponse, CosanError> {
         let user = self
             .user_repository
-            .update_user(
+            .get_user(user_id)
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+        if user.email_verified.value() {
+            return Err(CosanError::Conflict(
+                "Email address is already verified".to_string(),
+            ));
+        }
+
+        self.send_verification_email(&user.user_id, &user.email)
+            .await?;
+
+        Ok(response::ResendVerificationEmailResponse {
+            status: "sent".to_string(),
+        })
+    }
+
+    async fn send_verification_email(
+        &self,
+        user_id: &entity::UserId,
+        email: &entity::Email,
+    ) -> Result<(), CosanError> {
+        let token = entity::VerificationToken::generate();
+        let expires_at = (chrono::Utc::now()
+            + chrono::Duration::seconds(entity::EMAIL_VERIFICATION_TTL_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%S%.6f")
+        .to_string();
+
+        self.user_repository
+            .create_email_verification_token(
                 user_id.value(),
-                last_name.value(),
-                first_name.value(),
-                login_id.value(),
-                hashed_password.value(),
                 email.value(),
-                country.value(),
+                token.digest().as_str(),
+                expires_at.as_str(),
             )
+            .await?
+            .ok_or_else(|| CosanError::Internal("Verification token not created".to_string()))?;
+
+        self.mailer
+            .send(&util::mailer::Mail {
+                to: email.value().to_string(),
+                subject: "Confirm your email address".to_string(),
+                body: format!(
+                    "Confirm your email address by sending this token to POST /cosan/v1/user/verify:\n\n{}\n\nThe token expires in {} hours.",
+                    token.value(),
+                    entity::EMAIL_VERIFICATION_TTL_SECONDS / 3600
+                ),
+            })
             .await?;
 
-        match user {
-            Some(user) => Ok(response::UpdateUserResponse {
-                user_id: user.user_id.value() as u64,
-                user_last_name: user.last_name.value().to_string(),
-                user_first_name: user.first_name.value().to_string(),
-                user_email: user.email.value().to_string(),
-                user_country: user.country.value().to_string(),
-            }),
-            None => Err(anyhow::anyhow!("User not updated")),
+        Ok(())
+    }
+
+    /// Word registration is reserved for users who confirmed their address.
+    async fn require_verified_email(&self, user_id: &entity::UserId) -> Result<(), CosanError> {
+        let user = self
+            .user_repository
+            .get_user(user_id.value())
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+        if !user.may_register_words() {
+            return Err(CosanError::Forbidden(
+                "Email address must be verified to register words".to_string(),
+            ));
         }
+
+        Ok(())
     }
 
-    pub async fn delete_user(
+    /// Mails a reset token when the address belongs to a user, and answers the same either way.
+    pub async fn forgot_password(
         &self,
-        id: i64,
-    ) -> Result<response::DeleteUserResponse, anyhow::Error> {
-        let user_id = entity::UserId::new(id);
+        request: request::ForgotPasswordRequest,
+    ) -> Result<response::ForgotPasswordResponse, CosanError> {
+        let email = entity::Email::new(request.email.as_str());
 
-        let result = self.user_repository.delete_user(user_id.value()).await?;
-        match result {
-            Some(_) => Ok(response::DeleteUserResponse {
-                status: "success".to_string(),
-            }),
-            None => Err(anyhow::anyhow!("User not deleted")),
+        let user = match self.user_repository.get_user_by_email(email.value()).await {
+            Ok(user) => user,
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        // a failed mail must not reveal that the account exists
+        if let Some(user) = user {
+            if let Err(err) = self.send_password_reset_email(&user).await {
+                warn!(
+                    "forgot_password: reset mail to user {} failed: {}",
+                    user.user_id.value(),
+                    err
+                );
+            }
         }
+
+        Ok(response::ForgotPasswordResponse {
+            status: "accepted".to_string(),
+        })
     }
 
-    pub async fn get_user_by_login_id_and_password(
+    /// Sets a new password with a mailed reset token and ends every session of the user.
+    pub async fn reset_password(
         &self,
-        login_id: String,
-        password: String,
-    ) -> Result<response::GetUserResponse, anyhow::Error> {
-        let login_id = entity::LoginId::new(login_id.as_str());
-        let hashed_password = entity::Password::new(password.as_str()).hash().await?;
+        request: request::ResetPasswordRequest,
+    ) -> Result<response::ResetPasswordResponse, CosanError> {
+        let token = entity::VerificationToken::new(request.token.as_str());
 
-        let user = self
+        let user = match self
             .user_repository
-            .get_user_by_login_id_and_password(login_id.value(), hashed_password.clone().value())
-            .await?;
+            .get_password_reset_user(token.digest().as_str())
+            .await
+        {
+            Ok(Some(user)) => user,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(CosanError::BadRequest(
+                    "Invalid or expired reset token".to_string(),
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
 
-        match user {
-            Some(user) => {
-                let valid = user.password.verify(hashed_password.value()).await?;
-                if !valid {
-                    return Err(anyhow::anyhow!("Invalid password"));
-                }
+        // checked here because the policy needs the login id only the token reveals
+        let mut errors = FieldErrors::new();
+        for violation in
+            util::password_policy::violations(request.password.as_str(), user.login_id.value())
+        {
+            errors.add("password", violation);
+        }
+        errors.into_result()?;
 
-                Ok(response::GetUserResponse {
-                    user_id: user.user_id.value() as u64,
-                    user_last_name: user.last_name.value().to_string(),
-                    user_first_name: user.first_name.value().to_string(),
-                    user_email: user.email.value().to_string(),
-                    user_country: user.country.value().to_string(),
-                })
+        let hashed_password = entity::Password::new(request.password.as_str())
+            .hash()
+            .await?;
+
+        let user_id = match self
+            .user_repository
+            .reset_password(token.digest().as_str(), hashed_password.value())
+            .await
+        {
+            Ok(Some(user_id)) => user_id,
+            // another reset consumed the token since the lookup
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(CosanError::BadRequest(
+                    "Invalid or expired reset token".to_string(),
+                ))
             }
-            None => Err(anyhow::anyhow!("User not found")),
-        }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(response::ResetPasswordResponse {
+            user_id: user_id.value() as u64,
+            status: "reset".to_string(),
+        })
     }
 
-    pub async fn get_word(&self, id: i64) -> Result<response::GetWordResponse, anyhow::Error> {
-        let word_id = entity::WordId::new(id);
+    async fn send_password_reset_email(&self, user: &entity::User) -> Result<(), CosanError> {
+        let token = entity::VerificationToken::generate();
+        let expires_at = (chrono::Utc::now()
+            + chrono::Duration::seconds(entity::PASSWORD_RESET_TTL_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%S%.6f")
+        .to_string();
 
-        let word = self.word_repository.get_word(word_id.value()).await?;
-        match word {
-            Some(word) => Ok(response::GetWordResponse {
-                word_id: word.word_id.value() as u64,
-                word: word.word.value().to_string(),
-            }),
-            None => Err(anyhow::anyhow!("Word not found")),
-        }
+        self.user_repository
+            .create_password_reset_token(
+                user.user_id.value(),
+                token.digest().as_str(),
+                expires_at.as_str(),
+            )
+            .await?
+            .ok_or_else(|| CosanError::Internal("Reset token not created".to_string()))?;
+
+        self.mailer
+            .send(&util::mailer::Mail {
+                to: user.email.value().to_string(),
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Someone asked to reset the password of {}. Set a new one by sending this token with it to POST /cosan/v1/auth/password/reset:\n\n{}\n\nThe token expires in {} minutes. If you did not ask for this, ignore this mail.",
+                    user.login_id.value(),
+                    token.value(),
+                    entity::PASSWORD_RESET_TTL_SECONDS / 60
+                ),
+            })
+            .await?;
+
+        Ok(())
     }
 
-    pub async fn create_word(
+    pub async fn update_user(
         &self,
-        request: request::CreateWordRequest,
-    ) -> Result<response::CreateWordResponse, anyhow::Error> {
-        let word = entity::WordString::new(request.word.as_str());
+        request: request::UpdateUserRequest,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateUserResponse>, CosanError> {
+        let user_id = entity::UserId::new(request.user_id);
+        let details = entity::UserDetails::new(
+            entity::LastName::new(request.last_name.as_str()),
+            entity::FirstName::new(request.first_name.as_str()),
+            entity::LoginId::new(request.login_id.as_str()),
+            entity::Email::new(request.email.as_str()),
+            entity::Country::new(request.country.as_str()),
+        );
 
-        let word = self.word_repository.create_word(word.value()).await?;
+        let user = match self
+            .user_repository
+            .update_user(user_id.value(), &details, precondition.versions())
+            .await
+        {
+            Ok(Some(user)) => user,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.user_precondition_error(user_id.value()).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
 
-        match word {
-            Some(word) => Ok(response::CreateWordResponse {
-                word_id: word.word_id.value() as u64,
-                word: word.word.value().to_string(),
-            }),
-            None => Err(anyhow::anyhow!("Word not created")),
+        Ok(response::Versioned {
+            version: user.version.value(),
+            body: response::UpdateUserResponse {
+                user_id: user.user_id.value() as u64,
+                user_last_name: user.last_name.value().to_string(),
+                user_first_name: user.first_name.value().to_string(),
+                user_email: user.email.value().to_string(),
+                user_country: user.country.value().to_string(),
+                user_email_verified: user.email_verified.value(),
+            },
+        })
+    }
+
+    /// Updates only the fields present in the request, a new email address has to be verified again.
+    pub async fn patch_user(
+        &self,
+        user_id: i64,
+        request: request::PatchUserRequest,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateUserResponse>, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let last_name = request.last_name.as_deref().map(entity::LastName::new);
+        let first_name = request.first_name.as_deref().map(entity::FirstName::new);
+        let email = request.email.as_deref().map(entity::Email::new);
+        let country = request.country.as_deref().map(entity::Country::new);
+
+        let user = match self
+            .user_repository
+            .patch_user(
+                user_id.value(),
+                last_name.as_ref().map(|last_name| last_name.value()),
+                first_name.as_ref().map(|first_name| first_name.value()),
+                email.as_ref().map(|email| email.value()),
+                country.as_ref().map(|country| country.value()),
+                precondition.versions(),
+            )
+            .await
+        {
+            Ok(Some(user)) => user,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.user_precondition_error(user_id.value()).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // the profile is saved either way, a lost mail can be sent again
+        if email.is_some() && !user.email_verified.value() {
+            if let Err(err) = self
+                .send_verification_email(&user.user_id, &user.email)
+                .await
+            {
+                warn!(
+                    "patch_user: verification mail to user {} failed: {}",
+                    user.user_id.value(),
+                    err
+                );
+            }
         }
+
+        Ok(response::Versioned {
+            version: user.version.value(),
+            body: response::UpdateUserResponse {
+                user_id: user.user_id.value() as u64,
+                user_last_name: user.last_name.value().to_string(),
+                user_first_name: user.first_name.value().to_string(),
+                user_email: user.email.value().to_string(),
+                user_country: user.country.value().to_string(),
+                user_email_verified: user.email_verified.value(),
+            },
+        })
     }
 
-    pub async fn update_word(
+    /// Replaces the password after checking the current one, which ends every session of the user.
+    pub async fn change_password(
         &self,
-        request: request::UpdateWordRequest,
-    ) -> Result<response::UpdateWordResponse, anyhow::Error> {
-        let word_id = entity::WordId::new(request.word_id as i64);
-        let word = entity::WordString::new(request.word.as_str());
+        user_id: i64,
+        request: request::ChangePasswordRequest,
+        client_ip: Option<&str>,
+    ) -> Result<response::ChangePasswordResponse, CosanError> {
+        let user = self
+            .user_repository
+            .get_user(user_id)
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
 
-        let word = self
-            .word_repository
-            .update_word(word_id.value(), word.value())
+        // guessing the current password here counts like guessing it at login
+        self.check_login_lockout(user.login_id.value(), client_ip, chrono::Utc::now())
             .await?;
+        if !user
+            .password
+            .verify(request.current_password.as_str())
+            .await?
+        {
+            self.session_repository
+                .record_login_attempt(user.login_id.value(), client_ip, false)
+                .await?;
+            return Err(CosanError::Forbidden(
+                "Current password is incorrect".to_string(),
+            ));
+        }
 
-        match word {
-            Some(word) => Ok(response::UpdateWordResponse {
-                word_id: word.word_id.value() as u64,
-                word: word.word.value().to_string(),
-            }),
-            None => Err(anyhow::anyhow!("Word not updated")),
+        let mut errors = FieldErrors::new();
+        for violation in
+            util::password_policy::violations(request.new_password.as_str(), user.login_id.value())
+        {
+            errors.add("new_password", violation);
+        }
+        if request.new_password == request.current_password {
+            errors.add(
+                "new_password",
+                "New password must differ from the current password.",
+            );
         }
+        errors.into_result()?;
+
+        let hashed_password = entity::Password::new(request.new_password.as_str())
+            .hash()
+            .await?;
+
+        self.user_repository
+            .change_password(
+                user.user_id.value(),
+                user.password.value(),
+                hashed_password.value(),
+            )
+            .await?
+            .ok_or_else(|| {
+                CosanError::Conflict("Password was changed meanwhile, try again".to_string())
+            })?;
+
+        Ok(response::ChangePasswordResponse {
+            user_id: user.user_id.value() as u64,
+            status: "changed".to_string(),
+        })
     }
 
-    pub async fn delete_word(
+    pub async fn delete_user(
         &self,
         id: i64,
-    ) -> Result<response::DeleteWordResponse, anyhow::Error> {
-        let result = self.word_repository.delete_word(id).await?;
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::DeleteUserResponse, CosanError> {
+        let user_id = entity::UserId::new(id);
+
+        let result = self
+            .user_repository
+            .delete_user(user_id.value(), precondition.versions())
+            .await?;
         match result {
-            Some(_) => Ok(response::DeleteWordResponse {
+            Some(_) => Ok(response::DeleteUserResponse {
                 status: "success".to_string(),
             }),
-            None => Err(anyhow::anyhow!("Word not deleted")),
+            None => Err(self.user_precondition_error(user_id.value()).await),
         }
     }
 
-    pub async fn get_user_word_by_user_id_and_word_id(
+    pub async fn login(
         &self,
-        request: request::GetUserWordRequest,
-    ) -> Result<response::GetUserWordResponse, anyhow::Error> {
-        let user_id = entity::UserId::new(request.user_id as i64);
-        let word_id = entity::WordId::new(request.word_id as i64);
+        request: request::LoginRequest,
+        client_ip: Option<&str>,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginStepResponse, CosanError> {
+        let login_id = entity::LoginId::new(request.login_id.as_str());
+        let now = chrono::Utc::now();
 
-        let user_word = self
-            .user_word_repository
-            .get_user_word_by_user_id_and_word_id(user_id.value(), word_id.value())
+        self.check_login_lockout(login_id.value(), client_ip, now)
             .await?;
 
-        match user_word {
-            Some(user_word) => Ok(response::GetUserWordResponse {
-                user_word_id: user_word.user_word_id.value() as u64,
-                user_id: user_word.user_id.value() as u64,
-                last_name: user_word.last_name.value().to_string(),
-                first_name: user_word.first_name.value().to_string(),
-                email: user_word.email.value().to_string(),
-                country: user_word.country.value().to_string(),
-                word_id: user_word.word_id.value() as u64,
-                word: user_word.word.value().to_string(),
-                created_at: user_word.created_at.value().to_string(),
-            }),
-            None => Err(anyhow::anyhow!("User word not found")),
+        let credential = match self.authenticate(&login_id, &request.password).await {
+            Ok(credential) => credential,
+            Err(CosanError::Unauthorized(message)) => {
+                self.session_repository
+                    .record_login_attempt(login_id.value(), client_ip, false)
+                    .await?;
+                return Err(CosanError::Unauthorized(message));
+            }
+            Err(err) => return Err(err),
+        };
+        // with a second factor the login only succeeds, and clears the lockout, once the code is accepted
+        if !credential.totp_enabled {
+            self.session_repository
+                .record_login_attempt(login_id.value(), client_ip, true)
+                .await?;
+        }
+
+        // the plaintext is only at hand now, so legacy and weaker hashes are upgraded here
+        if credential.password.needs_rehash() {
+            let rehashed = match entity::Password::new(request.password.as_str())
+                .hash()
+                .await
+            {
+                Ok(rehashed) => Some(rehashed),
+                Err(err) => {
+                    warn!("login: failed to rehash password: {}", err);
+                    None
+                }
+            };
+            if let Some(rehashed) = rehashed {
+                if let Err(err) = self
+                    .user_repository
+                    .update_user_password(credential.user_id.value(), rehashed.value())
+                    .await
+                {
+                    warn!("login: failed to store rehashed password: {}", err);
+                }
+            }
+        }
+
+        if credential.totp_enabled {
+            return self.start_login_challenge(&credential.user_id, now).await;
         }
+
+        Ok(response::LoginStepResponse::Authenticated(
+            self.start_session(&credential.user_id, credential.role, now, verifier)
+                .await?,
+        ))
     }
 
-    pub async fn get_user_word_by_word_id(
+    /// Finishes a login held back for the second factor with an authenticator or recovery code.
+    pub async fn login_totp(
         &self,
-        request: request::GetUserWordRequest,
-    ) -> Result<Vec<response::GetUserWordResponse>, anyhow::Error> {
-        let word_id = entity::WordId::new(request.word_id as i64);
+        request: request::LoginTotpRequest,
+        client_ip: Option<&str>,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginResponse, CosanError> {
+        let now = chrono::Utc::now();
 
-        let user_words = self
-            .user_word_repository
-            .get_user_word_by_word_id(word_id.value())
+        let challenge_token = entity::VerificationToken::new(request.challenge_token.as_str());
+        let challenge = match self
+            .session_repository
+            .get_login_challenge(challenge_token.digest().as_str())
+            .await
+        {
+            Ok(Some(challenge)) => challenge,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(CosanError::Unauthorized(
+                    "Invalid or expired login challenge".to_string(),
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        self.check_login_lockout(challenge.login_id.value(), client_ip, now)
             .await?;
 
-        match user_words {
-            Some(user_words) => Ok(user_words
-                .into_iter()
-                .map(|user_word| response::GetUserWordResponse {
-                    user_word_id: user_word.user_word_id.value() as u64,
-                    user_id: user_word.user_id.value() as u64,
-                    last_name: user_word.last_name.value().to_string(),
-                    first_name: user_word.first_name.value().to_string(),
-                    email: user_word.email.value().to_string(),
-                    country: user_word.country.value().to_string(),
-                    word_id: user_word.word_id.value() as u64,
-                    word: user_word.word.value().to_string(),
-                    created_at: user_word.created_at.value().to_string(),
-                })
-                .collect()),
-            None => Err(anyhow::anyhow!("User word not found")),
+        let code = entity::TotpCode::new(request.code.as_str());
+        if !self.check_second_factor(&challenge.user_id, &code).await? {
+            self.session_repository
+                .record_login_challenge_failure(
+                    challenge.login_challenge_id,
+                    entity::LOGIN_CHALLENGE_MAX_ATTEMPTS,
+                )
+                .await?;
+            self.session_repository
+                .record_login_attempt(challenge.login_id.value(), client_ip, false)
+                .await?;
+            return Err(CosanError::Unauthorized(
+                "Invalid two-factor code".to_string(),
+            ));
         }
+
+        // a concurrent request with another valid code may have finished it first
+        self.session_repository
+            .complete_login_challenge(challenge.login_challenge_id)
+            .await?
+            .ok_or_else(|| {
+                CosanError::Unauthorized("Invalid or expired login challenge".to_string())
+            })?;
+        self.session_repository
+            .record_login_attempt(challenge.login_id.value(), client_ip, true)
+            .await?;
+
+        self.start_session(&challenge.user_id, challenge.role, now, verifier)
+            .await
     }
 
-    pub async fn get_user_word_by_user_id(
+    async fn start_login_challenge(
         &self,
-        request: request::GetUserWordRequest,
-    ) -> Result<Vec<response::GetUserWordResponse>, anyhow::Error> {
-        let user_id = entity::UserId::new(request.user_id as i64);
+        user_id: &entity::UserId,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<response::LoginStepResponse, CosanError> {
+        let challenge_token = entity::VerificationToken::generate();
+        let expires_at = (now + chrono::Duration::seconds(entity::LOGIN_CHALLENGE_TTL_SECONDS))
+            .format("%Y-%m-%dT%H:%M:%S%.6f")
+            .to_string();
 
-        let user_words = self
-            .user_word_repository
-            .get_user_word_by_user_id(user_id.value())
-            .await?;
+        self.session_repository
+            .create_login_challenge(
+                user_id.value(),
+                challenge_token.digest().as_str(),
+                expires_at.as_str(),
+            )
+            .await?
+            .ok_or_else(|| CosanError::Internal("Login challenge not created".to_string()))?;
 
-        match user_words {
-            Some(user_words) => Ok(user_words
-                .into_iter()
-                .map(|user_word| response::GetUserWordResponse {
-                    user_word_id: user_word.user_word_id.value() as u64,
-                    user_id: user_word.user_id.value() as u64,
-                    last_name: user_word.last_name.value().to_string(),
-                    first_name: user_word.first_name.value().to_string(),
-                    email: user_word.email.value().to_string(),
-                    country: user_word.country.value().to_string(),
-                    word_id: user_word.word_id.value() as u64,
-                    word: user_word.word.value().to_string(),
-                    created_at: user_word.created_at.value().to_string(),
-                })
-                .collect()),
-            None => Err(anyhow::anyhow!("User word not found")),
-        }
+        Ok(response::LoginStepResponse::TwoFactorRequired(
+            response::TwoFactorRequiredResponse {
+                two_factor_required: true,
+                challenge_token: challenge_token.value().to_string(),
+                expires_in: entity::LOGIN_CHALLENGE_TTL_SECONDS as u64,
+            },
+        ))
     }
 
-    pub async fn create_user_word(
+    /// Stores a new refresh token family and mints the tokens of a finished login.
+    async fn start_session(
         &self,
-        request: request::CreateUserWordRequest,
-    ) -> Result<response::CreateUserWordRelationResponse, anyhow::Error> {
-        let user_id = entity::UserId::new(request.user_id as i64);
-        let word_id = entity::WordId::new(request.word_id as i64);
-
-        let user_word = self
-            .user_word_repository
-            .create_user_word(user_id.value(), word_id.value())
+        user_id: &entity::UserId,
+        role: entity::Role,
+        now: chrono::DateTime<chrono::Utc>,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginResponse, CosanError> {
+        let refresh_token = entity::RefreshToken::generate();
+        let created = self
+            .session_repository
+            .create_refresh_token(
+                user_id.value(),
+                entity::TokenFamilyId::generate().value(),
+                refresh_token.digest().as_str(),
+                Self::refresh_token_expires_at(now).as_str(),
+            )
             .await?;
+        if created.is_none() {
+            return Err(CosanError::Internal(
+                "Refresh token not created".to_string(),
+            ));
+        }
 
-        match user_word {
+        Self::token_response(user_id, role, &refresh_token, now, verifier)
+    }
+
+    /// Starts TOTP enrollment with a fresh secret, which only takes effect once a code confirms it.
+    pub async fn enroll_totp(
+        &self,
+        user_id: i64,
+    ) -> Result<response::EnrollTotpResponse, CosanError> {
+        let totp = self
+            .user_repository
+            .get_user_totp(user_id)
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+        if totp.enabled {
+            return Err(CosanError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = util::totp::generate_secret();
+        self.user_repository
+            .set_pending_totp_secret(totp.user_id.value(), secret.as_str())
+            .await?
+            .ok_or_else(|| {
+                CosanError::Conflict("Two-factor authentication is already enabled".to_string())
+            })?;
+
+        Ok(response::EnrollTotpResponse {
+            otpauth_uri: util::totp::otpauth_uri(
+                secret.as_str(),
+                entity::TOTP_ISSUER,
+                totp.login_id.value(),
+            )?,
+            secret,
+        })
+    }
+
+    /// Enables TOTP once a code proves the authenticator holds the pending secret.
+    pub async fn confirm_totp(
+        &self,
+        user_id: i64,
+        request: request::ConfirmTotpRequest,
+    ) -> Result<response::ConfirmTotpResponse, CosanError> {
+        let totp = self
+            .user_repository
+            .get_user_totp(user_id)
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+        if totp.enabled {
+            return Err(CosanError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+        let Some(secret) = totp.secret else {
+            return Err(CosanError::Conflict(
+                "Two-factor enrollment has not been started".to_string(),
+            ));
+        };
+
+        let code = entity::TotpCode::new(request.code.as_str());
+        let step = if code.is_authenticator_code() {
+            util::totp::matching_step(
+                secret.as_str(),
+                code.value(),
+                chrono::Utc::now().timestamp() as u64,
+            )?
+        } else {
+            None
+        };
+        let Some(step) = step else {
+            return Err(CosanError::BadRequest(
+                "Invalid two-factor code".to_string(),
+            ));
+        };
+
+        let recovery_codes: Vec<entity::RecoveryCode> = (0..entity::TOTP_RECOVERY_CODE_COUNT)
+            .map(|_| entity::RecoveryCode::generate())
+            .collect();
+        let recovery_code_hashes: Vec<String> =
+            recovery_codes.iter().map(|code| code.digest()).collect();
+
+        // enrollment restarted meanwhile, the code belonged to the replaced secret
+        self.user_repository
+            .enable_totp(
+                totp.user_id.value(),
+                secret.as_str(),
+                step,
+                &recovery_code_hashes,
+            )
+            .await?
+            .ok_or_else(|| {
+                CosanError::Conflict("Two-factor enrollment has changed, start again".to_string())
+            })?;
+
+        Ok(response::ConfirmTotpResponse {
+            totp_enabled: true,
+            recovery_codes: recovery_codes
+                .iter()
+                .map(|code| code.value().to_string())
+                .collect(),
+        })
+    }
+
+    /// Turns TOTP off, which takes a current code or a recovery code just like a login does.
+    pub async fn disable_totp(
+        &self,
+        user_id: i64,
+        request: request::DisableTotpRequest,
+    ) -> Result<response::DisableTotpResponse, CosanError> {
+        let totp = self
+            .user_repository
+            .get_user_totp(user_id)
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+        if !totp.enabled {
+            return Err(CosanError::Conflict(
+                "Two-factor authentication is not enabled".to_string(),
+            ));
+        }
+
+        let code = entity::TotpCode::new(request.code.as_str());
+        if !self.check_second_factor(&totp.user_id, &code).await? {
+            return Err(CosanError::Forbidden("Invalid two-factor code".to_string()));
+        }
+
+        self.user_repository
+            .disable_totp(totp.user_id.value())
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+
+        Ok(response::DisableTotpResponse {
+            totp_enabled: false,
+        })
+    }
+
+    /// Accepts an unused authenticator code or recovery code, using it up either way.
+    async fn check_second_factor(
+        &self,
+        user_id: &entity::UserId,
+        code: &entity::TotpCode,
+    ) -> Result<bool, CosanError> {
+        let totp = self
+            .user_repository
+            .get_user_totp(user_id.value())
+            .await?
+            .ok_or_else(|| CosanError::NotFound("User not found".to_string()))?;
+        let (true, Some(secret)) = (totp.enabled, totp.secret.as_deref()) else {
+            return Ok(false);
+        };
+
+        if code.is_authenticator_code() {
+            let step = util::totp::matching_step(
+                secret,
+                code.value(),
+                chrono::Utc::now().timestamp() as u64,
+            )?;
+            let Some(step) = step else {
+                return Ok(false);
+            };
+            // a code seen once is rejected, it may have been read over a shoulder
+            return Ok(self
+                .user_repository
+                .use_totp_step(user_id.value(), step)
+                .await?
+                .is_some());
+        }
+
+        let recovery_code = entity::RecoveryCode::new(code.value());
+        Ok(self
+            .user_repository
+            .use_recovery_code(user_id.value(), recovery_code.digest().as_str())
+            .await?
+            .is_some())
+    }
+
+    async fn authenticate(
+        &self,
+        login_id: &entity::LoginId,
+        password: &str,
+    ) -> Result<entity::UserCredential, CosanError> {
+        // unknown login ids and wrong passwords are indistinguishable to the client
+        let credential = match self
+            .user_repository
+            .get_user_credential(login_id.value())
+            .await
+        {
+            Ok(Some(credential)) => credential,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                // spend the same hashing time as a wrong password, so timing does not reveal the login id
+                util::crypt::verify_dummy_password(password).await?;
+                return Err(CosanError::Unauthorized(
+                    "Invalid login ID or password".to_string(),
+                ));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if !credential.password.verify(password).await? {
+            return Err(CosanError::Unauthorized(
+                "Invalid login ID or password".to_string(),
+            ));
+        }
+
+        Ok(credential)
+    }
+
+    /// Rejects the attempt while either the login id or the client address is backing off.
+    async fn check_login_lockout(
+        &self,
+        login_id: &str,
+        client_ip: Option<&str>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), CosanError> {
+        let by_login_id = self
+            .session_repository
+            .get_login_failures_by_login_id(login_id, entity::LOGIN_ATTEMPT_WINDOW_SECONDS)
+            .await?
+            .retry_after(&entity::LOGIN_ID_LOCKOUT, now);
+        let by_ip_address = match client_ip {
+            Some(client_ip) => self
+                .session_repository
+                .get_login_failures_by_ip_address(client_ip, entity::LOGIN_ATTEMPT_WINDOW_SECONDS)
+                .await?
+                .retry_after(&entity::IP_ADDRESS_LOCKOUT, now),
+            None => None,
+        };
+
+        match by_login_id.into_iter().chain(by_ip_address).max() {
+            Some(retry_after) => {
+                warn!("login: locked out for {} seconds", retry_after);
+                Err(CosanError::TooManyRequests(
+                    "Too many failed login attempts, try again later".to_string(),
+                    retry_after,
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub async fn refresh(
+        &self,
+        request: request::RefreshRequest,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginResponse, CosanError> {
+        let presented = entity::RefreshToken::new(request.refresh_token.as_str());
+
+        let session = match self
+            .session_repository
+            .get_refresh_token(presented.digest().as_str())
+            .await
+        {
+            Ok(Some(session)) => session,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(CosanError::Unauthorized(
+                    "Invalid refresh token".to_string(),
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let now = chrono::Utc::now();
+        if session.revoked || session.is_expired(now) {
+            return Err(CosanError::Unauthorized(
+                "Refresh token is expired or revoked".to_string(),
+            ));
+        }
+
+        // a rotated token coming back means it leaked, so nobody keeps the session
+        if session.used {
+            self.session_repository
+                .revoke_refresh_token_family(session.family_id.value())
+                .await?;
+            return Err(CosanError::Unauthorized(
+                "Refresh token has already been used".to_string(),
+            ));
+        }
+
+        let refresh_token = entity::RefreshToken::generate();
+        let rotated = self
+            .session_repository
+            .rotate_refresh_token(
+                session.refresh_token_id,
+                refresh_token.digest().as_str(),
+                Self::refresh_token_expires_at(now).as_str(),
+            )
+            .await?;
+        if rotated.is_none() {
+            // lost the race against another exchange of the same token
+            self.session_repository
+                .revoke_refresh_token_family(session.family_id.value())
+                .await?;
+            return Err(CosanError::Unauthorized(
+                "Refresh token has already been used".to_string(),
+            ));
+        }
+
+        Self::token_response(
+            &session.user_id,
+            session.role,
+            &refresh_token,
+            now,
+            verifier,
+        )
+    }
+
+    pub async fn logout(
+        &self,
+        token: &util::auth::Token,
+        request: request::LogoutRequest,
+    ) -> Result<response::LogoutResponse, CosanError> {
+        if let Some(refresh_token) = request.refresh_token {
+            let presented = entity::RefreshToken::new(refresh_token.as_str());
+            match self
+                .session_repository
+                .get_refresh_token(presented.digest().as_str())
+                .await
+            {
+                Ok(Some(session)) => {
+                    token.authorize_owner(session.user_id.value())?;
+                    self.session_repository
+                        .revoke_refresh_token_family(session.family_id.value())
+                        .await?;
+                }
+                // an unknown token has nothing left to revoke
+                Ok(None) | Err(sqlx::Error::RowNotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if let (Some(jti), Some(exp)) = (token.jti.as_deref(), token.exp) {
+            let expires_at = chrono::DateTime::from_timestamp(exp, 0).ok_or_else(|| {
+                CosanError::Unauthorized("Token expiration is invalid".to_string())
+            })?;
+            self.session_repository
+                .revoke_access_token(
+                    jti,
+                    expires_at
+                        .format("%Y-%m-%dT%H:%M:%S%.6f")
+                        .to_string()
+                        .as_str(),
+                )
+                .await?;
+        }
+
+        Ok(response::LogoutResponse {
+            status: "success".to_string(),
+        })
+    }
+
+    /// Validates the bearer token and rejects it once logout revoked its `jti` or a password reset ended the session.
+    pub async fn verify_access_token(
+        &self,
+        token: &str,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<util::auth::Token, CosanError> {
+        let token = verifier.verify(token).await?;
+
+        let issued_at = token
+            .iat
+            .map(|iat| iat.format("%Y-%m-%dT%H:%M:%S%.6f").to_string());
+        if self
+            .session_repository
+            .is_access_token_revoked(token.jti.as_deref(), token.uid, issued_at.as_deref())
+            .await?
+        {
+            return Err(CosanError::Unauthorized(
+                "Token has been revoked".to_string(),
+            ));
+        }
+
+        Ok(token)
+    }
+
+    fn refresh_token_expires_at(now: chrono::DateTime<chrono::Utc>) -> String {
+        (now + chrono::Duration::seconds(util::auth::REFRESH_TOKEN_TTL_SECONDS))
+            .format("%Y-%m-%dT%H:%M:%S%.6f")
+            .to_string()
+    }
+
+    fn token_response(
+        user_id: &entity::UserId,
+        role: entity::Role,
+        refresh_token: &entity::RefreshToken,
+        now: chrono::DateTime<chrono::Utc>,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginResponse, CosanError> {
+        let token = util::auth::Token::new(user_id.value(), role.scopes(), role.value(), now);
+
+        Ok(response::LoginResponse {
+            access_token: verifier.sign(&token)?,
+            token_type: "Bearer".to_string(),
+            expires_in: util::auth::ACCESS_TOKEN_TTL_SECONDS as u64,
+            refresh_token: refresh_token.value().to_string(),
+            refresh_expires_in: util::auth::REFRESH_TOKEN_TTL_SECONDS as u64,
+        })
+    }
+
+    pub async fn get_word(
+        &self,
+        id: i64,
+    ) -> Result<response::Versioned<response::GetWordResponse>, CosanError> {
+        let word_id = entity::WordId::new(id);
+
+        let word = self.word_repository.get_word(word_id.value()).await?;
+        match word {
+            Some(word) => Ok(response::Versioned {
+                version: word.version.value(),
+                body: response::GetWordResponse {
+                    word_id: word.word_id.value() as u64,
+                    word: word.word.value().to_string(),
+                    definition: word
+                        .definition
+                        .map(|definition| definition.value().to_string()),
+                },
+            }),
+            None => Err(CosanError::NotFound("Word not found".to_string())),
+        }
+    }
+
+    /// Tells a stale If-Match from a missing word once a conditional write matched no row.
+    async fn word_precondition_error(&self, word_id: i64) -> CosanError {
+        match self.word_repository.get_word(word_id).await {
+            Ok(Some(_)) => CosanError::PreconditionFailed("Word has been modified".to_string()),
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                CosanError::NotFound("Word not found".to_string())
+            }
+            Err(err) => err.into(),
+        }
+    }
+
+    /// Recomputes normalized_word for every word the migrations queued, since SQL cannot
+    /// reproduce the case folding of entity::WordString::normalized. Returns the number done.
+    pub async fn rekey_words(&self) -> Result<u64, CosanError> {
+        let mut rekeyed = 0;
+        loop {
+            let words = self
+                .word_repository
+                .get_words_to_rekey(entity::WORD_REKEY_BATCH_SIZE)
+                .await?
+                .unwrap_or_default();
+            if words.is_empty() {
+                return Ok(rekeyed);
+            }
+
+            for word in words {
+                self.word_repository
+                    .rekey_word(word.word_id.value(), &word.word.normalized())
+                    .await?
+                    .ok_or_else(|| CosanError::Internal("Word not rekeyed".to_string()))?;
+                rekeyed += 1;
+            }
+        }
+    }
+
+    pub async fn create_word(
+        &self,
+        request: request::CreateWordRequest,
+    ) -> Result<response::CreateWordResponse, CosanError> {
+        let word = entity::WordString::new(request.word.as_str());
+        let definition = request
+            .definition
+            .as_deref()
+            .map(entity::WordDefinition::new);
+
+        let word = self
+            .word_repository
+            .create_word(
+                word.value(),
+                &word.normalized(),
+                definition.as_ref().map(|definition| definition.value()),
+            )
+            .await?;
+
+        match word {
+            Some(word) => Ok(response::CreateWordResponse {
+                word_id: word.word_id.value() as u64,
+                word: word.word.value().to_string(),
+                definition: word
+                    .definition
+                    .map(|definition| definition.value().to_string()),
+            }),
+            None => Err(CosanError::Internal("Word not created".to_string())),
+        }
+    }
+
+    pub async fn update_word(
+        &self,
+        request: request::UpdateWordRequest,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateWordResponse>, CosanError> {
+        let word_id = entity::WordId::new(request.word_id as i64);
+        let word = entity::WordString::new(request.word.as_str());
+        // a replacement, leaving the definition out clears it
+        let definition = request
+            .definition
+            .as_deref()
+            .map(entity::WordDefinition::new);
+
+        let word = match self
+            .word_repository
+            .update_word(
+                word_id.value(),
+                word.value(),
+                &word.normalized(),
+                definition.as_ref().map(|definition| definition.value()),
+                precondition.versions(),
+            )
+            .await
+        {
+            Ok(Some(word)) => word,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.word_precondition_error(word_id.value()).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(response::Versioned {
+            version: word.version.value(),
+            body: response::UpdateWordResponse {
+                word_id: word.word_id.value() as u64,
+                word: word.word.value().to_string(),
+                definition: word
+                    .definition
+                    .map(|definition| definition.value().to_string()),
+            },
+        })
+    }
+
+    pub async fn delete_word(
+        &self,
+        id: i64,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::DeleteWordResponse, CosanError> {
+        let result = self
+            .word_repository
+            .delete_word(id, precondition.versions())
+            .await?;
+        match result {
+            Some(_) => Ok(response::DeleteWordResponse {
+                status: "success".to_string(),
+            }),
+            None => Err(self.word_precondition_error(id).await),
+        }
+    }
+
+    pub async fn search_words(
+        &self,
+        request: request::SearchWordRequest,
+    ) -> Result<Vec<response::SearchWordResponse>, CosanError> {
+        let query = entity::WordString::new(&request.q);
+        let limit = request
+            .limit
+            .unwrap_or(request::SearchWordRequest::DEFAULT_LIMIT);
+
+        // an empty query matches nothing rather than every word
+        if query.normalized().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let words = self
+            .word_repository
+            .search_words(&query.normalized(), limit as i64)
+            .await?;
+
+        // no match is a valid search result, not a missing resource
+        Ok(words
+            .unwrap_or_default()
+            .into_iter()
+            .map(|word| response::SearchWordResponse {
+                word_id: word.word_id.value() as u64,
+                word: word.word.value().to_string(),
+                score: word.score.value(),
+            })
+            .collect())
+    }
+
+    pub async fn get_word_ranking(
+        &self,
+        request: request::GetWordRankingRequest,
+    ) -> Result<Vec<response::GetWordRankingResponse>, CosanError> {
+        let period = entity::RankingPeriod::new(
+            request
+                .period
+                .as_deref()
+                .unwrap_or(request::GetWordRankingRequest::DEFAULT_PERIOD),
+        )
+        .ok_or_else(|| CosanError::BadRequest("Invalid ranking period".to_string()))?;
+        let limit = request
+            .limit
+            .unwrap_or(request::GetWordRankingRequest::DEFAULT_LIMIT);
+
+        let rankings = self
+            .user_word_repository
+            .get_word_ranking(period.days(), limit as i64)
+            .await?;
+
+        // an empty window is a valid ranking, not a missing resource
+        Ok(rankings
+            .unwrap_or_default()
+            .into_iter()
+            .map(|ranking| response::GetWordRankingResponse {
+                rank: ranking.rank.value() as u64,
+                word_id: ranking.word_id.value() as u64,
+                word: ranking.word.value().to_string(),
+                registration_count: ranking.registration_count.value() as u64,
+            })
+            .collect())
+    }
+
+    pub async fn get_user_word_by_user_id_and_word_id(
+        &self,
+        request: request::GetUserWordRequest,
+    ) -> Result<response::GetUserWordResponse, CosanError> {
+        let user_id = entity::UserId::new(request.user_id as i64);
+        let word_id = entity::WordId::new(request.word_id as i64);
+
+        let user_word = self
+            .user_word_repository
+            .get_user_word_by_user_id_and_word_id(user_id.value(), word_id.value())
+            .await?;
+
+        match user_word {
+            Some(user_word) => Ok(response::GetUserWordResponse {
+                user_word_id: user_word.user_word_id.value() as u64,
+                user_id: user_word.user_id.value() as u64,
+                last_name: user_word.last_name.value().to_string(),
+                first_name: user_word.first_name.value().to_string(),
+                email: user_word.email.value().to_string(),
+                country: user_word.country.value().to_string(),
+                word_id: user_word.word_id.value() as u64,
+                word: user_word.word.value().to_string(),
+                created_at: user_word.created_at.value().to_string(),
+            }),
+            None => Err(CosanError::NotFound("User word not found".to_string())),
+        }
+    }
+
+    pub async fn get_user_word_by_word_id(
+        &self,
+        request: request::GetUserWordRequest,
+        page: request::ListUserWordRequest,
+    ) -> Result<response::GetUserWordListResponse, CosanError> {
+        let word_id = entity::WordId::new(request.word_id as i64);
+        let (sort, cursor, limit) = Self::page_params(page)?;
+
+        // fetch one extra row to know whether a next page exists
+        let user_words = self
+            .user_word_repository
+            .get_user_word_by_word_id(word_id.value(), sort, cursor, limit + 1)
+            .await?;
+
+        Ok(Self::user_word_page(
+            user_words.unwrap_or_default(),
+            sort,
+            limit,
+        ))
+    }
+
+    pub async fn get_user_word_by_user_id(
+        &self,
+        request: request::GetUserWordRequest,
+        page: request::ListUserWordRequest,
+    ) -> Result<response::GetUserWordListResponse, CosanError> {
+        let user_id = entity::UserId::new(request.user_id as i64);
+        let (sort, cursor, limit) = Self::page_params(page)?;
+
+        // fetch one extra row to know whether a next page exists
+        let user_words = self
+            .user_word_repository
+            .get_user_word_by_user_id(user_id.value(), sort, cursor, limit + 1)
+            .await?;
+
+        Ok(Self::user_word_page(
+            user_words.unwrap_or_default(),
+            sort,
+            limit,
+        ))
+    }
+
+    fn page_params(
+        page: request::ListUserWordRequest,
+    ) -> Result<(entity::UserWordSort, Option<entity::PageCursor>, i64), CosanError> {
+        let sort = entity::UserWordSort::new(
+            page.sort
+                .as_deref()
+                .unwrap_or(request::ListUserWordRequest::DEFAULT_SORT),
+        )
+        .ok_or_else(|| CosanError::BadRequest("Invalid sort".to_string()))?;
+        let cursor = match page.cursor {
+            Some(cursor) => Some(
+                entity::PageCursor::decode(cursor.as_str(), sort)
+                    .ok_or_else(|| CosanError::BadRequest("Invalid cursor".to_string()))?,
+            ),
+            None => None,
+        };
+        let limit = page
+            .limit
+            .unwrap_or(request::ListUserWordRequest::DEFAULT_LIMIT);
+
+        Ok((sort, cursor, limit as i64))
+    }
+
+    fn user_word_page(
+        mut user_words: Vec<entity::UserWord>,
+        sort: entity::UserWordSort,
+        limit: i64,
+    ) -> response::GetUserWordListResponse {
+        let has_next = user_words.len() as i64 > limit;
+        user_words.truncate(limit as usize);
+
+        let next_cursor = match user_words.last() {
+            Some(last) if has_next => {
+                let key = match sort {
+                    entity::UserWordSort::CreatedAtAsc | entity::UserWordSort::CreatedAtDesc => {
+                        entity::PageKey::CreatedAt(last.created_at.value().naive_utc())
+                    }
+                    entity::UserWordSort::Word => {
+                        entity::PageKey::Word(last.word.value().to_string())
+                    }
+                };
+                Some(entity::PageCursor::new(sort, key, last.user_word_id.value()).encode())
+            }
+            _ => None,
+        };
+
+        response::GetUserWordListResponse {
+            items: user_words
+                .into_iter()
+                .map(|user_word| response::GetUserWordResponse {
+                    user_word_id: user_word.user_word_id.value() as u64,
+                    user_id: user_word.user_id.value() as u64,
+                    last_name: user_word.last_name.value().to_string(),
+                    first_name: user_word.first_name.value().to_string(),
+                    email: user_word.email.value().to_string(),
+                    country: user_word.country.value().to_string(),
+                    word_id: user_word.word_id.value() as u64,
+                    word: user_word.word.value().to_string(),
+                    created_at: user_word.created_at.value().to_string(),
+                })
+                .collect(),
+            next_cursor,
+        }
+    }
+
+    pub async fn create_user_word(
+        &self,
+        request: request::CreateUserWordRequest,
+    ) -> Result<response::CreateUserWordRelationResponse, CosanError> {
+        let user_id = entity::UserId::new(request.user_id as i64);
+        let word_id = entity::WordId::new(request.word_id as i64);
+        self.require_verified_email(&user_id).await?;
+
+        let user_word = self
+            .user_word_repository
+            .create_user_word(user_id.value(), word_id.value())
+            .await?;
+
+        match user_word {
             Some(user_word) => Ok(response::CreateUserWordRelationResponse {
                 user_id: user_word.user_id.value() as u64,
                 word_id: user_word.word_id.value() as u64,
                 created_at: user_word.created_at.value().to_string(),
             }),
-            None => Err(anyhow::anyhow!("User word relation not created")),
+            None => Err(CosanError::Internal(
+                "User word relation not created".to_string(),
+            )),
+        }
+    }
+
+    pub async fn import_user_words(
+        &self,
+        user_id: i64,
+        request: request::ImportUserWordRequest,
+    ) -> Result<response::ImportUserWordResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        self.require_verified_email(&user_id).await?;
+        let words = request
+            .lines
+            .iter()
+            .filter_map(|line| match &line.entry {
+                request::ImportUserWordEntry::Word(word) => Some(entity::WordString::new(word)),
+                request::ImportUserWordEntry::Invalid { .. } => None,
+            })
+            .collect::<Vec<entity::WordString>>();
+
+        let mut imported_words = if words.is_empty() {
+            Vec::new()
+        } else {
+            self.user_word_repository
+                .import_user_words(user_id.value(), &words)
+                .await?
+                .ok_or_else(|| CosanError::Internal("User words not imported".to_string()))?
+        }
+        .into_iter();
+
+        let mut result = response::ImportUserWordResponse {
+            created: 0,
+            already_present: 0,
+            invalid: 0,
+            lines: Vec::with_capacity(request.lines.len()),
+        };
+        // imported words come back in input order, one per valid line
+        for line in request.lines {
+            let line_result = match line.entry {
+                request::ImportUserWordEntry::Word(word) => {
+                    let imported = imported_words.next().ok_or_else(|| {
+                        CosanError::Internal("User words not imported".to_string())
+                    })?;
+                    let status = if imported.created {
+                        result.created += 1;
+                        "created"
+                    } else {
+                        result.already_present += 1;
+                        "already_present"
+                    };
+                    response::ImportUserWordLineResponse {
+                        line: line.line,
+                        word,
+                        status: status.to_string(),
+                        word_id: Some(imported.word_id.value() as u64),
+                        reason: None,
+                    }
+                }
+                request::ImportUserWordEntry::Invalid { raw, reason } => {
+                    result.invalid += 1;
+                    response::ImportUserWordLineResponse {
+                        line: line.line,
+                        word: raw,
+                        status: "invalid".to_string(),
+                        word_id: None,
+                        reason: Some(reason),
+                    }
+                }
+            };
+            result.lines.push(line_result);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn get_due_reviews(
+        &self,
+        user_id: i64,
+        request: request::GetDueReviewRequest,
+    ) -> Result<Vec<response::GetDueReviewResponse>, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let limit = request
+            .limit
+            .unwrap_or(request::GetDueReviewRequest::DEFAULT_LIMIT);
+
+        let reviews = self
+            .user_word_repository
+            .get_due_reviews(user_id.value(), limit as i64)
+            .await?;
+
+        // nothing due is a normal state, not a missing resource
+        Ok(reviews
+            .unwrap_or_default()
+            .into_iter()
+            .map(|review| response::GetDueReviewResponse {
+                user_word_id: review.user_word_id.value() as u64,
+                word_id: review.word_id.value() as u64,
+                word: review.word.value().to_string(),
+                ease_factor: review.state.ease_factor.value(),
+                interval_days: review.state.interval_days.value(),
+                repetitions: review.state.repetitions.value(),
+                due_at: review.state.due_at.value().to_rfc3339(),
+            })
+            .collect())
+    }
+
+    pub async fn review_user_word(
+        &self,
+        user_id: i64,
+        user_word_id: i64,
+        request: request::ReviewUserWordRequest,
+    ) -> Result<response::ReviewUserWordResponse, CosanError> {
+        // grades of one word that keep racing give up with a conflict instead of looping
+        const REVIEW_ATTEMPTS: usize = 3;
+
+        let user_id = entity::UserId::new(user_id);
+        let user_word_id = entity::UserWordId::new(user_word_id);
+        let grade = entity::ReviewGrade::new(request.grade)
+            .ok_or_else(|| CosanError::BadRequest("Invalid grade".to_string()))?;
+
+        // a concurrent grade of the same word makes the update miss, so schedule again from its result
+        let mut review = None;
+        for _ in 0..REVIEW_ATTEMPTS {
+            let current = self
+                .user_word_repository
+                .get_review(user_id.value(), user_word_id.value())
+                .await?
+                .ok_or_else(|| CosanError::NotFound("Review not found".to_string()))?;
+
+            let state = current.state.schedule(grade, chrono::Utc::now());
+
+            match self
+                .user_word_repository
+                .update_review(
+                    user_id.value(),
+                    user_word_id.value(),
+                    &current.state,
+                    &state,
+                )
+                .await
+            {
+                Ok(Some(updated)) => {
+                    review = Some(updated);
+                    break;
+                }
+                Ok(None) | Err(sqlx::Error::RowNotFound) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        match review {
+            Some(review) => Ok(response::ReviewUserWordResponse {
+                user_word_id: review.user_word_id.value() as u64,
+                word_id: review.word_id.value() as u64,
+                word: review.word.value().to_string(),
+                ease_factor: review.state.ease_factor.value(),
+                interval_days: review.state.interval_days.value(),
+                repetitions: review.state.repetitions.value(),
+                due_at: review.state.due_at.value().to_rfc3339(),
+            }),
+            None => Err(CosanError::Conflict(
+                "The word is being reviewed concurrently".to_string(),
+            )),
+        }
+    }
+
+    pub async fn create_quiz(
+        &self,
+        user_id: i64,
+        request: request::CreateQuizRequest,
+    ) -> Result<response::CreateQuizResponse, CosanError> {
+        // upper bound of the distractor pool read per quiz
+        const DISTRACTOR_POOL: i64 = 500;
+
+        let user_id = entity::UserId::new(user_id);
+        let mode = entity::QuizMode::new(
+            request
+                .mode
+                .as_deref()
+                .unwrap_or(request::CreateQuizRequest::DEFAULT_MODE),
+        )
+        .ok_or_else(|| CosanError::BadRequest("Invalid quiz mode".to_string()))?;
+        let count = request
+            .count
+            .unwrap_or(request::CreateQuizRequest::DEFAULT_COUNT);
+        // without a seed the quiz is random, the seed is returned to replay it
+        let seed = entity::QuizSeed::new(request.seed.unwrap_or_else(rand::random));
+
+        let words = self
+            .quiz_repository
+            .get_quiz_words(user_id.value())
+            .await?
+            .ok_or_else(|| {
+                CosanError::Validation("User has no registered words to quiz".to_string())
+            })?;
+        let distractors = match mode {
+            entity::QuizMode::MultipleChoice => self
+                .quiz_repository
+                .get_distractor_words(user_id.value(), seed.value() as i64, DISTRACTOR_POOL)
+                .await?
+                .unwrap_or_default(),
+            entity::QuizMode::Typing => Vec::new(),
+        };
+
+        let questions =
+            entity::QuizQuestion::generate(mode, seed, count as usize, &words, &distractors);
+        if questions.is_empty() {
+            return Err(CosanError::Validation(
+                "User has no registered words with a definition to quiz".to_string(),
+            ));
+        }
+
+        let quiz_id = self
+            .quiz_repository
+            .create_quiz(
+                user_id.value(),
+                mode.value(),
+                seed.value() as i64,
+                &questions,
+            )
+            .await?;
+
+        match quiz_id {
+            Some(quiz_id) => {
+                let quiz = entity::Quiz::new(quiz_id, mode, seed, questions);
+                Ok(response::CreateQuizResponse {
+                    quiz_id: quiz.quiz_id.value() as u64,
+                    mode: quiz.mode.value().to_string(),
+                    seed: quiz.seed.value(),
+                    questions: quiz
+                        .questions
+                        .into_iter()
+                        .map(|question| response::QuizQuestionResponse {
+                            position: question.position.value() as u32,
+                            prompt: question.prompt,
+                            choices: question
+                                .choices
+                                .iter()
+                                .map(|choice| choice.value().to_string())
+                                .collect(),
+                        })
+                        .collect(),
+                })
+            }
+            None => Err(CosanError::Internal("Quiz not created".to_string())),
+        }
+    }
+
+    pub async fn answer_quiz(
+        &self,
+        user_id: i64,
+        quiz_id: i64,
+        request: request::AnswerQuizRequest,
+    ) -> Result<response::AnswerQuizResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let quiz_id = entity::QuizId::new(quiz_id);
+        let position = entity::QuizPosition::new(request.position as i32);
+        let answer = entity::WordString::new(request.answer.as_str());
+
+        let question = self
+            .quiz_repository
+            .get_quiz_question(user_id.value(), quiz_id.value(), position.value())
+            .await?
+            .ok_or_else(|| CosanError::NotFound("Quiz question not found".to_string()))?;
+        let correct = question.is_correct(&answer);
+
+        // the question exists, so finding no unanswered row means it was answered before
+        let result = match self
+            .quiz_repository
+            .answer_quiz_question(
+                user_id.value(),
+                quiz_id.value(),
+                position.value(),
+                answer.value(),
+                correct,
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        match result {
+            Some(result) => Ok(response::AnswerQuizResponse {
+                quiz_id: result.quiz_id.value() as u64,
+                position: result.position.value() as u32,
+                correct: result.correct,
+                expected: question.word.value().to_string(),
+                score: result.score.value() as u32,
+                answered: result.answered as u32,
+                total: result.total as u32,
+            }),
+            None => Err(CosanError::Conflict(
+                "Quiz question already answered".to_string(),
+            )),
+        }
+    }
+
+    fn deck_response(deck: entity::Deck) -> response::DeckResponse {
+        response::DeckResponse {
+            deck_id: deck.deck_id.value() as u64,
+            name: deck.name.value().to_string(),
+            word_count: deck.word_count as u64,
+            share_token: deck
+                .share_token
+                .map(|share_token| share_token.value().to_string()),
+            created_at: deck.created_at.value().to_rfc3339(),
+        }
+    }
+
+    async fn get_deck_response(
+        &self,
+        deck: entity::Deck,
+    ) -> Result<response::GetDeckResponse, CosanError> {
+        let words = self
+            .deck_repository
+            .get_deck_words(deck.deck_id.value())
+            .await?
+            .unwrap_or_default();
+        let deck = Self::deck_response(deck);
+
+        Ok(response::GetDeckResponse {
+            deck_id: deck.deck_id,
+            name: deck.name,
+            word_count: deck.word_count,
+            share_token: deck.share_token,
+            created_at: deck.created_at,
+            words: words
+                .into_iter()
+                .map(|word| response::DeckWordResponse {
+                    user_word_id: word.user_word_id.value() as u64,
+                    word_id: word.word_id.value() as u64,
+                    word: word.word.value().to_string(),
+                })
+                .collect(),
+        })
+    }
+
+    pub async fn create_deck(
+        &self,
+        user_id: i64,
+        request: request::CreateDeckRequest,
+    ) -> Result<response::DeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let name = entity::DeckName::new(request.name.as_str());
+
+        let deck = self
+            .deck_repository
+            .create_deck(user_id.value(), name.value())
+            .await?;
+
+        match deck {
+            Some(deck) => Ok(Self::deck_response(deck)),
+            None => Err(CosanError::Internal("Deck not created".to_string())),
+        }
+    }
+
+    pub async fn get_decks(&self, user_id: i64) -> Result<Vec<response::DeckResponse>, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+
+        let decks = self.deck_repository.get_decks(user_id.value()).await?;
+
+        Ok(decks
+            .unwrap_or_default()
+            .into_iter()
+            .map(Self::deck_response)
+            .collect())
+    }
+
+    pub async fn get_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<response::GetDeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+
+        let deck = self
+            .deck_repository
+            .get_deck(user_id.value(), deck_id.value())
+            .await?;
+
+        match deck {
+            Some(deck) => self.get_deck_response(deck).await,
+            None => Err(CosanError::NotFound("Deck not found".to_string())),
+        }
+    }
+
+    pub async fn update_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        request: request::UpdateDeckRequest,
+    ) -> Result<response::DeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+        let name = entity::DeckName::new(request.name.as_str());
+
+        let deck = self
+            .deck_repository
+            .update_deck(user_id.value(), deck_id.value(), name.value())
+            .await?;
+
+        match deck {
+            Some(deck) => Ok(Self::deck_response(deck)),
+            None => Err(CosanError::Internal("Deck not updated".to_string())),
+        }
+    }
+
+    pub async fn delete_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<response::DeleteDeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+
+        let result = self
+            .deck_repository
+            .delete_deck(user_id.value(), deck_id.value())
+            .await?;
+
+        match result {
+            Some(_) => Ok(response::DeleteDeckResponse {
+                status: "success".to_string(),
+            }),
+            None => Err(CosanError::Internal("Deck not deleted".to_string())),
+        }
+    }
+
+    pub async fn add_deck_words(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        request: request::AddDeckWordRequest,
+    ) -> Result<response::GetDeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+        let user_word_ids = request
+            .user_word_ids
+            .iter()
+            // validated to fit an i64
+            .map(|user_word_id| entity::UserWordId::new(*user_word_id as i64).value())
+            .collect::<Vec<i64>>();
+
+        // resolves ownership first so a foreign deck is a 404, not an empty insert
+        let deck = self
+            .deck_repository
+            .get_deck(user_id.value(), deck_id.value())
+            .await?
+            .ok_or_else(|| CosanError::NotFound("Deck not found".to_string()))?;
+
+        self.deck_repository
+            .add_deck_words(user_id.value(), deck.deck_id.value(), &user_word_ids)
+            .await?;
+
+        let deck = self
+            .deck_repository
+            .get_deck(user_id.value(), deck_id.value())
+            .await?;
+
+        match deck {
+            Some(deck) => self.get_deck_response(deck).await,
+            None => Err(CosanError::NotFound("Deck not found".to_string())),
+        }
+    }
+
+    pub async fn remove_deck_word(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        user_word_id: i64,
+    ) -> Result<response::DeleteDeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+        let user_word_id = entity::UserWordId::new(user_word_id);
+
+        let result = self
+            .deck_repository
+            .remove_deck_word(user_id.value(), deck_id.value(), user_word_id.value())
+            .await?;
+
+        match result {
+            Some(_) => Ok(response::DeleteDeckResponse {
+                status: "success".to_string(),
+            }),
+            None => Err(CosanError::Internal("Deck word not deleted".to_string())),
+        }
+    }
+
+    pub async fn share_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<response::DeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+        // sharing again rotates the token and invalidates the previous link
+        let share_token = entity::ShareToken::generate();
+
+        let deck = self
+            .deck_repository
+            .update_share_token(user_id.value(), deck_id.value(), Some(share_token.value()))
+            .await?;
+
+        match deck {
+            Some(deck) => Ok(Self::deck_response(deck)),
+            None => Err(CosanError::Internal("Deck not shared".to_string())),
         }
     }
 
+    pub async fn unshare_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<response::DeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let deck_id = entity::DeckId::new(deck_id);
+
+        let deck = self
+            .deck_repository
+            .update_share_token(user_id.value(), deck_id.value(), None)
+            .await?;
+
+        match deck {
+            Some(deck) => Ok(Self::deck_response(deck)),
+            None => Err(CosanError::Internal("Deck not unshared".to_string())),
+        }
+    }
+
+    pub async fn get_shared_deck(
+        &self,
+        share_token: &str,
+    ) -> Result<response::GetDeckResponse, CosanError> {
+        let share_token = entity::ShareToken::new(share_token);
+
+        let deck = self
+            .deck_repository
+            .get_shared_deck(share_token.value())
+            .await?;
+
+        match deck {
+            Some(deck) => self.get_deck_response(deck).await,
+            None => Err(CosanError::NotFound("Deck not found".to_string())),
+        }
+    }
+
+    pub async fn clone_shared_deck(
+        &self,
+        user_id: i64,
+        share_token: &str,
+    ) -> Result<response::CloneDeckResponse, CosanError> {
+        let user_id = entity::UserId::new(user_id);
+        let share_token = entity::ShareToken::new(share_token);
+
+        // cloning registers the deck's words for the caller, so it takes the same gate
+        self.require_verified_email(&user_id).await?;
+
+        // an unknown or revoked token is a 404 rather than an empty clone
+        self.deck_repository
+            .get_shared_deck(share_token.value())
+            .await?
+            .ok_or_else(|| CosanError::NotFound("Deck not found".to_string()))?;
+
+        let cloned = self
+            .deck_repository
+            .clone_shared_deck(user_id.value(), share_token.value())
+            .await?;
+
+        match cloned {
+            Some(cloned) => Ok(response::CloneDeckResponse {
+                created: cloned.created as u64,
+                already_present: cloned.already_present as u64,
+            }),
+            None => Err(CosanError::Internal("Deck not cloned".to_string())),
+        }
+    }
+
+    pub fn export_user_words(
+        &self,
+        user_id: i64,
+        format: entity::ExportFormat,
+    ) -> BoxStream<'static, Result<String, anyhow::Error>> {
+        let user_id = entity::UserId::new(user_id);
+        let mut words = self.user_word_repository.export_user_words(user_id.value());
+
+        // each chunk is one serialized row, nothing is buffered beyond it
+        Box::pin(try_stream! {
+            match format {
+                entity::ExportFormat::Csv => yield "word,created_at\n".to_string(),
+                entity::ExportFormat::Json => yield "[".to_string(),
+                entity::ExportFormat::Ndjson => {}
+            }
+
+            let mut first = true;
+            while let Some(word) = words.try_next().await? {
+                let record = response::ExportUserWordResponse {
+                    word: word.word.value().to_string(),
+                    created_at: word.created_at.value().to_rfc3339(),
+                };
+                let chunk = match format {
+                    entity::ExportFormat::Csv => {
+                        let mut writer = csv::WriterBuilder::new()
+                            .has_headers(false)
+                            .from_writer(Vec::new());
+                        writer.serialize(&record)?;
+                        String::from_utf8(writer.into_inner().map_err(|e| e.into_error())?)?
+                    }
+                    entity::ExportFormat::Json => {
+                        let json = serde_json::to_string(&record)?;
+                        if first {
+                            json
+                        } else {
+                            format!(",{}", json)
+                        }
+                    }
+                    entity::ExportFormat::Ndjson => {
+                        let json = serde_json::to_string(&record)?;
+                        format!("{}\n", json)
+                    }
+                };
+                first = false;
+
+                yield chunk;
+            }
+
+            if format == entity::ExportFormat::Json {
+                yield "]".to_string();
+            }
+        })
+    }
+
     pub async fn delete_user_word(
         &self,
         id: i64,
-    ) -> Result<response::DeleteUserWordResponse, anyhow::Error> {
+    ) -> Result<response::DeleteUserWordResponse, CosanError> {
         let result = self.user_word_repository.delete_user_word(id).await?;
         match result {
             Some(_) => Ok(response::DeleteUserWordResponse {
                 status: "success".to_string(),
             }),
-            None => Err(anyhow::anyhow!("User word not deleted")),
+            None => Err(CosanError::Internal("User word not deleted".to_string())),
+        }
+    }
+
+    pub async fn get_user_word_owner(&self, user_word_id: i64) -> Result<i64, CosanError> {
+        let owner = self
+            .user_word_repository
+            .get_user_word_owner(user_word_id)
+            .await?;
+        match owner {
+            Some(owner) => Ok(owner.value()),
+            None => Err(CosanError::NotFound("User word not found".to_string())),
+        }
+    }
+
+    /// Reserves an `Idempotency-Key` for a request, or returns the response of its earlier run to replay.
+    ///
+    /// A key reused for another request is rejected, and so is one whose first request is still running.
+    pub async fn begin_idempotent_request(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+    ) -> Result<Option<entity::StoredResponse>, CosanError> {
+        let expires_at = (chrono::Utc::now()
+            + chrono::Duration::seconds(entity::IDEMPOTENCY_KEY_TTL_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%S%.6f")
+        .to_string();
+
+        let claimed = self
+            .idempotency_repository
+            .claim_idempotency_key(
+                principal,
+                idempotency_key,
+                request_hash,
+                expires_at.as_str(),
+                entity::IDEMPOTENCY_KEY_LOCK_SECONDS,
+            )
+            .await?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let existing = match self
+            .idempotency_repository
+            .get_idempotency_key(principal, idempotency_key)
+            .await
+        {
+            Ok(Some(existing)) => existing,
+            // released or expired between the two statements, the client may simply retry
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(CosanError::Conflict(
+                    "A request with this Idempotency-Key is in progress".to_string(),
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if existing.request_hash != request_hash {
+            return Err(CosanError::Validation(
+                "Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+
+        match existing.response {
+            Some(response) => Ok(Some(response)),
+            None => Err(CosanError::Conflict(
+                "A request with this Idempotency-Key is in progress".to_string(),
+            )),
+        }
+    }
+
+    /// Keeps the response of the request holding the key for the retries to come.
+    pub async fn complete_idempotent_request(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+        response: &entity::StoredResponse,
+    ) -> Result<(), CosanError> {
+        self.idempotency_repository
+            .complete_idempotency_key(principal, idempotency_key, response)
+            .await?
+            .ok_or_else(|| CosanError::Internal("Idempotency key not completed".to_string()))?;
+
+        Ok(())
+    }
+
+    pub async fn release_idempotent_request(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+    ) -> Result<(), CosanError> {
+        self.idempotency_repository
+            .release_idempotency_key(principal, idempotency_key)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::repository;
+    use async_trait::async_trait;
+    use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+    use interface::{
+        DeckRepositoryTrait, QuizRepositoryTrait, SessionRepositoryTrait, UserRepositoryTrait,
+        UserWordRepositoryTrait, WordRepositoryTrait,
+    };
+    use sqlx::Pool;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    struct StoredKey {
+        request_hash: String,
+        response: Option<entity::StoredResponse>,
+        expires_at: DateTime<Utc>,
+        created_at: DateTime<Utc>,
+    }
+
+    /// Keeps keys in memory the way `idempotency_keys` does, on a clock the test can move forward.
+    #[derive(Clone, Default)]
+    struct MemoryIdempotencyRepository {
+        keys: Arc<Mutex<HashMap<(String, String), StoredKey>>>,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl MemoryIdempotencyRepository {
+        fn now(&self) -> DateTime<Utc> {
+            Utc::now() + *self.elapsed.lock().unwrap()
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.elapsed.lock().unwrap() += by;
+        }
+    }
+
+    #[async_trait]
+    impl interface::IdempotencyRepositoryTrait for MemoryIdempotencyRepository {
+        fn new(_pool: Pool<sqlx::Postgres>) -> Self {
+            Self::default()
+        }
+
+        async fn claim_idempotency_key(
+            &self,
+            principal: &str,
+            idempotency_key: &str,
+            request_hash: &str,
+            expires_at: &str,
+            lock_seconds: i64,
+        ) -> Result<Option<()>, sqlx::Error> {
+            let now = self.now();
+            let mut keys = self.keys.lock().unwrap();
+            let key = (principal.to_string(), idempotency_key.to_string());
+            if let Some(stored) = keys.get(&key) {
+                let abandoned = stored.response.is_none()
+                    && stored.created_at <= now - Duration::seconds(lock_seconds);
+                if stored.expires_at > now && !abandoned {
+                    return Ok(None);
+                }
+            }
+
+            // expires_at is measured from the real clock, shift it onto the test clock
+            let expires_at = NaiveDateTime::parse_from_str(expires_at, "%Y-%m-%dT%H:%M:%S%.6f")
+                .unwrap()
+                .and_utc()
+                + *self.elapsed.lock().unwrap();
+            keys.insert(
+                key,
+                StoredKey {
+                    request_hash: request_hash.to_string(),
+                    response: None,
+                    expires_at,
+                    created_at: now,
+                },
+            );
+            Ok(Some(()))
+        }
+
+        async fn get_idempotency_key(
+            &self,
+            principal: &str,
+            idempotency_key: &str,
+        ) -> Result<Option<entity::IdempotencyKey>, sqlx::Error> {
+            let now = self.now();
+            let keys = self.keys.lock().unwrap();
+            Ok(keys
+                .get(&(principal.to_string(), idempotency_key.to_string()))
+                .filter(|stored| stored.expires_at > now)
+                .map(|stored| {
+                    entity::IdempotencyKey::new(
+                        stored.request_hash.clone(),
+                        stored.response.clone(),
+                    )
+                }))
         }
+
+        async fn complete_idempotency_key(
+            &self,
+            principal: &str,
+            idempotency_key: &str,
+            response: &entity::StoredResponse,
+        ) -> Result<Option<()>, sqlx::Error> {
+            let mut keys = self.keys.lock().unwrap();
+            match keys.get_mut(&(principal.to_string(), idempotency_key.to_string())) {
+                Some(stored) if stored.response.is_none() => {
+                    stored.response = Some(response.clone());
+                    Ok(Some(()))
+                }
+                _ => Ok(None),
+            }
+        }
+
+        async fn release_idempotency_key(
+            &self,
+            principal: &str,
+            idempotency_key: &str,
+        ) -> Result<(), sqlx::Error> {
+            let mut keys = self.keys.lock().unwrap();
+            let key = (principal.to_string(), idempotency_key.to_string());
+            if keys
+                .get(&key)
+                .is_some_and(|stored| stored.response.is_none())
+            {
+                keys.remove(&key);
+            }
+            Ok(())
+        }
+    }
+
+    type IdempotencyService = CosanService<
+        repository::UserRepository,
+        repository::WordRepository,
+        repository::UserWordRepository,
+        repository::QuizRepository,
+        repository::DeckRepository,
+        repository::SessionRepository,
+        MemoryIdempotencyRepository,
+    >;
+
+    fn idempotency_service(
+        idempotency_repository: MemoryIdempotencyRepository,
+    ) -> IdempotencyService {
+        // never connected, the idempotency methods reach no other repository
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/cosan")
+            .unwrap();
+
+        CosanService::new(
+            repository::UserRepository::new(pool.clone()),
+            repository::WordRepository::new(pool.clone()),
+            repository::UserWordRepository::new(pool.clone()),
+            repository::QuizRepository::new(pool.clone()),
+            repository::DeckRepository::new(pool.clone()),
+            repository::SessionRepository::new(pool),
+            idempotency_repository,
+        )
+    }
+
+    fn created() -> entity::StoredResponse {
+        entity::StoredResponse::new(
+            201,
+            vec![("location".to_string(), "/cosan/v1/word/7".to_string())],
+            br#"{"word_id":7}"#.to_vec(),
+        )
+    }
+
+    #[tokio::test]
+    async fn begin_idempotent_request_replays_the_completed_response() {
+        let service = idempotency_service(MemoryIdempotencyRepository::default());
+
+        assert!(service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap()
+            .is_none());
+        service
+            .complete_idempotent_request("user:1", "key", &created())
+            .await
+            .unwrap();
+
+        let replayed = service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replayed.status_code, 201);
+        assert_eq!(replayed.headers, created().headers);
+        assert_eq!(replayed.body, created().body);
+    }
+
+    #[tokio::test]
+    async fn begin_idempotent_request_rejects_a_key_reused_for_another_request() {
+        let service = idempotency_service(MemoryIdempotencyRepository::default());
+
+        service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap();
+        assert!(matches!(
+            service
+                .begin_idempotent_request("user:1", "key", "hash")
+                .await,
+            Err(CosanError::Conflict(_))
+        ));
+        service
+            .complete_idempotent_request("user:1", "key", &created())
+            .await
+            .unwrap();
+
+        // a 422 whatever became of the first request
+        assert!(matches!(
+            service
+                .begin_idempotent_request("user:1", "key", "other hash")
+                .await,
+            Err(CosanError::Validation(_))
+        ));
+        // other principals have keys of their own
+        assert!(service
+            .begin_idempotent_request("user:2", "key", "other hash")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn begin_idempotent_request_runs_the_request_again_once_the_key_expired() {
+        let idempotency_repository = MemoryIdempotencyRepository::default();
+        let service = idempotency_service(idempotency_repository.clone());
+
+        service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap();
+        service
+            .complete_idempotent_request("user:1", "key", &created())
+            .await
+            .unwrap();
+
+        idempotency_repository.advance(Duration::seconds(entity::IDEMPOTENCY_KEY_TTL_SECONDS - 60));
+        assert!(service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap()
+            .is_some());
+
+        idempotency_repository.advance(Duration::seconds(120));
+        assert!(service
+            .begin_idempotent_request("user:1", "key", "other hash")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn release_idempotent_request_lets_the_retry_run_again() {
+        let service = idempotency_service(MemoryIdempotencyRepository::default());
+
+        service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap();
+        service
+            .release_idempotent_request("user:1", "key")
+            .await
+            .unwrap();
+
+        assert!(service
+            .begin_idempotent_request("user:1", "key", "hash")
+            .await
+            .unwrap()
+            .is_none());
     }
 }
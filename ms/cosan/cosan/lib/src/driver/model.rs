@@ -10,29 +10,11 @@ pub struct GetUser {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl GetUser {
-    pub fn new(
-        user_id: i64,
-        last_name: String,
-        first_name: String,
-        login_id: String,
-        password: String,
-        email: String,
-        country: String,
-    ) -> Self {
-        Self {
-            user_id,
-            last_name,
-            first_name,
-            login_id,
-            password,
-            email,
-            country,
-        }
-    }
-
     pub fn is_valid(&self) -> bool {
         self.user_id >= 0
             && !self.last_name.is_empty()
@@ -43,7 +25,7 @@ impl GetUser {
             && !self.country.is_empty()
     }
 
-    pub async fn convert_hash_password(self) -> Result<Self, bcrypt::BcryptError> {
+    pub async fn convert_hash_password(self) -> Result<Self, util::crypt::CryptError> {
         let hashed_password = util::crypt::hash_password(&self.password).await?;
         Ok(Self {
             password: hashed_password,
@@ -52,6 +34,56 @@ impl GetUser {
     }
 }
 
+#[derive(Debug, FromRow)]
+pub struct VerifyEmail {
+    pub user_id: i64,
+}
+
+impl VerifyEmail {
+    pub fn is_valid(&self) -> bool {
+        self.user_id >= 0
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct ResetPassword {
+    pub user_id: i64,
+}
+
+impl ResetPassword {
+    pub fn is_valid(&self) -> bool {
+        self.user_id >= 0
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetUserCredential {
+    pub user_id: i64,
+    pub password: String,
+    pub role: String,
+    pub totp_enabled: bool,
+}
+
+impl GetUserCredential {
+    pub fn is_valid(&self) -> bool {
+        self.user_id >= 0 && !self.password.is_empty() && !self.role.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetUserTotp {
+    pub user_id: i64,
+    pub login_id: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+}
+
+impl GetUserTotp {
+    pub fn is_valid(&self) -> bool {
+        self.user_id >= 0 && !self.login_id.is_empty()
+    }
+}
+
 #[derive(Debug, FromRow)]
 pub struct CreateUser {
     pub user_id: i64,
@@ -61,6 +93,8 @@ pub struct CreateUser {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl CreateUser {
@@ -74,7 +108,7 @@ impl CreateUser {
             && !self.country.is_empty()
     }
 
-    pub async fn convert_hash_password(self) -> Result<Self, bcrypt::BcryptError> {
+    pub async fn convert_hash_password(self) -> Result<Self, util::crypt::CryptError> {
         let hashed_password = util::crypt::hash_password(&self.password).await?;
         Ok(Self {
             password: hashed_password,
@@ -92,6 +126,8 @@ pub struct UpdateUser {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl UpdateUser {
@@ -105,7 +141,7 @@ impl UpdateUser {
             && !self.country.is_empty()
     }
 
-    pub async fn convert_hash_password(self) -> Result<Self, bcrypt::BcryptError> {
+    pub async fn convert_hash_password(self) -> Result<Self, util::crypt::CryptError> {
         let hashed_password = util::crypt::hash_password(&self.password).await?;
         Ok(Self {
             password: hashed_password,
@@ -118,6 +154,8 @@ impl UpdateUser {
 pub struct GetWord {
     pub word_id: i64,
     pub word: String,
+    pub definition: Option<String>,
+    pub version: i64,
 }
 
 impl GetWord {
@@ -130,11 +168,13 @@ impl GetWord {
 pub struct CreateWord {
     pub word_id: i64,
     pub word: String,
+    pub definition: Option<String>,
+    pub version: i64,
 }
 
 impl CreateWord {
     pub fn is_valid(&self) -> bool {
-        self.word_id >= 0 && self.word.is_empty()
+        self.word_id >= 0 && !self.word.is_empty()
     }
 }
 
@@ -142,6 +182,8 @@ impl CreateWord {
 pub struct UpdateWord {
     pub word_id: i64,
     pub word: String,
+    pub definition: Option<String>,
+    pub version: i64,
 }
 
 impl UpdateWord {
@@ -150,6 +192,33 @@ impl UpdateWord {
     }
 }
 
+#[derive(Debug, FromRow)]
+pub struct SearchWord {
+    pub word_id: i64,
+    pub word: String,
+    pub score: f64,
+}
+
+impl SearchWord {
+    pub fn is_valid(&self) -> bool {
+        self.word_id >= 0 && !self.word.is_empty() && self.score >= 0.0
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetWordRanking {
+    pub rank: i64,
+    pub word_id: i64,
+    pub word: String,
+    pub registration_count: i64,
+}
+
+impl GetWordRanking {
+    pub fn is_valid(&self) -> bool {
+        self.rank > 0 && self.word_id >= 0 && !self.word.is_empty() && self.registration_count > 0
+    }
+}
+
 #[derive(Debug, FromRow)]
 pub struct GetUserWord {
     pub user_word_id: i64,
@@ -213,3 +282,193 @@ impl CreateUserWord {
         self.user_id >= 0 && self.word_id >= 0
     }
 }
+
+#[derive(Debug, FromRow)]
+pub struct ImportUserWord {
+    pub word_id: i64,
+    pub word: String,
+    pub created: bool,
+}
+
+impl ImportUserWord {
+    pub fn is_valid(&self) -> bool {
+        self.word_id >= 0 && !self.word.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct ExportUserWord {
+    pub word: String,
+    pub created_at: String,
+}
+
+impl ExportUserWord {
+    pub fn is_valid(&self) -> bool {
+        !self.word.is_empty() && !self.created_at.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetReview {
+    pub user_word_id: i64,
+    pub word_id: i64,
+    pub word: String,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub due_at: String,
+}
+
+impl GetReview {
+    pub fn is_valid(&self) -> bool {
+        self.user_word_id >= 0 && self.word_id >= 0 && !self.due_at.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct CreateQuiz {
+    pub quiz_id: i64,
+}
+
+impl CreateQuiz {
+    pub fn is_valid(&self) -> bool {
+        self.quiz_id >= 0
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetQuizQuestion {
+    pub position: i32,
+    pub word_id: i64,
+    pub word: String,
+    pub prompt: String,
+    pub choices: Vec<String>,
+}
+
+impl GetQuizQuestion {
+    pub fn is_valid(&self) -> bool {
+        self.position >= 0 && self.word_id >= 0 && !self.word.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct AnswerQuizQuestion {
+    pub quiz_id: i64,
+    pub position: i32,
+    pub correct: bool,
+    pub score: i32,
+    pub answered: i32,
+    pub total: i32,
+}
+
+impl AnswerQuizQuestion {
+    pub fn is_valid(&self) -> bool {
+        self.quiz_id >= 0 && self.position >= 0 && self.answered <= self.total
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetDeck {
+    pub deck_id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub share_token: Option<String>,
+    pub word_count: i64,
+    pub created_at: String,
+}
+
+impl GetDeck {
+    pub fn is_valid(&self) -> bool {
+        self.deck_id >= 0 && self.user_id >= 0 && !self.name.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetDeckWord {
+    pub user_word_id: i64,
+    pub word_id: i64,
+    pub word: String,
+}
+
+impl GetDeckWord {
+    pub fn is_valid(&self) -> bool {
+        self.user_word_id >= 0 && self.word_id >= 0 && !self.word.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct CloneSharedDeck {
+    pub created: i64,
+    pub total: i64,
+}
+
+impl CloneSharedDeck {
+    pub fn is_valid(&self) -> bool {
+        self.created >= 0 && self.created <= self.total
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetRefreshSession {
+    pub refresh_token_id: i64,
+    pub user_id: i64,
+    pub family_id: String,
+    pub role: String,
+    pub expires_at: String,
+    pub used: bool,
+    pub revoked: bool,
+}
+
+impl GetRefreshSession {
+    pub fn is_valid(&self) -> bool {
+        self.refresh_token_id >= 0
+            && self.user_id >= 0
+            && !self.family_id.is_empty()
+            && !self.role.is_empty()
+            && !self.expires_at.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetLoginFailures {
+    pub failures: i64,
+    pub last_failed_at: Option<String>,
+}
+
+impl GetLoginFailures {
+    pub fn is_valid(&self) -> bool {
+        self.failures >= 0 && (self.failures == 0 || self.last_failed_at.is_some())
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetLoginChallenge {
+    pub login_challenge_id: i64,
+    pub user_id: i64,
+    pub login_id: String,
+    pub role: String,
+    pub attempts: i32,
+}
+
+impl GetLoginChallenge {
+    pub fn is_valid(&self) -> bool {
+        self.login_challenge_id >= 0
+            && self.user_id >= 0
+            && !self.login_id.is_empty()
+            && !self.role.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetIdempotencyKey {
+    pub request_hash: String,
+    pub status_code: Option<i32>,
+    pub response_headers: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+}
+
+impl GetIdempotencyKey {
+    pub fn is_valid(&self) -> bool {
+        !self.request_hash.is_empty()
+    }
+}
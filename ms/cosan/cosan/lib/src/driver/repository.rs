@@ -1,9 +1,12 @@
 use crate::domain::entity;
 use crate::domain::interface;
 use crate::driver::model;
+use async_stream::try_stream;
 use async_trait::async_trait;
 use chrono::DateTime;
 use chrono::Utc;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 use sqlx;
 use sqlx::Pool;
 
@@ -21,7 +24,7 @@ impl interface::UserRepositoryTrait for UserRepository {
         let record = sqlx::query_as::<_, model::GetUser>(
             r#"
             SELECT 
-                user_id, last_name, first_name, login_id, password, email, country
+                user_id, last_name, first_name, login_id, password, email, country, email_verified, version
             FROM 
                 users
             WHERE 
@@ -38,12 +41,16 @@ impl interface::UserRepositoryTrait for UserRepository {
 
         Ok(Some(entity::User::new(
             entity::UserId::new(record.user_id),
-            entity::LastName::new(record.last_name.as_str()),
-            entity::FirstName::new(record.first_name.as_str()),
-            entity::LoginId::new(record.login_id.as_str()),
-            entity::PasswordHash::new(record.password.as_str()),
-            entity::Email::new(record.email.as_str()),
-            entity::Country::new(record.country.as_str()),
+            entity::UserProfile::new(
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::LoginId::new(record.login_id.as_str()),
+                entity::PasswordHash::new(record.password.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
+            ),
+            entity::EmailVerified::new(record.email_verified),
+            entity::Version::new(record.version),
         )))
     }
 
@@ -63,7 +70,7 @@ impl interface::UserRepositoryTrait for UserRepository {
             VALUES 
                 ($1, $2, $3, $4, $5, $6)
             RETURNING 
-                user_id, last_name, first_name, login_id, password, email, country;
+                user_id, last_name, first_name, login_id, password, email, country, email_verified, version;
             "#,
         )
         .bind(last_name)
@@ -81,42 +88,101 @@ impl interface::UserRepositoryTrait for UserRepository {
 
         Ok(Some(entity::User::new(
             entity::UserId::new(record.user_id),
-            entity::LastName::new(record.last_name.as_str()),
-            entity::FirstName::new(record.first_name.as_str()),
-            entity::LoginId::new(record.login_id.as_str()),
-            entity::PasswordHash::new(record.password.as_str()),
-            entity::Email::new(record.email.as_str()),
-            entity::Country::new(record.country.as_str()),
+            entity::UserProfile::new(
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::LoginId::new(record.login_id.as_str()),
+                entity::PasswordHash::new(record.password.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
+            ),
+            entity::EmailVerified::new(record.email_verified),
+            entity::Version::new(record.version),
         )))
     }
 
     async fn update_user(
         &self,
         user_id: i64,
-        last_name: &str,
-        first_name: &str,
-        login_id: &str,
-        password: &str,
-        email: &str,
-        country: &str,
+        details: &entity::UserDetails,
+        versions: Option<&[i64]>,
     ) -> Result<Option<entity::User>, sqlx::Error> {
         let record = sqlx::query_as::<_, model::UpdateUser>(
             r#"
             UPDATE users
-                SET last_name = $1, first_name = $2, login_id = $3, password = $4, email = $5, country = $6
+                SET last_name = $1, first_name = $2, login_id = $3, email = $4, country = $5,
+                    -- a new address has to be confirmed again
+                    email_verified = email_verified AND email = $4,
+                    version = version + 1
             WHERE 
-                user_id = $7
+                user_id = $6
+                AND ($7::BIGINT[] IS NULL OR version = ANY($7))
             RETURNING 
-                user_id, last_name, first_name, login_id, password, email, country;
+                user_id, last_name, first_name, login_id, password, email, country, email_verified, version;
+            "#,
+        )
+        .bind(details.last_name.value())
+        .bind(details.first_name.value())
+        .bind(details.login_id.value())
+        .bind(details.email.value())
+        .bind(details.country.value())
+        .bind(user_id)
+        .bind(versions)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::User::new(
+            entity::UserId::new(record.user_id),
+            entity::UserProfile::new(
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::LoginId::new(record.login_id.as_str()),
+                entity::PasswordHash::new(record.password.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
+            ),
+            entity::EmailVerified::new(record.email_verified),
+            entity::Version::new(record.version),
+        )))
+    }
+
+    async fn patch_user(
+        &self,
+        user_id: i64,
+        last_name: Option<&str>,
+        first_name: Option<&str>,
+        email: Option<&str>,
+        country: Option<&str>,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<entity::User>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::UpdateUser>(
+            r#"
+            UPDATE users
+                SET last_name = COALESCE($2, last_name),
+                    first_name = COALESCE($3, first_name),
+                    email = COALESCE($4, email),
+                    country = COALESCE($5, country),
+                    -- a new address has to be confirmed again
+                    email_verified = email_verified AND email = COALESCE($4, email),
+                    version = version + 1,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND ($6::BIGINT[] IS NULL OR version = ANY($6))
+            RETURNING
+                user_id, last_name, first_name, login_id, password, email, country, email_verified, version;
             "#,
         )
+        .bind(user_id)
         .bind(last_name)
         .bind(first_name)
-        .bind(login_id)
-        .bind(password)
         .bind(email)
         .bind(country)
-        .bind(user_id)
+        .bind(versions)
         .fetch_one(&self.pool)
         .await?;
 
@@ -126,49 +192,60 @@ impl interface::UserRepositoryTrait for UserRepository {
 
         Ok(Some(entity::User::new(
             entity::UserId::new(record.user_id),
-            entity::LastName::new(record.last_name.as_str()),
-            entity::FirstName::new(record.first_name.as_str()),
-            entity::LoginId::new(record.login_id.as_str()),
-            entity::PasswordHash::new(record.password.as_str()),
-            entity::Email::new(record.email.as_str()),
-            entity::Country::new(record.country.as_str()),
+            entity::UserProfile::new(
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::LoginId::new(record.login_id.as_str()),
+                entity::PasswordHash::new(record.password.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
+            ),
+            entity::EmailVerified::new(record.email_verified),
+            entity::Version::new(record.version),
         )))
     }
 
-    async fn delete_user(&self, id: i64) -> Result<Option<()>, sqlx::Error> {
-        sqlx::query(
+    async fn delete_user(
+        &self,
+        id: i64,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
             r#"
             DELETE FROM 
                 users
             WHERE 
-                user_id = $1;
+                user_id = $1
+                AND ($2::BIGINT[] IS NULL OR version = ANY($2));
             "#,
         )
         .bind(i64::try_from(id).unwrap())
+        .bind(versions)
         .execute(&self.pool)
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
         Ok(Some(()))
     }
 
-    async fn get_user_by_login_id_and_password(
+    async fn get_user_credential(
         &self,
         login_id: &str,
-        hashed_password: &str,
-    ) -> Result<Option<entity::User>, sqlx::Error> {
-        let record = sqlx::query_as::<_, model::GetUser>(
+    ) -> Result<Option<entity::UserCredential>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUserCredential>(
             r#"
-            SELECT 
-                user_id, last_name, first_name, login_id, password, email, country
-            FROM 
+            SELECT
+                user_id, password, role, totp_enabled
+            FROM
                 users
-            WHERE 
-                login_id = $1 
-                AND password = $2;
+            WHERE
+                login_id = $1;
             "#,
         )
         .bind(login_id)
-        .bind(hashed_password)
         .fetch_one(&self.pool)
         .await?;
 
@@ -176,332 +253,2487 @@ impl interface::UserRepositoryTrait for UserRepository {
             return Ok(None);
         }
 
-        Ok(Some(entity::User::new(
+        // the column is check-constrained, an unknown role is a data error
+        let role = entity::Role::new(&record.role).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown user role: {}", record.role).into())
+        })?;
+
+        Ok(Some(entity::UserCredential::new(
             entity::UserId::new(record.user_id),
-            entity::LastName::new(record.last_name.as_str()),
-            entity::FirstName::new(record.first_name.as_str()),
-            entity::LoginId::new(record.login_id.as_str()),
             entity::PasswordHash::new(record.password.as_str()),
-            entity::Email::new(record.email.as_str()),
-            entity::Country::new(record.country.as_str()),
+            role,
+            record.totp_enabled,
         )))
     }
-}
-
-#[derive(Clone)]
-pub struct WordRepository {
-    pool: Pool<sqlx::Postgres>,
-}
 
-#[async_trait]
-impl interface::WordRepositoryTrait for WordRepository {
-    fn new(pool: Pool<sqlx::Postgres>) -> Self {
-        Self { pool }
-    }
-    async fn get_word(&self, word_id: i64) -> Result<Option<entity::Word>, sqlx::Error> {
-        let record = sqlx::query_as::<_, model::GetWord>(
+    async fn update_user_password(
+        &self,
+        user_id: i64,
+        password: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
             r#"
-            SELECT 
-                word_id, word
-            FROM 
-                words
-            WHERE 
-                word_id = $1;
+            UPDATE users
+                SET password = $2,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1;
             "#,
         )
-        .bind(word_id)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .bind(password)
+        .execute(&self.pool)
         .await?;
 
-        if !record.is_valid() {
+        if result.rows_affected() == 0 {
             return Ok(None);
         }
 
-        Ok(Some(entity::Word::new(
-            entity::WordId::new(record.word_id),
-            entity::WordString::new(record.word.as_str()),
-        )))
+        Ok(Some(()))
     }
 
-    async fn create_word(&self, word: &str) -> Result<Option<entity::Word>, sqlx::Error> {
-        let record = sqlx::query_as::<_, model::CreateWord>(
+    async fn change_password(
+        &self,
+        user_id: i64,
+        current_password: &str,
+        password: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // a concurrent change or reset makes the checked password stale
+        let changed = sqlx::query(
             r#"
-            INSERT INTO 
-                words (word)
-            VALUES 
-                ($1)
-            RETURNING 
-                word_id, word;
+            UPDATE users
+                SET password = $3,
+                    sessions_revoked_at = CURRENT_TIMESTAMP,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND password = $2;
             "#,
         )
-        .bind(word)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .bind(current_password)
+        .bind(password)
+        .execute(&mut *tx)
         .await?;
 
-        if !record.is_valid() {
+        if changed.rows_affected() == 0 {
+            tx.rollback().await?;
             return Ok(None);
         }
 
-        Ok(Some(entity::Word::new(
-            entity::WordId::new(record.word_id),
-            entity::WordString::new(record.word.as_str()),
-        )))
-    }
+        // reset links mailed before the change must not undo it
+        sqlx::query(
+            r#"
+            UPDATE password_reset_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
 
-    async fn update_word(
-        &self,
-        word_id: i64,
-        word: &str,
-    ) -> Result<Option<entity::Word>, sqlx::Error> {
-        let record = sqlx::query_as::<_, model::UpdateWord>(
+        sqlx::query(
             r#"
-            UPDATE words
-                SET word = $1
-            WHERE 
-                word_id = $2
-            RETURNING 
-                word_id, word;
+            UPDATE refresh_tokens
+                SET revoked_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND revoked_at IS NULL;
             "#,
         )
-        .bind(word)
-        .bind(word_id)
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .execute(&mut *tx)
         .await?;
 
-        if !record.is_valid() {
-            return Ok(None);
-        }
+        tx.commit().await?;
 
-        Ok(Some(entity::Word::new(
-            entity::WordId::new(record.word_id),
-            entity::WordString::new(record.word.as_str()),
-        )))
+        Ok(Some(()))
     }
 
-    async fn delete_word(&self, id: i64) -> Result<Option<()>, sqlx::Error> {
+    async fn create_email_verification_token(
+        &self,
+        user_id: i64,
+        email: &str,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
         sqlx::query(
             r#"
-            DELETE FROM 
-                words
-            WHERE 
-                word_id = $1;
+            INSERT INTO
+                email_verification_tokens (user_id, email, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3, $4::TIMESTAMP);
             "#,
         )
-        .bind(i64::try_from(id).unwrap())
+        .bind(user_id)
+        .bind(email)
+        .bind(token_hash)
+        .bind(expires_at)
         .execute(&self.pool)
         .await?;
 
         Ok(Some(()))
     }
-}
 
-#[derive(Clone)]
-pub struct UserWordRepository {
-    pool: Pool<sqlx::Postgres>,
-}
+    async fn verify_email(&self, token_hash: &str) -> Result<Option<entity::UserId>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
 
-#[async_trait]
-impl interface::UserWordRepositoryTrait for UserWordRepository {
-    fn new(pool: Pool<sqlx::Postgres>) -> Self {
-        Self { pool }
+        // the used_at guard lets a token confirm once, the email match voids it after an address change
+        let record = sqlx::query_as::<_, model::VerifyEmail>(
+            r#"
+            UPDATE email_verification_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            FROM
+                users
+            WHERE
+                email_verification_tokens.token_hash = $1
+                AND email_verification_tokens.used_at IS NULL
+                AND email_verification_tokens.expires_at > CURRENT_TIMESTAMP
+                AND users.user_id = email_verification_tokens.user_id
+                AND users.email = email_verification_tokens.email
+            RETURNING
+                email_verification_tokens.user_id;
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !record.is_valid() {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE users
+                SET email_verified = TRUE,
+                    version = version + 1,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1;
+            "#,
+        )
+        .bind(record.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(entity::UserId::new(record.user_id)))
     }
-    async fn get_user_word_by_user_id_and_word_id(
-        &self,
-        user_id: i64,
-        word_id: i64,
-    ) -> Result<Option<entity::UserWord>, sqlx::Error> {
-        let record = sqlx::query_as::<_, model::GetUserWord>(
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<entity::User>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUser>(
             r#"
             SELECT 
-                uw.user_word_id,
-                u.user_id,
-                u.last_name,
-                u.first_name,
-                u.email,
-                u.country,
-                w.word_id,
-                w.word,
-                uw.created_at
+                user_id, last_name, first_name, login_id, password, email, country, email_verified, version
             FROM 
-                user_words AS uw
-            INNER JOIN
-                users AS u 
-                    ON uw.user_id = u.user_id
-            INNER JOIN
-                words AS w 
-                    ON uw.word_id = w.word_id
+                users
             WHERE 
-                uw.user_id = $1
-                AND uw.word_id = $2
+                email = $1;
             "#,
         )
-        .bind(user_id)
-        .bind(word_id)
+        .bind(email)
         .fetch_one(&self.pool)
         .await?;
 
-        if record.is_valid() {
+        if !record.is_valid() {
             return Ok(None);
         }
 
-        Ok(Some(entity::UserWord::new(
-            entity::UserWordId::new(record.user_word_id),
+        Ok(Some(entity::User::new(
             entity::UserId::new(record.user_id),
-            entity::LastName::new(record.last_name.as_str()),
-            entity::FirstName::new(record.first_name.as_str()),
-            entity::Email::new(record.email.as_str()),
-            entity::Country::new(record.country.as_str()),
-            entity::WordId::new(record.word_id),
-            entity::WordString::new(record.word.as_str()),
-            entity::CreatedAt::new(
-                DateTime::parse_from_rfc3339(record.created_at.as_str())
-                    .expect("Invalid date")
-                    .with_timezone(&Utc),
+            entity::UserProfile::new(
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::LoginId::new(record.login_id.as_str()),
+                entity::PasswordHash::new(record.password.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
             ),
+            entity::EmailVerified::new(record.email_verified),
+            entity::Version::new(record.version),
         )))
     }
 
-    async fn get_user_word_by_user_id(
+    async fn create_password_reset_token(
         &self,
         user_id: i64,
-    ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error> {
-        let records = sqlx::query_as::<_, model::GetUserWord>(
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
             r#"
-            SELECT 
-                uw.user_word_id,
-                u.user_id,
-                u.last_name,
-                u.first_name,
-                u.email,
-                u.country,
-                w.word_id,
-                w.word,
-                uw.created_at
-            FROM 
-                user_words AS uw
-            INNER JOIN
-                users AS u 
-                    ON uw.user_id = u.user_id
-            INNER JOIN
-                words AS w 
-                    ON uw.word_id = w.word_id
-            WHERE 
-                uw.user_id = $1
-            ORDER BY
-                created_at ASC;
+            INSERT INTO
+                password_reset_tokens (user_id, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3::TIMESTAMP);
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
         .await?;
 
-        if records.is_empty() {
-            return Ok(None);
-        }
+        Ok(Some(()))
+    }
 
-        let user_words = records
-            .into_iter()
-            .map(|record| {
-                entity::UserWord::new(
-                    entity::UserWordId::new(record.user_word_id),
-                    entity::UserId::new(record.user_id),
-                    entity::LastName::new(record.last_name.as_str()),
-                    entity::FirstName::new(record.first_name.as_str()),
-                    entity::Email::new(record.email.as_str()),
-                    entity::Country::new(record.country.as_str()),
-                    entity::WordId::new(record.word_id),
-                    entity::WordString::new(record.word.as_str()),
-                    entity::CreatedAt::new(
+    async fn get_password_reset_user(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<entity::User>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUser>(
+            r#"
+            SELECT
+                users.user_id, users.last_name, users.first_name, users.login_id,
+                users.password, users.email, users.country, users.email_verified, users.version
+            FROM
+                password_reset_tokens
+                INNER JOIN users ON users.user_id = password_reset_tokens.user_id
+            WHERE
+                password_reset_tokens.token_hash = $1
+                AND password_reset_tokens.used_at IS NULL
+                AND password_reset_tokens.expires_at > CURRENT_TIMESTAMP;
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::User::new(
+            entity::UserId::new(record.user_id),
+            entity::UserProfile::new(
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::LoginId::new(record.login_id.as_str()),
+                entity::PasswordHash::new(record.password.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
+            ),
+            entity::EmailVerified::new(record.email_verified),
+            entity::Version::new(record.version),
+        )))
+    }
+
+    async fn reset_password(
+        &self,
+        token_hash: &str,
+        password: &str,
+    ) -> Result<Option<entity::UserId>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // the used_at guard lets a concurrent second reset with the same token find nothing
+        let record = sqlx::query_as::<_, model::ResetPassword>(
+            r#"
+            UPDATE password_reset_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                token_hash = $1
+                AND used_at IS NULL
+                AND expires_at > CURRENT_TIMESTAMP
+            RETURNING
+                user_id;
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !record.is_valid() {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE users
+                SET password = $2,
+                    sessions_revoked_at = CURRENT_TIMESTAMP,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1;
+            "#,
+        )
+        .bind(record.user_id)
+        .bind(password)
+        .execute(&mut *tx)
+        .await?;
+
+        // other links mailed before this reset must not undo it
+        sqlx::query(
+            r#"
+            UPDATE password_reset_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(record.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+                SET revoked_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND revoked_at IS NULL;
+            "#,
+        )
+        .bind(record.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(entity::UserId::new(record.user_id)))
+    }
+
+    async fn get_user_totp(&self, user_id: i64) -> Result<Option<entity::UserTotp>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUserTotp>(
+            r#"
+            SELECT
+                user_id, login_id, totp_secret, totp_enabled
+            FROM
+                users
+            WHERE
+                user_id = $1;
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::UserTotp::new(
+            entity::UserId::new(record.user_id),
+            entity::LoginId::new(record.login_id.as_str()),
+            record.totp_secret,
+            record.totp_enabled,
+        )))
+    }
+
+    async fn set_pending_totp_secret(
+        &self,
+        user_id: i64,
+        secret: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+                SET totp_secret = $2,
+                    totp_last_step = NULL,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND totp_enabled = FALSE;
+            "#,
+        )
+        .bind(user_id)
+        .bind(secret)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn enable_totp(
+        &self,
+        user_id: i64,
+        secret: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<Option<()>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let enabled = sqlx::query(
+            r#"
+            UPDATE users
+                SET totp_enabled = TRUE,
+                    totp_last_step = $3,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND totp_enabled = FALSE
+                AND totp_secret = $2;
+            "#,
+        )
+        .bind(user_id)
+        .bind(secret)
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+
+        if enabled.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE
+                user_id = $1;
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO
+                totp_recovery_codes (user_id, code_hash)
+            SELECT
+                $1, code_hash
+            FROM
+                UNNEST($2::VARCHAR[]) AS code_hash;
+            "#,
+        )
+        .bind(user_id)
+        .bind(recovery_code_hashes)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+
+    async fn disable_totp(&self, user_id: i64) -> Result<Option<()>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let disabled = sqlx::query(
+            r#"
+            UPDATE users
+                SET totp_enabled = FALSE,
+                    totp_secret = NULL,
+                    totp_last_step = NULL,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1;
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if disabled.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE
+                user_id = $1;
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+
+    async fn use_totp_step(&self, user_id: i64, step: i64) -> Result<Option<()>, sqlx::Error> {
+        // a code is only good once, and never after a later one was used
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+                SET totp_last_step = $2
+            WHERE
+                user_id = $1
+                AND totp_enabled = TRUE
+                AND (totp_last_step IS NULL OR totp_last_step < $2);
+            "#,
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: i64,
+        code_hash: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE totp_recovery_codes
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                user_id = $1
+                AND code_hash = $2
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+}
+
+#[derive(Clone)]
+pub struct WordRepository {
+    pool: Pool<sqlx::Postgres>,
+}
+
+#[async_trait]
+impl interface::WordRepositoryTrait for WordRepository {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self {
+        Self { pool }
+    }
+    async fn get_word(&self, word_id: i64) -> Result<Option<entity::Word>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetWord>(
+            r#"
+            SELECT 
+                word_id, word, definition, version
+            FROM 
+                words
+            WHERE 
+                word_id = $1;
+            "#,
+        )
+        .bind(word_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Word::new(
+            entity::WordId::new(record.word_id),
+            entity::WordString::new(record.word.as_str()),
+            record
+                .definition
+                .as_deref()
+                .map(entity::WordDefinition::new),
+            entity::Version::new(record.version),
+        )))
+    }
+
+    async fn create_word(
+        &self,
+        word: &str,
+        normalized_word: &str,
+        definition: Option<&str>,
+    ) -> Result<Option<entity::Word>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::CreateWord>(
+            r#"
+            INSERT INTO 
+                words (word, normalized_word, definition)
+            VALUES 
+                ($1, $2, $3)
+            RETURNING 
+                word_id, word, definition, version;
+            "#,
+        )
+        .bind(word)
+        .bind(normalized_word)
+        .bind(definition)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Word::new(
+            entity::WordId::new(record.word_id),
+            entity::WordString::new(record.word.as_str()),
+            record
+                .definition
+                .as_deref()
+                .map(entity::WordDefinition::new),
+            entity::Version::new(record.version),
+        )))
+    }
+
+    async fn update_word(
+        &self,
+        word_id: i64,
+        word: &str,
+        normalized_word: &str,
+        definition: Option<&str>,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<entity::Word>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::UpdateWord>(
+            r#"
+            UPDATE words
+                SET word = $1, normalized_word = $2, definition = $5, version = version + 1
+            WHERE 
+                word_id = $3
+                AND ($4::BIGINT[] IS NULL OR version = ANY($4))
+            RETURNING 
+                word_id, word, definition, version;
+            "#,
+        )
+        .bind(word)
+        .bind(normalized_word)
+        .bind(word_id)
+        .bind(versions)
+        .bind(definition)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Word::new(
+            entity::WordId::new(record.word_id),
+            entity::WordString::new(record.word.as_str()),
+            record
+                .definition
+                .as_deref()
+                .map(entity::WordDefinition::new),
+            entity::Version::new(record.version),
+        )))
+    }
+
+    async fn delete_word(
+        &self,
+        id: i64,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM 
+                words
+            WHERE 
+                word_id = $1
+                AND ($2::BIGINT[] IS NULL OR version = ANY($2));
+            "#,
+        )
+        .bind(i64::try_from(id).unwrap())
+        .bind(versions)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn search_words(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::SearchedWord>>, sqlx::Error> {
+        // `query` is already canonical, see entity::WordString::normalized
+        // exact > prefix > substring > typo tolerant (trigram / edit distance)
+        // candidates come from the trigram index only, edit distance just ranks them
+        let records = sqlx::query_as::<_, model::SearchWord>(
+            r#"
+            SELECT
+                word_id, word, score
+            FROM (
+                SELECT
+                    word_id,
+                    word,
+                    CASE
+                        WHEN normalized_word = $1 THEN 1.0
+                        WHEN normalized_word LIKE $1 || '%' THEN 0.9
+                        WHEN normalized_word LIKE '%' || $1 || '%' THEN 0.8
+                        ELSE 0.7 * GREATEST(
+                            similarity(normalized_word, $1),
+                            1.0 - levenshtein_less_equal(normalized_word, $1, 2)::FLOAT8
+                                / GREATEST(length(normalized_word), length($1), 1)
+                        )
+                    END::FLOAT8 AS score
+                FROM
+                    words
+                WHERE
+                    normalized_word LIKE '%' || $1 || '%'
+                    OR normalized_word % $1
+            ) AS candidates
+            ORDER BY
+                score DESC, word ASC
+            LIMIT $2;
+            "#,
+        )
+        .bind(query)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let words = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(|record| {
+                entity::SearchedWord::new(
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
+                    entity::SimilarityScore::new(record.score),
+                )
+            })
+            .collect();
+
+        Ok(Some(words))
+    }
+
+    async fn get_words_to_rekey(
+        &self,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::Word>>, sqlx::Error> {
+        let records = sqlx::query_as::<_, model::GetWord>(
+            r#"
+            SELECT
+                w.word_id, w.word, w.definition, w.version
+            FROM
+                word_rekey_queue AS q
+            INNER JOIN
+                words AS w
+                    ON q.word_id = w.word_id
+            ORDER BY
+                w.word_id ASC
+            LIMIT $1;
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            records
+                .into_iter()
+                .map(|record| {
+                    entity::Word::new(
+                        entity::WordId::new(record.word_id),
+                        entity::WordString::new(record.word.as_str()),
+                        record
+                            .definition
+                            .as_deref()
+                            .map(entity::WordDefinition::new),
+                        entity::Version::new(record.version),
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+    async fn rekey_word(
+        &self,
+        word_id: i64,
+        normalized_word: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // the row lock keeps the canonical word from being deleted while this one moves onto it
+        let canonical = sqlx::query_as::<_, model::GetWord>(
+            r#"
+            SELECT
+                word_id, word, definition, version
+            FROM
+                words
+            WHERE
+                normalized_word = $2
+                AND word_id <> $1
+            FOR UPDATE;
+            "#,
+        )
+        .bind(word_id)
+        .bind(normalized_word)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        match canonical.first() {
+            Some(canonical) => {
+                // a user who has both keeps the canonical one
+                sqlx::query(
+                    r#"
+                    DELETE FROM user_words AS uw
+                    WHERE
+                        uw.word_id = $1
+                        AND EXISTS (
+                            SELECT 1 FROM user_words AS c
+                            WHERE c.user_id = uw.user_id AND c.word_id = $2
+                        );
+                    "#,
+                )
+                .bind(word_id)
+                .bind(canonical.word_id)
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    UPDATE user_words
+                        SET word_id = $2
+                    WHERE
+                        word_id = $1;
+                    "#,
+                )
+                .bind(word_id)
+                .bind(canonical.word_id)
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    UPDATE quiz_questions
+                        SET word_id = $2
+                    WHERE
+                        word_id = $1;
+                    "#,
+                )
+                .bind(word_id)
+                .bind(canonical.word_id)
+                .execute(&mut *tx)
+                .await?;
+
+                // also drops the queue entry
+                sqlx::query(
+                    r#"
+                    DELETE FROM
+                        words
+                    WHERE
+                        word_id = $1;
+                    "#,
+                )
+                .bind(word_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query(
+                    r#"
+                    UPDATE words
+                        SET normalized_word = $2
+                    WHERE
+                        word_id = $1;
+                    "#,
+                )
+                .bind(word_id)
+                .bind(normalized_word)
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    DELETE FROM
+                        word_rekey_queue
+                    WHERE
+                        word_id = $1;
+                    "#,
+                )
+                .bind(word_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+}
+
+#[derive(Clone)]
+pub struct UserWordRepository {
+    pool: Pool<sqlx::Postgres>,
+}
+
+impl UserWordRepository {
+    // keyset pagination over user_words filtered by `filter_column`,
+    // ordered by the sort key with user_word_id as a tie breaker
+    async fn get_user_word_page(
+        &self,
+        filter_column: &str,
+        filter_id: i64,
+        sort: entity::UserWordSort,
+        cursor: Option<entity::PageCursor>,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error> {
+        let (sort_key, sort_key_type, direction, comparison) = match sort {
+            entity::UserWordSort::CreatedAtAsc => ("uw.created_at", "TIMESTAMP", "ASC", ">"),
+            entity::UserWordSort::CreatedAtDesc => ("uw.created_at", "TIMESTAMP", "DESC", "<"),
+            entity::UserWordSort::Word => ("w.word", "VARCHAR", "ASC", ">"),
+        };
+
+        let query = format!(
+            r#"
+            SELECT
+                uw.user_word_id,
+                u.user_id,
+                u.last_name,
+                u.first_name,
+                u.email,
+                u.country,
+                w.word_id,
+                w.word,
+                to_char(uw.created_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS created_at
+            FROM
+                user_words AS uw
+            INNER JOIN
+                users AS u
+                    ON uw.user_id = u.user_id
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id
+            WHERE
+                {filter_column} = $1
+                AND (
+                    $2::{sort_key_type} IS NULL
+                    OR ({sort_key}, uw.user_word_id) {comparison} ($2::{sort_key_type}, $3)
+                )
+            ORDER BY
+                {sort_key} {direction}, uw.user_word_id {direction}
+            LIMIT $4;
+            "#
+        );
+
+        let records = sqlx::query_as::<_, model::GetUserWord>(query.as_str()).bind(filter_id);
+        let records = match cursor.as_ref().map(|cursor| cursor.key()) {
+            Some(entity::PageKey::CreatedAt(created_at)) => records.bind(Some(*created_at)),
+            Some(entity::PageKey::Word(word)) => records.bind(Some(word.clone())),
+            None => records.bind(None::<String>),
+        };
+        let records = records
+            .bind(cursor.as_ref().map(|cursor| cursor.id()))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let user_words = records
+            .into_iter()
+            .map(|record| {
+                entity::UserWord::new(
+                    entity::UserWordId::new(record.user_word_id),
+                    entity::UserWordOwner::new(
+                        entity::UserId::new(record.user_id),
+                        entity::LastName::new(record.last_name.as_str()),
+                        entity::FirstName::new(record.first_name.as_str()),
+                        entity::Email::new(record.email.as_str()),
+                        entity::Country::new(record.country.as_str()),
+                    ),
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
+                    entity::CreatedAt::new(
+                        DateTime::parse_from_rfc3339(record.created_at.as_str())
+                            .expect("Invalid date")
+                            .with_timezone(&Utc),
+                    ),
+                )
+            })
+            .collect();
+
+        Ok(Some(user_words))
+    }
+
+    fn review_from_record(record: model::GetReview) -> entity::Review {
+        entity::Review::new(
+            entity::UserWordId::new(record.user_word_id),
+            entity::WordId::new(record.word_id),
+            entity::WordString::new(record.word.as_str()),
+            entity::ReviewState::new(
+                entity::EaseFactor::new(record.ease_factor),
+                entity::IntervalDays::new(record.interval_days),
+                entity::Repetitions::new(record.repetitions),
+                entity::DueAt::new(
+                    DateTime::parse_from_rfc3339(record.due_at.as_str())
+                        .expect("Invalid date")
+                        .with_timezone(&Utc),
+                ),
+            ),
+        )
+    }
+}
+
+#[async_trait]
+impl interface::UserWordRepositoryTrait for UserWordRepository {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self {
+        Self { pool }
+    }
+    async fn get_user_word_by_user_id_and_word_id(
+        &self,
+        user_id: i64,
+        word_id: i64,
+    ) -> Result<Option<entity::UserWord>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUserWord>(
+            r#"
+            SELECT 
+                uw.user_word_id,
+                u.user_id,
+                u.last_name,
+                u.first_name,
+                u.email,
+                u.country,
+                w.word_id,
+                w.word,
+                uw.created_at
+            FROM 
+                user_words AS uw
+            INNER JOIN
+                users AS u 
+                    ON uw.user_id = u.user_id
+            INNER JOIN
+                words AS w 
+                    ON uw.word_id = w.word_id
+            WHERE 
+                uw.user_id = $1
+                AND uw.word_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(word_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::UserWord::new(
+            entity::UserWordId::new(record.user_word_id),
+            entity::UserWordOwner::new(
+                entity::UserId::new(record.user_id),
+                entity::LastName::new(record.last_name.as_str()),
+                entity::FirstName::new(record.first_name.as_str()),
+                entity::Email::new(record.email.as_str()),
+                entity::Country::new(record.country.as_str()),
+            ),
+            entity::WordId::new(record.word_id),
+            entity::WordString::new(record.word.as_str()),
+            entity::CreatedAt::new(
+                DateTime::parse_from_rfc3339(record.created_at.as_str())
+                    .expect("Invalid date")
+                    .with_timezone(&Utc),
+            ),
+        )))
+    }
+
+    async fn get_user_word_by_user_id(
+        &self,
+        user_id: i64,
+        sort: entity::UserWordSort,
+        cursor: Option<entity::PageCursor>,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error> {
+        self.get_user_word_page("uw.user_id", user_id, sort, cursor, limit)
+            .await
+    }
+
+    async fn get_user_word_by_word_id(
+        &self,
+        word_id: i64,
+        sort: entity::UserWordSort,
+        cursor: Option<entity::PageCursor>,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error> {
+        self.get_user_word_page("uw.word_id", word_id, sort, cursor, limit)
+            .await
+    }
+
+    async fn create_user_word(
+        &self,
+        user_id: i64,
+        word_id: i64,
+    ) -> Result<Option<entity::UserWordRelation>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUserWordRelation>(
+            r#"
+            INSERT INTO 
+                user_words (user_id, word_id)
+            VALUES 
+                ($1, $2)
+            RETURNING 
+                user_id, word_id, to_char(created_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS created_at;
+            "#,
+        )
+        .bind(user_id)
+        .bind(word_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::UserWordRelation::new(
+            entity::UserId::new(record.user_id),
+            entity::WordId::new(record.word_id),
+            entity::CreatedAt::new(
+                DateTime::parse_from_rfc3339(record.created_at.as_str())
+                    .expect("Invalid date")
+                    .with_timezone(&Utc),
+            ),
+        )))
+    }
+
+    async fn delete_user_word(&self, id: i64) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM 
+                user_words
+            WHERE 
+                user_word_id = $1;
+            "#,
+        )
+        .bind(i64::try_from(id).unwrap())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(()))
+    }
+
+    async fn get_user_word_owner(
+        &self,
+        user_word_id: i64,
+    ) -> Result<Option<entity::UserId>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetUserWordId>(
+            r#"
+            SELECT
+                user_id, word_id
+            FROM
+                user_words
+            WHERE
+                user_word_id = $1;
+            "#,
+        )
+        .bind(user_word_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::UserId::new(record.user_id)))
+    }
+
+    async fn import_user_words(
+        &self,
+        user_id: i64,
+        words: &[entity::WordString],
+    ) -> Result<Option<Vec<entity::ImportedWord>>, sqlx::Error> {
+        // one statement, so a failure on any line rolls back the whole import;
+        // a word listed twice is only created for its first line
+        let normalized_words = words
+            .iter()
+            .map(|word| word.normalized())
+            .collect::<Vec<String>>();
+        let records = sqlx::query_as::<_, model::ImportUserWord>(
+            r#"
+            WITH input AS (
+                SELECT
+                    word, normalized_word, position
+                FROM
+                    unnest($2::TEXT[], $3::TEXT[]) WITH ORDINALITY AS t(word, normalized_word, position)
+            ), first_input AS (
+                SELECT DISTINCT ON (normalized_word)
+                    word, normalized_word, position
+                FROM
+                    input
+                ORDER BY
+                    normalized_word, position
+            ), upserted_word AS (
+                INSERT INTO
+                    words (word, normalized_word)
+                SELECT
+                    word, normalized_word
+                FROM
+                    first_input
+                ORDER BY
+                    position
+                -- no-op update so RETURNING yields the existing row,
+                -- whose display form is kept as first registered
+                ON CONFLICT (normalized_word) DO UPDATE
+                    SET normalized_word = EXCLUDED.normalized_word
+                RETURNING
+                    word_id, word, normalized_word
+            ), inserted_user_word AS (
+                INSERT INTO
+                    user_words (user_id, word_id)
+                SELECT
+                    $1, word_id
+                FROM
+                    upserted_word
+                ON CONFLICT (user_id, word_id) DO NOTHING
+                RETURNING
+                    word_id
+            )
+            SELECT
+                w.word_id,
+                w.word,
+                (
+                    i.position = f.position
+                    AND EXISTS (SELECT 1 FROM inserted_user_word AS uw WHERE uw.word_id = w.word_id)
+                ) AS created
+            FROM
+                input AS i
+            INNER JOIN
+                first_input AS f
+                    ON i.normalized_word = f.normalized_word
+            INNER JOIN
+                upserted_word AS w
+                    ON i.normalized_word = w.normalized_word
+            ORDER BY
+                i.position;
+            "#,
+        )
+        .bind(user_id)
+        .bind(words.iter().map(|word| word.value()).collect::<Vec<&str>>())
+        .bind(normalized_words)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.len() != words.len() || records.iter().any(|record| !record.is_valid()) {
+            return Ok(None);
+        }
+
+        let imported_words = records
+            .into_iter()
+            .map(|record| {
+                entity::ImportedWord::new(
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
+                    record.created,
+                )
+            })
+            .collect();
+
+        Ok(Some(imported_words))
+    }
+
+    async fn get_due_reviews(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::Review>>, sqlx::Error> {
+        let records = sqlx::query_as::<_, model::GetReview>(
+            r#"
+            SELECT
+                uw.user_word_id,
+                w.word_id,
+                w.word,
+                uw.ease_factor,
+                uw.interval_days,
+                uw.repetitions,
+                to_char(uw.due_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS due_at
+            FROM
+                user_words AS uw
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id
+            WHERE
+                uw.user_id = $1
+                AND uw.due_at <= CURRENT_TIMESTAMP
+            ORDER BY
+                uw.due_at ASC, uw.user_word_id ASC
+            LIMIT $2;
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let reviews = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(Self::review_from_record)
+            .collect();
+
+        Ok(Some(reviews))
+    }
+
+    async fn get_review(
+        &self,
+        user_id: i64,
+        user_word_id: i64,
+    ) -> Result<Option<entity::Review>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetReview>(
+            r#"
+            SELECT
+                uw.user_word_id,
+                w.word_id,
+                w.word,
+                uw.ease_factor,
+                uw.interval_days,
+                uw.repetitions,
+                to_char(uw.due_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS due_at
+            FROM
+                user_words AS uw
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id
+            WHERE
+                uw.user_id = $1
+                AND uw.user_word_id = $2;
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_word_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::review_from_record(record)))
+    }
+
+    async fn update_review(
+        &self,
+        user_id: i64,
+        user_word_id: i64,
+        previous: &entity::ReviewState,
+        next: &entity::ReviewState,
+    ) -> Result<Option<entity::Review>, sqlx::Error> {
+        // compare and set, a grade computed from a state that has since changed writes nothing
+        let record = sqlx::query_as::<_, model::GetReview>(
+            r#"
+            WITH updated AS (
+                UPDATE user_words
+                    SET ease_factor = $3,
+                        interval_days = $4,
+                        repetitions = $5,
+                        due_at = $6,
+                        last_reviewed_at = CURRENT_TIMESTAMP
+                WHERE
+                    user_id = $1
+                    AND user_word_id = $2
+                    AND ease_factor = $7
+                    AND interval_days = $8
+                    AND repetitions = $9
+                    AND due_at = $10
+                RETURNING
+                    user_word_id, word_id, ease_factor, interval_days, repetitions, due_at
+            )
+            SELECT
+                uw.user_word_id,
+                w.word_id,
+                w.word,
+                uw.ease_factor,
+                uw.interval_days,
+                uw.repetitions,
+                to_char(uw.due_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS due_at
+            FROM
+                updated AS uw
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id;
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_word_id)
+        .bind(next.ease_factor.value())
+        .bind(next.interval_days.value())
+        .bind(next.repetitions.value())
+        .bind(next.due_at.value().naive_utc())
+        .bind(previous.ease_factor.value())
+        .bind(previous.interval_days.value())
+        .bind(previous.repetitions.value())
+        .bind(previous.due_at.value().naive_utc())
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::review_from_record(record)))
+    }
+
+    fn export_user_words(
+        &self,
+        user_id: i64,
+    ) -> BoxStream<'static, Result<entity::ExportedWord, sqlx::Error>> {
+        let pool = self.pool.clone();
+
+        // rows are pulled from the cursor as the client consumes the body
+        Box::pin(try_stream! {
+            let mut records = sqlx::query_as::<_, model::ExportUserWord>(
+                r#"
+                SELECT
+                    w.word,
+                    to_char(uw.created_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS created_at
+                FROM
+                    user_words AS uw
+                INNER JOIN
+                    words AS w
+                        ON uw.word_id = w.word_id
+                WHERE
+                    uw.user_id = $1
+                ORDER BY
+                    uw.created_at ASC, uw.user_word_id ASC;
+                "#,
+            )
+            .bind(user_id)
+            .fetch(&pool);
+
+            while let Some(record) = records.try_next().await? {
+                if !record.is_valid() {
+                    continue;
+                }
+
+                yield entity::ExportedWord::new(
+                    entity::WordString::new(record.word.as_str()),
+                    entity::CreatedAt::new(
                         DateTime::parse_from_rfc3339(record.created_at.as_str())
                             .expect("Invalid date")
                             .with_timezone(&Utc),
                     ),
+                );
+            }
+        })
+    }
+
+    async fn get_word_ranking(
+        &self,
+        days: Option<i32>,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::WordRanking>>, sqlx::Error> {
+        let records = sqlx::query_as::<_, model::GetWordRanking>(
+            r#"
+            SELECT
+                RANK() OVER (ORDER BY COUNT(uw.user_word_id) DESC)::BIGINT AS rank,
+                w.word_id,
+                w.word,
+                COUNT(uw.user_word_id)::BIGINT AS registration_count
+            FROM
+                user_words AS uw
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id
+            WHERE
+                $1::INT IS NULL
+                OR uw.created_at >= CURRENT_TIMESTAMP - make_interval(days => $1::INT)
+            GROUP BY
+                w.word_id, w.word
+            ORDER BY
+                registration_count DESC, w.word ASC
+            LIMIT $2;
+            "#,
+        )
+        .bind(days)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let rankings = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(|record| {
+                entity::WordRanking::new(
+                    entity::Rank::new(record.rank),
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
+                    entity::RegistrationCount::new(record.registration_count),
+                )
+            })
+            .collect();
+
+        Ok(Some(rankings))
+    }
+}
+
+#[derive(Clone)]
+pub struct QuizRepository {
+    pool: sqlx::PgPool,
+}
+
+#[async_trait]
+impl interface::QuizRepositoryTrait for QuizRepository {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self {
+        Self { pool }
+    }
+
+    async fn get_quiz_words(&self, user_id: i64) -> Result<Option<Vec<entity::Word>>, sqlx::Error> {
+        // stable order, the generator shuffles with its own seed
+        let records = sqlx::query_as::<_, model::GetWord>(
+            r#"
+            SELECT
+                w.word_id,
+                w.word,
+                w.definition,
+                w.version
+            FROM
+                user_words AS uw
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id
+            WHERE
+                uw.user_id = $1
+            ORDER BY
+                uw.user_word_id ASC;
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let words = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(|record| {
+                entity::Word::new(
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
+                    record
+                        .definition
+                        .as_deref()
+                        .map(entity::WordDefinition::new),
+                    entity::Version::new(record.version),
+                )
+            })
+            .collect();
+
+        Ok(Some(words))
+    }
+
+    async fn get_distractor_words(
+        &self,
+        user_id: i64,
+        seed: i64,
+        limit: i64,
+    ) -> Result<Option<Vec<entity::Word>>, sqlx::Error> {
+        // ordered by a hash keyed with the quiz seed: every quiz samples its own subset
+        // of the whole table, and replaying the seed reads the same one back
+        let records = sqlx::query_as::<_, model::GetWord>(
+            r#"
+            SELECT
+                w.word_id,
+                w.word,
+                w.definition,
+                w.version
+            FROM
+                words AS w
+            WHERE
+                NOT EXISTS (
+                    SELECT 1 FROM user_words AS uw
+                    WHERE uw.user_id = $1 AND uw.word_id = w.word_id
+                )
+            ORDER BY
+                md5($2::TEXT || ':' || w.word_id::TEXT) ASC, w.word_id ASC
+            LIMIT $3;
+            "#,
+        )
+        .bind(user_id)
+        .bind(seed)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let words = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(|record| {
+                entity::Word::new(
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
+                    record
+                        .definition
+                        .as_deref()
+                        .map(entity::WordDefinition::new),
+                    entity::Version::new(record.version),
+                )
+            })
+            .collect();
+
+        Ok(Some(words))
+    }
+
+    async fn create_quiz(
+        &self,
+        user_id: i64,
+        mode: &str,
+        seed: i64,
+        questions: &[entity::QuizQuestion],
+    ) -> Result<Option<entity::QuizId>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, model::CreateQuiz>(
+            r#"
+            INSERT INTO
+                quizzes (user_id, mode, seed)
+            VALUES
+                ($1, $2, $3)
+            RETURNING
+                quiz_id;
+            "#,
+        )
+        .bind(user_id)
+        .bind(mode)
+        .bind(seed)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        for question in questions {
+            sqlx::query(
+                r#"
+                INSERT INTO
+                    quiz_questions (quiz_id, position, word_id, prompt, choices)
+                VALUES
+                    ($1, $2, $3, $4, $5);
+                "#,
+            )
+            .bind(record.quiz_id)
+            .bind(question.position.value())
+            .bind(question.word_id.value())
+            .bind(question.prompt.as_str())
+            .bind(
+                question
+                    .choices
+                    .iter()
+                    .map(|choice| choice.value().to_string())
+                    .collect::<Vec<String>>(),
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(entity::QuizId::new(record.quiz_id)))
+    }
+
+    async fn get_quiz_question(
+        &self,
+        user_id: i64,
+        quiz_id: i64,
+        position: i32,
+    ) -> Result<Option<entity::QuizQuestion>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetQuizQuestion>(
+            r#"
+            SELECT
+                qq.position,
+                w.word_id,
+                w.word,
+                qq.prompt,
+                qq.choices
+            FROM
+                quiz_questions AS qq
+            INNER JOIN
+                quizzes AS q
+                    ON qq.quiz_id = q.quiz_id
+            INNER JOIN
+                words AS w
+                    ON qq.word_id = w.word_id
+            WHERE
+                q.user_id = $1
+                AND qq.quiz_id = $2
+                AND qq.position = $3;
+            "#,
+        )
+        .bind(user_id)
+        .bind(quiz_id)
+        .bind(position)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::QuizQuestion::new(
+            entity::QuizPosition::new(record.position),
+            entity::WordId::new(record.word_id),
+            entity::WordString::new(record.word.as_str()),
+            record.prompt.as_str(),
+            record
+                .choices
+                .iter()
+                .map(|choice| entity::WordString::new(choice))
+                .collect(),
+        )))
+    }
+
+    async fn answer_quiz_question(
+        &self,
+        user_id: i64,
+        quiz_id: i64,
+        position: i32,
+        answer: &str,
+        correct: bool,
+    ) -> Result<Option<entity::QuizAnswer>, sqlx::Error> {
+        // a question takes one answer only, answering again finds no row
+        let record = sqlx::query_as::<_, model::AnswerQuizQuestion>(
+            r#"
+            WITH answered AS (
+                UPDATE quiz_questions AS qq
+                    SET answer = $4, correct = $5, answered_at = CURRENT_TIMESTAMP
+                FROM
+                    quizzes AS q
+                WHERE
+                    qq.quiz_id = q.quiz_id
+                    AND q.user_id = $1
+                    AND qq.quiz_id = $2
+                    AND qq.position = $3
+                    AND qq.answered_at IS NULL
+                RETURNING
+                    qq.quiz_id, qq.position, qq.correct
+            ), scored AS (
+                UPDATE quizzes
+                    SET score = score + 1
+                WHERE
+                    quiz_id IN (SELECT quiz_id FROM answered WHERE correct)
+            )
+            SELECT
+                a.quiz_id,
+                a.position,
+                a.correct,
+                q.score + a.correct::INT AS score,
+                (SELECT COUNT(answered_at) FROM quiz_questions WHERE quiz_id = a.quiz_id)::INT + 1 AS answered,
+                (SELECT COUNT(*) FROM quiz_questions WHERE quiz_id = a.quiz_id)::INT AS total
+            FROM
+                answered AS a
+            INNER JOIN
+                quizzes AS q
+                    ON a.quiz_id = q.quiz_id;
+            "#,
+        )
+        .bind(user_id)
+        .bind(quiz_id)
+        .bind(position)
+        .bind(answer)
+        .bind(correct)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::QuizAnswer::new(
+            entity::QuizId::new(record.quiz_id),
+            entity::QuizPosition::new(record.position),
+            record.correct,
+            entity::QuizScore::new(record.score),
+            record.answered,
+            record.total,
+        )))
+    }
+}
+
+#[derive(Clone)]
+pub struct DeckRepository {
+    pool: sqlx::PgPool,
+}
+
+impl DeckRepository {
+    // projection of model::GetDeck over a relation aliased `d`
+    const DECK_COLUMNS: &'static str = r#"
+                d.deck_id,
+                d.user_id,
+                d.name,
+                d.share_token,
+                (SELECT COUNT(*) FROM deck_words AS dw WHERE dw.deck_id = d.deck_id) AS word_count,
+                to_char(d.created_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS created_at"#;
+
+    fn deck_from_record(record: model::GetDeck) -> entity::Deck {
+        entity::Deck::new(
+            entity::DeckId::new(record.deck_id),
+            entity::UserId::new(record.user_id),
+            entity::DeckName::new(record.name.as_str()),
+            record.share_token.as_deref().map(entity::ShareToken::new),
+            record.word_count,
+            entity::CreatedAt::new(
+                DateTime::parse_from_rfc3339(record.created_at.as_str())
+                    .expect("Invalid date")
+                    .with_timezone(&Utc),
+            ),
+        )
+    }
+}
+
+#[async_trait]
+impl interface::DeckRepositoryTrait for DeckRepository {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self {
+        Self { pool }
+    }
+
+    async fn create_deck(
+        &self,
+        user_id: i64,
+        name: &str,
+    ) -> Result<Option<entity::Deck>, sqlx::Error> {
+        let query = format!(
+            r#"
+            WITH d AS (
+                INSERT INTO
+                    decks (user_id, name)
+                VALUES
+                    ($1, $2)
+                RETURNING
+                    *
+            )
+            SELECT {} FROM d;
+            "#,
+            Self::DECK_COLUMNS
+        );
+
+        let record = sqlx::query_as::<_, model::GetDeck>(query.as_str())
+            .bind(user_id)
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::deck_from_record(record)))
+    }
+
+    async fn get_decks(&self, user_id: i64) -> Result<Option<Vec<entity::Deck>>, sqlx::Error> {
+        let query = format!(
+            r#"
+            SELECT {} FROM decks AS d
+            WHERE
+                d.user_id = $1
+            ORDER BY
+                d.name ASC, d.deck_id ASC;
+            "#,
+            Self::DECK_COLUMNS
+        );
+
+        let records = sqlx::query_as::<_, model::GetDeck>(query.as_str())
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let decks = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(Self::deck_from_record)
+            .collect();
+
+        Ok(Some(decks))
+    }
+
+    async fn get_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<Option<entity::Deck>, sqlx::Error> {
+        let query = format!(
+            r#"
+            SELECT {} FROM decks AS d
+            WHERE
+                d.user_id = $1
+                AND d.deck_id = $2;
+            "#,
+            Self::DECK_COLUMNS
+        );
+
+        let record = sqlx::query_as::<_, model::GetDeck>(query.as_str())
+            .bind(user_id)
+            .bind(deck_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::deck_from_record(record)))
+    }
+
+    async fn update_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        name: &str,
+    ) -> Result<Option<entity::Deck>, sqlx::Error> {
+        let query = format!(
+            r#"
+            WITH d AS (
+                UPDATE decks
+                    SET name = $3, updated_at = CURRENT_TIMESTAMP
+                WHERE
+                    user_id = $1
+                    AND deck_id = $2
+                RETURNING
+                    *
+            )
+            SELECT {} FROM d;
+            "#,
+            Self::DECK_COLUMNS
+        );
+
+        let record = sqlx::query_as::<_, model::GetDeck>(query.as_str())
+            .bind(user_id)
+            .bind(deck_id)
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::deck_from_record(record)))
+    }
+
+    async fn delete_deck(&self, user_id: i64, deck_id: i64) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM
+                decks
+            WHERE
+                user_id = $1
+                AND deck_id = $2;
+            "#,
+        )
+        .bind(user_id)
+        .bind(deck_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn get_deck_words(
+        &self,
+        deck_id: i64,
+    ) -> Result<Option<Vec<entity::DeckWord>>, sqlx::Error> {
+        let records = sqlx::query_as::<_, model::GetDeckWord>(
+            r#"
+            SELECT
+                uw.user_word_id,
+                w.word_id,
+                w.word,
+                w.version
+            FROM
+                deck_words AS dw
+            INNER JOIN
+                user_words AS uw
+                    ON dw.user_word_id = uw.user_word_id
+            INNER JOIN
+                words AS w
+                    ON uw.word_id = w.word_id
+            WHERE
+                dw.deck_id = $1
+            ORDER BY
+                dw.created_at ASC, uw.user_word_id ASC;
+            "#,
+        )
+        .bind(deck_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        let words = records
+            .into_iter()
+            .filter(|record| record.is_valid())
+            .map(|record| {
+                entity::DeckWord::new(
+                    entity::UserWordId::new(record.user_word_id),
+                    entity::WordId::new(record.word_id),
+                    entity::WordString::new(record.word.as_str()),
                 )
             })
             .collect();
 
-        Ok(Some(user_words))
+        Ok(Some(words))
+    }
+
+    async fn add_deck_words(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        user_word_ids: &[i64],
+    ) -> Result<Option<u64>, sqlx::Error> {
+        // only the deck owner's own user words can be added, others are skipped
+        let result = sqlx::query(
+            r#"
+            INSERT INTO
+                deck_words (deck_id, user_word_id)
+            SELECT
+                d.deck_id, uw.user_word_id
+            FROM
+                decks AS d
+            INNER JOIN
+                user_words AS uw
+                    ON uw.user_id = d.user_id
+            WHERE
+                d.user_id = $1
+                AND d.deck_id = $2
+                AND uw.user_word_id = ANY($3)
+            ON CONFLICT (deck_id, user_word_id) DO NOTHING;
+            "#,
+        )
+        .bind(user_id)
+        .bind(deck_id)
+        .bind(user_word_ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(result.rows_affected()))
+    }
+
+    async fn remove_deck_word(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        user_word_id: i64,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM
+                deck_words AS dw
+            USING
+                decks AS d
+            WHERE
+                dw.deck_id = d.deck_id
+                AND d.user_id = $1
+                AND dw.deck_id = $2
+                AND dw.user_word_id = $3;
+            "#,
+        )
+        .bind(user_id)
+        .bind(deck_id)
+        .bind(user_word_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn update_share_token(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        share_token: Option<&str>,
+    ) -> Result<Option<entity::Deck>, sqlx::Error> {
+        let query = format!(
+            r#"
+            WITH d AS (
+                UPDATE decks
+                    SET share_token = $3, updated_at = CURRENT_TIMESTAMP
+                WHERE
+                    user_id = $1
+                    AND deck_id = $2
+                RETURNING
+                    *
+            )
+            SELECT {} FROM d;
+            "#,
+            Self::DECK_COLUMNS
+        );
+
+        let record = sqlx::query_as::<_, model::GetDeck>(query.as_str())
+            .bind(user_id)
+            .bind(deck_id)
+            .bind(share_token)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::deck_from_record(record)))
+    }
+
+    async fn get_shared_deck(
+        &self,
+        share_token: &str,
+    ) -> Result<Option<entity::Deck>, sqlx::Error> {
+        let query = format!(
+            r#"
+            SELECT {} FROM decks AS d
+            WHERE
+                d.share_token = $1;
+            "#,
+            Self::DECK_COLUMNS
+        );
+
+        let record = sqlx::query_as::<_, model::GetDeck>(query.as_str())
+            .bind(share_token)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::deck_from_record(record)))
+    }
+
+    async fn clone_shared_deck(
+        &self,
+        user_id: i64,
+        share_token: &str,
+    ) -> Result<Option<entity::ClonedDeck>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::CloneSharedDeck>(
+            r#"
+            WITH source AS (
+                SELECT
+                    uw.word_id
+                FROM
+                    decks AS d
+                INNER JOIN
+                    deck_words AS dw
+                        ON d.deck_id = dw.deck_id
+                INNER JOIN
+                    user_words AS uw
+                        ON dw.user_word_id = uw.user_word_id
+                WHERE
+                    d.share_token = $2
+            ), inserted AS (
+                INSERT INTO
+                    user_words (user_id, word_id)
+                SELECT
+                    $1, word_id
+                FROM
+                    source
+                ON CONFLICT (user_id, word_id) DO NOTHING
+                RETURNING
+                    word_id
+            )
+            SELECT
+                (SELECT COUNT(*) FROM inserted) AS created,
+                (SELECT COUNT(*) FROM source) AS total;
+            "#,
+        )
+        .bind(user_id)
+        .bind(share_token)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::ClonedDeck::new(
+            record.created,
+            record.total - record.created,
+        )))
+    }
+}
+
+#[derive(Clone)]
+pub struct SessionRepository {
+    pool: Pool<sqlx::Postgres>,
+}
+
+impl SessionRepository {
+    fn login_failures_from_record(
+        record: model::GetLoginFailures,
+    ) -> Result<entity::LoginFailures, sqlx::Error> {
+        if !record.is_valid() {
+            return Err(sqlx::Error::Decode(
+                format!("invalid login failures: {:?}", record).into(),
+            ));
+        }
+
+        Ok(entity::LoginFailures::new(
+            record.failures,
+            record.last_failed_at.as_deref().map(|last_failed_at| {
+                DateTime::parse_from_rfc3339(last_failed_at)
+                    .expect("Invalid date")
+                    .with_timezone(&Utc)
+            }),
+        ))
+    }
+}
+
+#[async_trait]
+impl interface::SessionRepositoryTrait for SessionRepository {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self {
+        Self { pool }
+    }
+
+    async fn create_refresh_token(
+        &self,
+        user_id: i64,
+        family_id: &str,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3, $4::TIMESTAMP);
+            "#,
+        )
+        .bind(user_id)
+        .bind(family_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(()))
     }
 
-    async fn get_user_word_by_word_id(
+    async fn get_refresh_token(
         &self,
-        word_id: i64,
-    ) -> Result<Option<Vec<entity::UserWord>>, sqlx::Error> {
-        let records = sqlx::query_as::<_, model::GetUserWord>(
+        token_hash: &str,
+    ) -> Result<Option<entity::RefreshSession>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetRefreshSession>(
             r#"
-            SELECT 
-                uw.user_word_id,
-                u.user_id,
-                u.last_name,
-                u.first_name,
-                u.email,
-                u.country,
-                w.word_id,
-                w.word,
-                uw.created_at
-            FROM 
-                user_words AS uw
-            INNER JOIN
-                users AS u 
-                    ON uw.user_id = u.user_id
+            SELECT
+                rt.refresh_token_id,
+                rt.user_id,
+                rt.family_id,
+                u.role,
+                to_char(rt.expires_at, 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS expires_at,
+                rt.used_at IS NOT NULL AS used,
+                rt.revoked_at IS NOT NULL AS revoked
+            FROM
+                refresh_tokens AS rt
             INNER JOIN
-                words AS w 
-                    ON uw.word_id = w.word_id
-            WHERE 
-                uw.word_id = $2
-            ORDER BY
-                created_at ASC;
+                users AS u
+                    ON rt.user_id = u.user_id
+            WHERE
+                rt.token_hash = $1;
             "#,
         )
-        .bind(word_id)
-        .fetch_all(&self.pool)
+        .bind(token_hash)
+        .fetch_one(&self.pool)
         .await?;
 
-        if records.is_empty() {
+        if !record.is_valid() {
             return Ok(None);
         }
 
-        let user_words = records
-            .into_iter()
-            .map(|record| {
-                entity::UserWord::new(
-                    entity::UserWordId::new(record.user_word_id),
-                    entity::UserId::new(record.user_id),
-                    entity::LastName::new(record.last_name.as_str()),
-                    entity::FirstName::new(record.first_name.as_str()),
-                    entity::Email::new(record.email.as_str()),
-                    entity::Country::new(record.country.as_str()),
-                    entity::WordId::new(record.word_id),
-                    entity::WordString::new(record.word.as_str()),
-                    entity::CreatedAt::new(
-                        DateTime::parse_from_rfc3339(record.created_at.as_str())
-                            .expect("Invalid date")
-                            .with_timezone(&Utc),
-                    ),
-                )
-            })
-            .collect();
+        // the column is check-constrained, an unknown role is a data error
+        let role = entity::Role::new(&record.role).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown user role: {}", record.role).into())
+        })?;
 
-        Ok(Some(user_words))
+        Ok(Some(entity::RefreshSession::new(
+            record.refresh_token_id,
+            entity::UserId::new(record.user_id),
+            entity::TokenFamilyId::new(record.family_id.as_str()),
+            role,
+            entity::ExpiresAt::new(
+                DateTime::parse_from_rfc3339(record.expires_at.as_str())
+                    .expect("Invalid date")
+                    .with_timezone(&Utc),
+            ),
+            record.used,
+            record.revoked,
+        )))
     }
 
-    async fn create_user_word(
+    async fn rotate_refresh_token(
+        &self,
+        refresh_token_id: i64,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // the used_at guard makes two concurrent exchanges of one token race for a single row
+        let consumed = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                refresh_token_id = $1
+                AND used_at IS NULL
+                AND revoked_at IS NULL;
+            "#,
+        )
+        .bind(refresh_token_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if consumed.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO
+                refresh_tokens (user_id, family_id, token_hash, expires_at)
+            SELECT
+                user_id, family_id, $2, $3::TIMESTAMP
+            FROM
+                refresh_tokens
+            WHERE
+                refresh_token_id = $1;
+            "#,
+        )
+        .bind(refresh_token_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+                SET revoked_at = CURRENT_TIMESTAMP
+            WHERE
+                family_id = $1
+                AND revoked_at IS NULL;
+            "#,
+        )
+        .bind(family_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn revoke_access_token(&self, jti: &str, expires_at: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                revoked_access_tokens (jti, expires_at)
+            VALUES
+                ($1, $2::TIMESTAMP)
+            ON CONFLICT (jti) DO NOTHING;
+            "#,
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_login_challenge(
         &self,
         user_id: i64,
-        word_id: i64,
-    ) -> Result<Option<entity::UserWordRelation>, sqlx::Error> {
-        let record = sqlx::query_as::<_, model::GetUserWordRelation>(
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
             r#"
-            INSERT INTO 
-                user_words (user_id, word_id)
-            VALUES 
-                ($1, $2)
-            RETURNING 
-                user_id, word_id;
+            INSERT INTO
+                login_challenges (user_id, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3::TIMESTAMP);
             "#,
         )
         .bind(user_id)
-        .bind(word_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(()))
+    }
+
+    async fn get_login_challenge(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<entity::LoginChallenge>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetLoginChallenge>(
+            r#"
+            SELECT
+                lc.login_challenge_id,
+                lc.user_id,
+                u.login_id,
+                u.role,
+                lc.attempts
+            FROM
+                login_challenges AS lc
+            INNER JOIN
+                users AS u
+                    ON lc.user_id = u.user_id
+            WHERE
+                lc.token_hash = $1
+                AND lc.used_at IS NULL
+                AND lc.expires_at > CURRENT_TIMESTAMP;
+            "#,
+        )
+        .bind(token_hash)
         .fetch_one(&self.pool)
         .await?;
 
@@ -509,30 +2741,345 @@ impl interface::UserWordRepositoryTrait for UserWordRepository {
             return Ok(None);
         }
 
-        Ok(Some(entity::UserWordRelation::new(
+        // the column is check-constrained, an unknown role is a data error
+        let role = entity::Role::new(&record.role).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown user role: {}", record.role).into())
+        })?;
+
+        Ok(Some(entity::LoginChallenge::new(
+            record.login_challenge_id,
             entity::UserId::new(record.user_id),
-            entity::WordId::new(record.word_id),
-            entity::CreatedAt::new(
-                DateTime::parse_from_rfc3339(record.created_at.as_str())
-                    .expect("Invalid date")
-                    .with_timezone(&Utc),
-            ),
+            entity::LoginId::new(&record.login_id),
+            role,
+            record.attempts,
         )))
     }
 
-    async fn delete_user_word(&self, id: i64) -> Result<Option<()>, sqlx::Error> {
+    async fn record_login_challenge_failure(
+        &self,
+        login_challenge_id: i64,
+        max_attempts: i32,
+    ) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
-            DELETE FROM 
-                user_words
-            WHERE 
-                user_word_id = $1;
+            UPDATE login_challenges
+                SET attempts = attempts + 1,
+                    used_at = CASE
+                        WHEN attempts + 1 >= $2 THEN CURRENT_TIMESTAMP
+                        ELSE used_at
+                    END
+            WHERE
+                login_challenge_id = $1;
             "#,
         )
-        .bind(i64::try_from(id).unwrap())
+        .bind(login_challenge_id)
+        .bind(max_attempts)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn complete_login_challenge(
+        &self,
+        login_challenge_id: i64,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE login_challenges
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                login_challenge_id = $1
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(login_challenge_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn is_access_token_revoked(
+        &self,
+        jti: Option<&str>,
+        user_id: Option<i64>,
+        issued_at: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        // a whole-second iat from the same second as the revocation counts as earlier, erring on rejection
+        let revoked = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT
+                EXISTS (
+                    SELECT 1 FROM revoked_access_tokens WHERE jti = $1
+                )
+                OR EXISTS (
+                    SELECT 1 FROM users
+                    WHERE
+                        user_id = $2
+                        AND $3::TIMESTAMP < sessions_revoked_at
+                );
+            "#,
+        )
+        .bind(jti)
+        .bind(user_id)
+        .bind(issued_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(revoked)
+    }
+
+    async fn record_login_attempt(
+        &self,
+        login_id: &str,
+        ip_address: Option<&str>,
+        succeeded: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                login_attempts (login_id, ip_address, succeeded)
+            VALUES
+                ($1, $2, $3);
+            "#,
+        )
+        .bind(login_id)
+        .bind(ip_address)
+        .bind(succeeded)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_login_failures_by_login_id(
+        &self,
+        login_id: &str,
+        window_seconds: i64,
+    ) -> Result<entity::LoginFailures, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetLoginFailures>(
+            r#"
+            SELECT
+                COUNT(*) AS failures,
+                to_char(MAX(la.attempted_at), 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS last_failed_at
+            FROM
+                login_attempts AS la
+            WHERE
+                la.login_id = $1
+                AND NOT la.succeeded
+                AND la.attempted_at > CURRENT_TIMESTAMP - make_interval(secs => $2)
+                AND la.attempted_at > COALESCE(
+                    (
+                        SELECT MAX(s.attempted_at) FROM login_attempts AS s
+                        WHERE s.login_id = $1 AND s.succeeded
+                    ),
+                    '-infinity'::TIMESTAMP
+                );
+            "#,
+        )
+        .bind(login_id)
+        .bind(window_seconds as f64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Self::login_failures_from_record(record)
+    }
+
+    async fn get_login_failures_by_ip_address(
+        &self,
+        ip_address: &str,
+        window_seconds: i64,
+    ) -> Result<entity::LoginFailures, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetLoginFailures>(
+            r#"
+            SELECT
+                COUNT(*) AS failures,
+                to_char(MAX(la.attempted_at), 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS last_failed_at
+            FROM
+                login_attempts AS la
+            WHERE
+                la.ip_address = $1
+                AND NOT la.succeeded
+                AND la.attempted_at > CURRENT_TIMESTAMP - make_interval(secs => $2);
+            "#,
+        )
+        .bind(ip_address)
+        .bind(window_seconds as f64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Self::login_failures_from_record(record)
+    }
+}
+
+#[derive(Clone)]
+pub struct IdempotencyRepository {
+    pool: Pool<sqlx::Postgres>,
+}
+
+#[async_trait]
+impl interface::IdempotencyRepositoryTrait for IdempotencyRepository {
+    fn new(pool: Pool<sqlx::Postgres>) -> Self {
+        Self { pool }
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+        expires_at: &str,
+        lock_seconds: i64,
+    ) -> Result<Option<()>, sqlx::Error> {
+        // a conflicting row is only taken over once expired or abandoned mid-request
+        let result = sqlx::query(
+            r#"
+            INSERT INTO
+                idempotency_keys (principal, idempotency_key, request_hash, expires_at)
+            VALUES
+                ($1, $2, $3, $4::TIMESTAMP)
+            ON CONFLICT (principal, idempotency_key) DO UPDATE
+                SET request_hash = EXCLUDED.request_hash,
+                    status_code = NULL,
+                    response_headers = NULL,
+                    response_body = NULL,
+                    expires_at = EXCLUDED.expires_at,
+                    created_at = CURRENT_TIMESTAMP
+            WHERE
+                idempotency_keys.expires_at <= CURRENT_TIMESTAMP
+                OR (
+                    idempotency_keys.status_code IS NULL
+                    AND idempotency_keys.created_at <= CURRENT_TIMESTAMP - make_interval(secs => $5)
+                );
+            "#,
+        )
+        .bind(principal)
+        .bind(idempotency_key)
+        .bind(request_hash)
+        .bind(expires_at)
+        .bind(lock_seconds as f64)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    async fn get_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<entity::IdempotencyKey>, sqlx::Error> {
+        let record = sqlx::query_as::<_, model::GetIdempotencyKey>(
+            r#"
+            SELECT
+                ik.request_hash,
+                ik.status_code,
+                ik.response_headers::TEXT AS response_headers,
+                ik.response_body
+            FROM
+                idempotency_keys AS ik
+            WHERE
+                ik.principal = $1
+                AND ik.idempotency_key = $2
+                AND ik.expires_at > CURRENT_TIMESTAMP;
+            "#,
+        )
+        .bind(principal)
+        .bind(idempotency_key)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !record.is_valid() {
+            return Ok(None);
+        }
+
+        let response = match (record.status_code, record.response_body) {
+            (Some(status_code), Some(body)) => {
+                let headers = match record.response_headers.as_deref() {
+                    Some(headers) => serde_json::from_str::<Vec<(String, String)>>(headers)
+                        .map_err(|err| sqlx::Error::Decode(err.into()))?,
+                    None => Vec::new(),
+                };
+                Some(entity::StoredResponse::new(
+                    u16::try_from(status_code).unwrap_or_default(),
+                    headers,
+                    body,
+                ))
+            }
+            _ => None,
+        };
+
+        Ok(Some(entity::IdempotencyKey::new(
+            record.request_hash,
+            response,
+        )))
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+        response: &entity::StoredResponse,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+                SET status_code = $3,
+                    response_headers = $4::JSONB,
+                    response_body = $5
+            WHERE
+                principal = $1
+                AND idempotency_key = $2
+                AND status_code IS NULL;
+            "#,
+        )
+        .bind(principal)
+        .bind(idempotency_key)
+        .bind(i32::from(response.status_code))
+        .bind(
+            serde_json::to_string(&response.headers)
+                .map_err(|err| sqlx::Error::Encode(err.into()))?,
+        )
+        .bind(response.body.as_slice())
         .execute(&self.pool)
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
         Ok(Some(()))
     }
+
+    async fn release_idempotency_key(
+        &self,
+        principal: &str,
+        idempotency_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM
+                idempotency_keys
+            WHERE
+                principal = $1
+                AND idempotency_key = $2
+                AND status_code IS NULL;
+            "#,
+        )
+        .bind(principal)
+        .bind(idempotency_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
 }
@@ -1,46 +1,209 @@
+use crate::domain::entity;
+use crate::domain::error::CosanError;
 use crate::router::request;
 use crate::router::response;
+use crate::util;
+use futures::stream::BoxStream;
 
 pub trait CosanServiceTrait {
-    fn get_user(&self, id: i64) -> Result<response::GetUserResponse, anyhow::Error>;
+    fn get_user(
+        &self,
+        id: i64,
+    ) -> Result<response::Versioned<response::GetUserResponse>, CosanError>;
     fn create_user(
         &self,
         request: request::CreateUserRequest,
-    ) -> Result<response::CreateUserResponse, anyhow::Error>;
+    ) -> Result<response::CreateUserResponse, CosanError>;
     fn update_user(
         &self,
         request: request::UpdateUserRequest,
-    ) -> Result<response::UpdateUserResponse, anyhow::Error>;
-    fn delete_user(&self, id: i64) -> Result<(), anyhow::Error>;
-    fn get_user_by_login_id_and_password(
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateUserResponse>, CosanError>;
+    fn patch_user(
+        &self,
+        user_id: i64,
+        request: request::PatchUserRequest,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateUserResponse>, CosanError>;
+    fn change_password(
+        &self,
+        user_id: i64,
+        request: request::ChangePasswordRequest,
+        client_ip: Option<&str>,
+    ) -> Result<response::ChangePasswordResponse, CosanError>;
+    fn delete_user(
+        &self,
+        id: i64,
+        precondition: &util::etag::Precondition,
+    ) -> Result<(), CosanError>;
+    fn verify_email(
+        &self,
+        request: request::VerifyEmailRequest,
+    ) -> Result<response::VerifyEmailResponse, CosanError>;
+    fn resend_verification_email(
+        &self,
+        user_id: i64,
+    ) -> Result<response::ResendVerificationEmailResponse, CosanError>;
+    fn forgot_password(
+        &self,
+        request: request::ForgotPasswordRequest,
+    ) -> Result<response::ForgotPasswordResponse, CosanError>;
+    fn reset_password(
+        &self,
+        request: request::ResetPasswordRequest,
+    ) -> Result<response::ResetPasswordResponse, CosanError>;
+    fn login(
         &self,
-        login_request: request::GetUserRequest,
-    ) -> Result<response::GetUserResponse, anyhow::Error>;
-    fn get_word(&self, id: i64) -> Result<response::GetWordResponse, anyhow::Error>;
+        request: request::LoginRequest,
+        client_ip: Option<&str>,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginStepResponse, CosanError>;
+    fn login_totp(
+        &self,
+        request: request::LoginTotpRequest,
+        client_ip: Option<&str>,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginResponse, CosanError>;
+    fn enroll_totp(&self, user_id: i64) -> Result<response::EnrollTotpResponse, CosanError>;
+    fn confirm_totp(
+        &self,
+        user_id: i64,
+        request: request::ConfirmTotpRequest,
+    ) -> Result<response::ConfirmTotpResponse, CosanError>;
+    fn disable_totp(
+        &self,
+        user_id: i64,
+        request: request::DisableTotpRequest,
+    ) -> Result<response::DisableTotpResponse, CosanError>;
+    fn refresh(
+        &self,
+        request: request::RefreshRequest,
+        verifier: &util::auth::TokenVerifier,
+    ) -> Result<response::LoginResponse, CosanError>;
+    fn logout(
+        &self,
+        token: &util::auth::Token,
+        request: request::LogoutRequest,
+    ) -> Result<response::LogoutResponse, CosanError>;
+    fn get_word(
+        &self,
+        id: i64,
+    ) -> Result<response::Versioned<response::GetWordResponse>, CosanError>;
     fn create_word(
         &self,
         request: request::CreateWordRequest,
-    ) -> Result<response::CreateWordResponse, anyhow::Error>;
+    ) -> Result<response::CreateWordResponse, CosanError>;
     fn update_word(
         &self,
         request: request::UpdateWordRequest,
-    ) -> Result<response::UpdateWordResponse, anyhow::Error>;
-    fn delete_word(&self, id: i64) -> Result<(), anyhow::Error>;
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateWordResponse>, CosanError>;
+    fn delete_word(
+        &self,
+        id: i64,
+        precondition: &util::etag::Precondition,
+    ) -> Result<(), CosanError>;
+    fn search_words(
+        &self,
+        request: request::SearchWordRequest,
+    ) -> Result<Vec<response::SearchWordResponse>, CosanError>;
     fn get_user_word_by_user_id_and_word_id(
         &self,
         request: request::GetUserWordRequest,
-    ) -> Result<response::GetUserWordResponse, anyhow::Error>;
+    ) -> Result<response::GetUserWordResponse, CosanError>;
     fn get_user_word_by_word_id(
         &self,
         request: request::GetUserWordRequest,
-    ) -> Result<Vec<response::GetUserWordResponse>, anyhow::Error>;
+        page: request::ListUserWordRequest,
+    ) -> Result<response::GetUserWordListResponse, CosanError>;
     fn get_user_word_by_user_id(
         &self,
         request: request::GetUserWordRequest,
-    ) -> Result<Vec<response::GetUserWordResponse>, anyhow::Error>;
+        page: request::ListUserWordRequest,
+    ) -> Result<response::GetUserWordListResponse, CosanError>;
     fn create_user_word(
         &self,
         request: request::CreateUserWordRequest,
-    ) -> Result<response::CreateUserWordRelationResponse, anyhow::Error>;
-    fn delete_user_word(&self, id: i64) -> Result<(), anyhow::Error>;
+    ) -> Result<response::CreateUserWordRelationResponse, CosanError>;
+    fn delete_user_word(&self, id: i64) -> Result<(), CosanError>;
+    fn get_user_word_owner(&self, user_word_id: i64) -> Result<i64, CosanError>;
+    fn get_due_reviews(
+        &self,
+        user_id: i64,
+        request: request::GetDueReviewRequest,
+    ) -> Result<Vec<response::GetDueReviewResponse>, CosanError>;
+    fn review_user_word(
+        &self,
+        user_id: i64,
+        user_word_id: i64,
+        request: request::ReviewUserWordRequest,
+    ) -> Result<response::ReviewUserWordResponse, CosanError>;
+    fn create_quiz(
+        &self,
+        user_id: i64,
+        request: request::CreateQuizRequest,
+    ) -> Result<response::CreateQuizResponse, CosanError>;
+    fn answer_quiz(
+        &self,
+        user_id: i64,
+        quiz_id: i64,
+        request: request::AnswerQuizRequest,
+    ) -> Result<response::AnswerQuizResponse, CosanError>;
+    fn create_deck(
+        &self,
+        user_id: i64,
+        request: request::CreateDeckRequest,
+    ) -> Result<response::DeckResponse, CosanError>;
+    fn get_decks(&self, user_id: i64) -> Result<Vec<response::DeckResponse>, CosanError>;
+    fn get_deck(&self, user_id: i64, deck_id: i64)
+        -> Result<response::GetDeckResponse, CosanError>;
+    fn update_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        request: request::UpdateDeckRequest,
+    ) -> Result<response::DeckResponse, CosanError>;
+    fn delete_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<response::DeleteDeckResponse, CosanError>;
+    fn add_deck_words(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        request: request::AddDeckWordRequest,
+    ) -> Result<response::GetDeckResponse, CosanError>;
+    fn remove_deck_word(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+        user_word_id: i64,
+    ) -> Result<response::DeleteDeckResponse, CosanError>;
+    fn share_deck(&self, user_id: i64, deck_id: i64) -> Result<response::DeckResponse, CosanError>;
+    fn unshare_deck(
+        &self,
+        user_id: i64,
+        deck_id: i64,
+    ) -> Result<response::DeckResponse, CosanError>;
+    fn get_shared_deck(&self, share_token: &str) -> Result<response::GetDeckResponse, CosanError>;
+    fn clone_shared_deck(
+        &self,
+        user_id: i64,
+        share_token: &str,
+    ) -> Result<response::CloneDeckResponse, CosanError>;
+    fn export_user_words(
+        &self,
+        user_id: i64,
+        format: entity::ExportFormat,
+    ) -> BoxStream<'static, Result<String, anyhow::Error>>;
+    fn import_user_words(
+        &self,
+        user_id: i64,
+        request: request::ImportUserWordRequest,
+    ) -> Result<response::ImportUserWordResponse, CosanError>;
+    fn get_word_ranking(
+        &self,
+        request: request::GetWordRankingRequest,
+    ) -> Result<Vec<response::GetWordRankingResponse>, CosanError>;
 }
@@ -1,14 +1,33 @@
-use super::response::ErrorResponse;
+use super::response::ProblemDetails;
+use super::router::AppState;
+use crate::domain::entity;
+use crate::domain::error::CosanError;
+use crate::domain::interface;
 use crate::util;
-use axum::{extract::State, http, middleware::Next, response::Response, Json};
-use std::sync::Arc;
-use tracing::{error, info};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{self, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{net::SocketAddr, sync::Arc};
+use tracing::{error, info, warn};
 
-pub async fn verify_token_middleware(
-    State(secret_key): State<Arc<String>>,
+pub async fn verify_token_middleware<U, W, UW, Q, D, S, I>(
+    State(state): State<AppState<U, W, UW, Q, D, S, I>>,
     mut req: http::Request<axum::body::Body>,
     next: Next,
-) -> Result<Response, (http::StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, CosanError>
+where
+    U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+    W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+    UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+    Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+    D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+    S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+    I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+{
     let auth_header = req
         .headers()
         .get(http::header::AUTHORIZATION)
@@ -18,40 +37,30 @@ pub async fn verify_token_middleware(
         auth_header
     } else {
         error!("verify_token_middleware: Authorization header not found");
-        return Err((
-            http::StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Token is invalid"),
-                message: format!("Authorization header not found"),
-            }),
+        return Err(CosanError::Unauthorized(
+            "Authorization header not found".to_string(),
         ));
     };
 
     let bearer = auth_header.split_whitespace().nth(0).unwrap_or_default();
     if bearer != "Bearer" {
         error!("verify_token_middleware: Authorization header is not Bearer");
-        return Err((
-            http::StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Token is invalid"),
-                message: format!("Authorization header is not Bearer"),
-            }),
+        return Err(CosanError::Unauthorized(
+            "Authorization header is not Bearer".to_string(),
         ));
     }
 
     let token = auth_header.split_whitespace().nth(1).unwrap_or_default();
     if token.is_empty() {
         error!("verify_token_middleware: Token is empty");
-        return Err((
-            http::StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Token is invalid"),
-                message: format!("Token is empty"),
-            }),
-        ));
+        return Err(CosanError::Unauthorized("Token is empty".to_string()));
     }
 
-    let token = util::auth::validate_token(token, &secret_key);
+    // signature and expiry first, then the revocation list written by logout
+    let token = state
+        .service
+        .verify_access_token(token, &state.verifier)
+        .await;
     match token {
         Ok(token) => {
             info!("verify_token_middleware: Token is valid");
@@ -67,6 +76,263 @@ pub async fn verify_token_middleware(
     }
 }
 
+/// Scopes and roles a group of routes demands from an already verified token.
+#[derive(Debug, Clone, Copy)]
+pub struct AccessPolicy {
+    scopes: &'static [&'static str],
+    roles: &'static [&'static str],
+}
+
+impl AccessPolicy {
+    pub const fn scopes(scopes: &'static [&'static str]) -> Self {
+        Self { scopes, roles: &[] }
+    }
+
+    /// Restricts the routes to tokens carrying one of `roles` as well.
+    pub const fn with_roles(self, roles: &'static [&'static str]) -> Self {
+        Self { roles, ..self }
+    }
+
+    pub fn check(&self, token: &util::auth::Token) -> Result<(), CosanError> {
+        if let Some(scope) = self.scopes.iter().find(|scope| !token.has_scope(scope)) {
+            return Err(CosanError::Forbidden(format!(
+                "Missing required scope: {}",
+                scope
+            )));
+        }
+
+        if !self.roles.is_empty()
+            && !token
+                .role
+                .as_deref()
+                .is_some_and(|role| self.roles.contains(&role))
+        {
+            return Err(CosanError::Forbidden(format!(
+                "Missing required role: {}",
+                self.roles.join(" or ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn require_access_middleware(
+    State(policy): State<AccessPolicy>,
+    req: http::Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, CosanError> {
+    // verify_token_middleware runs first and leaves the decoded claims behind
+    let token = req
+        .extensions()
+        .get::<Arc<util::auth::Token>>()
+        .ok_or_else(|| CosanError::Unauthorized("Token is missing".to_string()))?;
+
+    if let Err(err) = policy.check(token) {
+        error!("require_access_middleware: {}", err);
+        return Err(err);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Draws one request from the route group's token bucket and reports the remaining quota.
+///
+/// Requests are keyed by the `uid` of a token verified further out, otherwise by the peer address,
+/// so it has to sit inside `verify_token_middleware` on routes that require a token.
+pub async fn rate_limit_middleware(
+    State(limiter): State<Arc<util::rate_limit::RateLimiter>>,
+    req: http::Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let uid = req
+        .extensions()
+        .get::<Arc<util::auth::Token>>()
+        .and_then(|token| token.uid);
+    let key = match uid {
+        Some(uid) => util::rate_limit::RateLimitKey::User(uid),
+        None => match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => util::rate_limit::RateLimitKey::Ip(addr.ip()),
+            // without a socket there is no client to tell apart, e.g. when driven in-process
+            None => return next.run(req).await,
+        },
+    };
+
+    let decision = limiter.check(key.clone());
+    let mut res = match decision.retry_after {
+        Some(retry_after) => {
+            warn!("rate_limit_middleware: {:?} exceeded its quota", key);
+            CosanError::TooManyRequests("Rate limit exceeded".to_string(), retry_after)
+                .into_response()
+        }
+        None => next.run(req).await,
+    };
+
+    let headers = res.headers_mut();
+    headers.insert("ratelimit-limit", HeaderValue::from(decision.limit));
+    headers.insert("ratelimit-remaining", HeaderValue::from(decision.remaining));
+    headers.insert("ratelimit-reset", HeaderValue::from(decision.reset));
+
+    res
+}
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+// longest key accepted, enough for any UUID or ULID a client generates
+const IDEMPOTENCY_KEY_MAX_LEN: usize = 255;
+// upper bound when buffering a create request or its response for the idempotency store
+const IDEMPOTENT_BODY_LIMIT: usize = 1024 * 1024;
+// describe the connection or the message framing rather than the response, so a replay sets its own
+const UNREPLAYED_HEADERS: [http::HeaderName; 4] = [
+    http::header::CONNECTION,
+    http::header::CONTENT_LENGTH,
+    http::header::DATE,
+    http::header::TRANSFER_ENCODING,
+];
+
+/// Replays the stored response when a create request is retried with the same `Idempotency-Key`.
+///
+/// The first success or client error is stored, headers included, while a server error releases the
+/// key so that the retry runs the request again. Keys belong to the `uid` of a token verified further
+/// out, so on routes that require a token it has to sit inside `verify_token_middleware`; requests
+/// without one are keyed by the request itself, so that an anonymous key only ever replays to the
+/// identical request and never to another client sending different content.
+pub async fn idempotency_middleware<U, W, UW, Q, D, S, I>(
+    State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+    req: http::Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, CosanError>
+where
+    U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+    W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+    UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+    Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+    D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+    S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+    I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+{
+    let idempotency_key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(idempotency_key) => idempotency_key
+            .to_str()
+            .ok()
+            .map(str::trim)
+            .filter(|key| !key.is_empty() && key.len() <= IDEMPOTENCY_KEY_MAX_LEN)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                CosanError::BadRequest(format!(
+                    "Idempotency-Key must be 1 to {} visible characters",
+                    IDEMPOTENCY_KEY_MAX_LEN
+                ))
+            })?,
+        None => return Ok(next.run(req).await),
+    };
+
+    let (parts, body) = req.into_parts();
+    let body = axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT)
+        .await
+        .map_err(|_| CosanError::BadRequest("Request body is too large".to_string()))?;
+    let request_hash = util::crypt::digest_request(parts.method.as_str(), parts.uri.path(), &body);
+
+    let uid = parts
+        .extensions
+        .get::<Arc<util::auth::Token>>()
+        .and_then(|token| token.uid);
+    let principal = match uid {
+        Some(uid) => format!("user:{}", uid),
+        None => format!("request:{}", request_hash),
+    };
+
+    let stored = state
+        .service
+        .begin_idempotent_request(&principal, &idempotency_key, &request_hash)
+        .await?;
+    if let Some(stored) = stored {
+        info!("idempotency_middleware: replaying the response to a retried request");
+        let mut res = Response::new(Body::from(stored.body));
+        *res.status_mut() =
+            http::StatusCode::from_u16(stored.status_code).unwrap_or(http::StatusCode::OK);
+        for (name, value) in stored.headers {
+            if let (Ok(name), Ok(value)) = (
+                http::HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                res.headers_mut().append(name, value);
+            }
+        }
+        res.headers_mut()
+            .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+        return Ok(res);
+    }
+
+    let res = next
+        .run(http::Request::from_parts(parts, Body::from(body)))
+        .await;
+
+    let (parts, body) = res.into_parts();
+    let body = match axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(_) => {
+            release_idempotency_key(&state, &principal, &idempotency_key).await;
+            return Err(CosanError::Internal(
+                "Response body could not be stored".to_string(),
+            ));
+        }
+    };
+    let stored = entity::StoredResponse::new(
+        parts.status.as_u16(),
+        parts
+            .headers
+            .iter()
+            .filter(|(name, _)| !UNREPLAYED_HEADERS.contains(*name))
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect(),
+        body.to_vec(),
+    );
+    if !stored.is_replayable() {
+        info!("idempotency_middleware: releasing the key of a failed request");
+        release_idempotency_key(&state, &principal, &idempotency_key).await;
+        return Ok(Response::from_parts(parts, Body::from(body)));
+    }
+    // the request has run either way, a retry then waits for the lock to time out
+    if let Err(err) = state
+        .service
+        .complete_idempotent_request(&principal, &idempotency_key, &stored)
+        .await
+    {
+        error!(
+            "idempotency_middleware: storing the response failed: {}",
+            err
+        );
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+async fn release_idempotency_key<U, W, UW, Q, D, S, I>(
+    state: &AppState<U, W, UW, Q, D, S, I>,
+    principal: &str,
+    idempotency_key: &str,
+) where
+    U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+    W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+    UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+    Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+    D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+    S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+    I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+{
+    if let Err(err) = state
+        .service
+        .release_idempotent_request(principal, idempotency_key)
+        .await
+    {
+        // the key frees itself once its lock times out
+        warn!("idempotency_middleware: releasing the key failed: {}", err);
+    }
+}
+
 pub async fn request_log_middleware(
     req: http::Request<axum::body::Body>,
     next: Next,
@@ -77,3 +343,58 @@ pub async fn request_log_middleware(
 
     Ok(res)
 }
+
+// upper bound when turning a plain-text rejection body into a problem detail
+const REJECTION_BODY_LIMIT: usize = 64 * 1024;
+
+pub async fn problem_details_middleware(
+    req: http::Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let instance = req.uri().path().to_string();
+    let res = next.run(req).await;
+    let (mut parts, body) = res.into_parts();
+
+    // errors raised by handlers only lack the request path
+    if let Some(problem) = parts.extensions.remove::<ProblemDetails>() {
+        let problem = problem.with_instance(instance);
+        let body = serde_json::to_vec(&problem).unwrap_or_default();
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::from(body));
+    }
+
+    // extractor rejections and unmatched routes come back as plain text or empty
+    let is_error = parts.status.is_client_error() || parts.status.is_server_error();
+    let is_plain = parts
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.starts_with("text/plain"));
+    if !is_error || !is_plain {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = axum::body::to_bytes(body, REJECTION_BODY_LIMIT)
+        .await
+        .unwrap_or_default();
+    let detail = match String::from_utf8_lossy(&bytes).trim() {
+        "" => parts
+            .status
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_string(),
+        detail => detail.to_string(),
+    };
+
+    let mut res = ProblemDetails::new(parts.status, detail)
+        .with_instance(instance)
+        .into_response();
+    res.extensions_mut().remove::<ProblemDetails>();
+    // append, so repeated headers such as Set-Cookie or Vary keep every value
+    for (name, value) in parts.headers.iter() {
+        if name != http::header::CONTENT_TYPE && name != http::header::CONTENT_LENGTH {
+            res.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    res
+}
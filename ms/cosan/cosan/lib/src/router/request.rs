@@ -1,132 +1,358 @@
-use anyhow::{anyhow, Ok, Result};
+use crate::domain::entity;
+use crate::domain::error::{CosanError, FieldErrors};
+use crate::util;
 use regex::Regex;
 use serde::Deserialize;
+use std::sync::LazyLock;
+
+const NAME_PATTERN: &str = r"^[\p{L}\p{N}\s'-]+$";
+const LOGIN_PATTERN: &str = r"^[a-zA-Z0-9]+$";
+const EMAIL_PATTERN: &str = r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$";
+const WORD_PATTERN: &str = r"^[\p{L}\p{M}\p{N}\s'-]+$";
+
+// compiled once, an import checks every one of up to MAX_LINES lines against it
+static WORD_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(WORD_PATTERN).unwrap());
 
 #[derive(Deserialize, Debug)]
-pub struct GetUserRequest {
+pub struct LoginRequest {
     pub login_id: String,
     pub password: String,
 }
 
-impl GetUserRequest {
-    pub fn new(login_id: String, password: String) -> Self {
-        Self { login_id, password }
+impl LoginRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        let login_regex = Regex::new(LOGIN_PATTERN).unwrap();
+        if !login_regex.is_match(&self.login_id) {
+            errors.add("login_id", "Invalid login id format.");
+        }
+        if self.password.is_empty() || self.password.len() > 255 {
+            errors.add("password", "Password must be between 1 and 255 bytes.");
+        }
+
+        errors.into_result()
     }
+}
 
-    pub async fn validate(self) -> Result<Self, anyhow::Error> {
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
+#[derive(Deserialize, Debug)]
+pub struct LoginTotpRequest {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+impl LoginTotpRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.challenge_token.trim().is_empty() || self.challenge_token.len() > 255 {
+            errors.add(
+                "challenge_token",
+                "Challenge token must be between 1 and 255 bytes.",
+            );
         }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
+        validate_totp_code(&mut errors, &self.code);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
+
+impl ConfirmTotpRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        validate_totp_code(&mut errors, &self.code);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DisableTotpRequest {
+    /// An authenticator code or an unused recovery code.
+    pub code: String,
+}
+
+impl DisableTotpRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        validate_totp_code(&mut errors, &self.code);
+
+        errors.into_result()
+    }
+}
+
+fn validate_totp_code(errors: &mut FieldErrors, code: &str) {
+    if code.trim().is_empty() || code.len() > 64 {
+        errors.add("code", "Code must be between 1 and 64 bytes.");
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+impl RefreshRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.refresh_token.trim().is_empty() || self.refresh_token.len() > 255 {
+            errors.add(
+                "refresh_token",
+                "Refresh token must be between 1 and 255 bytes.",
+            );
         }
 
-        Ok(self)
+        errors.into_result()
     }
 }
 
 #[derive(Deserialize, Debug)]
-pub struct CreateUserRequest {
-    pub last_name: String,
-    pub first_name: String,
-    pub login_id: String,
-    pub password: String,
-    pub email: String,
-    pub country: String,
+pub struct VerifyEmailRequest {
+    pub token: String,
 }
 
-impl CreateUserRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let name_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !name_regex.is_match(&self.last_name) {
-            return Err(anyhow!("Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
+impl VerifyEmailRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.token.trim().is_empty() || self.token.len() > 255 {
+            errors.add("token", "Token must be between 1 and 255 bytes.");
         }
-        if !name_regex.is_match(&self.first_name) {
-            return Err(anyhow!("Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+impl ForgotPasswordRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        let email_regex = Regex::new(EMAIL_PATTERN).unwrap();
+        if !email_regex.is_match(&self.email) {
+            errors.add("email", "Invalid email format.");
         }
 
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+impl ResetPasswordRequest {
+    /// The password policy is checked by the service, which knows whose password it is.
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.token.trim().is_empty() || self.token.len() > 255 {
+            errors.add("token", "Token must be between 1 and 255 bytes.");
         }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
+        if self.password.is_empty() || self.password.len() > 255 {
+            errors.add("password", "Password must be between 1 and 255 bytes.");
         }
 
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(&self.email) {
-            return Err(anyhow!("Invalid email format."));
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LogoutRequest {
+    /// Revokes the whole refresh token family when given, otherwise only the access token.
+    pub refresh_token: Option<String>,
+}
+
+impl LogoutRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if let Some(refresh_token) = &self.refresh_token {
+            if refresh_token.trim().is_empty() || refresh_token.len() > 255 {
+                errors.add(
+                    "refresh_token",
+                    "Refresh token must be between 1 and 255 bytes.",
+                );
+            }
         }
 
-        if self.country.is_empty() {
-            return Err(anyhow!("Country cannot be empty."));
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateUserRequest {
+    pub last_name: String,
+    pub first_name: String,
+    pub login_id: String,
+    pub password: String,
+    pub email: String,
+    pub country: String,
+}
+
+impl CreateUserRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        validate_user_fields(
+            &mut errors,
+            &self.last_name,
+            &self.first_name,
+            &self.login_id,
+            &self.email,
+            &self.country,
+        );
+        for violation in util::password_policy::violations(&self.password, &self.login_id) {
+            errors.add("password", violation);
         }
 
-        Ok(())
+        errors.into_result()
     }
 }
 
+/// Replaces the whole profile, the password only changes through its own endpoint.
 #[derive(Deserialize, Debug)]
 pub struct UpdateUserRequest {
     pub user_id: i64,
     pub last_name: String,
     pub first_name: String,
     pub login_id: String,
-    pub password: String,
     pub email: String,
     pub country: String,
 }
 
 impl UpdateUserRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.user_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.user_id < 0 {
+            errors.add("user_id", "Invalid id format.");
         }
+        validate_user_fields(
+            &mut errors,
+            &self.last_name,
+            &self.first_name,
+            &self.login_id,
+            &self.email,
+            &self.country,
+        );
+
+        errors.into_result()
+    }
+}
 
-        let name_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !name_regex.is_match(&self.last_name) {
-            return Err(anyhow!("Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
+/// Profile fields to change, absent ones keep their value.
+#[derive(Deserialize, Debug)]
+pub struct PatchUserRequest {
+    pub last_name: Option<String>,
+    pub first_name: Option<String>,
+    pub email: Option<String>,
+    pub country: Option<String>,
+}
+
+impl PatchUserRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        let name_regex = Regex::new(NAME_PATTERN).unwrap();
+        if let Some(last_name) = &self.last_name {
+            if !name_regex.is_match(last_name) {
+                errors.add("last_name", "Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+            }
         }
-        if !name_regex.is_match(&self.first_name) {
-            return Err(anyhow!("Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
+        if let Some(first_name) = &self.first_name {
+            if !name_regex.is_match(first_name) {
+                errors.add("first_name", "Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+            }
         }
-
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
+        if let Some(email) = &self.email {
+            let email_regex = Regex::new(EMAIL_PATTERN).unwrap();
+            if !email_regex.is_match(email) {
+                errors.add("email", "Invalid email format.");
+            }
         }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
+        if let Some(country) = &self.country {
+            if country.is_empty() {
+                errors.add("country", "Country cannot be empty.");
+            }
         }
 
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(&self.email) {
-            return Err(anyhow!("Invalid email format."));
-        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
 
-        if self.country.is_empty() {
-            return Err(anyhow!("Country cannot be empty."));
+impl ChangePasswordRequest {
+    /// The password policy is checked by the service, which knows whose password it is.
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.current_password.is_empty() || self.current_password.len() > 255 {
+            errors.add(
+                "current_password",
+                "Password must be between 1 and 255 bytes.",
+            );
+        }
+        if self.new_password.is_empty() || self.new_password.len() > 255 {
+            errors.add("new_password", "Password must be between 1 and 255 bytes.");
         }
 
-        Ok(())
+        errors.into_result()
+    }
+}
+
+fn validate_user_fields(
+    errors: &mut FieldErrors,
+    last_name: &str,
+    first_name: &str,
+    login_id: &str,
+    email: &str,
+    country: &str,
+) {
+    let name_regex = Regex::new(NAME_PATTERN).unwrap();
+    if !name_regex.is_match(last_name) {
+        errors.add("last_name", "Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+    }
+    if !name_regex.is_match(first_name) {
+        errors.add("first_name", "Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+    }
+
+    let login_regex = Regex::new(LOGIN_PATTERN).unwrap();
+    if !login_regex.is_match(login_id) {
+        errors.add("login_id", "Invalid login id format.");
+    }
+
+    let email_regex = Regex::new(EMAIL_PATTERN).unwrap();
+    if !email_regex.is_match(email) {
+        errors.add("email", "Invalid email format.");
+    }
+
+    if country.is_empty() {
+        errors.add("country", "Country cannot be empty.");
     }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct CreateWordRequest {
     pub word: String,
+    pub definition: Option<String>,
 }
 
 impl CreateWordRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let word_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !word_regex.is_match(&self.word) {
-            return Err(anyhow!("Invalid word format."));
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if !WORD_REGEX.is_match(&self.word) {
+            errors.add("word", "Invalid word format.");
         }
+        validate_definition(&mut errors, self.definition.as_deref());
 
-        Ok(())
+        errors.into_result()
     }
 }
 
@@ -134,16 +360,87 @@ impl CreateWordRequest {
 pub struct UpdateWordRequest {
     pub word_id: u64,
     pub word: String,
+    pub definition: Option<String>,
 }
 
 impl UpdateWordRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let word_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !word_regex.is_match(&self.word) {
-            return Err(anyhow!("Invalid word format."));
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if !WORD_REGEX.is_match(&self.word) {
+            errors.add("word", "Invalid word format.");
         }
+        validate_definition(&mut errors, self.definition.as_deref());
 
-        Ok(())
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchWordRequest {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<u64>,
+}
+
+impl SearchWordRequest {
+    pub const DEFAULT_LIMIT: u64 = 20;
+    pub const MAX_LIMIT: u64 = 100;
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if !self.q.trim().is_empty() && !WORD_REGEX.is_match(self.q.trim()) {
+            errors.add("q", "Invalid search query format.");
+        }
+        validate_limit(&mut errors, self.limit, Self::MAX_LIMIT);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GetWordRankingRequest {
+    pub period: Option<String>,
+    pub limit: Option<u64>,
+}
+
+impl GetWordRankingRequest {
+    pub const DEFAULT_PERIOD: &'static str = "all";
+    pub const DEFAULT_LIMIT: u64 = 10;
+    pub const MAX_LIMIT: u64 = 100;
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if let Some(period) = &self.period {
+            if !["day", "week", "month", "all"].contains(&period.as_str()) {
+                errors.add("period", "Period must be one of day, week, month or all.");
+            }
+        }
+        validate_limit(&mut errors, self.limit, Self::MAX_LIMIT);
+
+        errors.into_result()
+    }
+}
+
+fn validate_definition(errors: &mut FieldErrors, definition: Option<&str>) {
+    if let Some(definition) = definition {
+        let length = definition.trim().chars().count();
+        if length == 0 || length > entity::WordDefinition::MAX_LENGTH {
+            errors.add(
+                "definition",
+                format!(
+                    "Definition must be between 1 and {} characters.",
+                    entity::WordDefinition::MAX_LENGTH
+                ),
+            );
+        }
+    }
+}
+
+fn validate_limit(errors: &mut FieldErrors, limit: Option<u64>, max: u64) {
+    if let Some(limit) = limit {
+        if limit == 0 || limit > max {
+            errors.add("limit", format!("Limit must be between 1 and {}.", max));
+        }
     }
 }
 
@@ -154,16 +451,36 @@ pub struct GetUserWordRequest {
 }
 
 impl GetUserWordRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.user_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-        if !id_regex.is_match(&self.word_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        // u64 path segments are already checked by the extractor
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListUserWordRequest {
+    pub limit: Option<u64>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+}
+
+impl ListUserWordRequest {
+    pub const DEFAULT_SORT: &'static str = "created_at_asc";
+    pub const DEFAULT_LIMIT: u64 = 20;
+    pub const MAX_LIMIT: u64 = 100;
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        let sort = entity::UserWordSort::new(self.sort.as_deref().unwrap_or(Self::DEFAULT_SORT));
+        if sort.is_none() {
+            errors.add(
+                "sort",
+                "Sort must be one of created_at_asc, created_at_desc or word.",
+            );
         }
+        validate_limit(&mut errors, self.limit, Self::MAX_LIMIT);
 
-        Ok(())
+        errors.into_result()
     }
 }
 
@@ -174,15 +491,8 @@ pub struct CreateUserWordRequest {
 }
 
 impl CreateUserWordRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.user_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-        if !id_regex.is_match(&self.word_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        // u64 path segments are already checked by the extractor
         Ok(())
     }
 }
@@ -193,12 +503,341 @@ pub struct DeleteUserWordRequest {
 }
 
 impl DeleteUserWordRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.user_word_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        // u64 path segments are already checked by the extractor
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GetDueReviewRequest {
+    pub limit: Option<u64>,
+}
+
+impl GetDueReviewRequest {
+    pub const DEFAULT_LIMIT: u64 = 20;
+    pub const MAX_LIMIT: u64 = 100;
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        validate_limit(&mut errors, self.limit, Self::MAX_LIMIT);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReviewUserWordRequest {
+    pub grade: u8,
+}
+
+impl ReviewUserWordRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if entity::ReviewGrade::new(self.grade).is_none() {
+            errors.add(
+                "grade",
+                format!("Grade must be between 0 and {}.", entity::ReviewGrade::MAX),
+            );
         }
 
-        Ok(())
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateQuizRequest {
+    pub mode: Option<String>,
+    pub count: Option<u64>,
+    pub seed: Option<u64>,
+}
+
+impl CreateQuizRequest {
+    pub const DEFAULT_MODE: &'static str = "multiple_choice";
+    pub const DEFAULT_COUNT: u64 = 10;
+    pub const MAX_COUNT: u64 = 50;
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if let Some(mode) = &self.mode {
+            if entity::QuizMode::new(mode).is_none() {
+                errors.add("mode", "Mode must be one of multiple_choice or typing.");
+            }
+        }
+
+        if let Some(count) = self.count {
+            if count == 0 || count > Self::MAX_COUNT {
+                errors.add(
+                    "count",
+                    format!("Count must be between 1 and {}.", Self::MAX_COUNT),
+                );
+            }
+        }
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AnswerQuizRequest {
+    pub position: u32,
+    pub answer: String,
+}
+
+impl AnswerQuizRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        let answer = self.answer.trim();
+        if answer.is_empty() || answer.chars().count() > 255 {
+            errors.add("answer", "Answer must be between 1 and 255 characters.");
+        }
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateDeckRequest {
+    pub name: String,
+}
+
+impl CreateDeckRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        validate_deck_name(&mut errors, &self.name);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateDeckRequest {
+    pub name: String,
+}
+
+impl UpdateDeckRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        validate_deck_name(&mut errors, &self.name);
+
+        errors.into_result()
+    }
+}
+
+fn validate_deck_name(errors: &mut FieldErrors, name: &str) {
+    let name_regex = Regex::new(r"^[\p{L}\p{M}\p{N}\s'_-]+$").unwrap();
+    if !name_regex.is_match(name.trim()) {
+        errors.add("name", "Invalid deck name format.");
+    }
+
+    if name.trim().chars().count() > 100 {
+        errors.add("name", "Deck name must be at most 100 characters.");
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AddDeckWordRequest {
+    pub user_word_ids: Vec<u64>,
+}
+
+impl AddDeckWordRequest {
+    pub const MAX_WORDS: usize = 1_000;
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.user_word_ids.is_empty() || self.user_word_ids.len() > Self::MAX_WORDS {
+            errors.add(
+                "user_word_ids",
+                format!(
+                    "User word ids must contain between 1 and {} entries.",
+                    Self::MAX_WORDS
+                ),
+            );
+        }
+        if self
+            .user_word_ids
+            .iter()
+            .any(|user_word_id| i64::try_from(*user_word_id).is_err())
+        {
+            errors.add("user_word_ids", "User word ids must be valid integers.");
+        }
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShareTokenPath {
+    pub share_token: String,
+}
+
+impl ShareTokenPath {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        let token_regex = Regex::new(r"^[0-9a-f]{48}$").unwrap();
+        if !token_regex.is_match(&self.share_token) {
+            errors.add("share_token", "Invalid share token format.");
+        }
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportUserWordRequest {
+    pub format: Option<String>,
+}
+
+impl ExportUserWordRequest {
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if let Some(format) = &self.format {
+            if entity::ExportFormat::new(format).is_none() {
+                errors.add("format", "Format must be one of csv, json or ndjson.");
+            }
+        }
+
+        errors.into_result()
+    }
+
+    /// The `format` parameter wins over `Accept`, JSON is the fallback.
+    pub fn format(&self, accept: Option<&str>) -> entity::ExportFormat {
+        if let Some(format) = self.format.as_deref().and_then(entity::ExportFormat::new) {
+            return format;
+        }
+
+        let accept = accept.unwrap_or_default().to_ascii_lowercase();
+        if accept.contains("text/csv") {
+            entity::ExportFormat::Csv
+        } else if accept.contains("ndjson") {
+            entity::ExportFormat::Ndjson
+        } else {
+            entity::ExportFormat::Json
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportUserWordEntry {
+    Word(String),
+    Invalid { raw: String, reason: String },
+}
+
+#[derive(Debug)]
+pub struct ImportUserWordLine {
+    pub line: u64,
+    pub entry: ImportUserWordEntry,
+}
+
+#[derive(Deserialize, Debug)]
+struct ImportUserWordRecord {
+    word: String,
+}
+
+#[derive(Debug)]
+pub struct ImportUserWordRequest {
+    pub lines: Vec<ImportUserWordLine>,
+}
+
+impl ImportUserWordRequest {
+    pub const MAX_LINES: usize = 10_000;
+
+    /// One word per record in the first column, an optional `word` header is skipped.
+    pub fn from_csv(body: &str) -> Self {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(body.as_bytes());
+
+        let mut lines = Vec::new();
+        for (index, record) in reader.records().enumerate() {
+            let entry = match &record {
+                Result::Ok(record) => {
+                    let raw = record.get(0).unwrap_or_default();
+                    if index == 0 && raw.eq_ignore_ascii_case("word") {
+                        continue;
+                    }
+                    Self::entry(raw)
+                }
+                Err(err) => ImportUserWordEntry::Invalid {
+                    raw: String::new(),
+                    reason: format!("Invalid CSV record: {}", err),
+                },
+            };
+            let line = match &record {
+                Result::Ok(record) => record.position().map(|position| position.line()),
+                Err(err) => err.position().map(|position| position.line()),
+            };
+
+            lines.push(ImportUserWordLine {
+                line: line.unwrap_or(index as u64 + 1),
+                entry,
+            });
+        }
+
+        Self { lines }
+    }
+
+    /// One `{"word": "..."}` object per line, blank lines are skipped.
+    pub fn from_ndjson(body: &str) -> Self {
+        let lines = body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| ImportUserWordLine {
+                line: index as u64 + 1,
+                entry: match serde_json::from_str::<ImportUserWordRecord>(line) {
+                    Result::Ok(record) => Self::entry(record.word.trim()),
+                    Err(err) => ImportUserWordEntry::Invalid {
+                        raw: line.to_string(),
+                        reason: format!("Invalid JSON line: {}", err),
+                    },
+                },
+            })
+            .collect();
+
+        Self { lines }
+    }
+
+    fn entry(raw: &str) -> ImportUserWordEntry {
+        if raw.is_empty() {
+            return ImportUserWordEntry::Invalid {
+                raw: raw.to_string(),
+                reason: "Word cannot be empty.".to_string(),
+            };
+        }
+        if raw.chars().count() > 255 {
+            return ImportUserWordEntry::Invalid {
+                raw: raw.to_string(),
+                reason: "Word must be at most 255 characters.".to_string(),
+            };
+        }
+        if !WORD_REGEX.is_match(raw) {
+            return ImportUserWordEntry::Invalid {
+                raw: raw.to_string(),
+                reason: "Invalid word format.".to_string(),
+            };
+        }
+
+        ImportUserWordEntry::Word(raw.to_string())
+    }
+
+    pub async fn validate(&self) -> Result<(), CosanError> {
+        let mut errors = FieldErrors::new();
+        if self.lines.is_empty() {
+            errors.add("body", "Import body contains no words.");
+        }
+        if self.lines.len() > Self::MAX_LINES {
+            errors.add(
+                "body",
+                format!(
+                    "Import body must contain at most {} lines.",
+                    Self::MAX_LINES
+                ),
+            );
+        }
+
+        errors.into_result()
     }
 }
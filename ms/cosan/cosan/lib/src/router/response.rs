@@ -1,9 +1,17 @@
+use crate::domain::error::{CosanError, FieldErrors};
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
+use tracing::error;
+
+/// A representation and the row version its `ETag` is made from.
+pub struct Versioned<T> {
+    pub version: i64,
+    pub body: T,
+}
 
 #[derive(Serialize)]
 pub struct HealthCheckResponse {
@@ -23,6 +31,7 @@ pub struct GetUserResponse {
     pub user_first_name: String,
     pub user_email: String,
     pub user_country: String,
+    pub user_email_verified: bool,
 }
 
 impl IntoResponse for GetUserResponse {
@@ -31,6 +40,136 @@ impl IntoResponse for GetUserResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct LoginResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub refresh_token: String,
+    pub refresh_expires_in: u64,
+}
+
+impl IntoResponse for LoginResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// Password checked, a second factor is still needed to finish the login.
+#[derive(Serialize)]
+pub struct TwoFactorRequiredResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_in: u64,
+}
+
+/// What the password step of a login answers, tokens right away or a challenge for the second factor.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginStepResponse {
+    Authenticated(LoginResponse),
+    TwoFactorRequired(TwoFactorRequiredResponse),
+}
+
+impl IntoResponse for LoginStepResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+impl IntoResponse for EnrollTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ConfirmTotpResponse {
+    pub totp_enabled: bool,
+    /// Shown this once, only their digests are kept.
+    pub recovery_codes: Vec<String>,
+}
+
+impl IntoResponse for ConfirmTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DisableTotpResponse {
+    pub totp_enabled: bool,
+}
+
+impl IntoResponse for DisableTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct LogoutResponse {
+    pub status: String,
+}
+
+impl IntoResponse for LogoutResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct VerifyEmailResponse {
+    pub user_id: u64,
+    pub user_email_verified: bool,
+}
+
+impl IntoResponse for VerifyEmailResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ForgotPasswordResponse {
+    pub status: String,
+}
+
+impl IntoResponse for ForgotPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::ACCEPTED, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResetPasswordResponse {
+    pub user_id: u64,
+    pub status: String,
+}
+
+impl IntoResponse for ResetPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResendVerificationEmailResponse {
+    pub status: String,
+}
+
+impl IntoResponse for ResendVerificationEmailResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::ACCEPTED, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct CreateUserResponse {
     pub user_id: u64,
@@ -38,6 +177,7 @@ pub struct CreateUserResponse {
     pub user_first_name: String,
     pub user_email: String,
     pub user_country: String,
+    pub user_email_verified: bool,
 }
 
 impl IntoResponse for CreateUserResponse {
@@ -53,6 +193,7 @@ pub struct UpdateUserResponse {
     pub user_first_name: String,
     pub user_email: String,
     pub user_country: String,
+    pub user_email_verified: bool,
 }
 
 impl IntoResponse for UpdateUserResponse {
@@ -61,6 +202,18 @@ impl IntoResponse for UpdateUserResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct ChangePasswordResponse {
+    pub user_id: u64,
+    pub status: String,
+}
+
+impl IntoResponse for ChangePasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct DeleteUserResponse {
     pub status: String,
@@ -76,6 +229,7 @@ impl IntoResponse for DeleteUserResponse {
 pub struct GetWordResponse {
     pub word_id: u64,
     pub word: String,
+    pub definition: Option<String>,
 }
 
 impl IntoResponse for GetWordResponse {
@@ -88,6 +242,7 @@ impl IntoResponse for GetWordResponse {
 pub struct CreateWordResponse {
     pub word_id: u64,
     pub word: String,
+    pub definition: Option<String>,
 }
 
 impl IntoResponse for CreateWordResponse {
@@ -100,6 +255,7 @@ impl IntoResponse for CreateWordResponse {
 pub struct UpdateWordResponse {
     pub word_id: u64,
     pub word: String,
+    pub definition: Option<String>,
 }
 
 impl IntoResponse for UpdateWordResponse {
@@ -119,6 +275,33 @@ impl IntoResponse for DeleteWordResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct SearchWordResponse {
+    pub word_id: u64,
+    pub word: String,
+    pub score: f64,
+}
+
+impl IntoResponse for SearchWordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct GetWordRankingResponse {
+    pub rank: u64,
+    pub word_id: u64,
+    pub word: String,
+    pub registration_count: u64,
+}
+
+impl IntoResponse for GetWordRankingResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct GetUserWordResponse {
     pub user_word_id: u64,
@@ -138,6 +321,18 @@ impl IntoResponse for GetUserWordResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct GetUserWordListResponse {
+    pub items: Vec<GetUserWordResponse>,
+    pub next_cursor: Option<String>,
+}
+
+impl IntoResponse for GetUserWordListResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct CreateUserWordResponse {
     pub user_word_id: u64,
@@ -170,6 +365,170 @@ impl IntoResponse for CreateUserWordRelationResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct GetDueReviewResponse {
+    pub user_word_id: u64,
+    pub word_id: u64,
+    pub word: String,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub due_at: String,
+}
+
+impl IntoResponse for GetDueReviewResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReviewUserWordResponse {
+    pub user_word_id: u64,
+    pub word_id: u64,
+    pub word: String,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub due_at: String,
+}
+
+impl IntoResponse for ReviewUserWordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct QuizQuestionResponse {
+    pub position: u32,
+    pub prompt: String,
+    pub choices: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateQuizResponse {
+    pub quiz_id: u64,
+    pub mode: String,
+    pub seed: u64,
+    pub questions: Vec<QuizQuestionResponse>,
+}
+
+impl IntoResponse for CreateQuizResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::CREATED, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct AnswerQuizResponse {
+    pub quiz_id: u64,
+    pub position: u32,
+    pub correct: bool,
+    pub expected: String,
+    pub score: u32,
+    pub answered: u32,
+    pub total: u32,
+}
+
+impl IntoResponse for AnswerQuizResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeckResponse {
+    pub deck_id: u64,
+    pub name: String,
+    pub word_count: u64,
+    pub share_token: Option<String>,
+    pub created_at: String,
+}
+
+impl IntoResponse for DeckResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeckWordResponse {
+    pub user_word_id: u64,
+    pub word_id: u64,
+    pub word: String,
+}
+
+#[derive(Serialize)]
+pub struct GetDeckResponse {
+    pub deck_id: u64,
+    pub name: String,
+    pub word_count: u64,
+    pub share_token: Option<String>,
+    pub created_at: String,
+    pub words: Vec<DeckWordResponse>,
+}
+
+impl IntoResponse for GetDeckResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeleteDeckResponse {
+    pub status: String,
+}
+
+impl IntoResponse for DeleteDeckResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct CloneDeckResponse {
+    pub created: u64,
+    pub already_present: u64,
+}
+
+impl IntoResponse for CloneDeckResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ExportUserWordResponse {
+    pub word: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportUserWordLineResponse {
+    pub line: u64,
+    pub word: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportUserWordResponse {
+    pub created: u64,
+    pub already_present: u64,
+    pub invalid: u64,
+    pub lines: Vec<ImportUserWordLineResponse>,
+}
+
+impl IntoResponse for ImportUserWordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct DeleteUserWordResponse {
     pub status: String,
@@ -181,23 +540,97 @@ impl IntoResponse for DeleteUserWordResponse {
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub error: String,
-    pub message: String,
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// RFC 7807 error body, `instance` is filled in by `problem_details_middleware`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            instance: None,
+            errors: None,
+        }
+    }
+
+    pub fn with_errors(self, errors: FieldErrors) -> Self {
+        Self {
+            errors: Some(errors),
+            ..self
+        }
+    }
+
+    pub fn with_instance(self, instance: String) -> Self {
+        Self {
+            instance: Some(instance),
+            ..self
+        }
+    }
 }
 
-impl IntoResponse for ErrorResponse {
+impl IntoResponse for ProblemDetails {
     fn into_response(self) -> Response {
-        let status_code = match self.error.as_str() {
-            "NotFound" => StatusCode::NOT_FOUND,
-            "Unauthorized" => StatusCode::UNAUTHORIZED,
-            "Forbidden" => StatusCode::FORBIDDEN,
-            "BadRequest" => StatusCode::BAD_REQUEST,
-            "Conflict" => StatusCode::CONFLICT,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = serde_json::to_vec(&self).unwrap_or_default();
+
+        let mut res = (status, [(header::CONTENT_TYPE, PROBLEM_JSON)], body).into_response();
+        // kept so the middleware can re-render the body with the request path
+        res.extensions_mut().insert(self);
+        res
+    }
+}
+
+impl IntoResponse for CosanError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            CosanError::NotFound(_) => StatusCode::NOT_FOUND,
+            CosanError::Conflict(_) => StatusCode::CONFLICT,
+            CosanError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            CosanError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            CosanError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            CosanError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            CosanError::Forbidden(_) => StatusCode::FORBIDDEN,
+            CosanError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            CosanError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            CosanError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            CosanError::TooManyRequests(_, _) => StatusCode::TOO_MANY_REQUESTS,
+            CosanError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        // internal details stay in the log, the client gets the status only
+        let detail = match &self {
+            CosanError::Internal(message) => {
+                error!("Internal error: {}", message);
+                "Internal Server Error".to_string()
+            }
+            _ => self.to_string(),
         };
 
-        (status_code, Json(self)).into_response()
+        let problem = ProblemDetails::new(status, detail);
+        match self {
+            CosanError::InvalidFields(errors) => problem.with_errors(errors).into_response(),
+            CosanError::TooManyRequests(_, retry_after) => {
+                let mut res = problem.into_response();
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+                res
+            }
+            _ => problem.into_response(),
+        }
     }
 }
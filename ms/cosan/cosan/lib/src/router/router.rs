@@ -1,25 +1,32 @@
+use crate::domain::error::CosanError;
 use crate::domain::interface;
 use crate::{
     domain::service::CosanService, router::middleware, router::request, router::response, util,
 };
 use axum::{
-    extract::{FromRequestParts, Path, State},
-    http::{self, request::Parts},
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, Path, Query, State},
+    http::{self, request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
     Extension, Json, Router,
 };
-use std::{net::SocketAddr, sync::Arc};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
 use tracing::info;
 
 #[derive(Clone)]
-pub struct AppState<U, W, UW>
+pub struct AppState<U, W, UW, Q, D, S, I>
 where
     U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
     W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
     UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+    Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+    D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+    S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+    I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
 {
-    service: Arc<CosanService<U, W, UW>>,
-    secret_key: Arc<String>,
+    pub(crate) service: Arc<CosanService<U, W, UW, Q, D, S, I>>,
+    pub(crate) verifier: Arc<util::auth::TokenVerifier>,
 }
 
 // Add this struct to extract the token
@@ -29,43 +36,131 @@ impl<S> FromRequestParts<S> for Token
 where
     S: Send + Sync,
 {
-    type Rejection = (http::StatusCode, Json<response::ErrorResponse>);
+    type Rejection = CosanError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         let Extension(token) =
             Extension::<Arc<util::auth::Token>>::from_request_parts(parts, _state)
                 .await
-                .map_err(|_| {
-                    (
-                        http::StatusCode::UNAUTHORIZED,
-                        Json(response::ErrorResponse {
-                            error: "Unauthorized".to_string(),
-                            message: "Missing or invalid token".to_string(),
-                        }),
-                    )
-                })?;
+                .map_err(|_| CosanError::Unauthorized("Missing or invalid token".to_string()))?;
 
         Ok(Token(token))
     }
 }
 
+/// Peer address of the connection, `None` when the router is driven without a socket.
+pub struct ClientIp(pub Option<String>);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let client_ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+
+        Ok(ClientIp(client_ip))
+    }
+}
+
+/// `If-Match` of a write, which is required so that no client overwrites a change it has not seen.
+pub struct IfMatch(pub util::etag::Precondition);
+
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = CosanError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let if_match = parts.headers.get(http::header::IF_MATCH).ok_or_else(|| {
+            CosanError::PreconditionRequired("If-Match header is required".to_string())
+        })?;
+
+        // a value that is not even text can name no version
+        let precondition = match if_match.to_str() {
+            Ok(if_match) => util::etag::Precondition::parse(if_match),
+            Err(_) => util::etag::Precondition::Versions(Vec::new()),
+        };
+
+        Ok(IfMatch(precondition))
+    }
+}
+
+/// `If-None-Match` of a read, `None` when the client has no cached copy.
+pub struct IfNoneMatch(pub Option<String>);
+
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let if_none_match = parts
+            .headers
+            .get(http::header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        Ok(IfNoneMatch(if_none_match))
+    }
+}
+
+/// One token bucket set per route group, shared by the routers nested under it.
+struct RateLimiters {
+    signup: Arc<util::rate_limit::RateLimiter>,
+    auth: Arc<util::rate_limit::RateLimiter>,
+    user: Arc<util::rate_limit::RateLimiter>,
+    word: Arc<util::rate_limit::RateLimiter>,
+    user_word: Arc<util::rate_limit::RateLimiter>,
+    review: Arc<util::rate_limit::RateLimiter>,
+    quiz: Arc<util::rate_limit::RateLimiter>,
+    deck: Arc<util::rate_limit::RateLimiter>,
+}
+
+impl RateLimiters {
+    fn new(rate_limits: &util::rate_limit::RateLimits) -> Self {
+        let limiter = |quota| Arc::new(util::rate_limit::RateLimiter::new(quota));
+        Self {
+            signup: limiter(rate_limits.signup),
+            auth: limiter(rate_limits.auth),
+            user: limiter(rate_limits.user),
+            word: limiter(rate_limits.word),
+            user_word: limiter(rate_limits.user_word),
+            review: limiter(rate_limits.review),
+            quiz: limiter(rate_limits.quiz),
+            deck: limiter(rate_limits.deck),
+        }
+    }
+}
+
 pub struct AppRouter {
     pub router: Router,
 }
 
 impl AppRouter {
-    pub fn new<U, W, UW>(service: Arc<CosanService<U, W, UW>>, secret_key: Arc<String>) -> Self
+    pub fn new<U, W, UW, Q, D, S, I>(
+        service: Arc<CosanService<U, W, UW, Q, D, S, I>>,
+        verifier: Arc<util::auth::TokenVerifier>,
+        rate_limits: util::rate_limit::RateLimits,
+    ) -> Self
     where
         U: interface::UserRepositoryTrait,
         W: interface::WordRepositoryTrait,
         UW: interface::UserWordRepositoryTrait,
+        Q: interface::QuizRepositoryTrait,
+        D: interface::DeckRepositoryTrait,
+        S: interface::SessionRepositoryTrait,
+        I: interface::IdempotencyRepositoryTrait,
     {
-        let app_state = AppState {
-            service,
-            secret_key,
-        };
+        let app_state = AppState { service, verifier };
 
-        Self::init_router(app_state)
+        Self::init_router(app_state, RateLimiters::new(&rate_limits))
     }
 
     pub async fn serve(self) -> Result<(), anyhow::Error> {
@@ -74,7 +169,8 @@ impl AppRouter {
 
         axum::serve(
             tokio::net::TcpListener::bind(&addr).await.unwrap(),
-            self.router,
+            self.router
+                .into_make_service_with_connect_info::<SocketAddr>(),
         )
         .await
         .unwrap();
@@ -82,11 +178,18 @@ impl AppRouter {
         Ok(())
     }
 
-    fn init_router<U, W, UW>(state: AppState<U, W, UW>) -> AppRouter
+    fn init_router<U, W, UW, Q, D, S, I>(
+        state: AppState<U, W, UW, Q, D, S, I>,
+        rate_limiters: RateLimiters,
+    ) -> AppRouter
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
         let router = Router::new()
             .nest(
@@ -96,44 +199,284 @@ impl AppRouter {
                     .nest(
                         "/user",
                         Router::new()
-                            .route("/{user_id}", get(Self::get_user))
-                            .route("/", put(Self::update_user))
-                            .route("/{user_id}", delete(Self::delete_user))
+                            .merge(
+                                Router::new()
+                                    .route("/{user_id}", get(Self::get_user))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["users:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/", put(Self::update_user))
+                                    .route(
+                                        "/{user_id}",
+                                        delete(Self::delete_user).patch(Self::patch_user),
+                                    )
+                                    .route("/{user_id}/password", post(Self::change_password))
+                                    .route("/verify/resend", post(Self::resend_verification_email))
+                                    .route("/totp", post(Self::enroll_totp))
+                                    .route("/totp/confirm", post(Self::confirm_totp))
+                                    .route("/totp/disable", post(Self::disable_totp))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["users:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.user.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
                             .route_layer(axum::middleware::from_fn_with_state(
-                                state.secret_key.clone(),
+                                state.clone(),
                                 middleware::verify_token_middleware,
                             ))
-                            .route("/", post(Self::create_user))
-                            .route(
-                                "/login/{login_id}/password/{password}",
-                                get(Self::get_user_by_login_id_and_password),
+                            .merge(
+                                Router::new()
+                                    .route(
+                                        "/",
+                                        post(Self::create_user).route_layer(
+                                            axum::middleware::from_fn_with_state(
+                                                state.clone(),
+                                                middleware::idempotency_middleware,
+                                            ),
+                                        ),
+                                    )
+                                    .route("/verify", post(Self::verify_email))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        rate_limiters.signup.clone(),
+                                        middleware::rate_limit_middleware,
+                                    )),
                             ),
                     )
+                    .nest(
+                        "/auth",
+                        Router::new()
+                            .merge(
+                                Router::new()
+                                    .route("/logout", post(Self::logout))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        state.clone(),
+                                        middleware::verify_token_middleware,
+                                    )),
+                            )
+                            .route("/login", post(Self::login))
+                            .route("/login/totp", post(Self::login_totp))
+                            .route("/refresh", post(Self::refresh))
+                            .route("/password/forgot", post(Self::forgot_password))
+                            .route("/password/reset", post(Self::reset_password))
+                            // outside verify_token_middleware, so logout is keyed by address too
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.auth.clone(),
+                                middleware::rate_limit_middleware,
+                            )),
+                    )
                     .nest(
                         "/word",
                         Router::new()
-                            .route("/", post(Self::create_word))
-                            .route("/{word_id}", get(Self::get_word))
-                            .route("/", put(Self::update_word))
-                            .route("/{word_id}", delete(Self::delete_word))
+                            .merge(
+                                Router::new()
+                                    .route("/search", get(Self::search_words))
+                                    .route("/ranking", get(Self::get_word_ranking))
+                                    .route("/{word_id}", get(Self::get_word))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["words:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route(
+                                        "/",
+                                        post(Self::create_word).route_layer(
+                                            axum::middleware::from_fn_with_state(
+                                                state.clone(),
+                                                middleware::idempotency_middleware,
+                                            ),
+                                        ),
+                                    )
+                                    .route("/", put(Self::update_word))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["words:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            // words are shared by every user, removing one is an admin task
+                            .merge(
+                                Router::new()
+                                    .route("/{word_id}", delete(Self::delete_word))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["words:write"])
+                                            .with_roles(&[util::auth::ADMIN_ROLE]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.word.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                state.clone(),
+                                middleware::verify_token_middleware,
+                            )),
+                    )
+                    .nest(
+                        "/user/word",
+                        Router::new()
+                            .merge(
+                                Router::new()
+                                    .route("/export", get(Self::export_user_words))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["user_words:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/import", post(Self::import_user_words))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["user_words:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.user_word.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                state.clone(),
+                                middleware::verify_token_middleware,
+                            )),
+                    )
+                    .nest(
+                        "/review",
+                        Router::new()
+                            .merge(
+                                Router::new()
+                                    .route("/due", get(Self::get_due_reviews))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["reviews:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/{user_word_id}", post(Self::review_user_word))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["reviews:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.review.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                state.clone(),
+                                middleware::verify_token_middleware,
+                            )),
+                    )
+                    .nest(
+                        "/quiz",
+                        Router::new()
+                            .route("/", post(Self::create_quiz))
+                            .route("/{quiz_id}/answer", post(Self::answer_quiz))
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                middleware::AccessPolicy::scopes(&["quizzes:write"]),
+                                middleware::require_access_middleware,
+                            ))
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.quiz.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                state.clone(),
+                                middleware::verify_token_middleware,
+                            )),
+                    )
+                    .nest(
+                        "/deck",
+                        Router::new()
+                            .merge(
+                                Router::new()
+                                    .route("/", get(Self::get_decks))
+                                    .route("/{deck_id}", get(Self::get_deck))
+                                    .route("/shared/{share_token}", get(Self::get_shared_deck))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["decks:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/", post(Self::create_deck))
+                                    .route("/{deck_id}", put(Self::update_deck))
+                                    .route("/{deck_id}", delete(Self::delete_deck))
+                                    .route("/{deck_id}/word", post(Self::add_deck_words))
+                                    .route(
+                                        "/{deck_id}/word/{user_word_id}",
+                                        delete(Self::remove_deck_word),
+                                    )
+                                    .route("/{deck_id}/share", post(Self::share_deck))
+                                    .route("/{deck_id}/share", delete(Self::unshare_deck))
+                                    .route(
+                                        "/shared/{share_token}/clone",
+                                        post(Self::clone_shared_deck),
+                                    )
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["decks:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.deck.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
                             .route_layer(axum::middleware::from_fn_with_state(
-                                state.secret_key.clone(),
+                                state.clone(),
                                 middleware::verify_token_middleware,
                             )),
                     )
                     .nest(
                         "/user/word/relation",
                         Router::new()
-                            .route(
-                                "/user/{user_id}/word/{user_word_id}",
-                                get(Self::get_user_word_by_user_id_and_word_id),
+                            .merge(
+                                Router::new()
+                                    .route(
+                                        "/user/{user_id}/word/{user_word_id}",
+                                        get(Self::get_user_word_by_user_id_and_word_id),
+                                    )
+                                    .route("/user/{user_id}", get(Self::get_user_word_by_user_id))
+                                    .route("/word/{word_id}", get(Self::get_user_word_by_word_id))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["user_words:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
                             )
-                            .route("/user/{user_id}", get(Self::get_user_word_by_user_id))
-                            .route("/word/{word_id}", get(Self::get_user_word_by_word_id))
-                            .route("/", post(Self::create_user_word))
-                            .route("/{user_word_id}", delete(Self::delete_user_word))
+                            .merge(
+                                Router::new()
+                                    .route(
+                                        "/",
+                                        post(Self::create_user_word).route_layer(
+                                            axum::middleware::from_fn_with_state(
+                                                state.clone(),
+                                                middleware::idempotency_middleware,
+                                            ),
+                                        ),
+                                    )
+                                    .route("/{user_word_id}", delete(Self::delete_user_word))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["user_words:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .route_layer(axum::middleware::from_fn_with_state(
+                                rate_limiters.user_word.clone(),
+                                middleware::rate_limit_middleware,
+                            ))
                             .route_layer(axum::middleware::from_fn_with_state(
-                                state.secret_key.clone(),
+                                state.clone(),
                                 middleware::verify_token_middleware,
                             )),
                     )
@@ -141,41 +484,59 @@ impl AppRouter {
                         middleware::request_log_middleware,
                     )),
             )
+            .layer(axum::middleware::from_fn(
+                middleware::problem_details_middleware,
+            ))
             .with_state(state);
 
         AppRouter { router }
     }
 
     // Helper function to handle Result with custom error responses.
-    async fn handle_result<T, E: ToString>(
-        result: Result<T, E>,
+    async fn handle_result<T>(
+        result: Result<T, CosanError>,
         success_status: http::StatusCode,
         not_found_message: &str,
-    ) -> Result<(http::StatusCode, Json<T>), (http::StatusCode, Json<response::ErrorResponse>)>
-    {
+    ) -> Result<(http::StatusCode, Json<T>), CosanError> {
         match result {
             Ok(value) => Ok((success_status, Json(value))),
-            Err(err) => {
-                let error_message = err.to_string();
-                if error_message.contains("no rows") {
-                    Err((
-                        http::StatusCode::NOT_FOUND,
-                        Json(response::ErrorResponse {
-                            error: error_message,
-                            message: not_found_message.to_string(),
-                        }),
-                    ))
-                } else {
-                    Err((
-                        http::StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(response::ErrorResponse {
-                            error: error_message,
-                            message: "Internal Server Error".to_string(),
-                        }),
-                    ))
-                }
+            // the route knows which resource was missing better than the driver
+            Err(CosanError::NotFound(_)) => {
+                Err(CosanError::NotFound(not_found_message.to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Like handle_result, but tags the body with its ETag and answers 304 when the client's copy is current.
+    async fn versioned_result<T: serde::Serialize>(
+        result: Result<response::Versioned<T>, CosanError>,
+        success_status: http::StatusCode,
+        not_found_message: &str,
+        if_none_match: Option<&str>,
+    ) -> Result<Response, CosanError> {
+        let versioned = match result {
+            Ok(versioned) => versioned,
+            Err(CosanError::NotFound(_)) => {
+                return Err(CosanError::NotFound(not_found_message.to_string()))
             }
+            Err(err) => return Err(err),
+        };
+
+        let etag = [(http::header::ETAG, util::etag::etag(versioned.version))];
+        if if_none_match.is_some_and(|if_none_match| {
+            util::etag::is_not_modified(if_none_match, versioned.version)
+        }) {
+            return Ok((http::StatusCode::NOT_MODIFIED, etag).into_response());
         }
+
+        Ok((success_status, etag, Json(versioned.body)).into_response())
+    }
+
+    fn token_user_id(token: &util::auth::Token) -> Result<i64, CosanError> {
+        token
+            .uid
+            .ok_or_else(|| CosanError::Unauthorized("Token does not contain a user ID".to_string()))
     }
 
     async fn health_check() -> Result<(http::StatusCode, Json<response::HealthCheckResponse>), ()> {
@@ -187,64 +548,53 @@ impl AppRouter {
         ))
     }
 
-    async fn get_user<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn get_user<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
+        IfNoneMatch(if_none_match): IfNoneMatch,
         Path(user_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<response::GetUserResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    ) -> Result<Response, CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
         info!("Get user");
         info!(token = ?token);
 
-        let user_id = i64::try_from(user_id).map_err(|_| {
-            (
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Invalid user ID".to_string(),
-                    message: "User ID must be a valid integer".to_string(),
-                }),
-            )
-        })?;
+        let user_id = i64::try_from(user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(user_id)?;
 
-        Self::handle_result(
+        Self::versioned_result(
             state.service.get_user(user_id).await,
             http::StatusCode::OK,
             "User not found",
+            if_none_match.as_deref(),
         )
         .await
     }
 
-    async fn create_user<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn create_user<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Json(body): Json<request::CreateUserRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<response::CreateUserResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    ) -> Result<(http::StatusCode, Json<response::CreateUserResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
         info!("Create user");
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
 
         Self::handle_result(
             state.service.create_user(body).await,
@@ -254,106 +604,97 @@ impl AppRouter {
         .await
     }
 
-    async fn update_user<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn update_user<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
+        IfMatch(precondition): IfMatch,
         Json(body): Json<request::UpdateUserRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<response::UpdateUserResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    ) -> Result<Response, CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
         info!("Update user");
         info!(token = ?token);
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
+        token.authorize_owner(body.user_id)?;
 
-        Self::handle_result(
-            state.service.update_user(body).await,
+        Self::versioned_result(
+            state.service.update_user(body, &precondition).await,
             http::StatusCode::OK,
             "User not found",
+            None,
         )
         .await
     }
 
-    async fn delete_user<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn patch_user<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
+        IfMatch(precondition): IfMatch,
         Path(user_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<response::DeleteUserResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+        Json(body): Json<request::PatchUserRequest>,
+    ) -> Result<Response, CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Delete user");
+        info!("Patch user");
         info!(token = ?token);
 
-        let user_id = i64::try_from(user_id).map_err(|_| {
-            (
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Invalid user ID".to_string(),
-                    message: "User ID must be a valid integer".to_string(),
-                }),
-            )
-        })?;
+        body.validate().await?;
+        let user_id = i64::try_from(user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(user_id)?;
 
-        Self::handle_result(
-            state.service.delete_user(user_id).await,
+        Self::versioned_result(
+            state.service.patch_user(user_id, body, &precondition).await,
             http::StatusCode::OK,
             "User not found",
+            None,
         )
         .await
     }
 
-    async fn get_user_by_login_id_and_password<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
-        Path(request): Path<request::GetUserRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<response::GetUserResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    async fn change_password<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        ClientIp(client_ip): ClientIp,
+        Path(user_id): Path<u64>,
+        Json(body): Json<request::ChangePasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::ChangePasswordResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Get user by login_id and password");
+        info!("Change password");
+        info!(token = ?token);
 
-        let request = request::GetUserRequest::new(request.login_id, request.password)
-            .validate()
-            .await
-            .map_err(|_| {
-                (
-                    http::StatusCode::BAD_REQUEST,
-                    Json(response::ErrorResponse {
-                        error: "Bad Request".to_string(),
-                        message: "Invalid login ID or password".to_string(),
-                    }),
-                )
-            })?;
+        body.validate().await?;
+        let user_id = i64::try_from(user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(user_id)?;
 
         Self::handle_result(
             state
                 .service
-                .get_user_by_login_id_and_password(request.login_id, request.password)
+                .change_password(user_id, body, client_ip.as_deref())
                 .await,
             http::StatusCode::OK,
             "User not found",
@@ -361,303 +702,1252 @@ impl AppRouter {
         .await
     }
 
-    async fn get_word<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn delete_user<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
-        Path(word_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<response::GetWordResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+        IfMatch(precondition): IfMatch,
+        Path(user_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::DeleteUserResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Get word");
+        info!("Delete user");
         info!(token = ?token);
 
-        let word_id = i64::try_from(word_id).map_err(|_| {
-            (
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Invalid user ID".to_string(),
-                    message: "Word ID must be a valid integer".to_string(),
-                }),
-            )
-        })?;
+        let user_id = i64::try_from(user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(user_id)?;
 
         Self::handle_result(
-            state
-                .service
-                .get_word(i64::try_from(word_id).unwrap())
-                .await,
+            state.service.delete_user(user_id, &precondition).await,
             http::StatusCode::OK,
-            "Word not found",
+            "User not found",
         )
         .await
     }
 
-    async fn create_word<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
-        Json(body): Json<request::CreateWordRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<response::CreateWordResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    async fn verify_email<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Json(body): Json<request::VerifyEmailRequest>,
+    ) -> Result<(http::StatusCode, Json<response::VerifyEmailResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Create word");
+        info!("Verify email");
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
 
         Self::handle_result(
-            state.service.create_word(body).await,
-            http::StatusCode::CREATED,
-            "Word already exists",
+            state.service.verify_email(body).await,
+            http::StatusCode::OK,
+            "User not found",
         )
         .await
     }
 
-    async fn update_word<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn resend_verification_email<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
-        Json(body): Json<request::UpdateWordRequest>,
     ) -> Result<
-        (http::StatusCode, Json<response::UpdateWordResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
+        (
+            http::StatusCode,
+            Json<response::ResendVerificationEmailResponse>,
+        ),
+        CosanError,
     >
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Update supporter");
+        info!("Resend verification email");
         info!(token = ?token);
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        let user_id = Self::token_user_id(&token)?;
 
         Self::handle_result(
-            state.service.update_word(body).await,
-            http::StatusCode::OK,
-            "Word not found",
+            state.service.resend_verification_email(user_id).await,
+            http::StatusCode::ACCEPTED,
+            "User not found",
         )
         .await
     }
 
-    async fn delete_word<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
-        Token(token): Token,
-        Path(word_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<response::DeleteWordResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    async fn forgot_password<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Json(body): Json<request::ForgotPasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::ForgotPasswordResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Delete word");
-        info!(token = ?token);
+        info!("Forgot password");
 
-        let word_id = i64::try_from(word_id).map_err(|_| {
-            (
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Invalid user ID".to_string(),
-                    message: "Word ID must be a valid integer".to_string(),
-                }),
-            )
-        })?;
+        body.validate().await?;
+
+        Self::handle_result(
+            state.service.forgot_password(body).await,
+            http::StatusCode::ACCEPTED,
+            "User not found",
+        )
+        .await
+    }
+
+    async fn reset_password<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Json(body): Json<request::ResetPasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::ResetPasswordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Reset password");
+
+        body.validate().await?;
 
         Self::handle_result(
-            state.service.delete_word(word_id).await,
+            state.service.reset_password(body).await,
             http::StatusCode::OK,
-            "Word not found",
+            "User not found",
         )
         .await
     }
 
-    async fn get_user_word_by_user_id_and_word_id<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn enroll_totp<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
-        Path(request): Path<request::GetUserWordRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<response::GetUserWordResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    ) -> Result<(http::StatusCode, Json<response::EnrollTotpResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Get user word");
+        info!("Enroll TOTP");
         info!(token = ?token);
 
-        let valid = request.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        let user_id = Self::token_user_id(&token)?;
 
         Self::handle_result(
-            state
-                .service
-                .get_user_word_by_user_id_and_word_id(request)
-                .await,
+            state.service.enroll_totp(user_id).await,
             http::StatusCode::OK,
-            "User word not found",
+            "User not found",
         )
         .await
     }
 
-    async fn get_user_word_by_user_id<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn confirm_totp<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
-        Path(user_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<Vec<response::GetUserWordResponse>>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+        Json(body): Json<request::ConfirmTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<response::ConfirmTotpResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Get user word");
+        info!("Confirm TOTP");
         info!(token = ?token);
 
+        body.validate().await?;
+        let user_id = Self::token_user_id(&token)?;
+
         Self::handle_result(
-            state
-                .service
-                .get_user_word_by_user_id(request::GetUserWordRequest {
-                    user_id,
-                    word_id: 0,
-                })
-                .await,
+            state.service.confirm_totp(user_id, body).await,
             http::StatusCode::OK,
-            "User word not found",
+            "User not found",
         )
         .await
     }
 
-    async fn get_user_word_by_word_id<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
+    async fn disable_totp<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
         Token(token): Token,
-        Path(word_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<Vec<response::GetUserWordResponse>>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+        Json(body): Json<request::DisableTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<response::DisableTotpResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Get user word");
+        info!("Disable TOTP");
         info!(token = ?token);
 
+        body.validate().await?;
+        let user_id = Self::token_user_id(&token)?;
+
         Self::handle_result(
-            state
-                .service
-                .get_user_word_by_word_id(request::GetUserWordRequest {
-                    user_id: 0,
-                    word_id: u64::try_from(word_id).unwrap(),
-                })
-                .await,
+            state.service.disable_totp(user_id, body).await,
             http::StatusCode::OK,
-            "User word not found",
+            "User not found",
         )
         .await
     }
 
-    async fn create_user_word<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
-        Token(token): Token,
-        Json(body): Json<request::CreateUserWordRequest>,
-    ) -> Result<
-        (
-            http::StatusCode,
-            Json<response::CreateUserWordRelationResponse>,
-        ),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    async fn login<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        ClientIp(client_ip): ClientIp,
+        Json(body): Json<request::LoginRequest>,
+    ) -> Result<(http::StatusCode, Json<response::LoginStepResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Create user word");
-        info!(token = ?token);
+        info!("Login");
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(response::ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
 
         Self::handle_result(
-            state.service.create_user_word(body).await,
-            http::StatusCode::CREATED,
-            "User word already exists",
+            state
+                .service
+                .login(body, client_ip.as_deref(), &state.verifier)
+                .await,
+            http::StatusCode::OK,
+            "User not found",
         )
         .await
     }
 
-    async fn delete_user_word<U, W, UW>(
-        State(state): State<AppState<U, W, UW>>,
-        Token(token): Token,
-        Path(user_word_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<response::DeleteUserWordResponse>),
-        (http::StatusCode, Json<response::ErrorResponse>),
-    >
+    async fn login_totp<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        ClientIp(client_ip): ClientIp,
+        Json(body): Json<request::LoginTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<response::LoginResponse>), CosanError>
     where
         U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
         W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
         UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
     {
-        info!("Delete protagonist supporter");
-        info!(token = ?token);
+        info!("Login second factor");
+
+        body.validate().await?;
 
         Self::handle_result(
             state
                 .service
-                .delete_user_word(i64::try_from(user_word_id).unwrap())
+                .login_totp(body, client_ip.as_deref(), &state.verifier)
                 .await,
             http::StatusCode::OK,
+            "User not found",
+        )
+        .await
+    }
+
+    async fn refresh<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Json(body): Json<request::RefreshRequest>,
+    ) -> Result<(http::StatusCode, Json<response::LoginResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Refresh token");
+
+        body.validate().await?;
+
+        Self::handle_result(
+            state.service.refresh(body, &state.verifier).await,
+            http::StatusCode::OK,
+            "Refresh token not found",
+        )
+        .await
+    }
+
+    async fn logout<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Json(body): Json<request::LogoutRequest>,
+    ) -> Result<(http::StatusCode, Json<response::LogoutResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Logout");
+        info!(token = ?token);
+
+        body.validate().await?;
+
+        Self::handle_result(
+            state.service.logout(&token, body).await,
+            http::StatusCode::OK,
+            "Refresh token not found",
+        )
+        .await
+    }
+
+    async fn get_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        IfNoneMatch(if_none_match): IfNoneMatch,
+        Path(word_id): Path<u64>,
+    ) -> Result<Response, CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get word");
+        info!(token = ?token);
+
+        let word_id = i64::try_from(word_id)
+            .map_err(|_| CosanError::BadRequest("Word ID must be a valid integer".to_string()))?;
+
+        Self::versioned_result(
+            state.service.get_word(word_id).await,
+            http::StatusCode::OK,
+            "Word not found",
+            if_none_match.as_deref(),
+        )
+        .await
+    }
+
+    async fn create_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Json(body): Json<request::CreateWordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::CreateWordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Create word");
+
+        body.validate().await?;
+
+        Self::handle_result(
+            state.service.create_word(body).await,
+            http::StatusCode::CREATED,
+            "Word already exists",
+        )
+        .await
+    }
+
+    async fn update_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        IfMatch(precondition): IfMatch,
+        Json(body): Json<request::UpdateWordRequest>,
+    ) -> Result<Response, CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Update supporter");
+        info!(token = ?token);
+
+        body.validate().await?;
+
+        Self::versioned_result(
+            state.service.update_word(body, &precondition).await,
+            http::StatusCode::OK,
+            "Word not found",
+            None,
+        )
+        .await
+    }
+
+    async fn delete_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        IfMatch(precondition): IfMatch,
+        Path(word_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::DeleteWordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Delete word");
+        info!(token = ?token);
+
+        let word_id = i64::try_from(word_id)
+            .map_err(|_| CosanError::BadRequest("Word ID must be a valid integer".to_string()))?;
+
+        Self::handle_result(
+            state.service.delete_word(word_id, &precondition).await,
+            http::StatusCode::OK,
+            "Word not found",
+        )
+        .await
+    }
+
+    async fn search_words<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Query(request): Query<request::SearchWordRequest>,
+    ) -> Result<(http::StatusCode, Json<Vec<response::SearchWordResponse>>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Search words");
+        info!(token = ?token);
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.search_words(request).await,
+            http::StatusCode::OK,
+            "Word not found",
+        )
+        .await
+    }
+
+    async fn get_word_ranking<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Query(request): Query<request::GetWordRankingRequest>,
+    ) -> Result<
+        (
+            http::StatusCode,
+            Json<Vec<response::GetWordRankingResponse>>,
+        ),
+        CosanError,
+    >
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get word ranking");
+        info!(token = ?token);
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.get_word_ranking(request).await,
+            http::StatusCode::OK,
+            "Word ranking not found",
+        )
+        .await
+    }
+
+    async fn get_user_word_by_user_id_and_word_id<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(request): Path<request::GetUserWordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::GetUserWordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get user word");
+        info!(token = ?token);
+
+        request.validate().await?;
+        let owner_id = i64::try_from(request.user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(owner_id)?;
+
+        Self::handle_result(
+            state
+                .service
+                .get_user_word_by_user_id_and_word_id(request)
+                .await,
+            http::StatusCode::OK,
+            "User word not found",
+        )
+        .await
+    }
+
+    async fn get_user_word_by_user_id<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(user_id): Path<u64>,
+        Query(page): Query<request::ListUserWordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::GetUserWordListResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get user word");
+        info!(token = ?token);
+
+        page.validate().await?;
+        let owner_id = i64::try_from(user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(owner_id)?;
+
+        Self::handle_result(
+            state
+                .service
+                .get_user_word_by_user_id(
+                    request::GetUserWordRequest {
+                        user_id,
+                        word_id: 0,
+                    },
+                    page,
+                )
+                .await,
+            http::StatusCode::OK,
+            "User word not found",
+        )
+        .await
+    }
+
+    async fn get_user_word_by_word_id<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(word_id): Path<u64>,
+        Query(page): Query<request::ListUserWordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::GetUserWordListResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get user word");
+        info!(token = ?token);
+
+        // the rows carry the name and email of every user registered with the word
+        token.authorize_admin()?;
+        page.validate().await?;
+
+        Self::handle_result(
+            state
+                .service
+                .get_user_word_by_word_id(
+                    request::GetUserWordRequest {
+                        user_id: 0,
+                        word_id,
+                    },
+                    page,
+                )
+                .await,
+            http::StatusCode::OK,
+            "User word not found",
+        )
+        .await
+    }
+
+    async fn create_user_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Json(body): Json<request::CreateUserWordRequest>,
+    ) -> Result<
+        (
+            http::StatusCode,
+            Json<response::CreateUserWordRelationResponse>,
+        ),
+        CosanError,
+    >
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Create user word");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let owner_id = i64::try_from(body.user_id)
+            .map_err(|_| CosanError::BadRequest("User ID must be a valid integer".to_string()))?;
+        token.authorize_owner(owner_id)?;
+
+        Self::handle_result(
+            state.service.create_user_word(body).await,
+            http::StatusCode::CREATED,
+            "User word already exists",
+        )
+        .await
+    }
+
+    async fn import_user_words<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        headers: HeaderMap,
+        body: String,
+    ) -> Result<(http::StatusCode, Json<response::ImportUserWordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Import user words");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        let content_type = headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.split(';').next())
+            .map(|header| header.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let request = match content_type.as_str() {
+            "text/csv" => request::ImportUserWordRequest::from_csv(&body),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                request::ImportUserWordRequest::from_ndjson(&body)
+            }
+            _ => {
+                return Err(CosanError::UnsupportedMediaType(
+                    "Content-Type must be text/csv or application/x-ndjson".to_string(),
+                ))
+            }
+        };
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.import_user_words(user_id, request).await,
+            http::StatusCode::OK,
+            "User not found",
+        )
+        .await
+    }
+
+    async fn get_due_reviews<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Query(request): Query<request::GetDueReviewRequest>,
+    ) -> Result<(http::StatusCode, Json<Vec<response::GetDueReviewResponse>>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get due reviews");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.get_due_reviews(user_id, request).await,
+            http::StatusCode::OK,
+            "Due reviews not found",
+        )
+        .await
+    }
+
+    async fn review_user_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(user_word_id): Path<u64>,
+        Json(request): Json<request::ReviewUserWordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::ReviewUserWordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Review user word");
+        info!(token = ?token);
+
+        let user_word_id = i64::try_from(user_word_id).map_err(|_| {
+            CosanError::BadRequest("User word ID must be a valid integer".to_string())
+        })?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state
+                .service
+                .review_user_word(user_id, user_word_id, request)
+                .await,
+            http::StatusCode::OK,
+            "User word not found",
+        )
+        .await
+    }
+
+    async fn create_quiz<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Json(request): Json<request::CreateQuizRequest>,
+    ) -> Result<(http::StatusCode, Json<response::CreateQuizResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Create quiz");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.create_quiz(user_id, request).await,
+            http::StatusCode::CREATED,
+            "User words not found",
+        )
+        .await
+    }
+
+    async fn answer_quiz<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(quiz_id): Path<u64>,
+        Json(request): Json<request::AnswerQuizRequest>,
+    ) -> Result<(http::StatusCode, Json<response::AnswerQuizResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Answer quiz");
+        info!(token = ?token);
+
+        let quiz_id = i64::try_from(quiz_id)
+            .map_err(|_| CosanError::BadRequest("Quiz ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.answer_quiz(user_id, quiz_id, request).await,
+            http::StatusCode::OK,
+            "Quiz question not found",
+        )
+        .await
+    }
+
+    async fn create_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Json(request): Json<request::CreateDeckRequest>,
+    ) -> Result<(http::StatusCode, Json<response::DeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Create deck");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.create_deck(user_id, request).await,
+            http::StatusCode::CREATED,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn get_decks<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+    ) -> Result<(http::StatusCode, Json<Vec<response::DeckResponse>>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get decks");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        Self::handle_result(
+            state.service.get_decks(user_id).await,
+            http::StatusCode::OK,
+            "Decks not found",
+        )
+        .await
+    }
+
+    async fn get_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(deck_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::GetDeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get deck");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        Self::handle_result(
+            state.service.get_deck(user_id, deck_id).await,
+            http::StatusCode::OK,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn update_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(deck_id): Path<u64>,
+        Json(request): Json<request::UpdateDeckRequest>,
+    ) -> Result<(http::StatusCode, Json<response::DeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Update deck");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state.service.update_deck(user_id, deck_id, request).await,
+            http::StatusCode::OK,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn delete_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(deck_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::DeleteDeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Delete deck");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        Self::handle_result(
+            state.service.delete_deck(user_id, deck_id).await,
+            http::StatusCode::OK,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn add_deck_words<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(deck_id): Path<u64>,
+        Json(request): Json<request::AddDeckWordRequest>,
+    ) -> Result<(http::StatusCode, Json<response::GetDeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Add deck words");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        Self::handle_result(
+            state
+                .service
+                .add_deck_words(user_id, deck_id, request)
+                .await,
+            http::StatusCode::OK,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn remove_deck_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path((deck_id, user_word_id)): Path<(u64, u64)>,
+    ) -> Result<(http::StatusCode, Json<response::DeleteDeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Remove deck word");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_word_id = i64::try_from(user_word_id).map_err(|_| {
+            CosanError::BadRequest("User word ID must be a valid integer".to_string())
+        })?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        Self::handle_result(
+            state
+                .service
+                .remove_deck_word(user_id, deck_id, user_word_id)
+                .await,
+            http::StatusCode::OK,
+            "Deck word not found",
+        )
+        .await
+    }
+
+    async fn share_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(deck_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::DeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Share deck");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        Self::handle_result(
+            state.service.share_deck(user_id, deck_id).await,
+            http::StatusCode::OK,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn unshare_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(deck_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::DeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Unshare deck");
+        info!(token = ?token);
+
+        let deck_id = i64::try_from(deck_id)
+            .map_err(|_| CosanError::BadRequest("Deck ID must be a valid integer".to_string()))?;
+
+        let user_id = Self::token_user_id(&token)?;
+
+        Self::handle_result(
+            state.service.unshare_deck(user_id, deck_id).await,
+            http::StatusCode::OK,
+            "Deck not found",
+        )
+        .await
+    }
+
+    async fn get_shared_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(path): Path<request::ShareTokenPath>,
+    ) -> Result<(http::StatusCode, Json<response::GetDeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Get shared deck");
+        info!(token = ?token);
+
+        path.validate().await?;
+
+        Self::handle_result(
+            state.service.get_shared_deck(&path.share_token).await,
+            http::StatusCode::OK,
+            "Shared deck not found",
+        )
+        .await
+    }
+
+    async fn clone_shared_deck<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(path): Path<request::ShareTokenPath>,
+    ) -> Result<(http::StatusCode, Json<response::CloneDeckResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Clone shared deck");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        path.validate().await?;
+
+        Self::handle_result(
+            state
+                .service
+                .clone_shared_deck(user_id, &path.share_token)
+                .await,
+            http::StatusCode::OK,
+            "Shared deck not found",
+        )
+        .await
+    }
+
+    async fn export_user_words<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        headers: HeaderMap,
+        Query(request): Query<request::ExportUserWordRequest>,
+    ) -> Result<Response, CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Export user words");
+        info!(token = ?token);
+
+        let user_id = Self::token_user_id(&token)?;
+
+        request.validate().await?;
+
+        let format = request.format(
+            headers
+                .get(http::header::ACCEPT)
+                .and_then(|header| header.to_str().ok()),
+        );
+
+        Ok((
+            [
+                (
+                    http::header::CONTENT_TYPE,
+                    format.content_type().to_string(),
+                ),
+                (
+                    http::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"words.{}\"", format.extension()),
+                ),
+            ],
+            Body::from_stream(state.service.export_user_words(user_id, format)),
+        )
+            .into_response())
+    }
+
+    async fn delete_user_word<U, W, UW, Q, D, S, I>(
+        State(state): State<AppState<U, W, UW, Q, D, S, I>>,
+        Token(token): Token,
+        Path(user_word_id): Path<u64>,
+    ) -> Result<(http::StatusCode, Json<response::DeleteUserWordResponse>), CosanError>
+    where
+        U: interface::UserRepositoryTrait + Clone + Send + Sync + 'static,
+        W: interface::WordRepositoryTrait + Clone + Send + Sync + 'static,
+        UW: interface::UserWordRepositoryTrait + Clone + Send + Sync + 'static,
+        Q: interface::QuizRepositoryTrait + Clone + Send + Sync + 'static,
+        D: interface::DeckRepositoryTrait + Clone + Send + Sync + 'static,
+        S: interface::SessionRepositoryTrait + Clone + Send + Sync + 'static,
+        I: interface::IdempotencyRepositoryTrait + Clone + Send + Sync + 'static,
+    {
+        info!("Delete protagonist supporter");
+        info!(token = ?token);
+
+        let user_word_id = i64::try_from(user_word_id).map_err(|_| {
+            CosanError::BadRequest("User word ID must be a valid integer".to_string())
+        })?;
+
+        // admins skip the lookup, everyone else must own the user word
+        if !token.is_admin() {
+            let owner = match state.service.get_user_word_owner(user_word_id).await {
+                Ok(owner) => owner,
+                Err(CosanError::NotFound(_)) => {
+                    return Err(CosanError::NotFound("User word not found".to_string()))
+                }
+                Err(err) => return Err(err),
+            };
+            token.authorize_owner(owner)?;
+        }
+
+        Self::handle_result(
+            state.service.delete_user_word(user_word_id).await,
+            http::StatusCode::OK,
             "Protagonist supporter not found",
         )
         .await
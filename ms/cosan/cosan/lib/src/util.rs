@@ -1,3 +1,9 @@
+pub mod auth;
 pub mod crypt;
+pub mod etag;
+pub mod jwks;
+pub mod mailer;
+pub mod password_policy;
+pub mod rate_limit;
 pub mod slog;
-pub mod auth;
+pub mod totp;
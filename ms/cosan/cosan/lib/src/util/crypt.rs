@@ -1,12 +1,215 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha256};
+use std::sync::LazyLock;
+use thiserror::Error;
+use tokio::sync::Semaphore;
 
-pub async fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
-    hash(password, DEFAULT_COST)
+#[derive(Debug, Error)]
+pub enum CryptError {
+    #[error("bcrypt: {0}")]
+    Bcrypt(#[from] bcrypt::BcryptError),
+    #[error("argon2: {0}")]
+    Argon2(#[from] argon2::password_hash::Error),
+    #[error("Unknown password hash format")]
+    UnknownFormat,
+    #[error("Password hashing task failed: {0}")]
+    Task(String),
 }
 
-pub async fn verify_password(
-    password: &str,
-    hashed_password: &str,
-) -> Result<bool, bcrypt::BcryptError> {
-    verify(password, hashed_password)
+/// One password hashing scheme, recognised by the prefix of the hashes it writes.
+pub trait PasswordHasher: Send + Sync {
+    fn identifies(&self, hashed_password: &str) -> bool;
+
+    fn hash(&self, password: &str) -> Result<String, CryptError>;
+
+    fn verify(&self, password: &str, hashed_password: &str) -> Result<bool, CryptError>;
+
+    /// True when the hash was written with weaker settings than this hasher uses today.
+    fn is_outdated(&self, hashed_password: &str) -> bool;
+}
+
+pub struct Argon2idHasher {
+    params: Params,
+}
+
+impl Argon2idHasher {
+    pub fn new(params: Params) -> Self {
+        Self { params }
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PasswordHasher for Argon2idHasher {
+    fn identifies(&self, hashed_password: &str) -> bool {
+        hashed_password.starts_with("$argon2id$")
+    }
+
+    fn hash(&self, password: &str) -> Result<String, CryptError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hashed_password = self.argon2().hash_password(password.as_bytes(), &salt)?;
+        Ok(hashed_password.to_string())
+    }
+
+    fn verify(&self, password: &str, hashed_password: &str) -> Result<bool, CryptError> {
+        let parsed = PasswordHash::new(hashed_password)?;
+        match self.argon2().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn is_outdated(&self, hashed_password: &str) -> bool {
+        let params = PasswordHash::new(hashed_password)
+            .ok()
+            .and_then(|parsed| Params::try_from(&parsed).ok());
+        match params {
+            Some(params) => {
+                params.m_cost() < self.params.m_cost()
+                    || params.t_cost() < self.params.t_cost()
+                    || params.p_cost() < self.params.p_cost()
+            }
+            None => true,
+        }
+    }
+}
+
+/// The scheme every hash was written with before Argon2id.
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> Self {
+        Self { cost }
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn identifies(&self, hashed_password: &str) -> bool {
+        ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| hashed_password.starts_with(prefix))
+    }
+
+    fn hash(&self, password: &str) -> Result<String, CryptError> {
+        Ok(bcrypt::hash(password, self.cost)?)
+    }
+
+    fn verify(&self, password: &str, hashed_password: &str) -> Result<bool, CryptError> {
+        Ok(bcrypt::verify(password, hashed_password)?)
+    }
+
+    fn is_outdated(&self, hashed_password: &str) -> bool {
+        // $2b$12$... carries the cost as its second field
+        hashed_password
+            .split('$')
+            .nth(2)
+            .and_then(|cost| cost.parse::<u32>().ok())
+            .is_none_or(|cost| cost < self.cost)
+    }
+}
+
+// the first hasher writes every new hash, the rest only verify what is already stored
+static HASHERS: LazyLock<Vec<Box<dyn PasswordHasher>>> = LazyLock::new(|| {
+    vec![
+        Box::new(Argon2idHasher::new(Params::DEFAULT)),
+        Box::new(BcryptHasher::new(bcrypt::DEFAULT_COST)),
+    ]
+});
+
+// hashing is slow on purpose, so only this many jobs occupy the blocking pool at once
+static HASHING_PERMITS: LazyLock<Semaphore> = LazyLock::new(|| {
+    Semaphore::new(
+        std::thread::available_parallelism()
+            .map(|parallelism| parallelism.get())
+            .unwrap_or(1),
+    )
+});
+
+async fn run_blocking<T, F>(job: F) -> Result<T, CryptError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, CryptError> + Send + 'static,
+{
+    let _permit = HASHING_PERMITS
+        .acquire()
+        .await
+        .map_err(|err| CryptError::Task(err.to_string()))?;
+
+    tokio::task::spawn_blocking(job)
+        .await
+        .map_err(|err| CryptError::Task(err.to_string()))?
+}
+
+fn hasher_for(hashed_password: &str) -> Option<&'static dyn PasswordHasher> {
+    HASHERS
+        .iter()
+        .find(|hasher| hasher.identifies(hashed_password))
+        .map(|hasher| hasher.as_ref())
+}
+
+pub async fn hash_password(password: &str) -> Result<String, CryptError> {
+    let password = password.to_string();
+    run_blocking(move || HASHERS[0].hash(&password)).await
+}
+
+pub async fn verify_password(password: &str, hashed_password: &str) -> Result<bool, CryptError> {
+    let hasher = hasher_for(hashed_password).ok_or(CryptError::UnknownFormat)?;
+    let password = password.to_string();
+    let hashed_password = hashed_password.to_string();
+    run_blocking(move || hasher.verify(&password, &hashed_password)).await
+}
+
+// hashed once with the current hasher, so checking a login id that does not exist costs the same
+static DUMMY_HASH: LazyLock<Result<String, String>> = LazyLock::new(|| {
+    HASHERS[0]
+        .hash("cosan dummy password")
+        .map_err(|err| err.to_string())
+});
+
+/// Verifies against a throwaway hash and ignores the outcome, for login ids with no stored hash.
+pub async fn verify_dummy_password(password: &str) -> Result<(), CryptError> {
+    let password = password.to_string();
+    run_blocking(move || {
+        let hashed_password = DUMMY_HASH
+            .as_deref()
+            .map_err(|err| CryptError::Task(err.clone()))?;
+        HASHERS[0].verify(&password, hashed_password).map(|_| ())
+    })
+    .await
+}
+
+/// True when a verified hash should be replaced by one from the current hasher.
+pub fn needs_rehash(hashed_password: &str) -> bool {
+    let current = &HASHERS[0];
+    !current.identifies(hashed_password) || current.is_outdated(hashed_password)
+}
+
+/// Hex SHA-256 of a high-entropy token, which unlike a password needs no salt or stretching.
+pub fn digest_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hex SHA-256 of what a request asks for, which tells a retry from another request reusing its key.
+pub fn digest_request(method: &str, path: &str, body: &[u8]) -> String {
+    Sha256::new()
+        .chain_update(method.as_bytes())
+        .chain_update(b" ")
+        .chain_update(path.as_bytes())
+        .chain_update(b"\n")
+        .chain_update(body)
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
 }
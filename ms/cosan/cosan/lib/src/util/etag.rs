@@ -0,0 +1,97 @@
+/// What the `If-Match` header of a write accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Precondition {
+    /// `*`, whatever version is current.
+    Any,
+    /// The versions of the strong tags listed, weak and foreign tags match none.
+    Versions(Vec<i64>),
+}
+
+impl Precondition {
+    pub fn parse(if_match: &str) -> Self {
+        if if_match.trim() == "*" {
+            return Self::Any;
+        }
+
+        // If-Match compares strongly, so a W/ tag can never match
+        Self::Versions(
+            if_match
+                .split(',')
+                .filter_map(|tag| parse_version(tag.trim()))
+                .collect(),
+        )
+    }
+
+    /// The versions a conditional write may replace, None when any will do.
+    pub fn versions(&self) -> Option<&[i64]> {
+        match self {
+            Self::Any => None,
+            Self::Versions(versions) => Some(versions),
+        }
+    }
+}
+
+/// The strong entity tag of a row version.
+pub fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// True when the `If-None-Match` header of a read lists the current version, compared weakly.
+pub fn is_not_modified(if_none_match: &str, version: i64) -> bool {
+    if if_none_match.trim() == "*" {
+        return true;
+    }
+
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .filter_map(|tag| parse_version(tag.strip_prefix("W/").unwrap_or(tag)))
+        .any(|listed| listed == version)
+}
+
+fn parse_version(tag: &str) -> Option<i64> {
+    tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precondition_parse_reads_wildcard_and_strong_tags() {
+        assert_eq!(Precondition::parse(" * "), Precondition::Any);
+        assert_eq!(
+            Precondition::parse(r#""3", "7""#),
+            Precondition::Versions(vec![3, 7])
+        );
+        assert_eq!(Precondition::parse(&etag(12)).versions(), Some(&[12][..]));
+        assert_eq!(Precondition::Any.versions(), None);
+    }
+
+    #[test]
+    fn precondition_parse_matches_no_weak_or_malformed_tags() {
+        assert_eq!(
+            Precondition::parse(r#"W/"3", "x", 4, "5"#),
+            Precondition::Versions(vec![])
+        );
+        assert_eq!(
+            Precondition::parse(r#"W/"3", "5""#),
+            Precondition::Versions(vec![5])
+        );
+    }
+
+    #[test]
+    fn is_not_modified_compares_weakly() {
+        assert!(is_not_modified(r#""4""#, 4));
+        assert!(is_not_modified(r#"W/"4""#, 4));
+        assert!(is_not_modified(r#""1", W/"4""#, 4));
+        assert!(is_not_modified("*", 4));
+    }
+
+    #[test]
+    fn is_not_modified_is_false_for_other_versions() {
+        assert!(!is_not_modified(r#""3""#, 4));
+        assert!(!is_not_modified("4", 4));
+        assert!(!is_not_modified("", 4));
+    }
+}
@@ -0,0 +1,183 @@
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use crate::domain::error::CosanError;
+
+/// Unknown key IDs reload the key set at most this often, so made-up kids cannot flood the source.
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A key set URL that does not answer in time fails the reload instead of holding up requests.
+const FETCH_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    File(PathBuf),
+    Url(String),
+}
+
+#[derive(Clone)]
+pub struct VerificationKey {
+    pub algorithm: Algorithm,
+    pub key: DecodingKey,
+}
+
+#[derive(Default)]
+struct KeyState {
+    keys: HashMap<String, VerificationKey>,
+    loaded_at: Option<Instant>,
+    attempted_at: Option<Instant>,
+}
+
+/// Public keys of a JSON Web Key Set by `kid`.
+///
+/// Every key in the set is accepted, so a new key can be published before tokens are signed
+/// with it and the old one removed once its tokens have expired.
+pub struct JwksCache {
+    source: JwksSource,
+    ttl: Duration,
+    client: reqwest::Client,
+    state: RwLock<KeyState>,
+}
+
+impl JwksCache {
+    pub fn new(source: JwksSource, ttl: Duration) -> Self {
+        Self {
+            source,
+            ttl,
+            client: reqwest::Client::builder()
+                .connect_timeout(FETCH_CONNECT_TIMEOUT)
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .expect("JwksCache: failed to build HTTP client"),
+            state: RwLock::new(KeyState::default()),
+        }
+    }
+
+    pub async fn key(&self, kid: &str) -> Result<VerificationKey, CosanError> {
+        {
+            let state = self.state.read().await;
+            let fresh = state
+                .loaded_at
+                .is_some_and(|loaded_at| loaded_at.elapsed() < self.ttl);
+            if fresh || Self::throttled(&state) {
+                if let Some(key) = state.keys.get(kid) {
+                    return Ok(key.clone());
+                }
+            }
+            if Self::throttled(&state) {
+                return Err(Self::unknown_kid(kid));
+            }
+        }
+
+        {
+            let mut state = self.state.write().await;
+            // another request may have started a reload while this one waited for the lock
+            if Self::throttled(&state) {
+                return state
+                    .keys
+                    .get(kid)
+                    .cloned()
+                    .ok_or_else(|| Self::unknown_kid(kid));
+            }
+            state.attempted_at = Some(Instant::now());
+        }
+
+        // fetched without holding the lock, so requests with known keys keep being served meanwhile
+        match self.load().await {
+            Ok(keys) => {
+                info!(
+                    "JwksCache: loaded {} keys from {:?}",
+                    keys.len(),
+                    self.source
+                );
+                let mut state = self.state.write().await;
+                state.keys = keys;
+                state.loaded_at = Some(Instant::now());
+            }
+            // the previous keys stay usable while the source is unavailable
+            Err(err) => error!("JwksCache: failed to load {:?}: {}", self.source, err),
+        }
+
+        self.state
+            .read()
+            .await
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| Self::unknown_kid(kid))
+    }
+
+    fn throttled(state: &KeyState) -> bool {
+        state
+            .attempted_at
+            .is_some_and(|attempted_at| attempted_at.elapsed() < MIN_RELOAD_INTERVAL)
+    }
+
+    fn unknown_kid(kid: &str) -> CosanError {
+        CosanError::Unauthorized(format!("Unknown token key ID: {}", kid))
+    }
+
+    async fn load(&self) -> Result<HashMap<String, VerificationKey>, anyhow::Error> {
+        let document = match &self.source {
+            JwksSource::File(path) => tokio::fs::read_to_string(path).await?,
+            JwksSource::Url(url) => {
+                self.client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?
+            }
+        };
+        let set: JwkSet = serde_json::from_str(&document)?;
+
+        let mut keys = HashMap::new();
+        for jwk in set.keys.iter() {
+            let (Some(kid), Some(algorithm)) = (jwk.common.key_id.as_ref(), key_algorithm(jwk))
+            else {
+                continue;
+            };
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => {
+                    keys.insert(kid.clone(), VerificationKey { algorithm, key });
+                }
+                Err(err) => error!("JwksCache: skipping key {}: {}", kid, err),
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// The one algorithm a key may verify, from its `alg` or else its key type.
+fn key_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    let algorithm = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+        (Some(algorithm), _) => Algorithm::from_str(&algorithm.to_string()).ok()?,
+        (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+        (None, AlgorithmParameters::EllipticCurve(params)) => match params.curve {
+            EllipticCurve::P256 => Algorithm::ES256,
+            EllipticCurve::P384 => Algorithm::ES384,
+            _ => return None,
+        },
+        (None, AlgorithmParameters::OctetKeyPair(params))
+            if params.curve == EllipticCurve::Ed25519 =>
+        {
+            Algorithm::EdDSA
+        }
+        _ => return None,
+    };
+
+    // a published key set holds public keys only, shared secrets stay with SECRET_KEY
+    match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => None,
+        algorithm => Some(algorithm),
+    }
+}
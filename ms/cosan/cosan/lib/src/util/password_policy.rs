@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+static BREACHED_PASSWORDS: OnceLock<HashSet<String>> = OnceLock::new();
+
+/// Reads a breach list with one password per line, skipping blank lines and `#` comments.
+/// Without a list only the length and login id rules apply.
+pub fn load_breach_list(path: &Path) -> Result<usize, std::io::Error> {
+    let passwords: HashSet<String> = std::fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect();
+    let count = passwords.len();
+    // a second load keeps the first list, the policy is fixed once the server runs
+    let _ = BREACHED_PASSWORDS.set(passwords);
+
+    Ok(count)
+}
+
+pub fn is_breached(password: &str) -> bool {
+    BREACHED_PASSWORDS.get().is_some_and(|passwords| {
+        passwords.contains(password) || passwords.contains(&password.to_lowercase())
+    })
+}
+
+/// Every rule the password breaks, empty when it is acceptable.
+pub fn violations(password: &str, login_id: &str) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    let length = password.chars().count();
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&length) {
+        violations.push(format!(
+            "Password must be between {} and {} characters.",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        ));
+    }
+    if password.chars().any(char::is_control) {
+        violations.push("Password must not contain control characters.".to_string());
+    }
+    if !login_id.is_empty() && password.eq_ignore_ascii_case(login_id) {
+        violations.push("Password must not be the same as the login id.".to_string());
+    }
+    if is_breached(password) {
+        violations.push("Password appears in a list of breached passwords.".to_string());
+    }
+
+    violations
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// `limit` requests per `period`, refilled continuously so a client may burst up to `limit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub limit: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub const fn per_minute(limit: u32) -> Self {
+        Self {
+            limit,
+            period: Duration::from_secs(60),
+        }
+    }
+
+    fn refill_per_second(&self) -> f64 {
+        self.limit as f64 / self.period.as_secs_f64()
+    }
+}
+
+impl FromStr for Quota {
+    type Err = String;
+
+    /// Parses `<limit>/<seconds>`, e.g. `120/60`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (limit, seconds) = s
+            .split_once('/')
+            .ok_or_else(|| format!("Quota must look like <limit>/<seconds>: {}", s))?;
+        let limit = limit
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Quota limit is not a number: {}", s))?;
+        let seconds = seconds
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| format!("Quota period is not a number: {}", s))?;
+        if limit == 0 || seconds == 0 {
+            return Err(format!("Quota limit and period must be positive: {}", s));
+        }
+
+        Ok(Self {
+            limit,
+            period: Duration::from_secs(seconds),
+        })
+    }
+}
+
+/// Quotas of every route group, fixed when the router is built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimits {
+    /// Account creation, keyed by client address.
+    pub signup: Quota,
+    /// Login, refresh, logout and password reset, keyed by client address.
+    pub auth: Quota,
+    pub user: Quota,
+    pub word: Quota,
+    pub user_word: Quota,
+    pub review: Quota,
+    pub quiz: Quota,
+    pub deck: Quota,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            signup: Quota::per_minute(5),
+            auth: Quota::per_minute(30),
+            user: Quota::per_minute(60),
+            word: Quota::per_minute(300),
+            user_word: Quota::per_minute(300),
+            review: Quota::per_minute(300),
+            quiz: Quota::per_minute(120),
+            deck: Quota::per_minute(120),
+        }
+    }
+}
+
+impl RateLimits {
+    /// Replaces the quotas named in a `group=<limit>/<seconds>,...` list, e.g. `auth=10/60`.
+    pub fn with_overrides(mut self, overrides: &str) -> Result<Self, String> {
+        for entry in overrides
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+        {
+            let (group, quota) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Rate limit must look like <group>=<quota>: {}", entry))?;
+            let quota = quota.parse::<Quota>()?;
+            let slot = match group.trim() {
+                "signup" => &mut self.signup,
+                "auth" => &mut self.auth,
+                "user" => &mut self.user,
+                "word" => &mut self.word,
+                "user_word" => &mut self.user_word,
+                "review" => &mut self.review,
+                "quiz" => &mut self.quiz,
+                "deck" => &mut self.deck,
+                group => return Err(format!("Unknown rate limit group: {}", group)),
+            };
+            *slot = quota;
+        }
+
+        Ok(self)
+    }
+}
+
+/// Whose bucket a request draws from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    User(i64),
+    Ip(IpAddr),
+}
+
+/// Outcome of one request, carrying what the `RateLimit-*` headers report.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request would be admitted, `None` when this one was.
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+struct Buckets {
+    buckets: HashMap<RateLimitKey, Bucket>,
+    pruned_at: Instant,
+}
+
+/// Token buckets of one route group, kept in memory for this process only.
+pub struct RateLimiter {
+    quota: Quota,
+    state: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            state: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn check(&self, key: RateLimitKey) -> RateLimitDecision {
+        let now = Instant::now();
+        let capacity = self.quota.limit as f64;
+        let refill_per_second = self.quota.refill_per_second();
+
+        // a poisoned lock only means another request panicked mid-update, the counts are still usable
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // a bucket idle for a whole period is full again, which is the same as having none
+        if now.duration_since(state.pruned_at) >= self.quota.period {
+            let period = self.quota.period;
+            state
+                .buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated_at) < period);
+            state.pruned_at = now;
+        }
+
+        let bucket = state.buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_second).min(capacity);
+        bucket.updated_at = now;
+
+        let retry_after = if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(((1.0 - bucket.tokens) / refill_per_second).ceil() as u64)
+        };
+
+        RateLimitDecision {
+            limit: self.quota.limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset: ((capacity - bucket.tokens) / refill_per_second).ceil() as u64,
+            retry_after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn quota_from_str_parses_limit_and_seconds() {
+        assert_eq!(
+            "120/60".parse::<Quota>(),
+            Ok(Quota {
+                limit: 120,
+                period: Duration::from_secs(60),
+            })
+        );
+        assert_eq!(" 5 / 1 ".parse::<Quota>().map(|quota| quota.limit), Ok(5));
+    }
+
+    #[test]
+    fn quota_from_str_rejects_malformed_and_zero_quotas() {
+        for input in ["", "120", "x/60", "120/x", "-1/60", "0/60", "120/0"] {
+            assert!(input.parse::<Quota>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rate_limits_with_overrides_replaces_named_groups_only() {
+        let limits = RateLimits::default()
+            .with_overrides("auth=10/60, deck=1/1,")
+            .unwrap();
+
+        assert_eq!(limits.auth, "10/60".parse().unwrap());
+        assert_eq!(limits.deck, "1/1".parse().unwrap());
+        assert_eq!(limits.word, RateLimits::default().word);
+        assert!(RateLimits::default().with_overrides("nope=1/1").is_err());
+        assert!(RateLimits::default().with_overrides("auth").is_err());
+    }
+
+    #[test]
+    fn rate_limiter_check_admits_a_burst_up_to_the_limit() {
+        let limiter = RateLimiter::new(Quota::per_minute(3));
+
+        let remaining: Vec<u32> = (0..3)
+            .map(|_| {
+                let decision = limiter.check(RateLimitKey::User(1));
+                assert_eq!(decision.retry_after, None);
+                decision.remaining
+            })
+            .collect();
+        assert_eq!(remaining, vec![2, 1, 0]);
+
+        let rejected = limiter.check(RateLimitKey::User(1));
+        assert_eq!(rejected.limit, 3);
+        assert_eq!(rejected.remaining, 0);
+        // one token comes back every 20 seconds
+        assert_eq!(rejected.retry_after, Some(20));
+        assert_eq!(rejected.reset, 60);
+    }
+
+    #[test]
+    fn rate_limiter_check_keeps_a_bucket_per_key() {
+        let limiter = RateLimiter::new(Quota::per_minute(1));
+
+        assert_eq!(limiter.check(RateLimitKey::User(1)).retry_after, None);
+        assert!(limiter.check(RateLimitKey::User(1)).retry_after.is_some());
+        assert_eq!(limiter.check(RateLimitKey::User(2)).retry_after, None);
+        assert_eq!(
+            limiter
+                .check(RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)))
+                .retry_after,
+            None
+        );
+    }
+
+    #[test]
+    fn rate_limiter_check_refills_over_the_period() {
+        let limiter = RateLimiter::new(Quota {
+            limit: 1,
+            period: Duration::from_millis(50),
+        });
+
+        assert_eq!(limiter.check(RateLimitKey::User(1)).retry_after, None);
+        assert!(limiter.check(RateLimitKey::User(1)).retry_after.is_some());
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(limiter.check(RateLimitKey::User(1)).retry_after, None);
+    }
+}
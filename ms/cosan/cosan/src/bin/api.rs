@@ -1,24 +1,55 @@
 use dotenv::dotenv;
 use lib::{
-    domain::interface::{UserRepositoryTrait, UserWordRepositoryTrait, WordRepositoryTrait},
+    domain::interface::{
+        DeckRepositoryTrait, IdempotencyRepositoryTrait, QuizRepositoryTrait,
+        SessionRepositoryTrait, UserRepositoryTrait, UserWordRepositoryTrait, WordRepositoryTrait,
+    },
     domain::service::CosanService,
     driver::{database::new_database, repository},
     router::router::AppRouter,
+    util::auth::TokenVerifier,
+    util::jwks::{JwksCache, JwksSource},
+    util::mailer::{LogMailer, Mailer, SmtpMailer},
+    util::password_policy,
+    util::rate_limit::RateLimits,
     util::slog::new_logger,
 };
 use std::env;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info, span, Level};
 
 const MODE: &'static str = "MODE";
 const COSAN_PG_URL: &'static str = "COSAN_PG_URL";
 const SECRET_KEY: &'static str = "SECRET_KEY";
+const JWKS_URL: &str = "JWKS_URL";
+const JWKS_FILE: &str = "JWKS_FILE";
+const JWKS_CACHE_SECONDS: &str = "JWKS_CACHE_SECONDS";
+const JWT_ISSUER: &str = "JWT_ISSUER";
+const JWT_AUDIENCE: &str = "JWT_AUDIENCE";
+const ALLOW_SHARED_SECRET_TOKENS: &str = "ALLOW_SHARED_SECRET_TOKENS";
+const PASSWORD_BREACH_LIST: &str = "PASSWORD_BREACH_LIST";
+const RATE_LIMITS: &str = "RATE_LIMITS";
+const SMTP_URL: &str = "SMTP_URL";
+const MAIL_FROM: &str = "MAIL_FROM";
+const MAIL_FILE: &str = "MAIL_FILE";
 
 #[derive(Debug)]
 struct Env {
     mode: String,
     cosan_pg_url: String,
     secret_key: String,
+    jwks_source: Option<JwksSource>,
+    jwks_cache_seconds: u64,
+    jwt_issuer: Option<String>,
+    jwt_audience: Option<String>,
+    allow_shared_secret_tokens: Option<bool>,
+    password_breach_list: Option<String>,
+    rate_limits: RateLimits,
+    smtp_url: Option<String>,
+    mail_from: String,
+    mail_file: Option<String>,
 }
 
 async fn init_env() -> Env {
@@ -37,10 +68,55 @@ async fn init_env() -> Env {
         }
     }
 
+    // a URL wins over a file so a deployment can switch without unsetting the other
+    let jwks_source = match (env::var(JWKS_URL), env::var(JWKS_FILE)) {
+        (Ok(url), _) => Some(JwksSource::Url(url)),
+        (Err(_), Ok(path)) => Some(JwksSource::File(path.into())),
+        _ => None,
+    };
+
+    let jwks_cache_seconds = match env::var(JWKS_CACHE_SECONDS) {
+        Ok(seconds) => seconds.parse::<u64>().unwrap_or_else(|_| {
+            error!("{} is not valid", JWKS_CACHE_SECONDS);
+            panic!();
+        }),
+        Err(_) => 300,
+    };
+
+    // unset keeps HS256 only while no other token issuer is configured
+    let allow_shared_secret_tokens = match env::var(ALLOW_SHARED_SECRET_TOKENS) {
+        Ok(allowed) => Some(allowed.parse::<bool>().unwrap_or_else(|_| {
+            error!("{} is not valid", ALLOW_SHARED_SECRET_TOKENS);
+            panic!();
+        })),
+        Err(_) => None,
+    };
+
+    // e.g. RATE_LIMITS=auth=10/60,word=600/60, groups left out keep their defaults
+    let rate_limits = match env::var(RATE_LIMITS) {
+        Ok(overrides) => RateLimits::default()
+            .with_overrides(&overrides)
+            .unwrap_or_else(|err| {
+                error!("{} is not valid: {}", RATE_LIMITS, err);
+                panic!();
+            }),
+        Err(_) => RateLimits::default(),
+    };
+
     Env {
         mode,
         cosan_pg_url: env::var(COSAN_PG_URL).unwrap(),
         secret_key: env::var(SECRET_KEY).unwrap(),
+        jwks_source,
+        jwks_cache_seconds,
+        jwt_issuer: env::var(JWT_ISSUER).ok(),
+        jwt_audience: env::var(JWT_AUDIENCE).ok(),
+        allow_shared_secret_tokens,
+        password_breach_list: env::var(PASSWORD_BREACH_LIST).ok(),
+        rate_limits,
+        smtp_url: env::var(SMTP_URL).ok(),
+        mail_from: env::var(MAIL_FROM).unwrap_or("Cosan <no-reply@localhost>".to_string()),
+        mail_file: env::var(MAIL_FILE).ok(),
     }
 }
 
@@ -58,13 +134,61 @@ async fn main() {
         info!("Running in release mode");
     }
 
+    if let Some(path) = env.password_breach_list.as_deref() {
+        match password_policy::load_breach_list(Path::new(path)) {
+            Ok(count) => info!("Loaded {} breached passwords", count),
+            Err(err) => {
+                error!("{} could not be read: {}", PASSWORD_BREACH_LIST, err);
+                panic!();
+            }
+        }
+    }
+
     let pg_pool = new_database(&env.cosan_pg_url).await.unwrap();
     let cosan_service = CosanService::new(
         repository::UserRepository::new(pg_pool.clone()),
         repository::WordRepository::new(pg_pool.clone()),
         repository::UserWordRepository::new(pg_pool.clone()),
+        repository::QuizRepository::new(pg_pool.clone()),
+        repository::DeckRepository::new(pg_pool.clone()),
+        repository::SessionRepository::new(pg_pool.clone()),
+        repository::IdempotencyRepository::new(pg_pool.clone()),
     );
 
-    let router = AppRouter::new(Arc::new(cosan_service), Arc::new(env.secret_key));
+    // without an SMTP relay mails go to MAIL_FILE or the log, which is enough for development
+    let mailer: Arc<dyn Mailer> = match env.smtp_url.as_deref() {
+        Some(url) => Arc::new(SmtpMailer::new(url, &env.mail_from).unwrap_or_else(|err| {
+            error!("{} is not valid: {}", SMTP_URL, err);
+            panic!();
+        })),
+        None => Arc::new(LogMailer::new(env.mail_file.as_deref().map(PathBuf::from))),
+    };
+    let cosan_service = cosan_service.with_mailer(mailer);
+
+    // a failure leaves the remaining words queued for the next start
+    match cosan_service.rekey_words().await {
+        Ok(0) => {}
+        Ok(count) => info!("Recomputed normalized_word of {} words", count),
+        Err(err) => error!("Could not recompute normalized_word: {}", err),
+    }
+
+    let mut verifier = TokenVerifier::new(&env.secret_key);
+    if let Some(source) = env.jwks_source {
+        verifier = verifier.with_jwks(JwksCache::new(
+            source,
+            Duration::from_secs(env.jwks_cache_seconds),
+        ));
+    }
+    if let Some(issuer) = env.jwt_issuer.as_deref() {
+        verifier = verifier.with_issuer(issuer);
+    }
+    if let Some(audience) = env.jwt_audience.as_deref() {
+        verifier = verifier.with_audience(audience);
+    }
+    if let Some(allowed) = env.allow_shared_secret_tokens {
+        verifier = verifier.with_shared_secret(allowed);
+    }
+
+    let router = AppRouter::new(Arc::new(cosan_service), Arc::new(verifier), env.rate_limits);
     router.serve().await.unwrap();
 }
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS login_attempts (
+    login_attempt_id BIGSERIAL,
+    account_type VARCHAR(20) NOT NULL,
+    login_id VARCHAR(255) NOT NULL,
+    ip_address VARCHAR(45),
+    succeeded BOOLEAN NOT NULL,
+    attempted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (login_attempt_id),
+    CONSTRAINT login_attempts_account_type_check CHECK (account_type IN ('protagonist', 'supporter'))
+);
+CREATE INDEX IF NOT EXISTS login_attempts_login_id_idx ON login_attempts (account_type, login_id, attempted_at);
+CREATE INDEX IF NOT EXISTS login_attempts_ip_address_idx ON login_attempts (ip_address, attempted_at);
+COMMENT ON TABLE login_attempts IS 'audit trail of protagonist and supporter login attempts, also drives the login lockout';
+COMMENT ON COLUMN login_attempts.login_attempt_id IS 'login attempt id';
+COMMENT ON COLUMN login_attempts.account_type IS 'protagonist or supporter, login ids are unique per account type only';
+COMMENT ON COLUMN login_attempts.login_id IS 'login id as submitted, it may not belong to any account';
+COMMENT ON COLUMN login_attempts.ip_address IS 'client address, null when unknown';
+COMMENT ON COLUMN login_attempts.succeeded IS 'whether the password was accepted';
+COMMENT ON COLUMN login_attempts.attempted_at IS 'attempt datetime';
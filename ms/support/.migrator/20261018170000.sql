@@ -0,0 +1,30 @@
+-- accounts created before verification existed are trusted, new ones start unverified
+ALTER TABLE protagonists ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE protagonists ALTER COLUMN email_verified SET DEFAULT FALSE;
+COMMENT ON COLUMN protagonists.email_verified IS 'set once the protagonist confirmed the current email address';
+
+ALTER TABLE supporters ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE supporters ALTER COLUMN email_verified SET DEFAULT FALSE;
+COMMENT ON COLUMN supporters.email_verified IS 'set once the supporter confirmed the current email address';
+
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+    email_verification_token_id BIGSERIAL,
+    account_type VARCHAR(20) NOT NULL,
+    account_id BIGINT NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (email_verification_token_id),
+    UNIQUE (token_hash),
+    CONSTRAINT email_verification_tokens_account_type_check CHECK (account_type IN ('protagonist', 'supporter'))
+);
+COMMENT ON TABLE email_verification_tokens IS 'single-use tokens mailed to confirm an email address';
+COMMENT ON COLUMN email_verification_tokens.email_verification_token_id IS 'email verification token id';
+COMMENT ON COLUMN email_verification_tokens.account_type IS 'protagonist or supporter';
+COMMENT ON COLUMN email_verification_tokens.account_id IS 'protagonist id or supporter id the token was mailed to';
+COMMENT ON COLUMN email_verification_tokens.email IS 'address the token was mailed to, the token is void once the account changes it';
+COMMENT ON COLUMN email_verification_tokens.token_hash IS 'sha-256 hex digest of the token, the token itself is never stored';
+COMMENT ON COLUMN email_verification_tokens.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN email_verification_tokens.used_at IS 'set when the token confirmed the address';
@@ -0,0 +1,27 @@
+-- access tokens issued before this instant are rejected
+ALTER TABLE protagonists ADD COLUMN IF NOT EXISTS sessions_revoked_at TIMESTAMP;
+COMMENT ON COLUMN protagonists.sessions_revoked_at IS 'set when every session of the protagonist was ended, e.g. by a password reset';
+
+ALTER TABLE supporters ADD COLUMN IF NOT EXISTS sessions_revoked_at TIMESTAMP;
+COMMENT ON COLUMN supporters.sessions_revoked_at IS 'set when every session of the supporter was ended, e.g. by a password reset';
+
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    password_reset_token_id BIGSERIAL,
+    account_type VARCHAR(20) NOT NULL,
+    account_id BIGINT NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (password_reset_token_id),
+    UNIQUE (token_hash),
+    CONSTRAINT password_reset_tokens_account_type_check CHECK (account_type IN ('protagonist', 'supporter'))
+);
+COMMENT ON TABLE password_reset_tokens IS 'single-use tokens mailed to reset a forgotten password';
+COMMENT ON COLUMN password_reset_tokens.password_reset_token_id IS 'password reset token id';
+COMMENT ON COLUMN password_reset_tokens.account_type IS 'protagonist or supporter';
+COMMENT ON COLUMN password_reset_tokens.account_id IS 'protagonist id or supporter id the token was mailed to';
+COMMENT ON COLUMN password_reset_tokens.token_hash IS 'sha-256 hex digest of the token, the token itself is never stored';
+COMMENT ON COLUMN password_reset_tokens.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN password_reset_tokens.used_at IS 'set when the token reset the password, or voided by a later reset';
+COMMENT ON COLUMN password_reset_tokens.created_at IS 'created datetime';
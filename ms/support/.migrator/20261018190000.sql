@@ -0,0 +1,56 @@
+-- optional second factor, the secret stays readable because every code is derived from it
+ALTER TABLE protagonists ADD COLUMN IF NOT EXISTS totp_secret VARCHAR(64);
+ALTER TABLE protagonists ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE protagonists ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
+COMMENT ON COLUMN protagonists.totp_secret IS 'base32 TOTP secret, set on enrollment and pending until confirmed';
+COMMENT ON COLUMN protagonists.totp_enabled IS 'set once the protagonist confirmed the secret with a code, login then asks for one';
+COMMENT ON COLUMN protagonists.totp_last_step IS 'time step of the last accepted code, no code of it or earlier is accepted again';
+
+ALTER TABLE supporters ADD COLUMN IF NOT EXISTS totp_secret VARCHAR(64);
+ALTER TABLE supporters ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE supporters ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
+COMMENT ON COLUMN supporters.totp_secret IS 'base32 TOTP secret, set on enrollment and pending until confirmed';
+COMMENT ON COLUMN supporters.totp_enabled IS 'set once the supporter confirmed the secret with a code, login then asks for one';
+COMMENT ON COLUMN supporters.totp_last_step IS 'time step of the last accepted code, no code of it or earlier is accepted again';
+
+CREATE TABLE IF NOT EXISTS totp_recovery_codes (
+    totp_recovery_code_id BIGSERIAL,
+    account_type VARCHAR(20) NOT NULL,
+    account_id BIGINT NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (totp_recovery_code_id),
+    UNIQUE (account_type, account_id, code_hash),
+    CONSTRAINT totp_recovery_codes_account_type_check CHECK (account_type IN ('protagonist', 'supporter'))
+);
+COMMENT ON TABLE totp_recovery_codes IS 'single-use codes that stand in for a TOTP code when the authenticator is lost';
+COMMENT ON COLUMN totp_recovery_codes.totp_recovery_code_id IS 'totp recovery code id';
+COMMENT ON COLUMN totp_recovery_codes.account_type IS 'protagonist or supporter';
+COMMENT ON COLUMN totp_recovery_codes.account_id IS 'protagonist id or supporter id the code belongs to';
+COMMENT ON COLUMN totp_recovery_codes.code_hash IS 'sha-256 hex digest of the code, the code itself is never stored';
+COMMENT ON COLUMN totp_recovery_codes.used_at IS 'set when the code was used';
+COMMENT ON COLUMN totp_recovery_codes.created_at IS 'created datetime';
+
+CREATE TABLE IF NOT EXISTS login_challenges (
+    login_challenge_id BIGSERIAL,
+    account_type VARCHAR(20) NOT NULL,
+    account_id BIGINT NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (login_challenge_id),
+    UNIQUE (token_hash),
+    CONSTRAINT login_challenges_account_type_check CHECK (account_type IN ('protagonist', 'supporter'))
+);
+COMMENT ON TABLE login_challenges IS 'logins that passed the password check and wait for a second factor';
+COMMENT ON COLUMN login_challenges.login_challenge_id IS 'login challenge id';
+COMMENT ON COLUMN login_challenges.account_type IS 'protagonist or supporter';
+COMMENT ON COLUMN login_challenges.account_id IS 'protagonist id or supporter id that passed the password check';
+COMMENT ON COLUMN login_challenges.token_hash IS 'sha-256 hex digest of the challenge token, the token itself is never stored';
+COMMENT ON COLUMN login_challenges.attempts IS 'rejected codes so far, the challenge is void after a few';
+COMMENT ON COLUMN login_challenges.expires_at IS 'expiration datetime';
+COMMENT ON COLUMN login_challenges.used_at IS 'set when a code completed the login, or when too many were rejected';
+COMMENT ON COLUMN login_challenges.created_at IS 'created datetime';
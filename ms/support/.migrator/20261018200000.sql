@@ -0,0 +1,6 @@
+-- bumped by every write that changes what GET returns, exposed as the ETag of the resource
+ALTER TABLE protagonists ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+COMMENT ON COLUMN protagonists.version IS 'row version for optimistic concurrency control, sent as ETag';
+
+ALTER TABLE supporters ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+COMMENT ON COLUMN supporters.version IS 'row version for optimistic concurrency control, sent as ETag';
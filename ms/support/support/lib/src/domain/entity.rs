@@ -1,7 +1,151 @@
 use crate::util;
+use chrono::{DateTime, Utc};
 
-pub struct Protagonist {
-    pub protagonist_id: i64,
+/// Role claim issued to protagonists on login.
+pub const PROTAGONIST_ROLE: &str = "protagonist";
+/// Scopes issued to protagonists, matching the route policies in the router.
+pub const PROTAGONIST_SCOPES: &[&str] = &[
+    "protagonists:read",
+    "protagonists:write",
+    "protagonist_supporters:read",
+    "protagonist_supporters:write",
+];
+
+/// Role claim issued to supporters on login.
+pub const SUPPORTER_ROLE: &str = "supporter";
+/// Scopes issued to supporters, matching the route policies in the router.
+pub const SUPPORTER_SCOPES: &[&str] = &[
+    "supporters:read",
+    "supporters:write",
+    "protagonist_supporters:write",
+];
+
+/// Account type recorded with protagonist login attempts and verification tokens.
+pub const PROTAGONIST_ACCOUNT: &str = "protagonist";
+/// Account type recorded with supporter login attempts and verification tokens.
+pub const SUPPORTER_ACCOUNT: &str = "supporter";
+
+/// How long a mailed email verification token stays valid.
+pub const EMAIL_VERIFICATION_TTL_SECONDS: i64 = 24 * 60 * 60;
+
+/// How long a mailed password reset token stays valid, far shorter since it grants a password change.
+pub const PASSWORD_RESET_TTL_SECONDS: i64 = 60 * 60;
+
+/// Issuer shown next to the account in authenticator apps.
+pub const TOTP_ISSUER: &str = "Support";
+/// Recovery codes handed out when TOTP is enabled.
+pub const TOTP_RECOVERY_CODE_COUNT: usize = 10;
+/// How long a login may wait for its second factor after the password was accepted.
+pub const LOGIN_CHALLENGE_TTL_SECONDS: i64 = 5 * 60;
+/// Rejected codes after which the challenge is void and the login starts over with the password.
+pub const LOGIN_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+
+/// Second factor state of a protagonist or supporter, the secret is pending until a code confirmed it.
+#[derive(Debug, Clone)]
+pub struct AccountTotp {
+    pub account_id: i64,
+    pub login_id: String,
+    pub secret: Option<String>,
+    pub enabled: bool,
+}
+
+impl AccountTotp {
+    pub fn new(account_id: i64, login_id: String, secret: Option<String>, enabled: bool) -> Self {
+        Self {
+            account_id,
+            login_id,
+            secret,
+            enabled,
+        }
+    }
+}
+
+/// Login that passed the password check and waits for a second factor.
+#[derive(Debug, Clone)]
+pub struct LoginChallenge {
+    pub login_challenge_id: i64,
+    pub account_id: i64,
+    pub attempts: i32,
+}
+
+impl LoginChallenge {
+    pub fn new(login_challenge_id: i64, account_id: i64, attempts: i32) -> Self {
+        Self {
+            login_challenge_id,
+            account_id,
+            attempts,
+        }
+    }
+}
+
+/// How many failed logins are free before each further attempt waits twice as long.
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    pub threshold: i64,
+    pub base_delay_seconds: i64,
+    pub max_delay_seconds: i64,
+}
+
+/// Per account type and login id, reset by a successful login.
+pub const LOGIN_ID_LOCKOUT: LockoutPolicy = LockoutPolicy {
+    threshold: 5,
+    base_delay_seconds: 30,
+    max_delay_seconds: 15 * 60,
+};
+
+/// Per client address, looser because one address may front many users.
+pub const IP_ADDRESS_LOCKOUT: LockoutPolicy = LockoutPolicy {
+    threshold: 20,
+    base_delay_seconds: 30,
+    max_delay_seconds: 15 * 60,
+};
+
+/// Failures older than this no longer count towards a lockout.
+pub const LOGIN_ATTEMPT_WINDOW_SECONDS: i64 = 60 * 60;
+
+/// Recent failed logins for one login id or client address.
+#[derive(Debug, Clone)]
+pub struct LoginFailures {
+    pub count: i64,
+    pub last_failed_at: Option<DateTime<Utc>>,
+}
+
+impl LoginFailures {
+    pub fn new(count: i64, last_failed_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            count,
+            last_failed_at,
+        }
+    }
+
+    /// Seconds until the next attempt is allowed, `None` when it is allowed now.
+    pub fn retry_after(&self, policy: &LockoutPolicy, now: DateTime<Utc>) -> Option<u64> {
+        let last_failed_at = self.last_failed_at?;
+        if self.count < policy.threshold {
+            return None;
+        }
+
+        // 30s, 60s, 120s, ... capped, the exponent is clamped before it can overflow
+        let doublings = u32::try_from(self.count - policy.threshold)
+            .unwrap_or(u32::MAX)
+            .min(32);
+        let delay = policy
+            .base_delay_seconds
+            .saturating_mul(1_i64 << doublings)
+            .min(policy.max_delay_seconds);
+        let locked_until = last_failed_at + chrono::Duration::seconds(delay);
+
+        let remaining = (locked_until - now).num_milliseconds();
+        if remaining <= 0 {
+            return None;
+        }
+        // round up so clients never retry a moment too early
+        Some(u64::try_from((remaining + 999) / 1000).unwrap_or(1))
+    }
+}
+
+/// The fields a protagonist or supporter registers with, the password already hashed.
+pub struct AccountProfile {
     pub last_name: String,
     pub first_name: String,
     pub login_id: String,
@@ -10,9 +154,8 @@ pub struct Protagonist {
     pub country: String,
 }
 
-impl Protagonist {
+impl AccountProfile {
     pub fn new(
-        protagonist_id: i64,
         last_name: String,
         first_name: String,
         login_id: String,
@@ -21,7 +164,6 @@ impl Protagonist {
         country: String,
     ) -> Self {
         Self {
-            protagonist_id,
             last_name,
             first_name,
             login_id,
@@ -30,6 +172,39 @@ impl Protagonist {
             country,
         }
     }
+}
+
+pub struct Protagonist {
+    pub protagonist_id: i64,
+    pub last_name: String,
+    pub first_name: String,
+    pub login_id: String,
+    pub password: String,
+    pub email: String,
+    pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
+}
+
+impl Protagonist {
+    pub fn new(
+        protagonist_id: i64,
+        profile: AccountProfile,
+        email_verified: bool,
+        version: i64,
+    ) -> Self {
+        Self {
+            protagonist_id,
+            last_name: profile.last_name,
+            first_name: profile.first_name,
+            login_id: profile.login_id,
+            password: profile.password,
+            email: profile.email,
+            country: profile.country,
+            email_verified,
+            version,
+        }
+    }
 
     pub async fn verify_password(&self, password: &str) -> Result<bool, bcrypt::BcryptError> {
         util::crypt::verify_password(password, &self.password).await
@@ -44,26 +219,27 @@ pub struct Supporter {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl Supporter {
     pub fn new(
         supporter_id: i64,
-        last_name: String,
-        first_name: String,
-        login_id: String,
-        password: String,
-        email: String,
-        country: String,
+        profile: AccountProfile,
+        email_verified: bool,
+        version: i64,
     ) -> Self {
         Self {
             supporter_id,
-            last_name,
-            first_name,
-            login_id,
-            password,
-            email,
-            country,
+            last_name: profile.last_name,
+            first_name: profile.first_name,
+            login_id: profile.login_id,
+            password: profile.password,
+            email: profile.email,
+            country: profile.country,
+            email_verified,
+            version,
         }
     }
 
@@ -101,3 +277,96 @@ impl ProtagonistSupporterRelation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login_failures(count: i64, seconds_ago: i64, now: DateTime<Utc>) -> LoginFailures {
+        LoginFailures::new(count, Some(now - chrono::Duration::seconds(seconds_ago)))
+    }
+
+    #[test]
+    fn login_failures_retry_after_allows_attempts_below_threshold() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(4, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            None
+        );
+        assert_eq!(
+            LoginFailures::new(9, None).retry_after(&LOGIN_ID_LOCKOUT, now),
+            None
+        );
+    }
+
+    #[test]
+    fn login_failures_retry_after_doubles_delay_per_failure_over_threshold() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(5, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(30)
+        );
+        assert_eq!(
+            login_failures(6, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(60)
+        );
+        assert_eq!(
+            login_failures(7, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(120)
+        );
+    }
+
+    #[test]
+    fn login_failures_retry_after_caps_delay_and_survives_huge_counts() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(10, 0, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(900)
+        );
+        assert_eq!(
+            login_failures(i64::MAX, 0, now).retry_after(&IP_ADDRESS_LOCKOUT, now),
+            Some(900)
+        );
+    }
+
+    #[test]
+    fn login_failures_retry_after_counts_down_and_rounds_up() {
+        let now = Utc::now();
+        assert_eq!(
+            login_failures(5, 10, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(20)
+        );
+        let failed_at = now - chrono::Duration::milliseconds(29_500);
+        assert_eq!(
+            LoginFailures::new(5, Some(failed_at)).retry_after(&LOGIN_ID_LOCKOUT, now),
+            Some(1)
+        );
+        assert_eq!(
+            login_failures(5, 30, now).retry_after(&LOGIN_ID_LOCKOUT, now),
+            None
+        );
+    }
+
+    fn profile() -> AccountProfile {
+        AccountProfile::new(
+            "Doe".to_string(),
+            "Jane".to_string(),
+            "jane".to_string(),
+            "$2b$12$".to_string(),
+            "jane@example.com".to_string(),
+            "JP".to_string(),
+        )
+    }
+
+    #[test]
+    fn accounts_take_the_verified_flag_and_version_apart_from_the_profile() {
+        let protagonist = Protagonist::new(1, profile(), false, 3);
+        assert_eq!(protagonist.login_id, "jane");
+        assert!(!protagonist.email_verified);
+        assert_eq!(protagonist.version, 3);
+
+        let supporter = Supporter::new(2, profile(), true, 1);
+        assert_eq!(supporter.email, "jane@example.com");
+        assert!(supporter.email_verified);
+    }
+}
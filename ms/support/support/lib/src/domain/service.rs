@@ -1,39 +1,69 @@
 use crate::{
+    domain::{entity, error::SupportError},
     driver::{model, repository},
     router::{request, response},
+    util,
 };
+use std::sync::Arc;
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct SupportService {
     repository: repository::SupportRepository,
+    mailer: Arc<dyn util::mailer::Mailer>,
 }
 
 impl SupportService {
     pub fn new(repository: repository::SupportRepository) -> Self {
-        Self { repository }
+        Self {
+            repository,
+            mailer: Arc::new(util::mailer::LogMailer::new(None)),
+        }
+    }
+
+    /// Replaces the default mailer, which only writes to the log.
+    pub fn with_mailer(self, mailer: Arc<dyn util::mailer::Mailer>) -> Self {
+        Self { mailer, ..self }
     }
 
     pub async fn get_protagonist(
         &self,
         id: i64,
-    ) -> Result<response::GetProtagonistResponse, anyhow::Error> {
+    ) -> Result<response::Versioned<response::GetProtagonistResponse>, SupportError> {
         let protagonist = self.repository.get_protagonist(id).await?;
         match protagonist {
-            Some(protagonist) => Ok(response::GetProtagonistResponse {
-                protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
-                protagonist_last_name: protagonist.last_name,
-                protagonist_first_name: protagonist.first_name,
-                protagonist_email: protagonist.email,
-                protagonist_country: protagonist.country,
+            Some(protagonist) => Ok(response::Versioned {
+                version: protagonist.version,
+                body: response::GetProtagonistResponse {
+                    protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
+                    protagonist_last_name: protagonist.last_name,
+                    protagonist_first_name: protagonist.first_name,
+                    protagonist_email: protagonist.email,
+                    protagonist_country: protagonist.country,
+                    protagonist_email_verified: protagonist.email_verified,
+                },
             }),
-            None => Err(anyhow::anyhow!("Protagonist not found")),
+            None => Err(SupportError::NotFound("Protagonist not found".to_string())),
+        }
+    }
+
+    /// Tells a stale If-Match from a missing protagonist once a conditional write matched no row.
+    async fn protagonist_precondition_error(&self, protagonist_id: i64) -> SupportError {
+        match self.repository.get_protagonist(protagonist_id).await {
+            Ok(Some(_)) => {
+                SupportError::PreconditionFailed("Protagonist has been modified".to_string())
+            }
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                SupportError::NotFound("Protagonist not found".to_string())
+            }
+            Err(err) => err.into(),
         }
     }
 
     pub async fn create_protagonist(
         &self,
         protagonist: request::CreateProtagonistRequest,
-    ) -> Result<response::CreateProtagonistResponse, anyhow::Error> {
+    ) -> Result<response::CreateProtagonistResponse, SupportError> {
         // protagonist_id is set to -1 because it is auto-incremented in the database
         let result = self
             .repository
@@ -52,6 +82,16 @@ impl SupportService {
             )
             .await?;
 
+        // the account exists either way, a lost mail can be sent again
+        if let Some(protagonist) = result.as_ref() {
+            self.send_verification_email_or_warn(
+                entity::PROTAGONIST_ACCOUNT,
+                protagonist.protagonist_id,
+                &protagonist.email,
+            )
+            .await;
+        }
+
         match result {
             Some(protagonist) => Ok(response::CreateProtagonistResponse {
                 protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
@@ -59,16 +99,21 @@ impl SupportService {
                 protagonist_first_name: protagonist.first_name,
                 protagonist_email: protagonist.email,
                 protagonist_country: protagonist.country,
+                protagonist_email_verified: protagonist.email_verified,
             }),
-            None => Err(anyhow::anyhow!("Protagonist not created")),
+            None => Err(SupportError::Internal(
+                "Protagonist not created".to_string(),
+            )),
         }
     }
 
     pub async fn update_protagonist(
         &self,
         protagonist: request::UpdateProtagonistRequest,
-    ) -> Result<response::UpdateProtagonistResponse, anyhow::Error> {
-        let protagonist = self
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateProtagonistResponse>, SupportError> {
+        let protagonist_id = protagonist.protagonist_id;
+        let protagonist = match self
             .repository
             .update_protagonist(
                 model::UpdateProtagonist::new(
@@ -76,87 +121,269 @@ impl SupportService {
                     protagonist.last_name,
                     protagonist.first_name,
                     protagonist.login_id,
-                    protagonist.password,
                     protagonist.email,
                     protagonist.country,
-                )
-                .convert_hash_password()
-                .await?,
+                ),
+                precondition.versions(),
             )
-            .await?;
+            .await
+        {
+            Ok(Some(protagonist)) => protagonist,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.protagonist_precondition_error(protagonist_id).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
 
-        match protagonist {
-            Some(protagonist) => Ok(response::UpdateProtagonistResponse {
+        Ok(response::Versioned {
+            version: protagonist.version,
+            body: response::UpdateProtagonistResponse {
                 protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
                 protagonist_last_name: protagonist.last_name,
                 protagonist_first_name: protagonist.first_name,
                 protagonist_email: protagonist.email,
                 protagonist_country: protagonist.country,
-            }),
-            None => Err(anyhow::anyhow!("Protagonist not updated")),
+                protagonist_email_verified: protagonist.email_verified,
+            },
+        })
+    }
+
+    /// Updates only the fields present in the request, a new email address has to be verified again.
+    pub async fn patch_protagonist(
+        &self,
+        protagonist_id: i64,
+        request: request::PatchProfileRequest,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateProtagonistResponse>, SupportError> {
+        let protagonist = match self
+            .repository
+            .patch_protagonist(
+                protagonist_id,
+                request.last_name.as_deref(),
+                request.first_name.as_deref(),
+                request.email.as_deref(),
+                request.country.as_deref(),
+                precondition.versions(),
+            )
+            .await
+        {
+            Ok(Some(protagonist)) => protagonist,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.protagonist_precondition_error(protagonist_id).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // the profile is saved either way, a lost mail can be sent again
+        if request.email.is_some() && !protagonist.email_verified {
+            self.send_verification_email_or_warn(
+                entity::PROTAGONIST_ACCOUNT,
+                protagonist.protagonist_id,
+                &protagonist.email,
+            )
+            .await;
         }
+
+        Ok(response::Versioned {
+            version: protagonist.version,
+            body: response::UpdateProtagonistResponse {
+                protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
+                protagonist_last_name: protagonist.last_name,
+                protagonist_first_name: protagonist.first_name,
+                protagonist_email: protagonist.email,
+                protagonist_country: protagonist.country,
+                protagonist_email_verified: protagonist.email_verified,
+            },
+        })
     }
 
-    pub async fn delete_protagonist(&self, id: i64) -> Result<(), anyhow::Error> {
-        let result = self.repository.delete_protagonist(id).await?;
+    /// Replaces the password after checking the current one, which ends every session of the protagonist.
+    pub async fn change_protagonist_password(
+        &self,
+        protagonist_id: i64,
+        request: request::ChangePasswordRequest,
+        client_ip: Option<&str>,
+    ) -> Result<response::ChangeProtagonistPasswordResponse, SupportError> {
+        let protagonist = match self.repository.get_protagonist(protagonist_id).await {
+            Ok(Some(protagonist)) => protagonist,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        self.check_login_lockout(
+            entity::PROTAGONIST_ACCOUNT,
+            &protagonist.login_id,
+            client_ip,
+        )
+        .await?;
+        let verified = protagonist
+            .verify_password(request.current_password.as_str())
+            .await?;
+        self.check_current_password(
+            entity::PROTAGONIST_ACCOUNT,
+            &protagonist.login_id,
+            client_ip,
+            verified,
+        )
+        .await?;
+
+        let password = util::crypt::hash_password(&request.new_password).await?;
+        self.repository
+            .change_protagonist_password(
+                protagonist.protagonist_id,
+                &protagonist.password,
+                &password,
+            )
+            .await?
+            .ok_or_else(|| {
+                SupportError::Conflict("Password was changed meanwhile, try again".to_string())
+            })?;
+
+        Ok(response::ChangeProtagonistPasswordResponse {
+            protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
+            status: "changed".to_string(),
+        })
+    }
+
+    pub async fn delete_protagonist(
+        &self,
+        id: i64,
+        precondition: &util::etag::Precondition,
+    ) -> Result<(), SupportError> {
+        let result = self
+            .repository
+            .delete_protagonist(id, precondition.versions())
+            .await?;
         match result {
             Some(_) => Ok(()),
-            None => Err(anyhow::anyhow!("Protagonist not deleted")),
+            None => Err(self.protagonist_precondition_error(id).await),
         }
     }
 
-    pub async fn get_protagonist_by_login_id_and_password(
+    /// Confirms the address a verification token was mailed to.
+    pub async fn verify_protagonist_email(
         &self,
-        login_request: request::GetProtagonistRequest,
-    ) -> Result<response::GetProtagonistResponse, anyhow::Error> {
-        let protagonist = self
-            .repository
-            .get_protagonist_by_login_id_and_password(login_request.login_id.as_str())
+        request: request::VerifyEmailRequest,
+    ) -> Result<response::VerifyProtagonistEmailResponse, SupportError> {
+        let token_hash = util::crypt::digest_token(request.token.trim());
+
+        match self.repository.verify_protagonist_email(&token_hash).await {
+            Ok(Some(protagonist_id)) => Ok(response::VerifyProtagonistEmailResponse {
+                protagonist_id: u64::try_from(protagonist_id).unwrap(),
+                protagonist_email_verified: true,
+            }),
+            // unknown, used, expired and superseded tokens all look the same to the client
+            Ok(None) | Err(sqlx::Error::RowNotFound) => Err(SupportError::BadRequest(
+                "Invalid or expired verification token".to_string(),
+            )),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub async fn login_protagonist(
+        &self,
+        request: request::LoginRequest,
+        client_ip: Option<&str>,
+        secret_key: &str,
+    ) -> Result<response::LoginStepResponse, SupportError> {
+        self.check_login_lockout(entity::PROTAGONIST_ACCOUNT, &request.login_id, client_ip)
             .await?;
 
-        match protagonist {
-            Some(protagonist) => {
-                let valid = protagonist
-                    .verify_password(login_request.password.as_str())
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Error verifying password: {}", e.to_string()))?;
-                if !valid {
-                    return Err(anyhow::anyhow!("Invalid password"));
-                }
-
-                Ok(response::GetProtagonistResponse {
-                    protagonist_id: u64::try_from(protagonist.protagonist_id).unwrap(),
-                    protagonist_last_name: protagonist.last_name,
-                    protagonist_first_name: protagonist.first_name,
-                    protagonist_email: protagonist.email,
-                    protagonist_country: protagonist.country,
-                })
+        let authenticated = self.authenticate_protagonist(&request).await;
+        let protagonist_id = self
+            .record_login_attempt(
+                entity::PROTAGONIST_ACCOUNT,
+                &request.login_id,
+                client_ip,
+                authenticated,
+            )
+            .await?;
+
+        self.finish_password_login(entity::PROTAGONIST_ACCOUNT, protagonist_id, secret_key)
+            .await
+    }
+
+    /// Finishes a protagonist login held back for the second factor.
+    pub async fn login_protagonist_totp(
+        &self,
+        request: request::LoginTotpRequest,
+        secret_key: &str,
+    ) -> Result<response::LoginResponse, SupportError> {
+        self.login_totp(entity::PROTAGONIST_ACCOUNT, request, secret_key)
+            .await
+    }
+
+    async fn authenticate_protagonist(
+        &self,
+        request: &request::LoginRequest,
+    ) -> Result<i64, SupportError> {
+        // unknown login ids and wrong passwords are indistinguishable to the client
+        let protagonist = match self
+            .repository
+            .get_protagonist_by_login_id(request.login_id.as_str())
+            .await
+        {
+            Ok(Some(protagonist)) => protagonist,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(SupportError::Unauthorized(
+                    "Invalid login ID or password".to_string(),
+                ))
             }
-            None => Err(anyhow::anyhow!("Protagonist not found")),
+            Err(err) => return Err(err.into()),
+        };
+
+        if !protagonist
+            .verify_password(request.password.as_str())
+            .await?
+        {
+            return Err(SupportError::Unauthorized(
+                "Invalid login ID or password".to_string(),
+            ));
         }
+
+        Ok(protagonist.protagonist_id)
     }
 
     pub async fn get_supporter(
         &self,
         id: i64,
-    ) -> Result<response::GetSupporterResponse, anyhow::Error> {
+    ) -> Result<response::Versioned<response::GetSupporterResponse>, SupportError> {
         let supporter = self.repository.get_supporter(id).await?;
         match supporter {
-            Some(supporter) => Ok(response::GetSupporterResponse {
-                supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
-                supporter_last_name: supporter.last_name,
-                supporter_first_name: supporter.first_name,
-                supporter_email: supporter.email,
-                supporter_country: supporter.country,
+            Some(supporter) => Ok(response::Versioned {
+                version: supporter.version,
+                body: response::GetSupporterResponse {
+                    supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
+                    supporter_last_name: supporter.last_name,
+                    supporter_first_name: supporter.first_name,
+                    supporter_email: supporter.email,
+                    supporter_country: supporter.country,
+                    supporter_email_verified: supporter.email_verified,
+                },
             }),
-            None => Err(anyhow::anyhow!("Supporter not found")),
+            None => Err(SupportError::NotFound("Supporter not found".to_string())),
+        }
+    }
+
+    /// Tells a stale If-Match from a missing supporter once a conditional write matched no row.
+    async fn supporter_precondition_error(&self, supporter_id: i64) -> SupportError {
+        match self.repository.get_supporter(supporter_id).await {
+            Ok(Some(_)) => {
+                SupportError::PreconditionFailed("Supporter has been modified".to_string())
+            }
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                SupportError::NotFound("Supporter not found".to_string())
+            }
+            Err(err) => err.into(),
         }
     }
 
     pub async fn create_supporter(
         &self,
         supporter: request::CreateSupporterRequest,
-    ) -> Result<response::CreateSupporterResponse, anyhow::Error> {
+    ) -> Result<response::CreateSupporterResponse, SupportError> {
         // support_id is set to -1 because it is auto-incremented in the database
         let supporter = self
             .repository
@@ -175,6 +402,16 @@ impl SupportService {
             )
             .await?;
 
+        // the account exists either way, a lost mail can be sent again
+        if let Some(supporter) = supporter.as_ref() {
+            self.send_verification_email_or_warn(
+                entity::SUPPORTER_ACCOUNT,
+                supporter.supporter_id,
+                &supporter.email,
+            )
+            .await;
+        }
+
         match supporter {
             Some(supporter) => Ok(response::CreateSupporterResponse {
                 supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
@@ -182,16 +419,19 @@ impl SupportService {
                 supporter_first_name: supporter.first_name,
                 supporter_email: supporter.email,
                 supporter_country: supporter.country,
+                supporter_email_verified: supporter.email_verified,
             }),
-            None => Err(anyhow::anyhow!("Supporter not created")),
+            None => Err(SupportError::Internal("Supporter not created".to_string())),
         }
     }
 
     pub async fn update_supporter(
         &self,
         supporter: request::UpdateSupporterRequest,
-    ) -> Result<response::UpdateSupporterResponse, anyhow::Error> {
-        let supporter = self
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateSupporterResponse>, SupportError> {
+        let supporter_id = supporter.supporter_id;
+        let supporter = match self
             .repository
             .update_supporter(
                 model::UpdateSupporter::new(
@@ -199,70 +439,224 @@ impl SupportService {
                     supporter.last_name,
                     supporter.first_name,
                     supporter.login_id,
-                    supporter.password,
                     supporter.email,
                     supporter.country,
-                )
-                .convert_hash_password()
-                .await?,
+                ),
+                precondition.versions(),
             )
-            .await?;
+            .await
+        {
+            Ok(Some(supporter)) => supporter,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.supporter_precondition_error(supporter_id).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
 
-        match supporter {
-            Some(supporter) => Ok(response::UpdateSupporterResponse {
+        Ok(response::Versioned {
+            version: supporter.version,
+            body: response::UpdateSupporterResponse {
                 supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
                 supporter_last_name: supporter.last_name,
                 supporter_first_name: supporter.first_name,
                 supporter_email: supporter.email,
                 supporter_country: supporter.country,
-            }),
-            None => Err(anyhow::anyhow!("Supporter not updated")),
+                supporter_email_verified: supporter.email_verified,
+            },
+        })
+    }
+
+    /// Updates only the fields present in the request, a new email address has to be verified again.
+    pub async fn patch_supporter(
+        &self,
+        supporter_id: i64,
+        request: request::PatchProfileRequest,
+        precondition: &util::etag::Precondition,
+    ) -> Result<response::Versioned<response::UpdateSupporterResponse>, SupportError> {
+        let supporter = match self
+            .repository
+            .patch_supporter(
+                supporter_id,
+                request.last_name.as_deref(),
+                request.first_name.as_deref(),
+                request.email.as_deref(),
+                request.country.as_deref(),
+                precondition.versions(),
+            )
+            .await
+        {
+            Ok(Some(supporter)) => supporter,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(self.supporter_precondition_error(supporter_id).await)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // the profile is saved either way, a lost mail can be sent again
+        if request.email.is_some() && !supporter.email_verified {
+            self.send_verification_email_or_warn(
+                entity::SUPPORTER_ACCOUNT,
+                supporter.supporter_id,
+                &supporter.email,
+            )
+            .await;
         }
+
+        Ok(response::Versioned {
+            version: supporter.version,
+            body: response::UpdateSupporterResponse {
+                supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
+                supporter_last_name: supporter.last_name,
+                supporter_first_name: supporter.first_name,
+                supporter_email: supporter.email,
+                supporter_country: supporter.country,
+                supporter_email_verified: supporter.email_verified,
+            },
+        })
     }
 
-    pub async fn delete_supporter(&self, id: i64) -> Result<(), anyhow::Error> {
-        let result = self.repository.delete_supporter(id).await?;
+    /// Replaces the password after checking the current one, which ends every session of the supporter.
+    pub async fn change_supporter_password(
+        &self,
+        supporter_id: i64,
+        request: request::ChangePasswordRequest,
+        client_ip: Option<&str>,
+    ) -> Result<response::ChangeSupporterPasswordResponse, SupportError> {
+        let supporter = match self.repository.get_supporter(supporter_id).await {
+            Ok(Some(supporter)) => supporter,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        self.check_login_lockout(entity::SUPPORTER_ACCOUNT, &supporter.login_id, client_ip)
+            .await?;
+        let verified = supporter
+            .verify_password(request.current_password.as_str())
+            .await?;
+        self.check_current_password(
+            entity::SUPPORTER_ACCOUNT,
+            &supporter.login_id,
+            client_ip,
+            verified,
+        )
+        .await?;
+
+        let password = util::crypt::hash_password(&request.new_password).await?;
+        self.repository
+            .change_supporter_password(supporter.supporter_id, &supporter.password, &password)
+            .await?
+            .ok_or_else(|| {
+                SupportError::Conflict("Password was changed meanwhile, try again".to_string())
+            })?;
+
+        Ok(response::ChangeSupporterPasswordResponse {
+            supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
+            status: "changed".to_string(),
+        })
+    }
+
+    pub async fn delete_supporter(
+        &self,
+        id: i64,
+        precondition: &util::etag::Precondition,
+    ) -> Result<(), SupportError> {
+        let result = self
+            .repository
+            .delete_supporter(id, precondition.versions())
+            .await?;
         match result {
             Some(_) => Ok(()),
-            None => Err(anyhow::anyhow!("Supporter not deleted")),
+            None => Err(self.supporter_precondition_error(id).await),
         }
     }
 
-    pub async fn get_supporter_by_login_id_and_password(
+    /// Confirms the address a verification token was mailed to.
+    pub async fn verify_supporter_email(
         &self,
-        login_request: request::GetSupporterRequest,
-    ) -> Result<response::GetSupporterResponse, anyhow::Error> {
-        let supporter = self
-            .repository
-            .get_supporter_by_login_id_and_password(login_request.login_id.as_str())
+        request: request::VerifyEmailRequest,
+    ) -> Result<response::VerifySupporterEmailResponse, SupportError> {
+        let token_hash = util::crypt::digest_token(request.token.trim());
+
+        match self.repository.verify_supporter_email(&token_hash).await {
+            Ok(Some(supporter_id)) => Ok(response::VerifySupporterEmailResponse {
+                supporter_id: u64::try_from(supporter_id).unwrap(),
+                supporter_email_verified: true,
+            }),
+            // unknown, used, expired and superseded tokens all look the same to the client
+            Ok(None) | Err(sqlx::Error::RowNotFound) => Err(SupportError::BadRequest(
+                "Invalid or expired verification token".to_string(),
+            )),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub async fn login_supporter(
+        &self,
+        request: request::LoginRequest,
+        client_ip: Option<&str>,
+        secret_key: &str,
+    ) -> Result<response::LoginStepResponse, SupportError> {
+        self.check_login_lockout(entity::SUPPORTER_ACCOUNT, &request.login_id, client_ip)
             .await?;
 
-        match supporter {
-            Some(supporter) => {
-                let valid = supporter
-                    .verify_password(login_request.password.as_str())
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Error verifying password: {}", e.to_string()))?; // This is a bug, the error should be returned instead of being ignored
-                if !valid {
-                    return Err(anyhow::anyhow!("Invalid password"));
-                }
-
-                Ok(response::GetSupporterResponse {
-                    supporter_id: u64::try_from(supporter.supporter_id).unwrap(),
-                    supporter_last_name: supporter.last_name,
-                    supporter_first_name: supporter.first_name,
-                    supporter_email: supporter.email,
-                    supporter_country: supporter.country,
-                })
+        let authenticated = self.authenticate_supporter(&request).await;
+        let supporter_id = self
+            .record_login_attempt(
+                entity::SUPPORTER_ACCOUNT,
+                &request.login_id,
+                client_ip,
+                authenticated,
+            )
+            .await?;
+
+        self.finish_password_login(entity::SUPPORTER_ACCOUNT, supporter_id, secret_key)
+            .await
+    }
+
+    /// Finishes a supporter login held back for the second factor.
+    pub async fn login_supporter_totp(
+        &self,
+        request: request::LoginTotpRequest,
+        secret_key: &str,
+    ) -> Result<response::LoginResponse, SupportError> {
+        self.login_totp(entity::SUPPORTER_ACCOUNT, request, secret_key)
+            .await
+    }
+
+    async fn authenticate_supporter(
+        &self,
+        request: &request::LoginRequest,
+    ) -> Result<i64, SupportError> {
+        // unknown login ids and wrong passwords are indistinguishable to the client
+        let supporter = match self
+            .repository
+            .get_supporter_by_login_id(request.login_id.as_str())
+            .await
+        {
+            Ok(Some(supporter)) => supporter,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(SupportError::Unauthorized(
+                    "Invalid login ID or password".to_string(),
+                ))
             }
-            None => Err(anyhow::anyhow!("Supporter not found")),
+            Err(err) => return Err(err.into()),
+        };
+
+        if !supporter.verify_password(request.password.as_str()).await? {
+            return Err(SupportError::Unauthorized(
+                "Invalid login ID or password".to_string(),
+            ));
         }
+
+        Ok(supporter.supporter_id)
     }
 
     pub async fn get_protagonist_supporter(
         &self,
         id: i64,
-    ) -> Result<Vec<response::GetProtagonistSupporterResponse>, anyhow::Error> {
+    ) -> Result<Vec<response::GetProtagonistSupporterResponse>, SupportError> {
         let protagonist_supporters = self.repository.get_protagonist_supporter(id).await?;
         match protagonist_supporters {
             Some(protagonist_supporters) => Ok(protagonist_supporters
@@ -276,14 +670,16 @@ impl SupportService {
                     },
                 )
                 .collect()),
-            None => Err(anyhow::anyhow!("Protagonist supporter not found")),
+            None => Err(SupportError::NotFound(
+                "Protagonist supporter not found".to_string(),
+            )),
         }
     }
 
     pub async fn create_protagonist_supporter(
         &self,
         protagonist_supporter_request: request::CreateProtagonistSupporterRequest,
-    ) -> Result<response::CreateProtagonistSupporterResponse, anyhow::Error> {
+    ) -> Result<response::CreateProtagonistSupporterResponse, SupportError> {
         let protagonist_supporter = self
             .repository
             .create_protagonist_supporter(model::CreateProtagonistSupporter::new(
@@ -300,15 +696,651 @@ impl SupportService {
                 )
                 .unwrap(),
             }),
-            None => Err(anyhow::anyhow!("Protagonist supporter not created")),
+            None => Err(SupportError::Internal(
+                "Protagonist supporter not created".to_string(),
+            )),
         }
     }
 
-    pub async fn delete_protagonist_supporter(&self, id: i64) -> Result<(), anyhow::Error> {
+    pub async fn delete_protagonist_supporter(&self, id: i64) -> Result<(), SupportError> {
         let result = self.repository.delete_protagonist_supporter(id).await?;
         match result {
             Some(_) => Ok(()),
-            None => Err(anyhow::anyhow!("Protagonist supporter not deleted")),
+            None => Err(SupportError::Internal(
+                "Protagonist supporter not deleted".to_string(),
+            )),
+        }
+    }
+
+    /// Issues the access token after a correct password, or a challenge when the account has TOTP.
+    async fn finish_password_login(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        secret_key: &str,
+    ) -> Result<response::LoginStepResponse, SupportError> {
+        let totp = self.get_account_totp(account_type, account_id).await?;
+        if totp.enabled {
+            return self.start_login_challenge(account_type, account_id).await;
+        }
+
+        Ok(response::LoginStepResponse::Authenticated(
+            Self::access_token_response(account_type, account_id, secret_key)?,
+        ))
+    }
+
+    async fn login_totp(
+        &self,
+        account_type: &str,
+        request: request::LoginTotpRequest,
+        secret_key: &str,
+    ) -> Result<response::LoginResponse, SupportError> {
+        let token_hash = util::crypt::digest_token(request.challenge_token.trim());
+        let challenge = match self
+            .repository
+            .get_login_challenge(account_type, &token_hash)
+            .await
+        {
+            Ok(Some(challenge)) => challenge,
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                return Err(SupportError::Unauthorized(
+                    "Invalid or expired login challenge".to_string(),
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if !self
+            .check_second_factor(account_type, challenge.account_id, &request.code)
+            .await?
+        {
+            self.repository
+                .record_login_challenge_failure(
+                    challenge.login_challenge_id,
+                    entity::LOGIN_CHALLENGE_MAX_ATTEMPTS,
+                )
+                .await?;
+            return Err(SupportError::Unauthorized(
+                "Invalid two-factor code".to_string(),
+            ));
+        }
+
+        // a concurrent request with another valid code may have finished it first
+        self.repository
+            .complete_login_challenge(challenge.login_challenge_id)
+            .await?
+            .ok_or_else(|| {
+                SupportError::Unauthorized("Invalid or expired login challenge".to_string())
+            })?;
+
+        Self::access_token_response(account_type, challenge.account_id, secret_key)
+    }
+
+    async fn start_login_challenge(
+        &self,
+        account_type: &str,
+        account_id: i64,
+    ) -> Result<response::LoginStepResponse, SupportError> {
+        let bytes: [u8; 32] = rand::random();
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let expires_at = (chrono::Utc::now()
+            + chrono::Duration::seconds(entity::LOGIN_CHALLENGE_TTL_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%S%.6f")
+        .to_string();
+
+        self.repository
+            .create_login_challenge(
+                account_type,
+                account_id,
+                &util::crypt::digest_token(&token),
+                &expires_at,
+            )
+            .await?
+            .ok_or_else(|| SupportError::Internal("Login challenge not created".to_string()))?;
+
+        Ok(response::LoginStepResponse::TwoFactorRequired(
+            response::TwoFactorRequiredResponse {
+                two_factor_required: true,
+                challenge_token: token,
+                expires_in: entity::LOGIN_CHALLENGE_TTL_SECONDS as u64,
+            },
+        ))
+    }
+
+    fn access_token_response(
+        account_type: &str,
+        account_id: i64,
+        secret_key: &str,
+    ) -> Result<response::LoginResponse, SupportError> {
+        let (scopes, role) = match account_type {
+            entity::PROTAGONIST_ACCOUNT => (entity::PROTAGONIST_SCOPES, entity::PROTAGONIST_ROLE),
+            entity::SUPPORTER_ACCOUNT => (entity::SUPPORTER_SCOPES, entity::SUPPORTER_ROLE),
+            _ => {
+                return Err(SupportError::Internal(format!(
+                    "Unknown account type: {}",
+                    account_type
+                )))
+            }
+        };
+        let token = util::auth::Token::new(
+            account_id,
+            scopes.iter().map(|scope| scope.to_string()).collect(),
+            role,
+            chrono::Utc::now(),
+        );
+
+        Ok(response::LoginResponse {
+            access_token: token.encode(secret_key)?,
+            token_type: "Bearer".to_string(),
+            expires_in: util::auth::ACCESS_TOKEN_TTL_SECONDS as u64,
+        })
+    }
+
+    /// Starts TOTP enrollment with a fresh secret, which only takes effect once a code confirms it.
+    pub async fn enroll_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+    ) -> Result<response::EnrollTotpResponse, SupportError> {
+        let totp = self.get_account_totp(account_type, account_id).await?;
+        if totp.enabled {
+            return Err(SupportError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = util::totp::generate_secret();
+        self.repository
+            .set_pending_totp_secret(account_type, account_id, &secret)
+            .await?
+            .ok_or_else(|| {
+                SupportError::Conflict("Two-factor authentication is already enabled".to_string())
+            })?;
+
+        Ok(response::EnrollTotpResponse {
+            otpauth_uri: util::totp::otpauth_uri(&secret, entity::TOTP_ISSUER, &totp.login_id)?,
+            secret,
+        })
+    }
+
+    /// Enables TOTP once a code proves the authenticator holds the pending secret.
+    pub async fn confirm_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        request: request::ConfirmTotpRequest,
+    ) -> Result<response::ConfirmTotpResponse, SupportError> {
+        let totp = self.get_account_totp(account_type, account_id).await?;
+        if totp.enabled {
+            return Err(SupportError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+        let Some(secret) = totp.secret else {
+            return Err(SupportError::Conflict(
+                "Two-factor enrollment has not been started".to_string(),
+            ));
+        };
+
+        let code: String = request.code.split_whitespace().collect();
+        let step = if util::totp::is_authenticator_code(&code) {
+            util::totp::matching_step(&secret, &code, chrono::Utc::now().timestamp() as u64)?
+        } else {
+            None
+        };
+        let Some(step) = step else {
+            return Err(SupportError::BadRequest(
+                "Invalid two-factor code".to_string(),
+            ));
+        };
+
+        let recovery_codes: Vec<String> = (0..entity::TOTP_RECOVERY_CODE_COUNT)
+            .map(|_| util::totp::generate_recovery_code())
+            .collect();
+        let recovery_code_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| util::totp::digest_recovery_code(code))
+            .collect();
+
+        // enrollment restarted meanwhile, the code belonged to the replaced secret
+        self.repository
+            .enable_totp(
+                account_type,
+                account_id,
+                &secret,
+                step,
+                &recovery_code_hashes,
+            )
+            .await?
+            .ok_or_else(|| {
+                SupportError::Conflict("Two-factor enrollment has changed, start again".to_string())
+            })?;
+
+        Ok(response::ConfirmTotpResponse {
+            totp_enabled: true,
+            recovery_codes,
+        })
+    }
+
+    /// Turns TOTP off, which takes a current code or a recovery code just like a login does.
+    pub async fn disable_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        request: request::DisableTotpRequest,
+    ) -> Result<response::DisableTotpResponse, SupportError> {
+        let totp = self.get_account_totp(account_type, account_id).await?;
+        if !totp.enabled {
+            return Err(SupportError::Conflict(
+                "Two-factor authentication is not enabled".to_string(),
+            ));
+        }
+
+        if !self
+            .check_second_factor(account_type, account_id, &request.code)
+            .await?
+        {
+            return Err(SupportError::Forbidden(
+                "Invalid two-factor code".to_string(),
+            ));
+        }
+
+        self.repository
+            .disable_totp(account_type, account_id)
+            .await?
+            .ok_or_else(|| SupportError::NotFound("Account not found".to_string()))?;
+
+        Ok(response::DisableTotpResponse {
+            totp_enabled: false,
+        })
+    }
+
+    async fn get_account_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+    ) -> Result<entity::AccountTotp, SupportError> {
+        match self
+            .repository
+            .get_account_totp(account_type, account_id)
+            .await
+        {
+            Ok(Some(totp)) => Ok(totp),
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {
+                Err(SupportError::NotFound("Account not found".to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Accepts an unused authenticator code or recovery code, using it up either way.
+    async fn check_second_factor(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        code: &str,
+    ) -> Result<bool, SupportError> {
+        let totp = self.get_account_totp(account_type, account_id).await?;
+        let (true, Some(secret)) = (totp.enabled, totp.secret.as_deref()) else {
+            return Ok(false);
+        };
+
+        let code: String = code.split_whitespace().collect();
+        if util::totp::is_authenticator_code(&code) {
+            let step =
+                util::totp::matching_step(secret, &code, chrono::Utc::now().timestamp() as u64)?;
+            let Some(step) = step else {
+                return Ok(false);
+            };
+            // a code seen once is rejected, it may have been read over a shoulder
+            return Ok(self
+                .repository
+                .use_totp_step(account_type, account_id, step)
+                .await?
+                .is_some());
+        }
+
+        Ok(self
+            .repository
+            .use_recovery_code(
+                account_type,
+                account_id,
+                &util::totp::digest_recovery_code(&code),
+            )
+            .await?
+            .is_some())
+    }
+
+    /// Rejects the attempt while either the login id or the client address is backing off.
+    async fn check_login_lockout(
+        &self,
+        account_type: &str,
+        login_id: &str,
+        client_ip: Option<&str>,
+    ) -> Result<(), SupportError> {
+        let now = chrono::Utc::now();
+        let by_login_id = self
+            .repository
+            .get_login_failures_by_login_id(
+                account_type,
+                login_id,
+                entity::LOGIN_ATTEMPT_WINDOW_SECONDS,
+            )
+            .await?
+            .retry_after(&entity::LOGIN_ID_LOCKOUT, now);
+        let by_ip_address = match client_ip {
+            Some(client_ip) => self
+                .repository
+                .get_login_failures_by_ip_address(client_ip, entity::LOGIN_ATTEMPT_WINDOW_SECONDS)
+                .await?
+                .retry_after(&entity::IP_ADDRESS_LOCKOUT, now),
+            None => None,
+        };
+
+        match by_login_id.into_iter().chain(by_ip_address).max() {
+            Some(retry_after) => {
+                warn!(
+                    "login: {} locked out for {} seconds",
+                    account_type, retry_after
+                );
+                Err(SupportError::TooManyRequests(
+                    "Too many failed login attempts, try again later".to_string(),
+                    retry_after,
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Guessing the current password on a password change counts like guessing it at login.
+    async fn check_current_password(
+        &self,
+        account_type: &str,
+        login_id: &str,
+        client_ip: Option<&str>,
+        verified: bool,
+    ) -> Result<(), SupportError> {
+        if verified {
+            return Ok(());
+        }
+
+        self.repository
+            .record_login_attempt(account_type, login_id, client_ip, false)
+            .await?;
+        Err(SupportError::Forbidden(
+            "Current password is incorrect".to_string(),
+        ))
+    }
+
+    /// Stores the outcome of a password check, errors other than a rejection are not attempts.
+    async fn record_login_attempt(
+        &self,
+        account_type: &str,
+        login_id: &str,
+        client_ip: Option<&str>,
+        authenticated: Result<i64, SupportError>,
+    ) -> Result<i64, SupportError> {
+        match authenticated {
+            Ok(account_id) => {
+                self.repository
+                    .record_login_attempt(account_type, login_id, client_ip, true)
+                    .await?;
+                Ok(account_id)
+            }
+            Err(SupportError::Unauthorized(message)) => {
+                self.repository
+                    .record_login_attempt(account_type, login_id, client_ip, false)
+                    .await?;
+                Err(SupportError::Unauthorized(message))
+            }
+            Err(err) => Err(err),
         }
     }
+
+    /// Mails a reset token when the address belongs to a protagonist, and answers the same either way.
+    pub async fn forgot_protagonist_password(
+        &self,
+        request: request::ForgotPasswordRequest,
+    ) -> Result<response::ForgotPasswordResponse, SupportError> {
+        match self
+            .repository
+            .get_protagonist_by_email(&request.email)
+            .await
+        {
+            Ok(Some(protagonist)) => {
+                self.send_password_reset_email_or_warn(
+                    entity::PROTAGONIST_ACCOUNT,
+                    protagonist.protagonist_id,
+                    &protagonist.email,
+                )
+                .await
+            }
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(response::ForgotPasswordResponse {
+            status: "accepted".to_string(),
+        })
+    }
+
+    /// Sets a new password with a mailed reset token and ends every session of the protagonist.
+    pub async fn reset_protagonist_password(
+        &self,
+        request: request::ResetPasswordRequest,
+    ) -> Result<response::ResetProtagonistPasswordResponse, SupportError> {
+        let token_hash = util::crypt::digest_token(request.token.trim());
+        let password = util::crypt::hash_password(&request.password).await?;
+
+        match self
+            .repository
+            .reset_protagonist_password(&token_hash, &password)
+            .await
+        {
+            Ok(Some(protagonist_id)) => Ok(response::ResetProtagonistPasswordResponse {
+                protagonist_id: u64::try_from(protagonist_id).unwrap(),
+                status: "reset".to_string(),
+            }),
+            Ok(None) | Err(sqlx::Error::RowNotFound) => Err(SupportError::BadRequest(
+                "Invalid or expired reset token".to_string(),
+            )),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Mails a reset token when the address belongs to a supporter, and answers the same either way.
+    pub async fn forgot_supporter_password(
+        &self,
+        request: request::ForgotPasswordRequest,
+    ) -> Result<response::ForgotPasswordResponse, SupportError> {
+        match self.repository.get_supporter_by_email(&request.email).await {
+            Ok(Some(supporter)) => {
+                self.send_password_reset_email_or_warn(
+                    entity::SUPPORTER_ACCOUNT,
+                    supporter.supporter_id,
+                    &supporter.email,
+                )
+                .await
+            }
+            Ok(None) | Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(response::ForgotPasswordResponse {
+            status: "accepted".to_string(),
+        })
+    }
+
+    /// Sets a new password with a mailed reset token and ends every session of the supporter.
+    pub async fn reset_supporter_password(
+        &self,
+        request: request::ResetPasswordRequest,
+    ) -> Result<response::ResetSupporterPasswordResponse, SupportError> {
+        let token_hash = util::crypt::digest_token(request.token.trim());
+        let password = util::crypt::hash_password(&request.password).await?;
+
+        match self
+            .repository
+            .reset_supporter_password(&token_hash, &password)
+            .await
+        {
+            Ok(Some(supporter_id)) => Ok(response::ResetSupporterPasswordResponse {
+                supporter_id: u64::try_from(supporter_id).unwrap(),
+                status: "reset".to_string(),
+            }),
+            Ok(None) | Err(sqlx::Error::RowNotFound) => Err(SupportError::BadRequest(
+                "Invalid or expired reset token".to_string(),
+            )),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Validates the bearer token and rejects it once a password reset ended the sessions of its account.
+    pub async fn verify_token(
+        &self,
+        token: &str,
+        secret_key: &str,
+    ) -> Result<util::auth::Token, SupportError> {
+        let token = util::auth::validate_token(token, secret_key)?;
+
+        let (Some(uid), Some(issued_at)) = (token.uid, token.iat) else {
+            return Ok(token);
+        };
+        let issued_at = issued_at.format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+        // admin tokens belong to neither table, they expire on their own
+        let revoked = match token.role.as_deref() {
+            Some(entity::PROTAGONIST_ROLE) => {
+                self.repository
+                    .is_protagonist_session_revoked(uid, &issued_at)
+                    .await?
+            }
+            Some(entity::SUPPORTER_ROLE) => {
+                self.repository
+                    .is_supporter_session_revoked(uid, &issued_at)
+                    .await?
+            }
+            _ => false,
+        };
+        if revoked {
+            return Err(SupportError::Unauthorized(
+                "Token has been revoked".to_string(),
+            ));
+        }
+
+        Ok(token)
+    }
+
+    async fn send_verification_email_or_warn(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        email: &str,
+    ) {
+        if let Err(err) = self
+            .send_verification_email(account_type, account_id, email)
+            .await
+        {
+            warn!(
+                "verification mail to {} {} failed: {}",
+                account_type, account_id, err
+            );
+        }
+    }
+
+    async fn send_verification_email(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        email: &str,
+    ) -> Result<(), SupportError> {
+        let bytes: [u8; 32] = rand::random();
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let expires_at = (chrono::Utc::now()
+            + chrono::Duration::seconds(entity::EMAIL_VERIFICATION_TTL_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%S%.6f")
+        .to_string();
+
+        self.repository
+            .create_email_verification_token(
+                account_type,
+                account_id,
+                email,
+                &util::crypt::digest_token(&token),
+                &expires_at,
+            )
+            .await?
+            .ok_or_else(|| SupportError::Internal("Verification token not created".to_string()))?;
+
+        self.mailer
+            .send(&util::mailer::Mail {
+                to: email.to_string(),
+                subject: "Confirm your email address".to_string(),
+                body: format!(
+                    "Confirm your email address by sending this token to POST /support/v1/{}/verify:\n\n{}\n\nThe token expires in {} hours.",
+                    account_type,
+                    token,
+                    entity::EMAIL_VERIFICATION_TTL_SECONDS / 3600
+                ),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// A failed mail must not reveal that the account exists, so it is only logged.
+    async fn send_password_reset_email_or_warn(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        email: &str,
+    ) {
+        if let Err(err) = self
+            .send_password_reset_email(account_type, account_id, email)
+            .await
+        {
+            warn!(
+                "password reset mail to {} {} failed: {}",
+                account_type, account_id, err
+            );
+        }
+    }
+
+    async fn send_password_reset_email(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        email: &str,
+    ) -> Result<(), SupportError> {
+        let bytes: [u8; 32] = rand::random();
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let expires_at = (chrono::Utc::now()
+            + chrono::Duration::seconds(entity::PASSWORD_RESET_TTL_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%S%.6f")
+        .to_string();
+
+        self.repository
+            .create_password_reset_token(
+                account_type,
+                account_id,
+                &util::crypt::digest_token(&token),
+                &expires_at,
+            )
+            .await?
+            .ok_or_else(|| SupportError::Internal("Reset token not created".to_string()))?;
+
+        self.mailer
+            .send(&util::mailer::Mail {
+                to: email.to_string(),
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Set a new password by sending this token with it to POST /support/v1/auth/{}/password/reset:\n\n{}\n\nThe token expires in {} minutes. If you did not ask for this, ignore this mail.",
+                    account_type,
+                    token,
+                    entity::PASSWORD_RESET_TTL_SECONDS / 60
+                ),
+            })
+            .await?;
+
+        Ok(())
+    }
 }
@@ -10,6 +10,8 @@ pub struct GetProtagonist {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl GetProtagonist {
@@ -30,6 +32,9 @@ impl GetProtagonist {
             password,
             email,
             country,
+            // filled from the row when read
+            email_verified: false,
+            version: 0,
         }
     }
 
@@ -61,6 +66,8 @@ pub struct CreateProtagonist {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl CreateProtagonist {
@@ -81,6 +88,9 @@ impl CreateProtagonist {
             password,
             email,
             country,
+            // never written, the database derives them
+            email_verified: false,
+            version: 0,
         }
     }
 
@@ -112,6 +122,8 @@ pub struct UpdateProtagonist {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl UpdateProtagonist {
@@ -120,7 +132,6 @@ impl UpdateProtagonist {
         last_name: String,
         first_name: String,
         login_id: String,
-        password: String,
         email: String,
         country: String,
     ) -> Self {
@@ -129,9 +140,13 @@ impl UpdateProtagonist {
             last_name,
             first_name,
             login_id,
-            password,
+            // never written, the password changes only through its own endpoint
+            password: String::new(),
             email,
             country,
+            // never written, the database derives them
+            email_verified: false,
+            version: 0,
         }
     }
 
@@ -144,14 +159,6 @@ impl UpdateProtagonist {
             && !self.email.is_empty()
             && !self.country.is_empty()
     }
-
-    pub async fn convert_hash_password(self) -> Result<Self, bcrypt::BcryptError> {
-        let hashed_password = util::crypt::hash_password(&self.password).await?;
-        Ok(Self {
-            password: hashed_password,
-            ..self
-        })
-    }
 }
 
 #[derive(Debug, FromRow)]
@@ -163,6 +170,8 @@ pub struct GetSupporter {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl GetSupporter {
@@ -183,6 +192,9 @@ impl GetSupporter {
             password,
             email,
             country,
+            // filled from the row when read
+            email_verified: false,
+            version: 0,
         }
     }
 
@@ -214,6 +226,8 @@ pub struct CreateSupporter {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl CreateSupporter {
@@ -234,6 +248,9 @@ impl CreateSupporter {
             password,
             email,
             country,
+            // never written, the database derives them
+            email_verified: false,
+            version: 0,
         }
     }
 
@@ -265,6 +282,8 @@ pub struct UpdateSupporter {
     pub password: String,
     pub email: String,
     pub country: String,
+    pub email_verified: bool,
+    pub version: i64,
 }
 
 impl UpdateSupporter {
@@ -273,7 +292,6 @@ impl UpdateSupporter {
         last_name: String,
         first_name: String,
         login_id: String,
-        password: String,
         email: String,
         country: String,
     ) -> Self {
@@ -282,9 +300,13 @@ impl UpdateSupporter {
             last_name,
             first_name,
             login_id,
-            password,
+            // never written, the password changes only through its own endpoint
+            password: String::new(),
             email,
             country,
+            // never written, the database derives them
+            email_verified: false,
+            version: 0,
         }
     }
 
@@ -297,14 +319,6 @@ impl UpdateSupporter {
             && !self.email.is_empty()
             && !self.country.is_empty()
     }
-
-    pub async fn convert_hash_password(self) -> Result<Self, bcrypt::BcryptError> {
-        let hashed_password = util::crypt::hash_password(&self.password).await?;
-        Ok(Self {
-            password: hashed_password,
-            ..self
-        })
-    }
 }
 
 #[derive(Debug, FromRow)]
@@ -353,3 +367,64 @@ impl CreateProtagonistSupporter {
         self.protagonist_id >= 0 && self.supporter_id >= 0 && self.protagonist_supporter_id >= 0
     }
 }
+
+#[derive(Debug, FromRow)]
+pub struct GetLoginFailures {
+    pub failures: i64,
+    pub last_failed_at: Option<String>,
+}
+
+impl GetLoginFailures {
+    pub fn is_valid(&self) -> bool {
+        self.failures >= 0 && (self.failures == 0 || self.last_failed_at.is_some())
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct VerifyEmail {
+    pub account_id: i64,
+}
+
+impl VerifyEmail {
+    pub fn is_valid(&self) -> bool {
+        self.account_id >= 0
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct ResetPassword {
+    pub account_id: i64,
+}
+
+impl ResetPassword {
+    pub fn is_valid(&self) -> bool {
+        self.account_id >= 0
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetAccountTotp {
+    pub account_id: i64,
+    pub login_id: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+}
+
+impl GetAccountTotp {
+    pub fn is_valid(&self) -> bool {
+        self.account_id >= 0 && !self.login_id.is_empty()
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct GetLoginChallenge {
+    pub login_challenge_id: i64,
+    pub account_id: i64,
+    pub attempts: i32,
+}
+
+impl GetLoginChallenge {
+    pub fn is_valid(&self) -> bool {
+        self.login_challenge_id >= 0 && self.account_id >= 0
+    }
+}
@@ -1,5 +1,6 @@
 use crate::domain::entity;
 use crate::driver::model;
+use chrono::{DateTime, Utc};
 use sqlx;
 
 #[derive(Clone)]
@@ -19,7 +20,7 @@ impl SupportRepository {
         let row = sqlx::query_as::<_, model::GetProtagonist>(
             r#"
             SELECT 
-                protagonist_id, last_name, first_name, login_id, password, email, country
+                protagonist_id, last_name, first_name, login_id, password, email, country, email_verified, version
             FROM 
                 protagonists
             WHERE 
@@ -36,12 +37,16 @@ impl SupportRepository {
 
         Ok(Some(entity::Protagonist::new(
             row.protagonist_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
@@ -56,7 +61,7 @@ impl SupportRepository {
             VALUES 
                 ($1, $2, $3, $4, $5, $6)
             RETURNING 
-                protagonist_id, last_name, first_name, login_id, password, email, country;
+                protagonist_id, last_name, first_name, login_id, password, email, country, email_verified, version;
             "#,
         )
         .bind(protagonist.last_name)
@@ -74,36 +79,46 @@ impl SupportRepository {
 
         Ok(Some(entity::Protagonist::new(
             row.protagonist_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
+    /// `versions` is the If-Match precondition, None accepts any, a stale one finds no row.
     pub async fn update_protagonist(
         &self,
         protagonist: model::UpdateProtagonist,
+        versions: Option<&[i64]>,
     ) -> Result<Option<entity::Protagonist>, sqlx::Error> {
         let row = sqlx::query_as::<_, model::UpdateProtagonist>(
             r#"
             UPDATE protagonists
-                SET last_name = $1, first_name = $2, login_id = $3, password = $4, email = $5, country = $6
+                SET last_name = $1, first_name = $2, login_id = $3, email = $4, country = $5,
+                    -- a new address has to be confirmed again
+                    email_verified = email_verified AND email = $4,
+                    version = version + 1
             WHERE 
-                protagonist_id = $7
+                protagonist_id = $6
+                AND ($7::BIGINT[] IS NULL OR version = ANY($7))
             RETURNING 
-                protagonist_id, last_name, first_name, login_id, password, email, country;
+                protagonist_id, last_name, first_name, login_id, password, email, country, email_verified, version;
             "#,
         )
         .bind(protagonist.last_name)
         .bind(protagonist.first_name)
         .bind(protagonist.login_id)
-        .bind(protagonist.password)
         .bind(protagonist.email)
         .bind(protagonist.country)
         .bind(protagonist.protagonist_id)
+        .bind(versions)
         .fetch_one(&self.db)
         .await?;
 
@@ -113,39 +128,110 @@ impl SupportRepository {
 
         Ok(Some(entity::Protagonist::new(
             row.protagonist_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
-    pub async fn delete_protagonist(&self, id: i64) -> Result<Option<()>, sqlx::Error> {
-        sqlx::query(
+    /// Updates the supplied profile fields and leaves the others as they are, `versions` as for `update_protagonist`.
+    pub async fn patch_protagonist(
+        &self,
+        protagonist_id: i64,
+        last_name: Option<&str>,
+        first_name: Option<&str>,
+        email: Option<&str>,
+        country: Option<&str>,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<entity::Protagonist>, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::UpdateProtagonist>(
+            r#"
+            UPDATE protagonists
+                SET last_name = COALESCE($2, last_name),
+                    first_name = COALESCE($3, first_name),
+                    email = COALESCE($4, email),
+                    country = COALESCE($5, country),
+                    -- a new address has to be confirmed again
+                    email_verified = email_verified AND email = COALESCE($4, email),
+                    version = version + 1,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                protagonist_id = $1
+                AND ($6::BIGINT[] IS NULL OR version = ANY($6))
+            RETURNING
+                protagonist_id, last_name, first_name, login_id, password, email, country, email_verified, version;
+            "#,
+        )
+        .bind(protagonist_id)
+        .bind(last_name)
+        .bind(first_name)
+        .bind(email)
+        .bind(country)
+        .bind(versions)
+        .fetch_one(&self.db)
+        .await?;
+
+        if !row.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Protagonist::new(
+            row.protagonist_id,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
+        )))
+    }
+
+    /// None when no protagonist with one of `versions` exists.
+    pub async fn delete_protagonist(
+        &self,
+        id: i64,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
             r#"
             DELETE FROM 
                 protagonists
             WHERE 
-                protagonist_id = $1;
+                protagonist_id = $1
+                AND ($2::BIGINT[] IS NULL OR version = ANY($2));
             "#,
         )
         .bind(i64::try_from(id).unwrap())
+        .bind(versions)
         .execute(&self.db)
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
         Ok(Some(()))
     }
 
-    pub async fn get_protagonist_by_login_id_and_password(
+    pub async fn get_protagonist_by_login_id(
         &self,
         login_id: &str,
     ) -> Result<Option<entity::Protagonist>, sqlx::Error> {
         let row = sqlx::query_as::<_, model::GetProtagonist>(
             r#"
             SELECT 
-                protagonist_id, last_name, first_name, login_id, password, email, country
+                protagonist_id, last_name, first_name, login_id, password, email, country, email_verified, version
             FROM 
                 protagonists
             WHERE 
@@ -162,12 +248,53 @@ impl SupportRepository {
 
         Ok(Some(entity::Protagonist::new(
             row.protagonist_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
+        )))
+    }
+
+    pub async fn get_protagonist_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<entity::Protagonist>, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::GetProtagonist>(
+            r#"
+            SELECT 
+                protagonist_id, last_name, first_name, login_id, password, email, country, email_verified, version
+            FROM 
+                protagonists
+            WHERE 
+                email = $1;
+            "#,
+        )
+        .bind(email)
+        .fetch_one(&self.db)
+        .await?;
+
+        if !row.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Protagonist::new(
+            row.protagonist_id,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
@@ -178,7 +305,7 @@ impl SupportRepository {
         let row = sqlx::query_as::<_, model::GetSupporter>(
             r#"
             SELECT 
-                supporter_id, last_name, first_name, login_id, password, email, country
+                supporter_id, last_name, first_name, login_id, password, email, country, email_verified, version
             FROM 
                 supporters
             WHERE 
@@ -195,12 +322,16 @@ impl SupportRepository {
 
         Ok(Some(entity::Supporter::new(
             row.supporter_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
@@ -215,7 +346,7 @@ impl SupportRepository {
             VALUES 
                 ($1, $2, $3, $4, $5, $6)
             RETURNING 
-                supporter_id, last_name, first_name, login_id, password, email, country;
+                supporter_id, last_name, first_name, login_id, password, email, country, email_verified, version;
             "#,
         )
         .bind(supporter.last_name)
@@ -233,36 +364,46 @@ impl SupportRepository {
 
         Ok(Some(entity::Supporter::new(
             row.supporter_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
+    /// `versions` is the If-Match precondition, None accepts any, a stale one finds no row.
     pub async fn update_supporter(
         &self,
         supporter: model::UpdateSupporter,
+        versions: Option<&[i64]>,
     ) -> Result<Option<entity::Supporter>, sqlx::Error> {
         let row = sqlx::query_as::<_, model::UpdateSupporter>(
             r#"
             UPDATE supporters
-                SET last_name = $1, first_name = $2, login_id = $3, password = $4, email = $5, country = $6
+                SET last_name = $1, first_name = $2, login_id = $3, email = $4, country = $5,
+                    -- a new address has to be confirmed again
+                    email_verified = email_verified AND email = $4,
+                    version = version + 1
             WHERE 
-                supporter_id = $7
+                supporter_id = $6
+                AND ($7::BIGINT[] IS NULL OR version = ANY($7))
             RETURNING 
-                supporter_id, last_name, first_name, login_id, password, email, country;
+                supporter_id, last_name, first_name, login_id, password, email, country, email_verified, version;
             "#,
         )
         .bind(supporter.last_name)
         .bind(supporter.first_name)
         .bind(supporter.login_id)
-        .bind(supporter.password)
         .bind(supporter.email)
         .bind(supporter.country)
         .bind(supporter.supporter_id)
+        .bind(versions)
         .fetch_one(&self.db)
         .await?;
 
@@ -272,39 +413,110 @@ impl SupportRepository {
 
         Ok(Some(entity::Supporter::new(
             row.supporter_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
-    pub async fn delete_supporter(&self, id: i64) -> Result<Option<()>, sqlx::Error> {
-        sqlx::query(
+    /// Updates the supplied profile fields and leaves the others as they are, `versions` as for `update_supporter`.
+    pub async fn patch_supporter(
+        &self,
+        supporter_id: i64,
+        last_name: Option<&str>,
+        first_name: Option<&str>,
+        email: Option<&str>,
+        country: Option<&str>,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<entity::Supporter>, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::UpdateSupporter>(
+            r#"
+            UPDATE supporters
+                SET last_name = COALESCE($2, last_name),
+                    first_name = COALESCE($3, first_name),
+                    email = COALESCE($4, email),
+                    country = COALESCE($5, country),
+                    -- a new address has to be confirmed again
+                    email_verified = email_verified AND email = COALESCE($4, email),
+                    version = version + 1,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                supporter_id = $1
+                AND ($6::BIGINT[] IS NULL OR version = ANY($6))
+            RETURNING
+                supporter_id, last_name, first_name, login_id, password, email, country, email_verified, version;
+            "#,
+        )
+        .bind(supporter_id)
+        .bind(last_name)
+        .bind(first_name)
+        .bind(email)
+        .bind(country)
+        .bind(versions)
+        .fetch_one(&self.db)
+        .await?;
+
+        if !row.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Supporter::new(
+            row.supporter_id,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
+        )))
+    }
+
+    /// None when no supporter with one of `versions` exists.
+    pub async fn delete_supporter(
+        &self,
+        id: i64,
+        versions: Option<&[i64]>,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
             r#"
             DELETE FROM 
                 supporters
             WHERE 
-                supporter_id = $1;
+                supporter_id = $1
+                AND ($2::BIGINT[] IS NULL OR version = ANY($2));
             "#,
         )
         .bind(i64::try_from(id).unwrap())
+        .bind(versions)
         .execute(&self.db)
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
         Ok(Some(()))
     }
 
-    pub async fn get_supporter_by_login_id_and_password(
+    pub async fn get_supporter_by_login_id(
         &self,
         login_id: &str,
     ) -> Result<Option<entity::Supporter>, sqlx::Error> {
         let row = sqlx::query_as::<_, model::GetSupporter>(
             r#"
             SELECT 
-                supporter_id, last_name, first_name, login_id, password, email, country
+                supporter_id, last_name, first_name, login_id, password, email, country, email_verified, version
             FROM 
                 supporters
             WHERE 
@@ -321,12 +533,53 @@ impl SupportRepository {
 
         Ok(Some(entity::Supporter::new(
             row.supporter_id,
-            row.last_name,
-            row.first_name,
-            row.login_id,
-            row.password,
-            row.email,
-            row.country,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
+        )))
+    }
+
+    pub async fn get_supporter_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<entity::Supporter>, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::GetSupporter>(
+            r#"
+            SELECT 
+                supporter_id, last_name, first_name, login_id, password, email, country, email_verified, version
+            FROM 
+                supporters
+            WHERE 
+                email = $1;
+            "#,
+        )
+        .bind(email)
+        .fetch_one(&self.db)
+        .await?;
+
+        if !row.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::Supporter::new(
+            row.supporter_id,
+            entity::AccountProfile::new(
+                row.last_name,
+                row.first_name,
+                row.login_id,
+                row.password,
+                row.email,
+                row.country,
+            ),
+            row.email_verified,
+            row.version,
         )))
     }
 
@@ -415,4 +668,848 @@ impl SupportRepository {
 
         Ok(Some(()))
     }
+
+    pub async fn record_login_attempt(
+        &self,
+        account_type: &str,
+        login_id: &str,
+        ip_address: Option<&str>,
+        succeeded: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                login_attempts (account_type, login_id, ip_address, succeeded)
+            VALUES
+                ($1, $2, $3, $4);
+            "#,
+        )
+        .bind(account_type)
+        .bind(login_id)
+        .bind(ip_address)
+        .bind(succeeded)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Failures within the window since the last successful login of `login_id`.
+    pub async fn get_login_failures_by_login_id(
+        &self,
+        account_type: &str,
+        login_id: &str,
+        window_seconds: i64,
+    ) -> Result<entity::LoginFailures, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::GetLoginFailures>(
+            r#"
+            SELECT
+                COUNT(*) AS failures,
+                to_char(MAX(la.attempted_at), 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS last_failed_at
+            FROM
+                login_attempts AS la
+            WHERE
+                la.account_type = $1
+                AND la.login_id = $2
+                AND NOT la.succeeded
+                AND la.attempted_at > CURRENT_TIMESTAMP - make_interval(secs => $3)
+                AND la.attempted_at > COALESCE(
+                    (
+                        SELECT MAX(s.attempted_at) FROM login_attempts AS s
+                        WHERE s.account_type = $1 AND s.login_id = $2 AND s.succeeded
+                    ),
+                    '-infinity'::TIMESTAMP
+                );
+            "#,
+        )
+        .bind(account_type)
+        .bind(login_id)
+        .bind(window_seconds as f64)
+        .fetch_one(&self.db)
+        .await?;
+
+        Self::login_failures_from_row(row)
+    }
+
+    /// Failures within the window from `ip_address`, whichever accounts they targeted.
+    pub async fn get_login_failures_by_ip_address(
+        &self,
+        ip_address: &str,
+        window_seconds: i64,
+    ) -> Result<entity::LoginFailures, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::GetLoginFailures>(
+            r#"
+            SELECT
+                COUNT(*) AS failures,
+                to_char(MAX(la.attempted_at), 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS last_failed_at
+            FROM
+                login_attempts AS la
+            WHERE
+                la.ip_address = $1
+                AND NOT la.succeeded
+                AND la.attempted_at > CURRENT_TIMESTAMP - make_interval(secs => $2);
+            "#,
+        )
+        .bind(ip_address)
+        .bind(window_seconds as f64)
+        .fetch_one(&self.db)
+        .await?;
+
+        Self::login_failures_from_row(row)
+    }
+
+    pub async fn create_email_verification_token(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        email: &str,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                email_verification_tokens (account_type, account_id, email, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3, $4, $5::TIMESTAMP);
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .bind(email)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.db)
+        .await?;
+
+        Ok(Some(()))
+    }
+
+    pub async fn verify_protagonist_email(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        self.verify_email(
+            entity::PROTAGONIST_ACCOUNT,
+            "protagonists",
+            "protagonist_id",
+            token_hash,
+        )
+        .await
+    }
+
+    pub async fn verify_supporter_email(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        self.verify_email(
+            entity::SUPPORTER_ACCOUNT,
+            "supporters",
+            "supporter_id",
+            token_hash,
+        )
+        .await
+    }
+
+    /// Consumes an unused, unexpired token and marks the address it was mailed to as verified.
+    async fn verify_email(
+        &self,
+        account_type: &str,
+        table: &'static str,
+        id_column: &'static str,
+        token_hash: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+
+        // the used_at guard lets a token confirm once, the email match voids it after an address change
+        let row = sqlx::query_as::<_, model::VerifyEmail>(&format!(
+            r#"
+            UPDATE email_verification_tokens AS t
+                SET used_at = CURRENT_TIMESTAMP
+            FROM
+                {table} AS a
+            WHERE
+                t.token_hash = $1
+                AND t.account_type = $2
+                AND t.used_at IS NULL
+                AND t.expires_at > CURRENT_TIMESTAMP
+                AND a.{id_column} = t.account_id
+                AND a.email = t.email
+            RETURNING
+                t.account_id;
+            "#
+        ))
+        .bind(token_hash)
+        .bind(account_type)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !row.is_valid() {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET email_verified = TRUE,
+                    version = version + 1,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                {id_column} = $1;
+            "#
+        ))
+        .bind(row.account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(row.account_id))
+    }
+
+    pub async fn create_password_reset_token(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                password_reset_tokens (account_type, account_id, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3, $4::TIMESTAMP);
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.db)
+        .await?;
+
+        Ok(Some(()))
+    }
+
+    pub async fn reset_protagonist_password(
+        &self,
+        token_hash: &str,
+        password: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        self.reset_password(
+            entity::PROTAGONIST_ACCOUNT,
+            "protagonists",
+            "protagonist_id",
+            token_hash,
+            password,
+        )
+        .await
+    }
+
+    pub async fn reset_supporter_password(
+        &self,
+        token_hash: &str,
+        password: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        self.reset_password(
+            entity::SUPPORTER_ACCOUNT,
+            "supporters",
+            "supporter_id",
+            token_hash,
+            password,
+        )
+        .await
+    }
+
+    /// Consumes the token, stores the new hash and ends every session of the account in one transaction.
+    async fn reset_password(
+        &self,
+        account_type: &str,
+        table: &'static str,
+        id_column: &'static str,
+        token_hash: &str,
+        password: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+
+        // the used_at guard lets a concurrent second reset with the same token find nothing
+        let row = sqlx::query_as::<_, model::ResetPassword>(
+            r#"
+            UPDATE password_reset_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                token_hash = $1
+                AND account_type = $2
+                AND used_at IS NULL
+                AND expires_at > CURRENT_TIMESTAMP
+            RETURNING
+                account_id;
+            "#,
+        )
+        .bind(token_hash)
+        .bind(account_type)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !row.is_valid() {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET password = $2,
+                    sessions_revoked_at = CURRENT_TIMESTAMP,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                {id_column} = $1;
+            "#
+        ))
+        .bind(row.account_id)
+        .bind(password)
+        .execute(&mut *tx)
+        .await?;
+
+        // other links mailed before this reset must not undo it
+        sqlx::query(
+            r#"
+            UPDATE password_reset_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                account_type = $1
+                AND account_id = $2
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(account_type)
+        .bind(row.account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(row.account_id))
+    }
+
+    pub async fn change_protagonist_password(
+        &self,
+        protagonist_id: i64,
+        current_password: &str,
+        password: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        self.change_password(
+            entity::PROTAGONIST_ACCOUNT,
+            "protagonists",
+            "protagonist_id",
+            protagonist_id,
+            current_password,
+            password,
+        )
+        .await
+    }
+
+    pub async fn change_supporter_password(
+        &self,
+        supporter_id: i64,
+        current_password: &str,
+        password: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        self.change_password(
+            entity::SUPPORTER_ACCOUNT,
+            "supporters",
+            "supporter_id",
+            supporter_id,
+            current_password,
+            password,
+        )
+        .await
+    }
+
+    /// Replaces the hash while it still is `current_password` and ends every session of the account.
+    async fn change_password(
+        &self,
+        account_type: &str,
+        table: &'static str,
+        id_column: &'static str,
+        account_id: i64,
+        current_password: &str,
+        password: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+
+        // a concurrent change or reset makes the checked password stale
+        let changed = sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET password = $3,
+                    sessions_revoked_at = CURRENT_TIMESTAMP,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                {id_column} = $1
+                AND password = $2;
+            "#
+        ))
+        .bind(account_id)
+        .bind(current_password)
+        .bind(password)
+        .execute(&mut *tx)
+        .await?;
+
+        if changed.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        // reset links mailed before the change must not undo it
+        sqlx::query(
+            r#"
+            UPDATE password_reset_tokens
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                account_type = $1
+                AND account_id = $2
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+
+    pub async fn is_protagonist_session_revoked(
+        &self,
+        protagonist_id: i64,
+        issued_at: &str,
+    ) -> Result<bool, sqlx::Error> {
+        self.is_session_revoked("protagonists", "protagonist_id", protagonist_id, issued_at)
+            .await
+    }
+
+    pub async fn is_supporter_session_revoked(
+        &self,
+        supporter_id: i64,
+        issued_at: &str,
+    ) -> Result<bool, sqlx::Error> {
+        self.is_session_revoked("supporters", "supporter_id", supporter_id, issued_at)
+            .await
+    }
+
+    /// True when every session of the account was ended after `issued_at`.
+    async fn is_session_revoked(
+        &self,
+        table: &'static str,
+        id_column: &'static str,
+        account_id: i64,
+        issued_at: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let revoked = sqlx::query_scalar::<_, bool>(&format!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM {table}
+                WHERE
+                    {id_column} = $1
+                    AND $2::TIMESTAMP < sessions_revoked_at
+            );
+            "#
+        ))
+        .bind(account_id)
+        .bind(issued_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(revoked)
+    }
+
+    pub async fn get_account_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+    ) -> Result<Option<entity::AccountTotp>, sqlx::Error> {
+        let (table, id_column) = Self::account_table(account_type)?;
+        let row = sqlx::query_as::<_, model::GetAccountTotp>(&format!(
+            r#"
+            SELECT
+                {id_column} AS account_id, login_id, totp_secret, totp_enabled
+            FROM
+                {table}
+            WHERE
+                {id_column} = $1;
+            "#
+        ))
+        .bind(account_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        if !row.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::AccountTotp::new(
+            row.account_id,
+            row.login_id,
+            row.totp_secret,
+            row.totp_enabled,
+        )))
+    }
+
+    /// Stores a secret awaiting confirmation, None when TOTP is already enabled.
+    pub async fn set_pending_totp_secret(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        secret: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let (table, id_column) = Self::account_table(account_type)?;
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET totp_secret = $2,
+                    totp_last_step = NULL,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                {id_column} = $1
+                AND totp_enabled = FALSE;
+            "#
+        ))
+        .bind(account_id)
+        .bind(secret)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    /// Enables the pending `secret` and replaces the recovery codes, None when it is no longer pending.
+    pub async fn enable_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        secret: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<Option<()>, sqlx::Error> {
+        let (table, id_column) = Self::account_table(account_type)?;
+        let mut tx = self.db.begin().await?;
+
+        let enabled = sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET totp_enabled = TRUE,
+                    totp_last_step = $3,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                {id_column} = $1
+                AND totp_enabled = FALSE
+                AND totp_secret = $2;
+            "#
+        ))
+        .bind(account_id)
+        .bind(secret)
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+
+        if enabled.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE
+                account_type = $1
+                AND account_id = $2;
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO
+                totp_recovery_codes (account_type, account_id, code_hash)
+            SELECT
+                $1, $2, code_hash
+            FROM
+                UNNEST($3::VARCHAR[]) AS code_hash;
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .bind(recovery_code_hashes)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+
+    /// Forgets the secret and the recovery codes.
+    pub async fn disable_totp(
+        &self,
+        account_type: &str,
+        account_id: i64,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let (table, id_column) = Self::account_table(account_type)?;
+        let mut tx = self.db.begin().await?;
+
+        let disabled = sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET totp_enabled = FALSE,
+                    totp_secret = NULL,
+                    totp_last_step = NULL,
+                    updated_at = CURRENT_TIMESTAMP
+            WHERE
+                {id_column} = $1;
+            "#
+        ))
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if disabled.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE
+                account_type = $1
+                AND account_id = $2;
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(()))
+    }
+
+    /// Records the step of an accepted code, None when it or a later one was accepted before.
+    pub async fn use_totp_step(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        step: i64,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let (table, id_column) = Self::account_table(account_type)?;
+        // a code is only good once, and never after a later one was used
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE {table}
+                SET totp_last_step = $2
+            WHERE
+                {id_column} = $1
+                AND totp_enabled = TRUE
+                AND (totp_last_step IS NULL OR totp_last_step < $2);
+            "#
+        ))
+        .bind(account_id)
+        .bind(step)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    /// Marks an unused recovery code used, None when the account has no such code.
+    pub async fn use_recovery_code(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        code_hash: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE totp_recovery_codes
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                account_type = $1
+                AND account_id = $2
+                AND code_hash = $3
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .bind(code_hash)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    pub async fn create_login_challenge(
+        &self,
+        account_type: &str,
+        account_id: i64,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<Option<()>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO
+                login_challenges (account_type, account_id, token_hash, expires_at)
+            VALUES
+                ($1, $2, $3, $4::TIMESTAMP);
+            "#,
+        )
+        .bind(account_type)
+        .bind(account_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.db)
+        .await?;
+
+        Ok(Some(()))
+    }
+
+    /// The unused and unexpired challenge of `account_type` the token belongs to.
+    pub async fn get_login_challenge(
+        &self,
+        account_type: &str,
+        token_hash: &str,
+    ) -> Result<Option<entity::LoginChallenge>, sqlx::Error> {
+        let row = sqlx::query_as::<_, model::GetLoginChallenge>(
+            r#"
+            SELECT
+                login_challenge_id, account_id, attempts
+            FROM
+                login_challenges
+            WHERE
+                token_hash = $1
+                AND account_type = $2
+                AND used_at IS NULL
+                AND expires_at > CURRENT_TIMESTAMP;
+            "#,
+        )
+        .bind(token_hash)
+        .bind(account_type)
+        .fetch_one(&self.db)
+        .await?;
+
+        if !row.is_valid() {
+            return Ok(None);
+        }
+
+        Ok(Some(entity::LoginChallenge::new(
+            row.login_challenge_id,
+            row.account_id,
+            row.attempts,
+        )))
+    }
+
+    /// Counts a rejected code and voids the challenge once `max_attempts` are reached.
+    pub async fn record_login_challenge_failure(
+        &self,
+        login_challenge_id: i64,
+        max_attempts: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE login_challenges
+                SET attempts = attempts + 1,
+                    used_at = CASE
+                        WHEN attempts + 1 >= $2 THEN CURRENT_TIMESTAMP
+                        ELSE used_at
+                    END
+            WHERE
+                login_challenge_id = $1;
+            "#,
+        )
+        .bind(login_challenge_id)
+        .bind(max_attempts)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the challenge used, None when it was completed or voided concurrently.
+    pub async fn complete_login_challenge(
+        &self,
+        login_challenge_id: i64,
+    ) -> Result<Option<()>, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE login_challenges
+                SET used_at = CURRENT_TIMESTAMP
+            WHERE
+                login_challenge_id = $1
+                AND used_at IS NULL;
+            "#,
+        )
+        .bind(login_challenge_id)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(()))
+    }
+
+    /// Table and key column of an account type, the only identifiers spliced into queries.
+    fn account_table(account_type: &str) -> Result<(&'static str, &'static str), sqlx::Error> {
+        match account_type {
+            entity::PROTAGONIST_ACCOUNT => Ok(("protagonists", "protagonist_id")),
+            entity::SUPPORTER_ACCOUNT => Ok(("supporters", "supporter_id")),
+            _ => Err(sqlx::Error::Protocol(format!(
+                "unknown account type: {}",
+                account_type
+            ))),
+        }
+    }
+
+    fn login_failures_from_row(
+        row: model::GetLoginFailures,
+    ) -> Result<entity::LoginFailures, sqlx::Error> {
+        if !row.is_valid() {
+            return Err(sqlx::Error::Decode(
+                format!("invalid login failures: {:?}", row).into(),
+            ));
+        }
+
+        let last_failed_at = match row.last_failed_at.as_deref() {
+            Some(last_failed_at) => Some(
+                DateTime::parse_from_rfc3339(last_failed_at)
+                    .map_err(|err| sqlx::Error::Decode(err.into()))?
+                    .with_timezone(&Utc),
+            ),
+            None => None,
+        };
+
+        Ok(entity::LoginFailures::new(row.failures, last_failed_at))
+    }
 }
@@ -1,14 +1,30 @@
-use super::response::ErrorResponse;
+use super::response::ProblemDetails;
+use crate::domain::error::SupportError;
+use crate::domain::service::SupportService;
 use crate::util;
-use axum::{extract::State, http, middleware::Next, response::Response, Json};
+use axum::{
+    body::{boxed, Full},
+    extract::State,
+    http,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
 use std::sync::Arc;
 use tracing::{error, info};
 
+/// What `verify_token_middleware` checks a bearer token against.
+#[derive(Clone)]
+pub struct TokenVerification {
+    pub secret_key: Arc<String>,
+    /// Knows which accounts ended their sessions, e.g. with a password reset.
+    pub service: SupportService,
+}
+
 pub async fn verify_token_middleware<B>(
-    State(secret_key): State<Arc<String>>,
+    State(verification): State<TokenVerification>,
     mut req: http::Request<B>,
     next: Next<B>,
-) -> Result<Response, (http::StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, SupportError> {
     let auth_header = req
         .headers()
         .get(http::header::AUTHORIZATION)
@@ -18,40 +34,29 @@ pub async fn verify_token_middleware<B>(
         auth_header
     } else {
         error!("verify_token_middleware: Authorization header not found");
-        return Err((
-            http::StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Token is invalid"),
-                message: format!("Authorization header not found"),
-            }),
+        return Err(SupportError::Unauthorized(
+            "Authorization header not found".to_string(),
         ));
     };
 
     let bearer = auth_header.split_whitespace().nth(0).unwrap_or_default();
     if bearer != "Bearer" {
         error!("verify_token_middleware: Authorization header is not Bearer");
-        return Err((
-            http::StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Token is invalid"),
-                message: format!("Authorization header is not Bearer"),
-            }),
+        return Err(SupportError::Unauthorized(
+            "Authorization header is not Bearer".to_string(),
         ));
     }
 
     let token = auth_header.split_whitespace().nth(1).unwrap_or_default();
     if token.is_empty() {
         error!("verify_token_middleware: Token is empty");
-        return Err((
-            http::StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Token is invalid"),
-                message: format!("Token is empty"),
-            }),
-        ));
+        return Err(SupportError::Unauthorized("Token is empty".to_string()));
     }
 
-    let token = util::auth::validate_token(token, &secret_key);
+    let token = verification
+        .service
+        .verify_token(token, &verification.secret_key)
+        .await;
     match token {
         Ok(token) => {
             info!("verify_token_middleware: Token is valid");
@@ -67,6 +72,66 @@ pub async fn verify_token_middleware<B>(
     }
 }
 
+/// Scopes and roles a group of routes demands from an already verified token.
+#[derive(Debug, Clone, Copy)]
+pub struct AccessPolicy {
+    scopes: &'static [&'static str],
+    roles: &'static [&'static str],
+}
+
+impl AccessPolicy {
+    pub const fn scopes(scopes: &'static [&'static str]) -> Self {
+        Self { scopes, roles: &[] }
+    }
+
+    /// Restricts the routes to tokens carrying one of `roles` as well.
+    pub const fn with_roles(self, roles: &'static [&'static str]) -> Self {
+        Self { roles, ..self }
+    }
+
+    pub fn check(&self, token: &util::auth::Token) -> Result<(), SupportError> {
+        if let Some(scope) = self.scopes.iter().find(|scope| !token.has_scope(scope)) {
+            return Err(SupportError::Forbidden(format!(
+                "Missing required scope: {}",
+                scope
+            )));
+        }
+
+        if !self.roles.is_empty()
+            && !token
+                .role
+                .as_deref()
+                .is_some_and(|role| self.roles.contains(&role))
+        {
+            return Err(SupportError::Forbidden(format!(
+                "Missing required role: {}",
+                self.roles.join(" or ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn require_access_middleware<B>(
+    State(policy): State<AccessPolicy>,
+    req: http::Request<B>,
+    next: Next<B>,
+) -> Result<Response, SupportError> {
+    // verify_token_middleware runs first and leaves the decoded claims behind
+    let token = req
+        .extensions()
+        .get::<Arc<util::auth::Token>>()
+        .ok_or_else(|| SupportError::Unauthorized("Token is missing".to_string()))?;
+
+    if let Err(err) = policy.check(token) {
+        error!("require_access_middleware: {}", err);
+        return Err(err);
+    }
+
+    Ok(next.run(req).await)
+}
+
 pub async fn request_log_middleware<B>(
     req: http::Request<B>,
     next: Next<B>,
@@ -77,3 +142,49 @@ pub async fn request_log_middleware<B>(
 
     Ok(res)
 }
+
+pub async fn problem_details_middleware<B>(req: http::Request<B>, next: Next<B>) -> Response {
+    let instance = req.uri().path().to_string();
+    let res = next.run(req).await;
+    let (mut parts, body) = res.into_parts();
+
+    // errors raised by handlers only lack the request path
+    if let Some(problem) = parts.extensions.remove::<ProblemDetails>() {
+        let problem = problem.with_instance(instance);
+        let body = serde_json::to_vec(&problem).unwrap_or_default();
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+        return Response::from_parts(parts, boxed(Full::from(body)));
+    }
+
+    // extractor rejections and unmatched routes come back as plain text or empty
+    let is_error = parts.status.is_client_error() || parts.status.is_server_error();
+    let is_plain = parts
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.starts_with("text/plain"));
+    if !is_error || !is_plain {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let detail = match String::from_utf8_lossy(&bytes).trim() {
+        "" => parts
+            .status
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_string(),
+        detail => detail.to_string(),
+    };
+
+    let mut res = ProblemDetails::new(parts.status, detail)
+        .with_instance(instance)
+        .into_response();
+    res.extensions_mut().remove::<ProblemDetails>();
+    for (name, value) in parts.headers.iter() {
+        if name != http::header::CONTENT_TYPE && name != http::header::CONTENT_LENGTH {
+            res.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    res
+}
@@ -1,142 +1,312 @@
-use anyhow::{anyhow, Ok, Result};
+use crate::domain::error::{FieldErrors, SupportError};
 use regex::Regex;
 use serde::Deserialize;
 
+const NAME_PATTERN: &str = r"^[\p{L}\p{N}\s'-]+$";
+const LOGIN_PATTERN: &str = r"^[a-zA-Z0-9]+$";
+const EMAIL_PATTERN: &str = r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$";
+
 #[derive(Deserialize, Debug)]
-pub struct GetProtagonistRequest {
+pub struct LoginRequest {
     pub login_id: String,
     pub password: String,
 }
 
-impl GetProtagonistRequest {
-    pub fn new(login_id: String, password: String) -> Self {
-        Self { login_id, password }
-    }
-
-    pub async fn validate(self) -> Result<Self, anyhow::Error> {
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
+impl LoginRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        let login_regex = Regex::new(LOGIN_PATTERN).unwrap();
         if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
+            errors.add("login_id", "Invalid login id format.");
         }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
+        if self.password.is_empty() || self.password.len() > 255 {
+            errors.add("password", "Password must be between 1 and 255 bytes.");
         }
 
-        Ok(self)
+        errors.into_result()
     }
 }
 
 #[derive(Deserialize, Debug)]
-pub struct CreateProtagonistRequest {
-    pub last_name: String,
-    pub first_name: String,
-    pub login_id: String,
-    pub password: String,
-    pub email: String,
-    pub country: String,
+pub struct VerifyEmailRequest {
+    pub token: String,
 }
 
-impl CreateProtagonistRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let name_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !name_regex.is_match(&self.last_name) {
-            return Err(anyhow!("Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
+impl VerifyEmailRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        if self.token.trim().is_empty() || self.token.len() > 255 {
+            errors.add("token", "Token must be between 1 and 255 bytes.");
         }
-        if !name_regex.is_match(&self.first_name) {
-            return Err(anyhow!("Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+impl ForgotPasswordRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        let email_regex = Regex::new(EMAIL_PATTERN).unwrap();
+        if !email_regex.is_match(&self.email) {
+            errors.add("email", "Invalid email format.");
         }
 
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+impl ResetPasswordRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        if self.token.trim().is_empty() || self.token.len() > 255 {
+            errors.add("token", "Token must be between 1 and 255 bytes.");
         }
+        // the same format accounts are created with
+        let login_regex = Regex::new(LOGIN_PATTERN).unwrap();
         if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
+            errors.add("password", "Invalid password format.");
         }
 
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(&self.email) {
-            return Err(anyhow!("Invalid email format."));
-        }
+        errors.into_result()
+    }
+}
 
-        if self.country.is_empty() {
-            return Err(anyhow!("Country cannot be empty."));
+#[derive(Deserialize, Debug)]
+pub struct LoginTotpRequest {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+impl LoginTotpRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        if self.challenge_token.trim().is_empty() || self.challenge_token.len() > 255 {
+            errors.add(
+                "challenge_token",
+                "Challenge token must be between 1 and 255 bytes.",
+            );
         }
+        validate_totp_code(&mut errors, &self.code);
 
-        Ok(())
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
+
+impl ConfirmTotpRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        validate_totp_code(&mut errors, &self.code);
+
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DisableTotpRequest {
+    pub code: String,
+}
+
+impl DisableTotpRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        validate_totp_code(&mut errors, &self.code);
+
+        errors.into_result()
+    }
+}
+
+/// Authenticator and recovery codes alike, which of the two it is gets decided when it is checked.
+fn validate_totp_code(errors: &mut FieldErrors, code: &str) {
+    if code.trim().is_empty() || code.len() > 64 {
+        errors.add("code", "Code must be between 1 and 64 bytes.");
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateProtagonistRequest {
+    pub last_name: String,
+    pub first_name: String,
+    pub login_id: String,
+    pub password: String,
+    pub email: String,
+    pub country: String,
+}
+
+impl CreateProtagonistRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        validate_person_fields(
+            &mut errors,
+            &self.last_name,
+            &self.first_name,
+            &self.login_id,
+            &self.password,
+            &self.email,
+            &self.country,
+        );
+
+        errors.into_result()
     }
 }
 
+/// Replaces the whole profile, the password only changes through its own endpoint.
 #[derive(Deserialize, Debug)]
 pub struct UpdateProtagonistRequest {
     pub protagonist_id: i64,
     pub last_name: String,
     pub first_name: String,
     pub login_id: String,
-    pub password: String,
     pub email: String,
     pub country: String,
 }
 
 impl UpdateProtagonistRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.protagonist_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-
-        let name_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !name_regex.is_match(&self.last_name) {
-            return Err(anyhow!("Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
-        }
-        if !name_regex.is_match(&self.first_name) {
-            return Err(anyhow!("Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
-        }
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        if self.protagonist_id < 0 {
+            errors.add("protagonist_id", "Invalid id format.");
+        }
+        validate_profile_fields(
+            &mut errors,
+            &self.last_name,
+            &self.first_name,
+            &self.login_id,
+            &self.email,
+            &self.country,
+        );
+
+        errors.into_result()
+    }
+}
 
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
-        }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
-        }
+/// Profile fields to change, absent ones keep their value.
+#[derive(Deserialize, Debug)]
+pub struct PatchProfileRequest {
+    pub last_name: Option<String>,
+    pub first_name: Option<String>,
+    pub email: Option<String>,
+    pub country: Option<String>,
+}
 
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(&self.email) {
-            return Err(anyhow!("Invalid email format."));
-        }
+impl PatchProfileRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        let name_regex = Regex::new(NAME_PATTERN).unwrap();
+        if let Some(last_name) = &self.last_name {
+            if !name_regex.is_match(last_name) {
+                errors.add("last_name", "Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+            }
+        }
+        if let Some(first_name) = &self.first_name {
+            if !name_regex.is_match(first_name) {
+                errors.add("first_name", "Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+            }
+        }
+        if let Some(email) = &self.email {
+            let email_regex = Regex::new(EMAIL_PATTERN).unwrap();
+            if !email_regex.is_match(email) {
+                errors.add("email", "Invalid email format.");
+            }
+        }
+        if let Some(country) = &self.country {
+            if country.is_empty() {
+                errors.add("country", "Country cannot be empty.");
+            }
+        }
+
+        errors.into_result()
+    }
+}
 
-        if self.country.is_empty() {
-            return Err(anyhow!("Country cannot be empty."));
-        }
+#[derive(Deserialize, Debug)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
 
-        Ok(())
+impl ChangePasswordRequest {
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        if self.current_password.is_empty() || self.current_password.len() > 255 {
+            errors.add(
+                "current_password",
+                "Password must be between 1 and 255 bytes.",
+            );
+        }
+        // the same format accounts are created with
+        let login_regex = Regex::new(LOGIN_PATTERN).unwrap();
+        if !login_regex.is_match(&self.new_password) {
+            errors.add("new_password", "Invalid password format.");
+        } else if self.new_password == self.current_password {
+            errors.add(
+                "new_password",
+                "New password must differ from the current password.",
+            );
+        }
+
+        errors.into_result()
     }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct GetSupporterRequest {
-    pub login_id: String,
-    pub password: String,
+fn validate_person_fields(
+    errors: &mut FieldErrors,
+    last_name: &str,
+    first_name: &str,
+    login_id: &str,
+    password: &str,
+    email: &str,
+    country: &str,
+) {
+    validate_profile_fields(errors, last_name, first_name, login_id, email, country);
+    if !Regex::new(LOGIN_PATTERN).unwrap().is_match(password) {
+        errors.add("password", "Invalid password format.");
+    }
 }
 
-impl GetSupporterRequest {
-    pub fn new(login_id: String, password: String) -> Self {
-        Self { login_id, password }
+fn validate_profile_fields(
+    errors: &mut FieldErrors,
+    last_name: &str,
+    first_name: &str,
+    login_id: &str,
+    email: &str,
+    country: &str,
+) {
+    let name_regex = Regex::new(NAME_PATTERN).unwrap();
+    if !name_regex.is_match(last_name) {
+        errors.add("last_name", "Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
+    }
+    if !name_regex.is_match(first_name) {
+        errors.add("first_name", "Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed.");
     }
 
-    pub async fn validate(self) -> Result<Self, anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
-        }
+    let login_regex = Regex::new(LOGIN_PATTERN).unwrap();
+    if !login_regex.is_match(login_id) {
+        errors.add("login_id", "Invalid login id format.");
+    }
 
-        let password_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !password_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
-        }
+    let email_regex = Regex::new(EMAIL_PATTERN).unwrap();
+    if !email_regex.is_match(email) {
+        errors.add("email", "Invalid email format.");
+    }
 
-        Ok(self)
+    if country.is_empty() {
+        errors.add("country", "Country cannot be empty.");
     }
 }
 
@@ -151,80 +321,49 @@ pub struct CreateSupporterRequest {
 }
 
 impl CreateSupporterRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let name_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !name_regex.is_match(&self.last_name) {
-            return Err(anyhow!("Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
-        }
-        if !name_regex.is_match(&self.first_name) {
-            return Err(anyhow!("Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
-        }
-
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
-        }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
-        }
-
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(&self.email) {
-            return Err(anyhow!("Invalid email format."));
-        }
-
-        if self.country.is_empty() {
-            return Err(anyhow!("Country cannot be empty."));
-        }
-
-        Ok(())
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        validate_person_fields(
+            &mut errors,
+            &self.last_name,
+            &self.first_name,
+            &self.login_id,
+            &self.password,
+            &self.email,
+            &self.country,
+        );
+
+        errors.into_result()
     }
 }
 
+/// Replaces the whole profile, the password only changes through its own endpoint.
 #[derive(Deserialize, Debug)]
 pub struct UpdateSupporterRequest {
     pub supporter_id: i64,
     pub last_name: String,
     pub first_name: String,
     pub login_id: String,
-    pub password: String,
     pub email: String,
     pub country: String,
 }
 
 impl UpdateSupporterRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.supporter_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-
-        let name_regex = Regex::new(r"^[\p{L}\p{N}\s'-]+$").unwrap();
-        if !name_regex.is_match(&self.last_name) {
-            return Err(anyhow!("Invalid last name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
-        }
-        if !name_regex.is_match(&self.first_name) {
-            return Err(anyhow!("Invalid first name: Contains invalid characters. Only alphabets, numbers, spaces, hyphens, and apostrophes are allowed."));
-        }
-
-        let login_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !login_regex.is_match(&self.login_id) {
-            return Err(anyhow!("Invalid login id format."));
-        }
-        if !login_regex.is_match(&self.password) {
-            return Err(anyhow!("Invalid password format."));
-        }
-
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(&self.email) {
-            return Err(anyhow!("Invalid email format."));
-        }
-
-        if self.country.is_empty() {
-            return Err(anyhow!("Country cannot be empty."));
-        }
-
-        Ok(())
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        let mut errors = FieldErrors::new();
+        if self.supporter_id < 0 {
+            errors.add("supporter_id", "Invalid id format.");
+        }
+        validate_profile_fields(
+            &mut errors,
+            &self.last_name,
+            &self.first_name,
+            &self.login_id,
+            &self.email,
+            &self.country,
+        );
+
+        errors.into_result()
     }
 }
 
@@ -235,15 +374,8 @@ pub struct CreateProtagonistSupporterRequest {
 }
 
 impl CreateProtagonistSupporterRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.protagonist_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-        if !id_regex.is_match(&self.supporter_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        // u64 path segments are already checked by the extractor
         Ok(())
     }
 }
@@ -254,12 +386,8 @@ pub struct DeleteProtagonistSupporterRequest {
 }
 
 impl DeleteProtagonistSupporterRequest {
-    pub async fn validate(&self) -> Result<(), anyhow::Error> {
-        let id_regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-        if !id_regex.is_match(&self.protagonist_supporter_id.to_string()) {
-            return Err(anyhow!("Invalid id format."));
-        }
-
+    pub async fn validate(&self) -> Result<(), SupportError> {
+        // u64 path segments are already checked by the extractor
         Ok(())
     }
 }
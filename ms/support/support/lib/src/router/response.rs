@@ -1,9 +1,17 @@
+use crate::domain::error::{FieldErrors, SupportError};
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
+use tracing::error;
+
+/// A representation and the row version its `ETag` is made from.
+pub struct Versioned<T> {
+    pub version: i64,
+    pub body: T,
+}
 
 #[derive(Serialize)]
 pub struct HealthCheckResponse {
@@ -23,6 +31,7 @@ pub struct GetProtagonistResponse {
     pub protagonist_first_name: String,
     pub protagonist_email: String,
     pub protagonist_country: String,
+    pub protagonist_email_verified: bool,
 }
 
 impl IntoResponse for GetProtagonistResponse {
@@ -31,6 +40,18 @@ impl IntoResponse for GetProtagonistResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct VerifyProtagonistEmailResponse {
+    pub protagonist_id: u64,
+    pub protagonist_email_verified: bool,
+}
+
+impl IntoResponse for VerifyProtagonistEmailResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct CreateProtagonistResponse {
     pub protagonist_id: u64,
@@ -38,6 +59,7 @@ pub struct CreateProtagonistResponse {
     pub protagonist_first_name: String,
     pub protagonist_email: String,
     pub protagonist_country: String,
+    pub protagonist_email_verified: bool,
 }
 
 impl IntoResponse for CreateProtagonistResponse {
@@ -53,6 +75,7 @@ pub struct UpdateProtagonistResponse {
     pub protagonist_first_name: String,
     pub protagonist_email: String,
     pub protagonist_country: String,
+    pub protagonist_email_verified: bool,
 }
 
 impl IntoResponse for UpdateProtagonistResponse {
@@ -79,6 +102,7 @@ pub struct GetSupporterResponse {
     pub supporter_first_name: String,
     pub supporter_email: String,
     pub supporter_country: String,
+    pub supporter_email_verified: bool,
 }
 
 impl IntoResponse for GetSupporterResponse {
@@ -87,6 +111,18 @@ impl IntoResponse for GetSupporterResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct VerifySupporterEmailResponse {
+    pub supporter_id: u64,
+    pub supporter_email_verified: bool,
+}
+
+impl IntoResponse for VerifySupporterEmailResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
 #[derive(Serialize)]
 pub struct CreateSupporterResponse {
     pub supporter_id: u64,
@@ -94,6 +130,7 @@ pub struct CreateSupporterResponse {
     pub supporter_first_name: String,
     pub supporter_email: String,
     pub supporter_country: String,
+    pub supporter_email_verified: bool,
 }
 
 impl IntoResponse for CreateSupporterResponse {
@@ -109,6 +146,7 @@ pub struct UpdateSupporterResponse {
     pub supporter_first_name: String,
     pub supporter_email: String,
     pub supporter_country: String,
+    pub supporter_email_verified: bool,
 }
 
 impl IntoResponse for UpdateSupporterResponse {
@@ -164,23 +202,224 @@ impl IntoResponse for DeleteProtagonistSupporterResponse {
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub error: String,
-    pub message: String,
+#[derive(Serialize)]
+pub struct ForgotPasswordResponse {
+    pub status: String,
+}
+
+impl IntoResponse for ForgotPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::ACCEPTED, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResetProtagonistPasswordResponse {
+    pub protagonist_id: u64,
+    pub status: String,
+}
+
+impl IntoResponse for ResetProtagonistPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResetSupporterPasswordResponse {
+    pub supporter_id: u64,
+    pub status: String,
+}
+
+impl IntoResponse for ResetSupporterPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChangeProtagonistPasswordResponse {
+    pub protagonist_id: u64,
+    pub status: String,
+}
+
+impl IntoResponse for ChangeProtagonistPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChangeSupporterPasswordResponse {
+    pub supporter_id: u64,
+    pub status: String,
+}
+
+impl IntoResponse for ChangeSupporterPasswordResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+}
+
+impl IntoResponse for LoginResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+/// Answer to a correct password of an account with TOTP, the login finishes at `/login/totp`.
+#[derive(Serialize)]
+pub struct TwoFactorRequiredResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_in: u64,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginStepResponse {
+    Authenticated(LoginResponse),
+    TwoFactorRequired(TwoFactorRequiredResponse),
+}
+
+impl IntoResponse for LoginStepResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
 }
 
-impl IntoResponse for ErrorResponse {
+impl IntoResponse for EnrollTotpResponse {
     fn into_response(self) -> Response {
-        let status_code = match self.error.as_str() {
-            "NotFound" => StatusCode::NOT_FOUND,
-            "Unauthorized" => StatusCode::UNAUTHORIZED,
-            "Forbidden" => StatusCode::FORBIDDEN,
-            "BadRequest" => StatusCode::BAD_REQUEST,
-            "Conflict" => StatusCode::CONFLICT,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ConfirmTotpResponse {
+    pub totp_enabled: bool,
+    pub recovery_codes: Vec<String>,
+}
+
+impl IntoResponse for ConfirmTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DisableTotpResponse {
+    pub totp_enabled: bool,
+}
+
+impl IntoResponse for DisableTotpResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, Json(self)).into_response()
+    }
+}
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// RFC 7807 error body, `instance` is filled in by `problem_details_middleware`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            instance: None,
+            errors: None,
+        }
+    }
+
+    pub fn with_errors(self, errors: FieldErrors) -> Self {
+        Self {
+            errors: Some(errors),
+            ..self
+        }
+    }
+
+    pub fn with_instance(self, instance: String) -> Self {
+        Self {
+            instance: Some(instance),
+            ..self
+        }
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = serde_json::to_vec(&self).unwrap_or_default();
+
+        let mut res = (status, [(header::CONTENT_TYPE, PROBLEM_JSON)], body).into_response();
+        // kept so the middleware can re-render the body with the request path
+        res.extensions_mut().insert(self);
+        res
+    }
+}
+
+impl IntoResponse for SupportError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            SupportError::NotFound(_) => StatusCode::NOT_FOUND,
+            SupportError::Conflict(_) => StatusCode::CONFLICT,
+            SupportError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            SupportError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            SupportError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            SupportError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            SupportError::Forbidden(_) => StatusCode::FORBIDDEN,
+            SupportError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            SupportError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            SupportError::TooManyRequests(_, _) => StatusCode::TOO_MANY_REQUESTS,
+            SupportError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        // internal details stay in the log, the client gets the status only
+        let detail = match &self {
+            SupportError::Internal(message) => {
+                error!("Internal error: {}", message);
+                "Internal Server Error".to_string()
+            }
+            _ => self.to_string(),
         };
 
-        (status_code, Json(self)).into_response()
+        let problem = ProblemDetails::new(status, detail);
+        match self {
+            SupportError::InvalidFields(errors) => problem.with_errors(errors).into_response(),
+            SupportError::TooManyRequests(_, retry_after) => {
+                let mut res = problem.into_response();
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+                res
+            }
+            _ => problem.into_response(),
+        }
     }
 }
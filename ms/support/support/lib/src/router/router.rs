@@ -1,27 +1,35 @@
 use super::{
     middleware,
     request::{
-        CreateProtagonistRequest, CreateProtagonistSupporterRequest, CreateSupporterRequest,
-        GetProtagonistRequest, GetSupporterRequest, UpdateProtagonistRequest,
-        UpdateSupporterRequest,
+        ChangePasswordRequest, ConfirmTotpRequest, CreateProtagonistRequest,
+        CreateProtagonistSupporterRequest, CreateSupporterRequest, DisableTotpRequest,
+        ForgotPasswordRequest, LoginRequest, LoginTotpRequest, PatchProfileRequest,
+        ResetPasswordRequest, UpdateProtagonistRequest, UpdateSupporterRequest, VerifyEmailRequest,
     },
     response::{
+        ChangeProtagonistPasswordResponse, ChangeSupporterPasswordResponse, ConfirmTotpResponse,
         CreateProtagonistResponse, CreateProtagonistSupporterResponse, CreateSupporterResponse,
         DeleteProtagonistResponse, DeleteProtagonistSupporterResponse, DeleteSupporterResponse,
-        ErrorResponse, GetProtagonistResponse, GetProtagonistSupporterResponse,
-        GetSupporterResponse, HealthCheckResponse, UpdateProtagonistResponse,
-        UpdateSupporterResponse,
+        DisableTotpResponse, EnrollTotpResponse, ForgotPasswordResponse,
+        GetProtagonistSupporterResponse, HealthCheckResponse, LoginResponse, LoginStepResponse,
+        ResetProtagonistPasswordResponse, ResetSupporterPasswordResponse,
+        VerifyProtagonistEmailResponse, VerifySupporterEmailResponse, Versioned,
     },
 };
-use crate::{domain::service::SupportService, util};
+use crate::{
+    domain::{entity, error::SupportError, service::SupportService},
+    util,
+};
 use axum::{
     http,
+    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
     {
-        extract::{Extension, Path, State},
+        extract::{ConnectInfo, Extension, Path, State},
         Json, Router, Server,
     },
 };
+use serde::Serialize;
 use std::{net::SocketAddr, sync::Arc};
 use tracing::info;
 
@@ -45,7 +53,7 @@ impl AppRouter {
         info!("Listening on {}", addr);
 
         Server::bind(&addr)
-            .serve(router.into_make_service())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .map_err(|e| anyhow::anyhow!("Failed to start server: {}", e))?;
 
@@ -54,6 +62,10 @@ impl AppRouter {
 
     async fn init_router(&self) -> Result<Router, anyhow::Error> {
         let arc_secret_key = Arc::new(self.secret_key.clone());
+        let token_verification = middleware::TokenVerification {
+            secret_key: arc_secret_key.clone(),
+            service: self.service.clone(),
+        };
 
         let router = Router::new()
             .nest(
@@ -63,49 +75,138 @@ impl AppRouter {
                     .nest(
                         "/protagonist",
                         Router::new()
-                            .route("/:protagonist_id", get(Self::get_protagonist))
-                            .route("/", put(Self::update_protagonist))
-                            .route("/:protagonist_id", delete(Self::delete_protagonist))
+                            .merge(
+                                Router::new()
+                                    .route("/:protagonist_id", get(Self::get_protagonist))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["protagonists:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/", put(Self::update_protagonist))
+                                    .route(
+                                        "/:protagonist_id",
+                                        delete(Self::delete_protagonist)
+                                            .patch(Self::patch_protagonist),
+                                    )
+                                    .route(
+                                        "/:protagonist_id/password",
+                                        post(Self::change_protagonist_password),
+                                    )
+                                    .route("/totp", post(Self::enroll_protagonist_totp))
+                                    .route("/totp/confirm", post(Self::confirm_protagonist_totp))
+                                    .route("/totp/disable", post(Self::disable_protagonist_totp))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["protagonists:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
                             .route_layer(axum::middleware::from_fn_with_state(
-                                arc_secret_key.clone(),
+                                token_verification.clone(),
                                 middleware::verify_token_middleware,
                             ))
                             .route("/", post(Self::create_protagonist))
-                            .route(
-                                "/login/:login_id/password/:password",
-                                get(Self::get_protagonist_by_login_id_and_password),
-                            ),
+                            .route("/verify", post(Self::verify_protagonist_email)),
                     )
                     .nest(
                         "/supporter",
                         Router::new()
-                            .route("/:supporter_id", get(Self::get_supporter))
-                            .route("/", put(Self::update_supporter))
-                            .route("/:supporter_id", delete(Self::delete_supporter))
+                            .merge(
+                                Router::new()
+                                    .route("/:supporter_id", get(Self::get_supporter))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["supporters:read"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/", put(Self::update_supporter))
+                                    .route(
+                                        "/:supporter_id",
+                                        delete(Self::delete_supporter).patch(Self::patch_supporter),
+                                    )
+                                    .route(
+                                        "/:supporter_id/password",
+                                        post(Self::change_supporter_password),
+                                    )
+                                    .route("/totp", post(Self::enroll_supporter_totp))
+                                    .route("/totp/confirm", post(Self::confirm_supporter_totp))
+                                    .route("/totp/disable", post(Self::disable_supporter_totp))
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&["supporters:write"]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
                             .route_layer(axum::middleware::from_fn_with_state(
-                                arc_secret_key.clone(),
+                                token_verification.clone(),
                                 middleware::verify_token_middleware,
                             ))
                             .route("/", post(Self::create_supporter))
-                            .route(
-                                "/login/:login_id/password/:password",
-                                get(Self::get_supporter_by_login_id_and_password),
-                            ),
+                            .route("/verify", post(Self::verify_supporter_email)),
                     )
                     .nest(
-                        "/protagonist_supporter",
+                        "/auth",
                         Router::new()
+                            .route("/protagonist/login", post(Self::login_protagonist))
+                            .route(
+                                "/protagonist/login/totp",
+                                post(Self::login_protagonist_totp),
+                            )
+                            .route("/supporter/login", post(Self::login_supporter))
+                            .route("/supporter/login/totp", post(Self::login_supporter_totp))
+                            .route(
+                                "/protagonist/password/forgot",
+                                post(Self::forgot_protagonist_password),
+                            )
+                            .route(
+                                "/protagonist/password/reset",
+                                post(Self::reset_protagonist_password),
+                            )
                             .route(
-                                "/:protagonist_supporter_id",
-                                get(Self::get_protagonist_supporter),
+                                "/supporter/password/forgot",
+                                post(Self::forgot_supporter_password),
                             )
-                            .route("/", post(Self::create_protagonist_supporter))
                             .route(
-                                "/:protagonist_supporter_id",
-                                delete(Self::delete_protagonist_supporter),
+                                "/supporter/password/reset",
+                                post(Self::reset_supporter_password),
+                            )
+                            .layer(Extension(arc_secret_key.clone())),
+                    )
+                    .nest(
+                        "/protagonist_supporter",
+                        Router::new()
+                            .merge(
+                                Router::new()
+                                    .route(
+                                        "/:protagonist_supporter_id",
+                                        get(Self::get_protagonist_supporter),
+                                    )
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&[
+                                            "protagonist_supporters:read",
+                                        ]),
+                                        middleware::require_access_middleware,
+                                    )),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/", post(Self::create_protagonist_supporter))
+                                    .route(
+                                        "/:protagonist_supporter_id",
+                                        delete(Self::delete_protagonist_supporter),
+                                    )
+                                    .route_layer(axum::middleware::from_fn_with_state(
+                                        middleware::AccessPolicy::scopes(&[
+                                            "protagonist_supporters:write",
+                                        ]),
+                                        middleware::require_access_middleware,
+                                    )),
                             )
                             .route_layer(axum::middleware::from_fn_with_state(
-                                arc_secret_key.clone(),
+                                token_verification.clone(),
                                 middleware::verify_token_middleware,
                             )),
                     )
@@ -113,11 +214,45 @@ impl AppRouter {
                         middleware::request_log_middleware,
                     )),
             )
+            .layer(axum::middleware::from_fn(
+                middleware::problem_details_middleware,
+            ))
             .with_state(self.service.clone());
 
         Ok(router)
     }
 
+    // `If-Match` is required on writes so that no client overwrites a change it has not seen.
+    fn if_match(headers: &http::HeaderMap) -> Result<util::etag::Precondition, SupportError> {
+        let if_match = headers.get(http::header::IF_MATCH).ok_or_else(|| {
+            SupportError::PreconditionRequired("If-Match header is required".to_string())
+        })?;
+
+        // a value that is not even text can name no version
+        Ok(match if_match.to_str() {
+            Ok(if_match) => util::etag::Precondition::parse(if_match),
+            Err(_) => util::etag::Precondition::Versions(Vec::new()),
+        })
+    }
+
+    // Tags the body with its ETag and answers 304 when the client's copy is current.
+    fn versioned_response<T: Serialize>(
+        versioned: Versioned<T>,
+        if_none_match: Option<&http::HeaderValue>,
+    ) -> Response {
+        let etag = [(http::header::ETAG, util::etag::etag(versioned.version))];
+        let not_modified = if_none_match
+            .and_then(|if_none_match| if_none_match.to_str().ok())
+            .is_some_and(|if_none_match| {
+                util::etag::is_not_modified(if_none_match, versioned.version)
+            });
+        if not_modified {
+            return (http::StatusCode::NOT_MODIFIED, etag).into_response();
+        }
+
+        (http::StatusCode::OK, etag, Json(versioned.body)).into_response()
+    }
+
     async fn health_check(
         State(_): State<SupportService>,
     ) -> Result<(http::StatusCode, Json<HealthCheckResponse>), ()> {
@@ -132,123 +267,101 @@ impl AppRouter {
     async fn get_protagonist(
         Extension(token): Extension<Arc<util::auth::Token>>,
         State(service): State<SupportService>,
+        headers: http::HeaderMap,
         Path(protagonist_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<GetProtagonistResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<Response, SupportError> {
         info!("Get protagonist");
         info!(token = ?token);
 
-        let protagonist = service
-            .get_protagonist(i64::try_from(protagonist_id).unwrap())
-            .await;
+        let protagonist_id = i64::try_from(protagonist_id).map_err(|_| {
+            SupportError::BadRequest("Protagonist ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(protagonist_id)?;
+
+        let protagonist = service.get_protagonist(protagonist_id).await;
 
         match protagonist {
-            Ok(protagonist) => Ok((http::StatusCode::OK, Json(protagonist))),
-            Err(err) => {
-                if err.to_string().contains("no rows") {
-                    Err((
-                        http::StatusCode::NOT_FOUND,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Protagonist not found".to_string(),
-                        }),
-                    ))
-                } else {
-                    Err((
-                        http::StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Internal Server Error".to_string(),
-                        }),
-                    ))
-                }
+            Ok(protagonist) => Ok(Self::versioned_response(
+                protagonist,
+                headers.get(http::header::IF_NONE_MATCH),
+            )),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
             }
+            Err(err) => Err(err),
         }
     }
 
     async fn create_protagonist(
         State(service): State<SupportService>,
         Json(body): Json<CreateProtagonistRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<CreateProtagonistResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<CreateProtagonistResponse>), SupportError> {
         info!("Create protagonist");
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
 
         let protagonist = service.create_protagonist(body).await;
         match protagonist {
             Ok(protagonist) => Ok((http::StatusCode::CREATED, Json(protagonist))),
-            Err(err) => Err((
-                http::StatusCode::CONFLICT,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Protagonist already exists".to_string(),
-                }),
+            Err(SupportError::Conflict(_)) => Err(SupportError::Conflict(
+                "Protagonist already exists".to_string(),
             )),
+            Err(err) => Err(err),
         }
     }
 
+    async fn verify_protagonist_email(
+        State(service): State<SupportService>,
+        Json(body): Json<VerifyEmailRequest>,
+    ) -> Result<(http::StatusCode, Json<VerifyProtagonistEmailResponse>), SupportError> {
+        info!("Verify protagonist email");
+
+        body.validate().await?;
+
+        let verified = service.verify_protagonist_email(body).await?;
+        Ok((http::StatusCode::OK, Json(verified)))
+    }
+
     async fn update_protagonist(
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
         Json(body): Json<UpdateProtagonistRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<UpdateProtagonistResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<Response, SupportError> {
         info!("Update protagonist");
         info!(token = ?token);
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
+        token.authorize_owner(body.protagonist_id)?;
 
-        let protagonist = service.update_protagonist(body).await;
+        let precondition = Self::if_match(&headers)?;
+        let protagonist = service.update_protagonist(body, &precondition).await;
         match protagonist {
-            Ok(protagonist) => Ok((http::StatusCode::OK, Json(protagonist))),
-            Err(err) => Err((
-                http::StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Protagonist not found".to_string(),
-                }),
-            )),
+            Ok(protagonist) => Ok(Self::versioned_response(protagonist, None)),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => Err(err),
         }
     }
 
     async fn delete_protagonist(
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
         Path(protagonist_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<DeleteProtagonistResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<DeleteProtagonistResponse>), SupportError> {
         info!("Delete protagonist");
         info!(token = ?token);
 
+        let protagonist_id = i64::try_from(protagonist_id).map_err(|_| {
+            SupportError::BadRequest("Protagonist ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(protagonist_id)?;
+
+        let precondition = Self::if_match(&headers)?;
         let result = service
-            .delete_protagonist(i64::try_from(protagonist_id).unwrap())
+            .delete_protagonist(protagonist_id, &precondition)
             .await;
 
         match result {
@@ -258,187 +371,292 @@ impl AppRouter {
                     status: "The protagonist has been deleted".to_string(),
                 }),
             )),
-            Err(err) => Err((
-                http::StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Protagonist not found".to_string(),
-                }),
-            )),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => Err(err),
         }
     }
 
-    async fn get_protagonist_by_login_id_and_password(
+    async fn patch_protagonist(
         State(service): State<SupportService>,
-        Path(login_request): Path<GetProtagonistRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<GetProtagonistResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
-        info!("Get protagonist by login_id and password");
-
-        let request = GetProtagonistRequest::new(login_request.login_id, login_request.password)
-            .validate()
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
+        Path(protagonist_id): Path<u64>,
+        Json(body): Json<PatchProfileRequest>,
+    ) -> Result<Response, SupportError> {
+        info!("Patch protagonist");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let protagonist_id = i64::try_from(protagonist_id).map_err(|_| {
+            SupportError::BadRequest("Protagonist ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(protagonist_id)?;
+
+        let precondition = Self::if_match(&headers)?;
+        let protagonist = service
+            .patch_protagonist(protagonist_id, body, &precondition)
             .await;
-        if request.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: request.err().unwrap().to_string(),
-                }),
-            ));
+        match protagonist {
+            Ok(protagonist) => Ok(Self::versioned_response(protagonist, None)),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => Err(err),
         }
+    }
 
-        let protagonist = service
-            .get_protagonist_by_login_id_and_password(request.unwrap())
+    async fn change_protagonist_password(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        connect_info: Option<ConnectInfo<SocketAddr>>,
+        Path(protagonist_id): Path<u64>,
+        Json(body): Json<ChangePasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<ChangeProtagonistPasswordResponse>), SupportError> {
+        info!("Change protagonist password");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let protagonist_id = i64::try_from(protagonist_id).map_err(|_| {
+            SupportError::BadRequest("Protagonist ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(protagonist_id)?;
+
+        let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+        let changed = service
+            .change_protagonist_password(protagonist_id, body, client_ip.as_deref())
             .await;
+        match changed {
+            Ok(changed) => Ok((http::StatusCode::OK, Json(changed))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
 
-        match protagonist {
-            Ok(protagonist) => Ok((http::StatusCode::OK, Json(protagonist))),
-            Err(err) => {
-                if err.to_string().contains("no rows") {
-                    Err((
-                        http::StatusCode::NOT_FOUND,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Protagonist not found".to_string(),
-                        }),
-                    ))
-                } else {
-                    Err((
-                        http::StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Internal Server Error".to_string(),
-                        }),
-                    ))
-                }
+    async fn login_protagonist(
+        State(service): State<SupportService>,
+        Extension(secret_key): Extension<Arc<String>>,
+        connect_info: Option<ConnectInfo<SocketAddr>>,
+        Json(body): Json<LoginRequest>,
+    ) -> Result<(http::StatusCode, Json<LoginStepResponse>), SupportError> {
+        info!("Login protagonist");
+
+        body.validate().await?;
+
+        let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+        let login = service
+            .login_protagonist(body, client_ip.as_deref(), &secret_key)
+            .await;
+        match login {
+            Ok(login) => Ok((http::StatusCode::OK, Json(login))),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn login_protagonist_totp(
+        State(service): State<SupportService>,
+        Extension(secret_key): Extension<Arc<String>>,
+        Json(body): Json<LoginTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<LoginResponse>), SupportError> {
+        info!("Login protagonist TOTP");
+
+        body.validate().await?;
+
+        let login = service.login_protagonist_totp(body, &secret_key).await?;
+        Ok((http::StatusCode::OK, Json(login)))
+    }
+
+    async fn enroll_protagonist_totp(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+    ) -> Result<(http::StatusCode, Json<EnrollTotpResponse>), SupportError> {
+        info!("Enroll protagonist TOTP");
+        info!(token = ?token);
+
+        let protagonist_id = token.authorize_account(entity::PROTAGONIST_ROLE)?;
+
+        let enrollment = service
+            .enroll_totp(entity::PROTAGONIST_ACCOUNT, protagonist_id)
+            .await;
+        match enrollment {
+            Ok(enrollment) => Ok((http::StatusCode::OK, Json(enrollment))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
             }
+            Err(err) => Err(err),
         }
     }
 
+    async fn confirm_protagonist_totp(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        Json(body): Json<ConfirmTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<ConfirmTotpResponse>), SupportError> {
+        info!("Confirm protagonist TOTP");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let protagonist_id = token.authorize_account(entity::PROTAGONIST_ROLE)?;
+
+        let confirmed = service
+            .confirm_totp(entity::PROTAGONIST_ACCOUNT, protagonist_id, body)
+            .await;
+        match confirmed {
+            Ok(confirmed) => Ok((http::StatusCode::OK, Json(confirmed))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn disable_protagonist_totp(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        Json(body): Json<DisableTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<DisableTotpResponse>), SupportError> {
+        info!("Disable protagonist TOTP");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let protagonist_id = token.authorize_account(entity::PROTAGONIST_ROLE)?;
+
+        let disabled = service
+            .disable_totp(entity::PROTAGONIST_ACCOUNT, protagonist_id, body)
+            .await;
+        match disabled {
+            Ok(disabled) => Ok((http::StatusCode::OK, Json(disabled))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Protagonist not found".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn forgot_protagonist_password(
+        State(service): State<SupportService>,
+        Json(body): Json<ForgotPasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<ForgotPasswordResponse>), SupportError> {
+        info!("Forgot protagonist password");
+
+        body.validate().await?;
+
+        let accepted = service.forgot_protagonist_password(body).await?;
+        Ok((http::StatusCode::ACCEPTED, Json(accepted)))
+    }
+
+    async fn reset_protagonist_password(
+        State(service): State<SupportService>,
+        Json(body): Json<ResetPasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<ResetProtagonistPasswordResponse>), SupportError> {
+        info!("Reset protagonist password");
+
+        body.validate().await?;
+
+        let reset = service.reset_protagonist_password(body).await?;
+        Ok((http::StatusCode::OK, Json(reset)))
+    }
+
     async fn get_supporter(
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
         Path(supporter_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<GetSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<Response, SupportError> {
         info!("Get supporter");
         info!(token = ?token);
 
-        let supporter = service
-            .get_supporter(i64::try_from(supporter_id).unwrap())
-            .await;
+        let supporter_id = i64::try_from(supporter_id).map_err(|_| {
+            SupportError::BadRequest("Supporter ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(supporter_id)?;
+
+        let supporter = service.get_supporter(supporter_id).await;
 
         match supporter {
-            Ok(supporter) => Ok((http::StatusCode::OK, Json(supporter))),
-            Err(err) => {
-                if err.to_string().contains("no rows") {
-                    Err((
-                        http::StatusCode::NOT_FOUND,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Supporter not found".to_string(),
-                        }),
-                    ))
-                } else {
-                    Err((
-                        http::StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Internal Server Error".to_string(),
-                        }),
-                    ))
-                }
+            Ok(supporter) => Ok(Self::versioned_response(
+                supporter,
+                headers.get(http::header::IF_NONE_MATCH),
+            )),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
             }
+            Err(err) => Err(err),
         }
     }
 
     async fn create_supporter(
         State(service): State<SupportService>,
         Json(body): Json<CreateSupporterRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<CreateSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<CreateSupporterResponse>), SupportError> {
         info!("Create supporter");
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
 
         let supporter = service.create_supporter(body).await;
         match supporter {
             Ok(supporter) => Ok((http::StatusCode::CREATED, Json(supporter))),
-            Err(err) => Err((
-                http::StatusCode::CONFLICT,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Supporter already exists".to_string(),
-                }),
+            Err(SupportError::Conflict(_)) => Err(SupportError::Conflict(
+                "Supporter already exists".to_string(),
             )),
+            Err(err) => Err(err),
         }
     }
 
+    async fn verify_supporter_email(
+        State(service): State<SupportService>,
+        Json(body): Json<VerifyEmailRequest>,
+    ) -> Result<(http::StatusCode, Json<VerifySupporterEmailResponse>), SupportError> {
+        info!("Verify supporter email");
+
+        body.validate().await?;
+
+        let verified = service.verify_supporter_email(body).await?;
+        Ok((http::StatusCode::OK, Json(verified)))
+    }
+
     async fn update_supporter(
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
         Json(body): Json<UpdateSupporterRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<UpdateSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<Response, SupportError> {
         info!("Update supporter");
         info!(token = ?token);
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
+        token.authorize_owner(body.supporter_id)?;
 
-        let supporter = service.update_supporter(body).await;
+        let precondition = Self::if_match(&headers)?;
+        let supporter = service.update_supporter(body, &precondition).await;
         match supporter {
-            Ok(supporter) => Ok((http::StatusCode::OK, Json(supporter))),
-            Err(err) => Err((
-                http::StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Supporter not found".to_string(),
-                }),
-            )),
+            Ok(supporter) => Ok(Self::versioned_response(supporter, None)),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => Err(err),
         }
     }
 
     async fn delete_supporter(
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
         Path(supporter_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<DeleteSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<DeleteSupporterResponse>), SupportError> {
         info!("Delete supporter");
         info!(token = ?token);
 
-        let result = service
-            .delete_supporter(i64::try_from(supporter_id).unwrap())
-            .await;
+        let supporter_id = i64::try_from(supporter_id).map_err(|_| {
+            SupportError::BadRequest("Supporter ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(supporter_id)?;
+
+        let precondition = Self::if_match(&headers)?;
+        let result = service.delete_supporter(supporter_id, &precondition).await;
 
         match result {
             Ok(_) => Ok((
@@ -447,79 +665,211 @@ impl AppRouter {
                     status: "The supporter has been deleted".to_string(),
                 }),
             )),
-            Err(err) => Err((
-                http::StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Supporter not found".to_string(),
-                }),
-            )),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => Err(err),
         }
     }
 
-    async fn get_supporter_by_login_id_and_password(
+    async fn patch_supporter(
         State(service): State<SupportService>,
-        Path(login_request): Path<GetSupporterRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<GetSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
-        info!("Get supporter by login_id and password");
-
-        let request = GetSupporterRequest::new(login_request.login_id, login_request.password)
-            .validate()
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        headers: http::HeaderMap,
+        Path(supporter_id): Path<u64>,
+        Json(body): Json<PatchProfileRequest>,
+    ) -> Result<Response, SupportError> {
+        info!("Patch supporter");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let supporter_id = i64::try_from(supporter_id).map_err(|_| {
+            SupportError::BadRequest("Supporter ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(supporter_id)?;
+
+        let precondition = Self::if_match(&headers)?;
+        let supporter = service
+            .patch_supporter(supporter_id, body, &precondition)
             .await;
-        if request.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: request.err().unwrap().to_string(),
-                }),
-            ));
+        match supporter {
+            Ok(supporter) => Ok(Self::versioned_response(supporter, None)),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => Err(err),
         }
+    }
 
-        let supporter = service
-            .get_supporter_by_login_id_and_password(request.unwrap())
+    async fn change_supporter_password(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        connect_info: Option<ConnectInfo<SocketAddr>>,
+        Path(supporter_id): Path<u64>,
+        Json(body): Json<ChangePasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<ChangeSupporterPasswordResponse>), SupportError> {
+        info!("Change supporter password");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let supporter_id = i64::try_from(supporter_id).map_err(|_| {
+            SupportError::BadRequest("Supporter ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(supporter_id)?;
+
+        let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+        let changed = service
+            .change_supporter_password(supporter_id, body, client_ip.as_deref())
             .await;
+        match changed {
+            Ok(changed) => Ok((http::StatusCode::OK, Json(changed))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
 
-        match supporter {
-            Ok(supporter) => Ok((http::StatusCode::OK, Json(supporter))),
-            Err(err) => {
-                if err.to_string().contains("no rows") {
-                    Err((
-                        http::StatusCode::NOT_FOUND,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Supporter not found".to_string(),
-                        }),
-                    ))
-                } else {
-                    Err((
-                        http::StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Internal Server Error".to_string(),
-                        }),
-                    ))
-                }
+    async fn login_supporter(
+        State(service): State<SupportService>,
+        Extension(secret_key): Extension<Arc<String>>,
+        connect_info: Option<ConnectInfo<SocketAddr>>,
+        Json(body): Json<LoginRequest>,
+    ) -> Result<(http::StatusCode, Json<LoginStepResponse>), SupportError> {
+        info!("Login supporter");
+
+        body.validate().await?;
+
+        let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+        let login = service
+            .login_supporter(body, client_ip.as_deref(), &secret_key)
+            .await;
+        match login {
+            Ok(login) => Ok((http::StatusCode::OK, Json(login))),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn login_supporter_totp(
+        State(service): State<SupportService>,
+        Extension(secret_key): Extension<Arc<String>>,
+        Json(body): Json<LoginTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<LoginResponse>), SupportError> {
+        info!("Login supporter TOTP");
+
+        body.validate().await?;
+
+        let login = service.login_supporter_totp(body, &secret_key).await?;
+        Ok((http::StatusCode::OK, Json(login)))
+    }
+
+    async fn enroll_supporter_totp(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+    ) -> Result<(http::StatusCode, Json<EnrollTotpResponse>), SupportError> {
+        info!("Enroll supporter TOTP");
+        info!(token = ?token);
+
+        let supporter_id = token.authorize_account(entity::SUPPORTER_ROLE)?;
+
+        let enrollment = service
+            .enroll_totp(entity::SUPPORTER_ACCOUNT, supporter_id)
+            .await;
+        match enrollment {
+            Ok(enrollment) => Ok((http::StatusCode::OK, Json(enrollment))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn confirm_supporter_totp(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        Json(body): Json<ConfirmTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<ConfirmTotpResponse>), SupportError> {
+        info!("Confirm supporter TOTP");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let supporter_id = token.authorize_account(entity::SUPPORTER_ROLE)?;
+
+        let confirmed = service
+            .confirm_totp(entity::SUPPORTER_ACCOUNT, supporter_id, body)
+            .await;
+        match confirmed {
+            Ok(confirmed) => Ok((http::StatusCode::OK, Json(confirmed))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn disable_supporter_totp(
+        State(service): State<SupportService>,
+        Extension(token): Extension<Arc<util::auth::Token>>,
+        Json(body): Json<DisableTotpRequest>,
+    ) -> Result<(http::StatusCode, Json<DisableTotpResponse>), SupportError> {
+        info!("Disable supporter TOTP");
+        info!(token = ?token);
+
+        body.validate().await?;
+        let supporter_id = token.authorize_account(entity::SUPPORTER_ROLE)?;
+
+        let disabled = service
+            .disable_totp(entity::SUPPORTER_ACCOUNT, supporter_id, body)
+            .await;
+        match disabled {
+            Ok(disabled) => Ok((http::StatusCode::OK, Json(disabled))),
+            Err(SupportError::NotFound(_)) => {
+                Err(SupportError::NotFound("Supporter not found".to_string()))
             }
+            Err(err) => Err(err),
         }
     }
 
+    async fn forgot_supporter_password(
+        State(service): State<SupportService>,
+        Json(body): Json<ForgotPasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<ForgotPasswordResponse>), SupportError> {
+        info!("Forgot supporter password");
+
+        body.validate().await?;
+
+        let accepted = service.forgot_supporter_password(body).await?;
+        Ok((http::StatusCode::ACCEPTED, Json(accepted)))
+    }
+
+    async fn reset_supporter_password(
+        State(service): State<SupportService>,
+        Json(body): Json<ResetPasswordRequest>,
+    ) -> Result<(http::StatusCode, Json<ResetSupporterPasswordResponse>), SupportError> {
+        info!("Reset supporter password");
+
+        body.validate().await?;
+
+        let reset = service.reset_supporter_password(body).await?;
+        Ok((http::StatusCode::OK, Json(reset)))
+    }
+
     async fn get_protagonist_supporter(
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
         Path(protagonist_supporter_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<Vec<GetProtagonistSupporterResponse>>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<Vec<GetProtagonistSupporterResponse>>), SupportError> {
         info!("Get protagonist supporter");
         info!(token = ?token);
 
+        // relations are keyed by the protagonist they belong to
+        let protagonist_supporter_id = i64::try_from(protagonist_supporter_id).map_err(|_| {
+            SupportError::BadRequest("Protagonist supporter ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(protagonist_supporter_id)?;
+
         let protagonist_supporters = service
-            .get_protagonist_supporter(i64::try_from(protagonist_supporter_id).unwrap())
+            .get_protagonist_supporter(protagonist_supporter_id)
             .await;
 
         match protagonist_supporters {
@@ -527,25 +877,10 @@ impl AppRouter {
                 http::StatusCode::OK,
                 Json(protagonist_supporters.into_iter().collect()),
             )),
-            Err(err) => {
-                if err.to_string().contains("no rows") {
-                    Err((
-                        http::StatusCode::NOT_FOUND,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Protagonist supporter not found".to_string(),
-                        }),
-                    ))
-                } else {
-                    Err((
-                        http::StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            error: err.to_string(),
-                            message: "Internal Server Error".to_string(),
-                        }),
-                    ))
-                }
-            }
+            Err(SupportError::NotFound(_)) => Err(SupportError::NotFound(
+                "Protagonist supporter not found".to_string(),
+            )),
+            Err(err) => Err(err),
         }
     }
 
@@ -553,36 +888,25 @@ impl AppRouter {
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
         Json(body): Json<CreateProtagonistSupporterRequest>,
-    ) -> Result<
-        (http::StatusCode, Json<CreateProtagonistSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<CreateProtagonistSupporterResponse>), SupportError> {
         info!("Create protagonist supporter");
         info!(token = ?token);
 
-        let valid = body.validate().await;
-        if valid.is_err() {
-            return Err((
-                http::StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Bad Request".to_string(),
-                    message: valid.err().unwrap().to_string(),
-                }),
-            ));
-        }
+        body.validate().await?;
+        // either side of the relation may create it
+        token
+            .authorize_owner(body.protagonist_id as i64)
+            .or_else(|_| token.authorize_owner(body.supporter_id as i64))?;
 
         let protagonist_supporter = service.create_protagonist_supporter(body).await;
         match protagonist_supporter {
             Ok(protagonist_supporter) => {
                 Ok((http::StatusCode::CREATED, Json(protagonist_supporter)))
             }
-            Err(err) => Err((
-                http::StatusCode::CONFLICT,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Protagonist supporter already exists".to_string(),
-                }),
+            Err(SupportError::Conflict(_)) => Err(SupportError::Conflict(
+                "Protagonist supporter already exists".to_string(),
             )),
+            Err(err) => Err(err),
         }
     }
 
@@ -590,15 +914,18 @@ impl AppRouter {
         State(service): State<SupportService>,
         Extension(token): Extension<Arc<util::auth::Token>>,
         Path(protagonist_supporter_id): Path<u64>,
-    ) -> Result<
-        (http::StatusCode, Json<DeleteProtagonistSupporterResponse>),
-        (http::StatusCode, Json<ErrorResponse>),
-    > {
+    ) -> Result<(http::StatusCode, Json<DeleteProtagonistSupporterResponse>), SupportError> {
         info!("Delete protagonist supporter");
         info!(token = ?token);
 
+        // relations are keyed by the protagonist they belong to
+        let protagonist_supporter_id = i64::try_from(protagonist_supporter_id).map_err(|_| {
+            SupportError::BadRequest("Protagonist supporter ID must be a valid integer".to_string())
+        })?;
+        token.authorize_owner(protagonist_supporter_id)?;
+
         let result = service
-            .delete_protagonist_supporter(i64::try_from(protagonist_supporter_id).unwrap())
+            .delete_protagonist_supporter(protagonist_supporter_id)
             .await;
 
         match result {
@@ -608,13 +935,10 @@ impl AppRouter {
                     status: "The protagonist supporter has been deleted".to_string(),
                 }),
             )),
-            Err(err) => Err((
-                http::StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: err.to_string(),
-                    message: "Protagonist supporter not found".to_string(),
-                }),
+            Err(SupportError::NotFound(_)) => Err(SupportError::NotFound(
+                "Protagonist supporter not found".to_string(),
             )),
+            Err(err) => Err(err),
         }
     }
 }
@@ -1,3 +1,6 @@
 pub mod auth;
 pub mod crypt;
+pub mod etag;
+pub mod mailer;
 pub mod slog;
+pub mod totp;
@@ -1,9 +1,8 @@
-use axum::{http, Json};
-use chrono::{DateTime, Local, TimeZone, Utc};
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
-use crate::router::response::ErrorResponse;
+use crate::domain::error::SupportError;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Token {
@@ -14,35 +13,88 @@ pub struct Token {
     pub role: Option<String>,
 }
 
-pub fn validate_token(
-    token_string: &str,
-    secret_key: &str,
-) -> Result<Token, (http::StatusCode, Json<ErrorResponse>)> {
+/// Role that may act on resources owned by any user.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Lifetime of issued access tokens, the same as the auth service uses.
+pub const ACCESS_TOKEN_TTL_SECONDS: i64 = 15 * 60;
+
+impl Token {
+    pub fn new(uid: i64, scopes: Vec<String>, role: &str, issued_at: DateTime<Utc>) -> Self {
+        Self {
+            uid: Some(uid),
+            exp: Some((issued_at + Duration::seconds(ACCESS_TOKEN_TTL_SECONDS)).timestamp()),
+            iat: Some(issued_at),
+            scopes: Some(scopes),
+            role: Some(role.to_string()),
+        }
+    }
+
+    /// Signs the claims the way `validate_token` expects them.
+    pub fn encode(&self, secret_key: &str) -> Result<String, SupportError> {
+        encode(
+            &Header::default(),
+            self,
+            &EncodingKey::from_secret(secret_key.as_bytes()),
+        )
+        .map_err(|err| SupportError::Internal(format!("Failed to sign token: {}", err)))
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role.as_deref() == Some(ADMIN_ROLE)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_some_and(|scopes| scopes.iter().any(|granted| granted == scope))
+    }
+
+    /// Ok when the token belongs to `owner_id` or carries the admin role.
+    pub fn authorize_owner(&self, owner_id: i64) -> Result<(), SupportError> {
+        if self.is_admin() {
+            return Ok(());
+        }
+
+        match self.uid {
+            Some(uid) if uid == owner_id => Ok(()),
+            Some(_) => Err(SupportError::Forbidden(
+                "Token is not allowed to access this resource".to_string(),
+            )),
+            None => Err(SupportError::Unauthorized(
+                "Token does not contain a user ID".to_string(),
+            )),
+        }
+    }
+
+    /// The account id of a token issued with `role`, for routes only an account itself may use.
+    pub fn authorize_account(&self, role: &str) -> Result<i64, SupportError> {
+        match (self.role.as_deref(), self.uid) {
+            (Some(granted), Some(uid)) if granted == role => Ok(uid),
+            (_, None) => Err(SupportError::Unauthorized(
+                "Token does not contain a user ID".to_string(),
+            )),
+            _ => Err(SupportError::Forbidden(
+                "Token is not allowed to access this resource".to_string(),
+            )),
+        }
+    }
+}
+
+pub fn validate_token(token_string: &str, secret_key: &str) -> Result<Token, SupportError> {
     let claims = get_claims_from_token::<Token>(token_string, secret_key);
     match claims {
         Ok(claims) => {
             if let Some(exp) = claims.exp {
                 let expired_datetime = Utc.timestamp_opt(exp, 0).unwrap();
                 if Local::now() > expired_datetime {
-                    return Err((
-                        http::StatusCode::UNAUTHORIZED,
-                        Json(ErrorResponse {
-                            error: format!("Token is invalid"),
-                            message: format!("Token is expired"),
-                        }),
-                    ));
+                    return Err(SupportError::Unauthorized("Token is expired".to_string()));
                 }
             }
             return Ok(claims);
         }
         Err(err) => {
-            return Err((
-                http::StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    error: format!("Token is invalid"),
-                    message: format!("{}", err),
-                }),
-            ));
+            return Err(SupportError::Unauthorized(format!("{}", err)));
         }
     }
 }
@@ -62,3 +114,73 @@ where
 
     Ok(token_data.claims)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(uid: i64, role: &str) -> Token {
+        Token::new(uid, vec!["protagonists:read".to_string()], role, Utc::now())
+    }
+
+    #[test]
+    fn authorize_owner_allows_only_the_owner_or_an_admin() {
+        assert!(token(1, "protagonist").authorize_owner(1).is_ok());
+        assert!(matches!(
+            token(2, "protagonist").authorize_owner(1),
+            Err(SupportError::Forbidden(_))
+        ));
+        assert!(token(2, ADMIN_ROLE).authorize_owner(1).is_ok());
+    }
+
+    #[test]
+    fn authorize_account_accepts_only_the_role_it_was_issued_with() {
+        assert_eq!(
+            token(3, "supporter")
+                .authorize_account("supporter")
+                .unwrap(),
+            3
+        );
+        assert!(matches!(
+            token(3, "protagonist").authorize_account("supporter"),
+            Err(SupportError::Forbidden(_))
+        ));
+        assert!(matches!(
+            token(3, ADMIN_ROLE).authorize_account("supporter"),
+            Err(SupportError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn validate_token_round_trips_encoded_claims() {
+        let encoded = token(5, "protagonist").encode("secret").unwrap();
+        let decoded = validate_token(&encoded, "secret").unwrap();
+
+        assert_eq!(decoded.uid, Some(5));
+        assert_eq!(decoded.role.as_deref(), Some("protagonist"));
+        assert!(decoded.has_scope("protagonists:read"));
+        assert!(!decoded.has_scope("protagonists:write"));
+    }
+
+    #[test]
+    fn validate_token_rejects_other_secret_and_expired_tokens() {
+        let encoded = token(5, "protagonist").encode("secret").unwrap();
+        assert!(matches!(
+            validate_token(&encoded, "other"),
+            Err(SupportError::Unauthorized(_))
+        ));
+
+        let expired = Token::new(
+            5,
+            vec![],
+            "protagonist",
+            Utc::now() - Duration::seconds(ACCESS_TOKEN_TTL_SECONDS + 120),
+        )
+        .encode("secret")
+        .unwrap();
+        assert!(matches!(
+            validate_token(&expired, "secret"),
+            Err(SupportError::Unauthorized(_))
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 use bcrypt::{hash, verify, DEFAULT_COST};
+use sha2::{Digest, Sha256};
 
 pub async fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
     hash(password, DEFAULT_COST)
@@ -10,3 +11,11 @@ pub async fn verify_password(
 ) -> Result<bool, bcrypt::BcryptError> {
     verify(password, hashed_password)
 }
+
+/// Hex SHA-256 of a high-entropy token, which unlike a password needs no salt or stretching.
+pub fn digest_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
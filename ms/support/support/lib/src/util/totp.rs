@@ -0,0 +1,152 @@
+use crate::util;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use thiserror::Error;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Digits, period and algorithm every authenticator app assumes when the URI leaves them out.
+const DIGITS: usize = 6;
+const PERIOD_SECONDS: u64 = 30;
+/// Codes of the neighbouring periods are accepted too, so a slightly skewed clock still works.
+const SKEW_STEPS: u64 = 1;
+
+/// Letters and digits that cannot be mistaken for one another when typed from paper.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(Debug, Error)]
+pub enum TotpError {
+    #[error("Invalid TOTP secret: {0}")]
+    Secret(String),
+    #[error("Invalid TOTP parameters: {0}")]
+    Url(#[from] totp_rs::TotpUrlError),
+}
+
+/// A fresh 160-bit secret, base32 encoded the way authenticator apps expect it.
+pub fn generate_secret() -> String {
+    let bytes: [u8; 20] = rand::thread_rng().gen();
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+/// The `otpauth://` URI authenticator apps enrol from, usually shown as a QR code.
+pub fn otpauth_uri(secret: &str, issuer: &str, account_name: &str) -> Result<String, TotpError> {
+    Ok(totp(secret, issuer, account_name)?.get_url())
+}
+
+/// Authenticator codes are six digits, anything else can only be a recovery code.
+pub fn is_authenticator_code(code: &str) -> bool {
+    code.len() == DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The time step `code` belongs to, None when it matches none within the allowed skew.
+pub fn matching_step(
+    secret: &str,
+    code: &str,
+    unix_seconds: u64,
+) -> Result<Option<i64>, TotpError> {
+    // the URI labels play no part in the code, only the secret does
+    let totp = totp(secret, "support", "account")?;
+    let current = unix_seconds / PERIOD_SECONDS;
+
+    let step = (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .find(|step| totp.check(code, step * PERIOD_SECONDS));
+
+    Ok(step.map(|step| step as i64))
+}
+
+/// A single-use code like `k3m9p-x2qrt`, shown once at enrollment.
+pub fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut chars = (0..10).map(|_| *RECOVERY_CODE_ALPHABET.choose(&mut rng).unwrap() as char);
+    let head: String = chars.by_ref().take(5).collect();
+    let tail: String = chars.collect();
+    format!("{}-{}", head, tail)
+}
+
+/// Case and separators are ignored, so `K3M9P-X2QRT` and `k3m9px2qrt` are the same code.
+pub fn digest_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    util::crypt::digest_token(&normalized)
+}
+
+fn totp(secret: &str, issuer: &str, account_name: &str) -> Result<TOTP, TotpError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|err| TotpError::Secret(format!("{:?}", err)))?;
+
+    // skew is applied by matching_step so it can tell which step matched
+    Ok(TOTP::new(
+        Algorithm::SHA1,
+        DIGITS,
+        0,
+        PERIOD_SECONDS,
+        secret,
+        Some(issuer.to_string()),
+        account_name.to_string(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the RFC 6238 SHA-1 key "12345678901234567890", base32 encoded
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn matching_step_accepts_rfc_6238_codes() {
+        assert_eq!(matching_step(RFC_SECRET, "287082", 59).unwrap(), Some(1));
+        assert_eq!(
+            matching_step(RFC_SECRET, "081804", 1111111109).unwrap(),
+            Some(37037036)
+        );
+    }
+
+    #[test]
+    fn matching_step_allows_one_step_of_skew() {
+        // 287082 belongs to step 1, seconds 30 to 59
+        assert_eq!(matching_step(RFC_SECRET, "287082", 0).unwrap(), Some(1));
+        assert_eq!(matching_step(RFC_SECRET, "287082", 89).unwrap(), Some(1));
+        assert_eq!(matching_step(RFC_SECRET, "287082", 90).unwrap(), None);
+    }
+
+    #[test]
+    fn matching_step_rejects_other_codes() {
+        assert_eq!(matching_step(RFC_SECRET, "000000", 59).unwrap(), None);
+        assert_eq!(matching_step(RFC_SECRET, "28708", 59).unwrap(), None);
+    }
+
+    #[test]
+    fn matching_step_rejects_secret_that_is_not_base32() {
+        assert!(matches!(
+            matching_step("not base32!", "287082", 59),
+            Err(TotpError::Secret(_))
+        ));
+    }
+
+    #[test]
+    fn generated_secret_matches_its_own_codes() {
+        let secret = generate_secret();
+        let code = totp(&secret, "support", "account")
+            .unwrap()
+            .generate(1_700_000_000);
+
+        assert_eq!(
+            matching_step(&secret, &code, 1_700_000_000).unwrap(),
+            Some(1_700_000_000 / 30)
+        );
+    }
+
+    #[test]
+    fn digest_recovery_code_ignores_case_and_separators() {
+        let code = generate_recovery_code();
+        assert!(!is_authenticator_code(&code));
+        assert_eq!(
+            digest_recovery_code(&code.to_uppercase().replace('-', " ")),
+            digest_recovery_code(&code)
+        );
+    }
+}
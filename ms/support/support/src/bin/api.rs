@@ -3,20 +3,29 @@ use lib::{
     domain::service::SupportService,
     driver::{database::new_database, repository::SupportRepository},
     router::router::AppRouter,
+    util::mailer::{LogMailer, Mailer, SmtpMailer},
     util::slog::new_logger,
 };
 use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tracing::{error, info, span, Level};
 
 const MODE: &'static str = "MODE";
 const SUPPORT_PG_URL: &'static str = "SUPPORT_PG_URL";
 const SECRET_KEY: &'static str = "SECRET_KEY";
+const SMTP_URL: &'static str = "SMTP_URL";
+const MAIL_FROM: &'static str = "MAIL_FROM";
+const MAIL_FILE: &'static str = "MAIL_FILE";
 
 #[derive(Debug)]
 struct Env {
     mode: String,
     support_pg_url: String,
     secret_key: String,
+    smtp_url: Option<String>,
+    mail_from: String,
+    mail_file: Option<String>,
 }
 
 async fn init_env() -> Env {
@@ -39,6 +48,9 @@ async fn init_env() -> Env {
         mode,
         support_pg_url: env::var(SUPPORT_PG_URL).unwrap(),
         secret_key: env::var(SECRET_KEY).unwrap(),
+        smtp_url: env::var(SMTP_URL).ok(),
+        mail_from: env::var(MAIL_FROM).unwrap_or(format!("Support <no-reply@localhost>")),
+        mail_file: env::var(MAIL_FILE).ok(),
     }
 }
 
@@ -56,9 +68,19 @@ async fn main() {
         info!("Running in release mode");
     }
 
+    // without an SMTP relay mails go to MAIL_FILE or the log, which is enough for development
+    let mailer: Arc<dyn Mailer> = match env.smtp_url.as_deref() {
+        Some(url) => Arc::new(SmtpMailer::new(url, &env.mail_from).unwrap_or_else(|err| {
+            error!("{} is not valid: {}", SMTP_URL, err);
+            panic!();
+        })),
+        None => Arc::new(LogMailer::new(env.mail_file.as_deref().map(PathBuf::from))),
+    };
+
     let support_service = SupportService::new(SupportRepository::new(
         new_database(&env.support_pg_url).await.unwrap(),
-    ));
+    ))
+    .with_mailer(mailer);
 
     let router = AppRouter::new(support_service, env.secret_key);
     router.serve().await.unwrap();